@@ -2,166 +2,83 @@
  * Account definition and operations
  */
 
-use std::{collections::HashMap, ptr::addr_of, vec};
+use std::{collections::HashMap, vec};
 
-use crate::{balance::Balance, post::Post};
+use serde::Serialize;
 
-#[derive(Debug, PartialEq)]
+use crate::{balance::Balance, expr::Expr, journal::Journal, post::PostId};
+
+/// Index of the account in the journal.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+pub struct AccountId(pub(crate) usize);
+
+impl AccountId {
+    /// The master (root) account, created with the Journal.
+    pub const MASTER: AccountId = AccountId(0);
+
+    pub fn index(&self) -> usize {
+        self.0
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize)]
 pub struct Account {
-    pub(crate) parent: *const Account,
+    pub(crate) parent: Option<AccountId>,
     pub name: String,
-    // note
+    pub note: Option<String>,
     // depth
-    pub accounts: HashMap<String, Account>,
-    pub posts: Vec<*const Post>,
+    /// Sub-accounts. Map (name, account id)
+    pub accounts: HashMap<String, AccountId>,
+    pub posts: Vec<PostId>,
+    /// `check` and `assert` expressions from the account directive.
+    #[serde(skip)]
+    pub checks: Vec<AccountCheck>,
+    /// Declared with the `account` directive.
+    pub known: bool,
+    /// The type, from `type:` in the account declaration, i.e. `account Assets  ; type: A`.
+    pub kind: Option<AccountType>,
     // deferred posts
     // value_expr
-    fullname: String,
+    pub(crate) fullname: String,
 }
 
 impl Account {
     pub fn new(name: &str) -> Self {
         Self {
-            parent: std::ptr::null(),
+            parent: None,
             name: name.to_owned(),
-            // note
+            note: None,
             accounts: HashMap::new(),
             posts: vec![],
+            checks: vec![],
+            known: false,
+            kind: None,
             fullname: "".to_string(),
             // post_indices: vec![],
         }
     }
 
-    /// called from find_or_create.
-    fn create_account(&self, first: &str) -> &Account {
-        let mut new_account = Account::new(first);
-        new_account.set_parent(self);
-
-        let self_mut = self.get_account_mut(self as *const Account as *mut Account);
-
-        self_mut.accounts.insert(first.into(), new_account);
-
-        let Some(new_ref) = self.accounts.get(first)
-            else {panic!("should not happen")};
-
-        log::debug!("The new account {:?} reference: {:p}", new_ref.name, new_ref);
-        new_ref
-    }
-
     pub fn fullname(&self) -> &str {
-        // skip the master account.
-        if self.parent.is_null() {
-            return "";
-        }
-
-        if !self.fullname.is_empty() {
-            return &self.fullname;
-        }
-
-        let mut fullname = self.name.to_owned();
-        let mut first = self;
-
-        while !first.parent.is_null() {
-            // If there is a parent account, use it.
-            first = self.get_account_mut(first.parent);
-
-            if !first.name.is_empty() {
-                fullname = format!("{}:{}", &first.name, fullname);
-            }
-        }
-
-        self.set_fullname(fullname);
-
         &self.fullname
     }
 
-    fn set_fullname(&self, fullname: String) {
-        // alchemy?
-        // let ptr = self as *const Account;
-        let ptr = addr_of!(*self);
-        let subject = self.get_account_mut(ptr);
-
-        subject.fullname = fullname;
+    pub fn parent(&self) -> Option<AccountId> {
+        self.parent
     }
 
-    /// Finds account by full name.
-    /// i.e. "Assets:Cash"
-    pub fn find_account(&self, name: &str) -> Option<&Account> {
-        if let Some(ptr) = self.find_or_create(name, false) {
-            let acct = Account::from_ptr(ptr);
-            return Some(acct);
-        } else {
-            return None;
-        }
-    }
-
-    /// The variant with all the parameters.
-    /// account_t * find_account(const string& name, bool auto_create = true);
-    pub fn find_or_create(&self, name: &str, auto_create: bool) -> Option<*const Account> {
-        // search for direct hit.
-        if let Some(found) = self.accounts.get(name) {
-            return Some(found);
-        }
-
-        // otherwise search for name parts in between the `:`
-
-        let mut account: *const Account;
-        let first: &str;
-        let rest: &str;
-        if let Some(separator_index) = name.find(':') {
-            // Contains separators
-            first = &name[..separator_index];
-            rest = &name[separator_index + 1..];
-        } else {
-            // take all
-            first = name;
-            rest = "";
-        }
-
-        if let Some(account_opt) = self.accounts.get(first) {
-            // keep this value
-            account = account_opt;
-        } else {
-            if !auto_create {
-                return None;
-            }
-
-            account = self.create_account(first);
-        }
-
-        // Search recursively.
-        if !rest.is_empty() {
-            let acct = self.get_account_mut(account);
-            account = acct.find_or_create(rest, auto_create).unwrap();
-        }
-
-        Some(account)
-    }
-
-    pub fn from_ptr<'a>(acct_ptr: *const Account) -> &'a Account {
-        unsafe { &*acct_ptr }
-    }
-
-    pub fn get_account_mut(&self, acct_ptr: *const Account) -> &mut Account {
-        let mut_ptr = acct_ptr as *mut Account;
-        unsafe { &mut *mut_ptr }
-    }
-
-    pub fn flatten_account_tree(&self) -> Vec<&Account> {
+    /// Lists this account and all the sub-accounts, sorted by name.
+    pub fn flatten_account_tree<'a>(&'a self, journal: &'a Journal) -> Vec<&'a Account> {
         let mut list: Vec<&Account> = vec![];
-        self.flatten(&mut list);
+        self.flatten(journal, &mut list);
         list
     }
 
     /// Returns the amount of this account only.
-    pub fn amount(&self) -> Balance {
+    pub fn amount(&self, journal: &Journal) -> Balance {
         let mut bal = Balance::new();
 
-        for post_ptr in &self.posts {
-            let post: &Post;
-            unsafe {
-                post = &**post_ptr;
-            }
+        for post_id in &self.posts {
+            let post = journal.get_post(*post_id);
             if let Some(amt) = post.amount {
                 bal.add(&amt);
             }
@@ -170,30 +87,24 @@ impl Account {
         bal
     }
 
-    fn flatten<'a>(&'a self, nodes: &mut Vec<&'a Account>) {
+    fn flatten<'a>(&'a self, journal: &'a Journal, nodes: &mut Vec<&'a Account>) {
         // Push the current node to the Vec
         nodes.push(self);
         // 
-        let mut children: Vec<&Account> = self.accounts.values().into_iter().collect();
+        let mut children: Vec<&Account> = self
+            .accounts
+            .values()
+            .map(|id| journal.get_account(*id))
+            .collect();
         children.sort_unstable_by_key(|acc| &acc.name);
         // If the node has children, recursively call flatten on them
         for child in children {
-            child.flatten(nodes);
+            child.flatten(journal, nodes);
         }
     }
 
-    pub(crate) fn set_parent(&mut self, parent: &Account) {
-        // Confirms the pointers are the same:
-        // assert_eq!(parent as *const Account, addr_of!(*parent));
-        // self.parent = parent as *const Account;
-
-        self.parent = addr_of!(*parent);
-        
-        // log::debug!("Setting the {:?} parent to {:?}, {:p}", self.name, parent.name, self.parent);
-    }
-
     /// Returns the balance of this account and all sub-accounts.
-    pub fn total(&self) -> Balance {
+    pub fn total(&self, journal: &Journal) -> Balance {
         let mut total = Balance::new();
 
         // Sort the accounts by name
@@ -202,27 +113,98 @@ impl Account {
 
         // iterate through children and get their totals
         for acct_name in acct_names {
-            let subacct = self.accounts.get(acct_name).unwrap();
-            // let subacct = journal.get_account(*index);
-            let subtotal = subacct.total();
+            let subacct = journal.get_account(self.accounts[acct_name]);
+            let subtotal = subacct.total(journal);
 
             total += subtotal;
         }
 
         // Add the balance of this account
-        total += self.amount();
+        total += self.amount(journal);
 
         total
     }
 }
 
+/// The account types of hledger, i.e. `account Assets:Cash  ; type: C`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum AccountType {
+    Asset,
+    Liability,
+    Equity,
+    Revenue,
+    Expense,
+    /// An asset that is cash or equivalent.
+    Cash,
+    /// For the commodity conversions.
+    Conversion,
+}
+
+impl AccountType {
+    /// Parses the type by its name or its letter, case-insensitively, i.e. `Asset` or `A`.
+    pub fn parse(text: &str) -> Option<Self> {
+        let kind = match text.trim().to_lowercase().as_str() {
+            "a" | "asset" | "assets" => Self::Asset,
+            "l" | "liability" | "liabilities" => Self::Liability,
+            "e" | "equity" => Self::Equity,
+            "r" | "revenue" | "revenues" | "income" => Self::Revenue,
+            "x" | "expense" | "expenses" => Self::Expense,
+            "c" | "cash" => Self::Cash,
+            "v" | "conversion" => Self::Conversion,
+            _ => return None,
+        };
+        Some(kind)
+    }
+
+    /// The type inferred from the top-level account name, i.e. `Assets` or `expenses`.
+    pub fn infer(fullname: &str) -> Option<Self> {
+        let root = fullname.split(':').next().unwrap_or_default();
+        match root.to_lowercase().as_str() {
+            "assets" | "asset" => Some(Self::Asset),
+            "liabilities" | "liability" | "debts" => Some(Self::Liability),
+            "equity" => Some(Self::Equity),
+            "income" | "revenue" | "revenues" => Some(Self::Revenue),
+            "expenses" | "expense" => Some(Self::Expense),
+            _ => None,
+        }
+    }
+
+    /// The name as in hledger, i.e. `Asset`.
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Asset => "Asset",
+            Self::Liability => "Liability",
+            Self::Equity => "Equity",
+            Self::Revenue => "Revenue",
+            Self::Expense => "Expense",
+            Self::Cash => "Cash",
+            Self::Conversion => "Conversion",
+        }
+    }
+}
+
+/// An expression, which every posting to the account has to satisfy.
+///
+/// `check` only warns, while `assert` fails the parsing.
+#[derive(Debug, PartialEq)]
+pub struct AccountCheck {
+    pub expr: Expr,
+    pub is_assertion: bool,
+}
+
+impl AccountCheck {
+    pub fn new(expr: Expr, is_assertion: bool) -> Self {
+        Self { expr, is_assertion }
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use std::{io::Cursor, ptr::addr_of};
+    use std::io::Cursor;
 
     use crate::{amount::Quantity, journal::Journal, parse_file, parse_text, parser};
 
-    use super::Account;
+    use super::{Account, AccountId};
 
     #[test]
     fn test_flatten() {
@@ -230,7 +212,7 @@ mod tests {
         let _acct = j.register_account("Assets:Cash");
         let mut nodes: Vec<&Account> = vec![];
 
-        j.master.flatten(&mut nodes);
+        j.master().flatten(&j, &mut nodes);
 
         assert_eq!(3, nodes.len());
     }
@@ -241,7 +223,7 @@ mod tests {
         let mut counter: u8 = 0;
 
         let _acct = j.register_account("Assets:Cash");
-        for _a in j.master.flatten_account_tree() {
+        for _a in j.master().flatten_account_tree(&j) {
             //println!("sub-account: {:?}", a);
             counter += 1;
         }
@@ -257,13 +239,13 @@ mod tests {
     Assets:Cash
 "#;
         let mut journal = Journal::new();
-        parser::read_into_journal(Cursor::new(input), &mut journal);
+        parser::read_into_journal(Cursor::new(input), &mut journal).unwrap();
 
         let account = journal.find_account("Expenses:Food").unwrap();
 
         let actual = account.fullname();
 
-        assert_eq!(5, journal.master.flatten_account_tree().len());
+        assert_eq!(5, journal.master().flatten_account_tree(&journal).len());
         assert_eq!("Food", account.name);
         assert_eq!("Expenses:Food", actual);
     }
@@ -274,30 +256,28 @@ mod tests {
         let mut journal = Journal::new();
 
         // act
-        parse_file("tests/basic.ledger", &mut journal);
+        parse_file("tests/basic.ledger", &mut journal).unwrap();
 
-        let ptr = journal.find_account("Assets:Cash").unwrap();
-        let account = journal.get_account(ptr);
+        let account = journal.find_account("Assets:Cash").unwrap();
 
-        let actual = account.amount();
+        let actual = account.amount(&journal);
 
         // assert
         assert!(!actual.amounts.is_empty());
         assert_eq!(Quantity::from(-20), actual.amounts[0].quantity);
         let commodity = actual.amounts[0].get_commodity().unwrap();
-        assert_eq!("EUR", commodity.symbol);
+        assert_eq!("EUR", journal.commodity_pool.get(commodity).symbol);
     }
 
     /// Test calculation of the account totals.
     #[test_log::test]
     fn test_total() {
         let mut journal = Journal::new();
-        parse_file("tests/two-xact-sub-acct.ledger", &mut journal);
-        let ptr = journal.find_account("Assets").unwrap();
-        let assets = journal.get_account(ptr);
+        parse_file("tests/two-xact-sub-acct.ledger", &mut journal).unwrap();
+        let assets = journal.find_account("Assets").unwrap();
 
         // act
-        let actual = assets.total();
+        let actual = assets.total(&journal);
 
         // assert
         assert_eq!(1, actual.amounts.len());
@@ -308,7 +288,8 @@ mod tests {
         );
 
         assert_eq!(actual.amounts[0].quantity, (-30).into());
-        assert_eq!(actual.amounts[0].get_commodity().unwrap().symbol, "EUR");
+        let commodity = actual.amounts[0].get_commodity().unwrap();
+        assert_eq!(journal.commodity_pool.get(commodity).symbol, "EUR");
     }
 
     #[test]
@@ -320,12 +301,11 @@ mod tests {
         let mut journal = Journal::new();
 
         // act
-        parse_text(input, &mut journal);
+        parse_text(input, &mut journal).unwrap();
 
-        let ptr = journal.master.find_account("Assets").unwrap();
-        let assets = journal.get_account(ptr);
+        let assets = journal.find_account("Assets").unwrap();
 
-        assert_eq!(&*journal.master as *const Account, assets.parent);
+        assert_eq!(Some(AccountId::MASTER), assets.parent);
     }
 
     #[test]
@@ -335,26 +315,25 @@ mod tests {
     Assets
 "#;
         let mut journal = Journal::new();
-        parse_text(input, &mut journal);
+        parse_text(input, &mut journal).unwrap();
 
         // test parent
-        let ptr = journal.master.find_account("Assets").unwrap();
-        let assets = journal.get_account(ptr);
+        let assets = journal.find_account("Assets").unwrap();
 
-        assert_eq!(&*journal.master as *const Account, assets.parent);
+        assert_eq!(Some(AccountId::MASTER), assets.parent);
 
         // test fullname
-        let assets_fullname = journal.master.accounts.get("Assets").unwrap().fullname();
-        let expenses_fullname = journal.master.accounts.get("Expenses").unwrap().fullname();
+        let master = journal.master();
+        let assets_fullname = journal.get_account(master.accounts["Assets"]).fullname();
+        let expenses_fullname = journal.get_account(master.accounts["Expenses"]).fullname();
 
         assert_eq!("Assets", assets_fullname);
         assert_eq!("Expenses", expenses_fullname);
 
         // test parent
-        let ptr = journal.master.find_account("Assets").unwrap();
-        let assets = journal.get_account(ptr);
+        let assets = journal.find_account("Assets").unwrap();
 
-        assert_eq!(&*journal.master as *const Account, assets.parent);
+        assert_eq!(Some(AccountId::MASTER), assets.parent);
     }
 
     #[test_log::test]
@@ -365,26 +344,24 @@ mod tests {
     "#;
         let mut journal = Journal::new();
 
-        parse_text(input, &mut journal);
+        parse_text(input, &mut journal).unwrap();
 
         // expenses
-        let expenses = journal.master.find_account("Expenses").unwrap();
-        assert_eq!(&*journal.master as *const Account, expenses.parent);
+        let expenses_id = journal.master().accounts["Expenses"];
+        let expenses = journal.get_account(expenses_id);
+        assert_eq!(Some(AccountId::MASTER), expenses.parent);
 
         // groceries
-        let groceries = expenses.find_account("Groceries").unwrap();
-        assert_eq!(expenses as *const Account, groceries.parent);
+        let groceries = journal.get_account(expenses.accounts["Groceries"]);
+        assert_eq!(Some(expenses_id), groceries.parent);
 
         // assets
-        let assets = journal.master.find_account("Assets").unwrap();
-        assert_eq!(&*journal.master as *const Account, assets.parent);
-
-        // confirm that addr_of! and `as *const` are the same.
-        assert_eq!(assets as *const Account, addr_of!(*assets));
+        let assets_id = journal.master().accounts["Assets"];
+        let assets = journal.get_account(assets_id);
+        assert_eq!(Some(AccountId::MASTER), assets.parent);
 
         // cash
-        let cash = assets.find_account("Cash").unwrap();
-        assert_eq!(assets as *const Account, cash.parent);
-
+        let cash = journal.find_account("Assets:Cash").unwrap();
+        assert_eq!(Some(assets_id), cash.parent);
     }
 }
@@ -8,23 +8,27 @@ use std::{
 };
 
 use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
+use serde::{Serialize, Serializer};
+
+use crate::{
+    commodity::{
+        Commodity, CommodityId, COMMODITY_STYLE_DECIMAL_COMMA, COMMODITY_STYLE_NO_MIGRATE,
+        COMMODITY_STYLE_SEPARATED, COMMODITY_STYLE_SUFFIXED, COMMODITY_STYLE_THOUSANDS,
+    },
+    pool::CommodityPool,
+};
 
-use crate::commodity::Commodity;
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 pub struct Amount {
     pub quantity: Quantity,
-    pub(crate) commodity: *const Commodity,
+    pub(crate) commodity: Option<CommodityId>,
 }
 
 impl Amount {
-    pub fn new(quantity: Quantity, commodity: Option<*const Commodity>) -> Self {
+    pub fn new(quantity: Quantity, commodity: Option<CommodityId>) -> Self {
         Self {
             quantity,
-            commodity: match commodity {
-                Some(c) => c,
-                _ => std::ptr::null()
-            }
+            commodity,
         }
     }
 
@@ -45,7 +49,7 @@ impl Amount {
     pub fn null() -> Self {
         Self {
             quantity: 0.into(),
-            commodity: std::ptr::null(),
+            commodity: None,
         }
     }
 
@@ -62,27 +66,15 @@ impl Amount {
         self.quantity += other.quantity;
     }
 
-    pub fn get_commodity(&self) -> Option<&Commodity> {
-        if self.commodity.is_null() {
-            None
-        } else {
-            unsafe { 
-                Some(&*self.commodity)
-            }
-        }
+    pub fn get_commodity(&self) -> Option<CommodityId> {
+        self.commodity
     }
 
     /// Creates an amount with the opposite sign on the quantity.
     pub fn inverse(&self) -> Amount {
-        let new_quantity = if self.quantity.is_sign_positive() {
-            let mut x = self.quantity.clone();
-            x.set_sign_negative();
-            x
-        } else {
-            self.quantity
-        };
-
-        unsafe { Amount::new(new_quantity, Some(&*self.commodity)) }
+        let mut inverse = *self;
+        inverse.invert();
+        inverse
     }
 
     /// Inverts the sign on the amount.
@@ -98,7 +90,7 @@ impl Amount {
     /// This is a 0 quantity and no Commodity.
     pub fn is_null(&self) -> bool {
         if self.quantity.is_zero() {
-            return self.commodity.is_null();
+            return self.commodity.is_none();
         } else {
             false
         }
@@ -109,7 +101,20 @@ impl Amount {
     }
 
     pub fn remove_commodity(&mut self) {
-        self.commodity = std::ptr::null();
+        self.commodity = None;
+    }
+
+    /// Converts the amount into the smallest unit declared with the `C` directive,
+    /// i.e. `2h` into `120m`.
+    ///
+    /// void amount_t::in_place_reduce()
+    pub fn reduce(&self, pool: &CommodityPool) -> Amount {
+        let mut result = *self;
+        while let Some(smaller) = result.get_commodity().and_then(|c| pool.get(c).smaller) {
+            result.quantity *= smaller.quantity;
+            result.commodity = smaller.commodity;
+        }
+        result
     }
 }
 
@@ -123,7 +128,7 @@ impl Add<Amount> for Amount {
 
         let sum = self.quantity + rhs.quantity;
 
-        unsafe { Amount::new(sum, Some(&*self.commodity)) }
+        Amount::new(sum, self.commodity)
     }
 }
 
@@ -143,7 +148,7 @@ impl Div for Amount {
     fn div(self, rhs: Self) -> Self::Output {
         let mut result = Amount::new(0.into(), None);
 
-        if self.commodity.is_null() {
+        if self.commodity.is_none() {
             result.commodity = rhs.commodity;
         } else {
             result.commodity = self.commodity
@@ -161,16 +166,92 @@ impl Mul<Amount> for Amount {
     fn mul(self, other: Amount) -> Amount {
         let quantity = self.quantity * other.quantity;
 
-        let commodity = if self.commodity.is_null() {
+        let commodity = if self.commodity.is_none() {
             other.commodity
         } else {
             self.commodity
         };
 
-        unsafe { Amount::new(quantity, Some(&*commodity)) }
+        Amount::new(quantity, commodity)
+    }
+}
+
+impl Amount {
+    /// Returns an object for formatting the amount with the commodity from the pool,
+    /// i.e. `amount.display(&journal.commodity_pool).to_string()`.
+    pub fn display<'a>(&'a self, pool: &'a CommodityPool) -> AmountDisplay<'a> {
+        AmountDisplay { amount: self, pool }
+    }
+}
+
+/// Helper for formatting an Amount, which only holds the id of its commodity.
+pub struct AmountDisplay<'a> {
+    amount: &'a Amount,
+    pool: &'a CommodityPool,
+}
+
+/// Formats the amount. The commodities with a `format` use their display style,
+/// the others are shown as the quantity followed by the symbol.
+///
+/// void amount_t::print(std::ostream& _out, const uint_least8_t flags) const
+impl fmt::Display for AmountDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let amount = self.amount;
+        let Some(commodity) = amount.get_commodity().map(|id| self.pool.get(id)) else {
+            return write!(f, "{}", amount.quantity);
+        };
+        if !commodity.has_flags(COMMODITY_STYLE_NO_MIGRATE) {
+            return write!(f, "{} {}", amount.quantity, commodity.symbol);
+        }
+
+        let quantity = format_quantity(&amount.quantity, commodity);
+        let separator = if commodity.has_flags(COMMODITY_STYLE_SEPARATED) {
+            " "
+        } else {
+            ""
+        };
+        if commodity.has_flags(COMMODITY_STYLE_SUFFIXED) {
+            write!(f, "{}{}{}", quantity, separator, commodity.symbol)
+        } else {
+            write!(f, "{}{}{}", commodity.symbol, separator, quantity)
+        }
     }
 }
 
+/// Formats the quantity with the commodity's precision, decimal mark and thousands separator.
+fn format_quantity(quantity: &Quantity, commodity: &Commodity) -> String {
+    let precision = commodity.precision as usize;
+    let text = format!("{:.*}", precision, quantity.0.round_dp(precision as u32));
+
+    let (sign, text) = match text.strip_prefix('-') {
+        Some(rest) => ("-", rest),
+        None => ("", text.as_str()),
+    };
+    let (integer, fraction) = match text.split_once('.') {
+        Some((integer, fraction)) => (integer, Some(fraction)),
+        None => (text, None),
+    };
+
+    let (thousands_mark, decimal_mark) = if commodity.has_flags(COMMODITY_STYLE_DECIMAL_COMMA) {
+        ('.', ',')
+    } else {
+        (',', '.')
+    };
+
+    let mut result = sign.to_string();
+    for (i, digit) in integer.chars().enumerate() {
+        if i > 0 && (integer.len() - i) % 3 == 0 && commodity.has_flags(COMMODITY_STYLE_THOUSANDS) {
+            result.push(thousands_mark);
+        }
+        result.push(digit);
+    }
+    if let Some(fraction) = fraction {
+        result.push(decimal_mark);
+        result.push_str(fraction);
+    }
+    result
+}
+
 impl From<i32> for Amount {
     fn from(value: i32) -> Self {
         Amount::new(Quantity::from(value), None)
@@ -193,7 +274,7 @@ impl MulAssign<Amount> for Amount {
         self.quantity *= rhs.quantity;
 
         // get the other commodity, if we don't have one.
-        if self.commodity.is_null() && !rhs.commodity.is_null() {
+        if self.commodity.is_none() && rhs.commodity.is_some() {
             self.commodity = rhs.commodity;
         }
     }
@@ -230,6 +311,11 @@ impl Quantity {
     pub fn set_sign_positive(&mut self) {
         self.0.set_sign_positive(true)
     }
+
+    /// Rounded to the given number of decimal places.
+    pub fn round_dp(&self, places: u32) -> Self {
+        Self(self.0.round_dp(places))
+    }
 }
 
 impl From<i32> for Quantity {
@@ -306,11 +392,18 @@ impl fmt::Display for Quantity {
     }
 }
 
+/// As the text, i.e. `"-12.30"`, to keep the precision.
+impl Serialize for Quantity {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use rust_decimal::prelude::ToPrimitive;
 
-    use crate::commodity::Commodity;
+    use crate::pool::CommodityPool;
 
     use super::{Amount, Quantity};
 
@@ -321,12 +414,19 @@ mod tests {
         assert_eq!(Some(5), x.0.to_i32());
     }
 
+    #[test]
+    fn test_inverse() {
+        assert_eq!(Amount::from(-20), Amount::from(20).inverse());
+        assert_eq!(Amount::from(20), Amount::from(-20).inverse());
+    }
+
     #[test]
     fn test_division() {
-        let currency = Commodity::new("EUR");
-        let a = Amount::new(10.into(), Some(&currency));
-        let b = Amount::new(5.into(), Some(&currency));
-        let expected = Amount::new(2.into(), Some(&currency));
+        let mut pool = CommodityPool::new();
+        let currency = pool.create("EUR", None);
+        let a = Amount::new(10.into(), Some(currency));
+        let b = Amount::new(5.into(), Some(currency));
+        let expected = Amount::new(2.into(), Some(currency));
 
         let c = a / b;
 
@@ -359,4 +459,20 @@ mod tests {
 
         assert!(actual.is_none());
     }
+
+    #[test]
+    fn test_display() {
+        let mut pool = CommodityPool::new();
+        let eur = pool.create("EUR", None);
+        let usd = pool.create("$", None);
+        pool.get_mut(eur).set_format("1.000,00 EUR").unwrap();
+        pool.get_mut(usd).set_format("$1,000.00").unwrap();
+        let plain = pool.create("VEUR", None);
+
+        let format = |amount: Amount| amount.display(&pool).to_string();
+        assert_eq!("1.234.567,50 EUR", format(Amount::new("1234567.5".into(), Some(eur))));
+        assert_eq!("$-12.30", format(Amount::new("-12.3".into(), Some(usd))));
+        assert_eq!("12.3 VEUR", format(Amount::new("12.3".into(), Some(plain))));
+        assert_eq!("5", format(Amount::new(5.into(), None)));
+    }
 }
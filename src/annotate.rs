@@ -9,7 +9,7 @@
 use anyhow::Error;
 use chrono::NaiveDate;
 
-use crate::{amount::{Amount, Quantity}, parser::ISO_DATE_FORMAT, journal::Journal};
+use crate::{amount::{Amount, Quantity}, parser, journal::Journal};
 
 pub struct Annotation {
     /// Price per unit. The {} value in the Lot syntax.
@@ -30,29 +30,32 @@ impl Annotation {
         }
     }
 
-    pub fn parse(date: &str, quantity: &str, commodity_symbol: &str, journal: &mut Journal) -> Result<Self, Error> {
+    /// Parses the lot price and date. The dates without a year get the given year.
+    pub fn parse(
+        date: &str,
+        quantity: &str,
+        commodity_symbol: &str,
+        year: Option<i32>,
+        journal: &mut Journal,
+    ) -> Result<Self, Error> {
         // parse amount
         let commodity = journal.commodity_pool.find_or_create(commodity_symbol, None);
 
         let price = if let Some(quantity) = Quantity::from_str(quantity) {
-            Some(Amount::new(quantity, Some(commodity)))
+            Some(Amount::new(quantity, commodity))
         } else {
             None
         };
-        
-        let result = Self {
-            price: price,
-            date: match date.is_empty() {
-                true => None,
-                false => {
-                    let d =
-                        NaiveDate::parse_from_str(date, ISO_DATE_FORMAT).expect("successful parse");
-                    Some(d)
-                }
-            },
+
+        let date = match date.is_empty() {
+            true => None,
+            false => {
+                let input_format = journal.input_date_format.as_deref();
+                Some(parser::parse_date_in(date, year, input_format)?)
+            }
         };
-        
-        Ok(result)
+
+        Ok(Self { price, date })
     }
 }
 
@@ -67,12 +70,22 @@ mod tests {
         let mut journal = Journal::new();
         let expected_symbol = "EUR";
 
-        let actual = Annotation::parse("2023-01-10", "20", expected_symbol, &mut journal).unwrap();
+        let actual = Annotation::parse("2023-01-10", "20", expected_symbol, None, &mut journal).unwrap();
 
         assert_eq!("2023-01-10", actual.date.unwrap().to_string());
         assert_eq!(actual.price.unwrap().quantity, 20.into());
 
-        let symbol = actual.price.unwrap().get_commodity().unwrap().symbol.to_owned();
+        let commodity = actual.price.unwrap().get_commodity().unwrap();
+        let symbol = journal.commodity_pool.get(commodity).symbol.to_owned();
         assert_eq!(expected_symbol, symbol);
     }
+
+    #[test]
+    fn test_invalid_date() {
+        let mut journal = Journal::new();
+
+        let actual = Annotation::parse("2023-13-10", "20", "EUR", None, &mut journal);
+
+        assert!(actual.is_err());
+    }
 }
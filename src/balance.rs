@@ -11,7 +11,7 @@ use std::ops::{AddAssign, SubAssign};
 use crate::amount::Amount;
 
 /// Balance
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Balance {
     /// Map of commodity index, Amount
     // pub amounts: HashMap<CommodityIndex, Amount>, // try Symbol/Amount for easier search.
@@ -105,7 +105,7 @@ mod tests {
     use super::Balance;
     use crate::{
         amount::{Amount, Quantity},
-        commodity::Commodity,
+        commodity::CommodityId,
     };
     // use crate::pool::CommodityIndex;
 
@@ -149,11 +149,11 @@ mod tests {
 
     #[test]
     fn test_adding_two_amounts_with_commodities() {
-        let cdty = Commodity::new("ABC");
+        let cdty = CommodityId(1);
         let mut balance = Balance::new();
 
         // Act
-        let amount = Amount::new(25.into(), Some(&cdty));
+        let amount = Amount::new(25.into(), Some(cdty));
         balance.add(&amount);
 
         let amount = Amount::new(5.into(), None);
@@ -167,7 +167,7 @@ mod tests {
             balance.amounts.iter().nth(0).unwrap().quantity
         );
         assert_eq!(
-            Some(&cdty),
+            Some(cdty),
             balance.amounts.iter().nth(0).unwrap().get_commodity()
         );
 
@@ -180,15 +180,15 @@ mod tests {
 
     #[test]
     fn test_adding_two_amounts_with_some_commodities() {
-        let cdty1 = Commodity::new("CD1");
-        let cdty2 = Commodity::new("CD2");
+        let cdty1 = CommodityId(2);
+        let cdty2 = CommodityId(3);
         let mut balance = Balance::new();
 
         // Act
-        let amount = Amount::new(25.into(), Some(&cdty1));
+        let amount = Amount::new(25.into(), Some(cdty1));
         balance.add(&amount);
 
-        let amount = Amount::new(5.into(), Some(&cdty2));
+        let amount = Amount::new(5.into(), Some(cdty2));
         balance.add(&amount);
 
         // Assert
@@ -200,7 +200,7 @@ mod tests {
             balance.amounts.iter().nth(0).unwrap().quantity
         );
         assert_eq!(
-            Some(&cdty1),
+            Some(cdty1),
             balance.amounts.iter().nth(0).unwrap().get_commodity()
         );
 
@@ -209,21 +209,21 @@ mod tests {
             balance.amounts.iter().nth(1).unwrap().quantity
         );
         assert_eq!(
-            Some(&cdty2),
+            Some(cdty2),
             balance.amounts.iter().nth(1).unwrap().get_commodity()
         );
     }
 
     #[test]
     fn test_adding_two_amounts_with_same_commodity() {
-        let cdty = Commodity::new("BAM");
+        let cdty = CommodityId(4);
         let mut balance = Balance::new();
 
         // Act
-        let amount = Amount::new(25.into(), Some(&cdty));
+        let amount = Amount::new(25.into(), Some(cdty));
         balance.add(&amount);
 
-        let amount = Amount::new(5.into(), Some(&cdty));
+        let amount = Amount::new(5.into(), Some(cdty));
         balance.add(&amount);
 
         // Assert
@@ -235,7 +235,7 @@ mod tests {
             balance.amounts.iter().nth(0).unwrap().quantity
         );
         assert_eq!(
-            Some(&cdty),
+            Some(cdty),
             balance.amounts.iter().nth(0).unwrap().get_commodity()
         );
     }
@@ -243,7 +243,7 @@ mod tests {
     #[test]
     fn test_sub_assign() {
         let mut bal = Balance::new();
-        let amount = Amount::new(10.into(), Some(&Commodity::new("ABC")));
+        let amount = Amount::new(10.into(), Some(CommodityId(5)));
         let expected = amount.inverse();
 
         bal -= amount;
@@ -254,16 +254,16 @@ mod tests {
 
     #[test]
     fn test_addition() {
-        let cdty = Commodity::new("ABC");
+        let cdty = CommodityId(6);
         let mut bal1 = Balance::new();
-        bal1.add(&Amount::new(10.into(), Some(&cdty)));
+        bal1.add(&Amount::new(10.into(), Some(cdty)));
         let mut bal2 = Balance::new();
-        bal2.add(&Amount::new(15.into(), Some(&cdty)));
+        bal2.add(&Amount::new(15.into(), Some(cdty)));
 
         bal2 += bal1;
 
         assert_eq!(1, bal2.amounts.len());
         assert_eq!(bal2.amounts[0].quantity, 25.into());
-        assert_eq!(bal2.amounts[0].get_commodity(), Some(&cdty));
+        assert_eq!(bal2.amounts[0].get_commodity(), Some(cdty));
     }
 }
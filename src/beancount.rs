@@ -0,0 +1,806 @@
+/*!
+ * Beancount journals
+ *
+ * Reads the Beancount syntax by converting the directives into the journal text, which is
+ * then parsed as usual:
+ *
+ * - `open` is an `account` declaration, with the type from the root account, and `close`
+ *   is ignored;
+ * - `commodity` and `price` are the `commodity` and `P` directives;
+ * - `balance` is a balance assertion on the account and its sub-accounts, at the end of
+ *   the previous day;
+ * - `pad` is a transaction that assigns the balance of the following `balance` directive,
+ *   from the source account;
+ * - the transactions keep the flag, the payee, the tags (also from `pushtag`) and the
+ *   metadata. The narration is the note of the transaction. The cost specs, `{}`, become
+ *   the lot prices and dates.
+ *
+ * The directives are sorted by date, as Beancount does not depend on their order.
+ * `option`, `plugin`, `note`, `document`, `event`, `query` and `custom` are ignored.
+ *
+ * `write_journal` writes the journal in the Beancount syntax.
+ */
+
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{Duration, NaiveDate};
+
+use crate::{
+    account::{AccountId, AccountType},
+    amount::{Amount, Quantity},
+    journal::Journal,
+    parser::ISO_DATE_FORMAT,
+    pool::CommodityPool,
+    scanner,
+};
+
+/// The journal text of one or more Beancount directives.
+#[derive(Debug, PartialEq)]
+pub struct Entry {
+    pub text: String,
+    /// The source line of each line of the text.
+    lines: Vec<usize>,
+}
+
+impl Entry {
+    fn new(lines: Vec<(usize, String)>) -> Self {
+        let mut text = String::new();
+        for (_, line) in &lines {
+            text += line;
+            text += "\n";
+        }
+        Self {
+            text,
+            lines: lines.into_iter().map(|(line, _)| line).collect(),
+        }
+    }
+
+    /// The line in the Beancount source of the given line of the text, 1-based.
+    pub fn source_line(&self, line: usize) -> usize {
+        self.lines[line.clamp(1, self.lines.len()) - 1]
+    }
+}
+
+/// A directive, converted, before the sorting.
+struct Directive {
+    date: Option<NaiveDate>,
+    /// The order of the directives on the same day.
+    rank: i8,
+    kind: DirectiveKind,
+    lines: Vec<(usize, String)>,
+}
+
+enum DirectiveKind {
+    Other,
+    Balance { account: String, amount: String },
+    Pad { account: String, source: String },
+}
+
+/// Reads the Beancount source into the journal entries, in the order of the dates.
+/// The errors have the line in the source.
+pub fn read_entries(source: &str) -> Result<Vec<Entry>, (usize, String)> {
+    let mut directives = vec![];
+    let mut tags: Vec<String> = vec![];
+    let mut lines = source
+        .lines()
+        .enumerate()
+        .map(|(index, text)| (index + 1, text))
+        .peekable();
+
+    while let Some((line, text)) = lines.next() {
+        let header = strip_comment(text);
+        // Org-mode headings are allowed between the directives.
+        if header.trim().is_empty() || header.starts_with('*') {
+            continue;
+        }
+        if header.starts_with(char::is_whitespace) {
+            return Err((line, format!("Unexpected indented line: {}", text.trim())));
+        }
+
+        let mut body = vec![];
+        while let Some((body_line, body_text)) =
+            lines.next_if(|(_, next)| next.starts_with(char::is_whitespace))
+        {
+            let body_text = strip_comment(body_text).trim();
+            if !body_text.is_empty() {
+                body.push((body_line, body_text));
+            }
+        }
+
+        let directive = read_directive(line, header, &body, &mut tags).map_err(|e| (line, e))?;
+        directives.extend(directive);
+    }
+
+    // Stable, so the file order is kept within the same rank.
+    directives.sort_by_key(|directive| (directive.date, directive.rank));
+
+    let mut entries = vec![];
+    let mut pads: HashMap<String, (NaiveDate, String, usize)> = HashMap::new();
+    for directive in directives {
+        match directive.kind {
+            DirectiveKind::Pad { account, source } => {
+                let line = directive.lines.first().map_or(0, |(line, _)| *line);
+                pads.insert(account, (directive.date.unwrap_or_default(), source, line));
+            }
+            DirectiveKind::Balance { account, amount } => {
+                if let Some((date, source, line)) = pads.remove(&account) {
+                    entries.push(Entry::new(vec![
+                        (line, format!("{} Padding", date.format(ISO_DATE_FORMAT))),
+                        (line, format!("    {}  = {}", account, amount)),
+                        (line, format!("    {}", source)),
+                    ]));
+                }
+                entries.push(Entry::new(directive.lines));
+            }
+            DirectiveKind::Other => entries.push(Entry::new(directive.lines)),
+        }
+    }
+
+    Ok(entries)
+}
+
+fn read_directive(
+    line: usize,
+    header: &str,
+    body: &[(usize, &str)],
+    tags: &mut Vec<String>,
+) -> Result<Option<Directive>, String> {
+    let tokens = tokens(header);
+    let argument = |index: usize| -> Result<&str, String> {
+        tokens
+            .get(index)
+            .map(String::as_str)
+            .ok_or_else(|| format!("Incomplete directive: {}", header))
+    };
+    let other = |date, rank, lines| Directive {
+        date,
+        rank,
+        kind: DirectiveKind::Other,
+        lines,
+    };
+
+    match argument(0)? {
+        "include" => {
+            let path = unquote(argument(1)?);
+            return Ok(Some(other(
+                None,
+                0,
+                vec![(line, format!("include {}", path))],
+            )));
+        }
+        "pushtag" => {
+            tags.push(argument(1)?.trim_start_matches('#').to_owned());
+            return Ok(None);
+        }
+        "poptag" => {
+            let tag = argument(1)?.trim_start_matches('#');
+            if let Some(index) = tags.iter().rposition(|pushed| pushed == tag) {
+                tags.remove(index);
+            }
+            return Ok(None);
+        }
+        "option" | "plugin" | "pushmeta" | "popmeta" => return Ok(None),
+        _ => (),
+    }
+
+    let date = tokens[0].as_str();
+    let date = NaiveDate::parse_from_str(&date.replace('/', "-"), ISO_DATE_FORMAT)
+        .map_err(|_| format!("Invalid date: {}", date))?;
+    let date_text = date.format(ISO_DATE_FORMAT).to_string();
+
+    let directive = match argument(1)? {
+        "open" => {
+            let account = argument(2)?;
+            let mut declaration = format!("account {}", account);
+            if let Some(kind) = AccountType::infer(account) {
+                declaration += &format!("  ; type: {}", kind.name());
+            }
+            other(Some(date), -2, vec![(line, declaration)])
+        }
+        "commodity" => other(
+            Some(date),
+            -2,
+            vec![(line, format!("commodity {}", argument(2)?))],
+        ),
+        "price" => {
+            let price = tokens[3..].join(" ");
+            let text = format!("P {} {} {}", date_text, argument(2)?, price);
+            other(Some(date), 0, vec![(line, text)])
+        }
+        "balance" => {
+            let account = argument(2)?.to_owned();
+            // Without the tolerance, `~ 0.01`.
+            let amount: Vec<&str> = tokens[3..]
+                .iter()
+                .map(String::as_str)
+                .take_while(|token| *token != "~")
+                .collect();
+            let Some(currency) = amount.last() else {
+                return Err(format!("Incomplete directive: {}", header));
+            };
+            let amount = amount.join(" ");
+            // Beancount checks the balance at the start of the day.
+            let previous = (date - Duration::days(1)).format(ISO_DATE_FORMAT);
+            let lines = vec![
+                (line, format!("{} Balance", previous)),
+                (
+                    line,
+                    format!("    {}  0 {} =* {}", account, currency, amount),
+                ),
+            ];
+            Directive {
+                date: Some(date),
+                rank: -1,
+                kind: DirectiveKind::Balance { account, amount },
+                lines,
+            }
+        }
+        "pad" => Directive {
+            date: Some(date),
+            rank: 0,
+            kind: DirectiveKind::Pad {
+                account: argument(2)?.to_owned(),
+                source: argument(3)?.to_owned(),
+            },
+            lines: vec![(line, String::new())],
+        },
+        "close" | "note" | "document" | "event" | "query" | "custom" => return Ok(None),
+        "txn" | "*" | "!" => other(
+            Some(date),
+            0,
+            read_transaction(line, &date_text, &tokens, body, tags)?,
+        ),
+        flag if flag.chars().count() == 1 => other(
+            Some(date),
+            0,
+            read_transaction(line, &date_text, &tokens, body, tags)?,
+        ),
+        kind => return Err(format!("Unknown directive: {}", kind)),
+    };
+
+    Ok(Some(directive))
+}
+
+/// The transaction, from the header tokens, date and flag first, and the metadata and
+/// postings lines.
+fn read_transaction(
+    line: usize,
+    date: &str,
+    tokens: &[String],
+    body: &[(usize, &str)],
+    pushed_tags: &[String],
+) -> Result<Vec<(usize, String)>, String> {
+    let mut strings = vec![];
+    let mut tags: Vec<&str> = pushed_tags.iter().map(String::as_str).collect();
+    for token in &tokens[2..] {
+        if token.starts_with('"') {
+            strings.push(unquote(token));
+        } else if let Some(tag) = token.strip_prefix('#') {
+            tags.push(tag);
+        }
+        // The links, `^link`, are not kept.
+    }
+    let (payee, narration) = match strings.as_slice() {
+        [] => (String::new(), String::new()),
+        [payee] => (payee.to_owned(), String::new()),
+        [payee, narration, ..] => (payee.to_owned(), narration.to_owned()),
+    };
+
+    let mut header = date.to_owned();
+    if let flag @ ("*" | "!") = tokens[1].as_str() {
+        header += " ";
+        header += flag;
+    }
+    header += " ";
+    header += &payee;
+    let mut lines = vec![(line, header)];
+
+    // The narration is the last note of the transaction, before the postings.
+    let mut note = narration;
+    if !tags.is_empty() {
+        note += &format!(" :{}:", tags.join(":"));
+    }
+    let mut note = match note.trim() {
+        "" => None,
+        note => Some((line, format!("    ; {}", note))),
+    };
+
+    for (body_line, text) in body {
+        if let Some((key, value)) = metadata(text) {
+            lines.push((*body_line, format!("    ; {}: {}", key, unquote(value))));
+            continue;
+        }
+        lines.extend(note.take());
+
+        let posting = text.strip_prefix(['*', '!']).map_or(*text, str::trim_start);
+        let (account, amount) = posting
+            .split_once(char::is_whitespace)
+            .unwrap_or((posting, ""));
+        let amount = posting_amount(amount).map_err(|e| format!("line {}: {}", body_line, e))?;
+        let posting = match amount.is_empty() {
+            true => format!("    {}", account),
+            false => format!("    {}  {}", account, amount),
+        };
+        lines.push((*body_line, posting));
+    }
+    lines.extend(note);
+
+    Ok(lines)
+}
+
+/// The metadata line, `key: value`. The keys start with a lowercase letter.
+fn metadata(text: &str) -> Option<(&str, &str)> {
+    let (key, value) = text.split_once(':')?;
+    let valid = key.starts_with(|c: char| c.is_ascii_lowercase())
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    valid.then_some((key, value.trim()))
+}
+
+/// The amount of the posting, with the cost spec as the lot price and date.
+/// `10 VEUR {20 EUR, 2023-05-01, "lot"}` is `10 VEUR {20 EUR} [2023-05-01]` and the total
+/// cost, `{{200 EUR}}`, is converted to the price per unit.
+fn posting_amount(text: &str) -> Result<String, String> {
+    let text = text.trim();
+    let Some(open) = text.find('{') else {
+        return Ok(text.to_owned());
+    };
+    let total = text[open..].starts_with("{{");
+    let (start, closing) = match total {
+        true => (open + 2, "}}"),
+        false => (open + 1, "}"),
+    };
+    let Some(length) = text[start..].find(closing) else {
+        return Err(format!("Unterminated cost: {}", text));
+    };
+    let units = text[..open].trim();
+    let spec = &text[start..start + length];
+    let rest = text[start + length + closing.len()..].trim();
+
+    let mut price = None;
+    let mut date = None;
+    for part in spec.split(',').map(str::trim) {
+        if part.is_empty() || part.starts_with('"') || part == "*" {
+            continue;
+        }
+        if NaiveDate::parse_from_str(part, ISO_DATE_FORMAT).is_ok() {
+            date = Some(part);
+        } else if part.contains('#') {
+            return Err(format!("Unsupported cost: {}", spec));
+        } else {
+            price = Some(part.to_owned());
+        }
+    }
+
+    if let (true, Some(total_cost)) = (total, &price) {
+        let quantity = units.split_whitespace().next().and_then(Quantity::from_str);
+        let (cost, currency) = total_cost.split_once(' ').unwrap_or((total_cost, ""));
+        let (Some(quantity), Some(cost)) = (quantity, Quantity::from_str(cost)) else {
+            return Err(format!("Invalid cost: {}", spec));
+        };
+        let mut quantity = quantity;
+        quantity.set_sign_positive();
+        price = Some(format!("{} {}", cost / quantity, currency.trim()));
+    }
+
+    let mut amount = units.to_owned();
+    if let Some(price) = price {
+        amount += &format!(" {{{}}}", price);
+    }
+    if let Some(date) = date {
+        amount += &format!(" [{}]", date);
+    }
+    if !rest.is_empty() {
+        amount += " ";
+        amount += rest;
+    }
+    Ok(amount)
+}
+
+/// The text without the comment, `; ...`, outside of the strings.
+fn strip_comment(text: &str) -> &str {
+    let mut quoted = false;
+    let mut escaped = false;
+    for (index, c) in text.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            ';' if !quoted => return text[..index].trim_end(),
+            _ => (),
+        }
+    }
+    text.trim_end()
+}
+
+/// The words of the line. The strings are kept with the quotes.
+fn tokens(text: &str) -> Vec<String> {
+    let mut tokens = vec![];
+    let mut token = String::new();
+    let mut quoted = false;
+    let mut escaped = false;
+
+    for c in text.chars() {
+        match c {
+            _ if escaped => {
+                token.push(c);
+                escaped = false;
+            }
+            '\\' if quoted => {
+                token.push(c);
+                escaped = true;
+            }
+            '"' => {
+                token.push(c);
+                quoted = !quoted;
+            }
+            _ if c.is_whitespace() && !quoted => {
+                if !token.is_empty() {
+                    tokens.push(std::mem::take(&mut token));
+                }
+            }
+            _ => token.push(c),
+        }
+    }
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+    tokens
+}
+
+/// The string without the quotes and the escapes.
+fn unquote(text: &str) -> String {
+    let Some(inner) = text
+        .strip_prefix('"')
+        .and_then(|text| text.strip_suffix('"'))
+    else {
+        return text.to_owned();
+    };
+    let mut result = String::new();
+    let mut escaped = false;
+    for c in inner.chars() {
+        match c {
+            '\\' if !escaped => escaped = true,
+            _ => {
+                result.push(c);
+                escaped = false;
+            }
+        }
+    }
+    result
+}
+
+/// Writes the journal in the Beancount syntax. The accounts are opened at their first
+/// post, the balance assertions are the `balance` directives on the next day, and the
+/// account names and commodities are adjusted to what Beancount accepts, i.e. `$` is `USD`.
+pub fn write_journal(journal: &Journal) -> Vec<String> {
+    let pool = &journal.commodity_pool;
+    let mut output = vec![];
+
+    let mut opened: BTreeMap<String, NaiveDate> = BTreeMap::new();
+    for xact in &journal.xacts {
+        let date = xact.date.unwrap_or_default();
+        for post in &xact.posts {
+            let name = account_name(journal, post.account);
+            let open = opened.entry(name).or_insert(date);
+            *open = date.min(*open);
+        }
+    }
+    for (account, date) in &opened {
+        output.push(format!("{} open {}", date.format(ISO_DATE_FORMAT), account));
+    }
+
+    for price in pool.prices() {
+        if !output.is_empty() {
+            output.push(String::new());
+        }
+        output.push(format!(
+            "{} price {} {}",
+            price.datetime.date().format(ISO_DATE_FORMAT),
+            commodity_name(&pool.get(price.commodity).symbol),
+            amount_text(&price.price, pool)
+        ));
+    }
+
+    for xact in &journal.xacts {
+        let date = xact.date.unwrap_or_default();
+        let next_day = (date + Duration::days(1)).format(ISO_DATE_FORMAT);
+        let mut balances = vec![];
+        for post in &xact.posts {
+            if let Some(assertion) = &post.assertion {
+                balances.push(format!(
+                    "{} balance {}  {}",
+                    next_day,
+                    account_name(journal, post.account),
+                    amount_text(&assertion.amount, pool)
+                ));
+            }
+        }
+
+        output.push(String::new());
+        // Only the balance assertions, as written by the `balance` directives.
+        let assertions_only = xact.posts.iter().all(|post| {
+            post.assertion.is_some() && post.amount.is_none_or(|amount| amount.is_zero())
+        });
+        if assertions_only && !xact.posts.is_empty() {
+            output.extend(balances);
+            continue;
+        }
+
+        let (flag, payee) = match xact.payee.split_once(' ') {
+            Some((flag @ ("*" | "!"), payee)) => (flag, payee),
+            _ => ("*", xact.payee.as_str()),
+        };
+        let narration = xact.note.as_deref().map(scanner::note_text);
+        let mut header = format!(
+            "{} {} \"{}\" \"{}\"",
+            date.format(ISO_DATE_FORMAT),
+            flag,
+            escape(payee),
+            escape(narration.as_deref().unwrap_or_default())
+        );
+        for (tag, value) in &xact.metadata {
+            if value.is_none() {
+                header += &format!(" #{}", tag_name(tag));
+            }
+        }
+        output.push(header);
+        if let Some(code) = &xact.code {
+            output.push(format!("  code: \"{}\"", escape(code)));
+        }
+        output.extend(metadata_lines(&xact.metadata, "  "));
+
+        for post in &xact.posts {
+            let mut line = format!("  {}", account_name(journal, post.account));
+            if let Some(amount) = &post.amount {
+                line += "  ";
+                line += &amount_text(amount, pool);
+                if let Some(cost) = &post.cost {
+                    if cost.get_commodity() != amount.get_commodity() {
+                        line += " @@ ";
+                        line += &amount_text(&cost.abs(), pool);
+                    }
+                }
+            }
+            let note = post.note.as_deref().map(scanner::note_text);
+            if let Some(note) = note.filter(|note| !note.is_empty()) {
+                line += "  ; ";
+                line += &note;
+            }
+            output.push(line);
+            output.extend(metadata_lines(&post.metadata, "    "));
+        }
+        output.extend(balances);
+    }
+
+    output
+}
+
+fn metadata_lines(metadata: &BTreeMap<String, Option<String>>, indent: &str) -> Vec<String> {
+    metadata
+        .iter()
+        .filter_map(|(key, value)| {
+            let value = value.as_ref()?;
+            Some(format!(
+                "{}{}: \"{}\"",
+                indent,
+                key_name(key),
+                escape(value)
+            ))
+        })
+        .collect()
+}
+
+fn amount_text(amount: &Amount, pool: &CommodityPool) -> String {
+    let symbol = amount
+        .get_commodity()
+        .map(|id| pool.get(id).symbol.as_str())
+        .unwrap_or_default();
+    format!("{} {}", amount.quantity, commodity_name(symbol))
+}
+
+/// The account under one of the Beancount root accounts. The other accounts go under the
+/// root for their type, or under `Equity`.
+fn account_name(journal: &Journal, account_id: AccountId) -> String {
+    let account = journal.get_account(account_id);
+    let fullname = account.fullname();
+    let mut components: Vec<String> = fullname.split(':').map(component_name).collect();
+
+    let root = |kind| match kind {
+        AccountType::Asset | AccountType::Cash => "Assets",
+        AccountType::Liability => "Liabilities",
+        AccountType::Equity | AccountType::Conversion => "Equity",
+        AccountType::Revenue => "Income",
+        AccountType::Expense => "Expenses",
+    };
+    match AccountType::infer(fullname) {
+        Some(kind) => components[0] = root(kind).to_owned(),
+        None => {
+            // The declared type, of the account or of its nearest parent.
+            let kind = std::iter::successors(Some(account), |account| {
+                account.parent.map(|id| journal.get_account(id))
+            })
+            .find_map(|account| account.kind)
+            .unwrap_or(AccountType::Equity);
+            components.insert(0, root(kind).to_owned());
+        }
+    }
+    components.join(":")
+}
+
+/// The account component starts with a capital letter or a digit, and has only the
+/// letters, digits and dashes.
+fn component_name(name: &str) -> String {
+    let mut result: String = name
+        .trim()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '-' })
+        .collect();
+    match result.chars().next() {
+        Some(first) if first.is_alphanumeric() => {
+            result = first.to_uppercase().chain(result.chars().skip(1)).collect();
+        }
+        _ => result.insert(0, 'X'),
+    }
+    result
+}
+
+/// The commodity in capitals, i.e. `USD` for `$`.
+fn commodity_name(symbol: &str) -> String {
+    let name = match symbol {
+        "$" => "USD".to_owned(),
+        "€" => "EUR".to_owned(),
+        "£" => "GBP".to_owned(),
+        "¥" => "JPY".to_owned(),
+        _ => symbol
+            .trim_matches('"')
+            .to_uppercase()
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric() || "'._-".contains(*c))
+            .take(24)
+            .collect(),
+    };
+    match name.chars().next() {
+        None => "UNKNOWN".to_owned(),
+        Some(first) if !first.is_ascii_uppercase() => format!("C{}", name),
+        _ => name,
+    }
+}
+
+fn key_name(key: &str) -> String {
+    let name: String = key
+        .chars()
+        .map(|c| match c.is_ascii_alphanumeric() || c == '_' {
+            true => c,
+            false => '-',
+        })
+        .collect();
+    match name.chars().next() {
+        Some(first) if first.is_ascii_lowercase() => name,
+        Some(first) if first.is_ascii_uppercase() => {
+            first.to_ascii_lowercase().to_string() + &name[1..]
+        }
+        _ => format!("x{}", name),
+    }
+}
+
+fn tag_name(tag: &str) -> String {
+    tag.chars()
+        .map(|c| match c.is_alphanumeric() || "-_/.".contains(c) {
+            true => c,
+            false => '-',
+        })
+        .collect()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{posting_amount, read_entries, strip_comment, tokens, write_journal};
+    use crate::{journal::Journal, parse_file, parse_sources};
+
+    #[test]
+    fn test_tokens() {
+        assert_eq!(
+            vec!["2023-05-01", "*", r#""Shop; Co""#, r#""a \"b\"""#, "#tag"],
+            tokens(r#"2023-05-01 * "Shop; Co" "a \"b\"" #tag"#)
+        );
+        assert_eq!(
+            r#"2023-05-01 * "Shop; Co""#,
+            strip_comment(r#"2023-05-01 * "Shop; Co" ; x"#)
+        );
+    }
+
+    #[test]
+    fn test_posting_amount() {
+        assert_eq!("-10 USD", posting_amount("  -10 USD").unwrap());
+        assert_eq!(
+            "10 VEUR {20 EUR} [2023-05-01]",
+            posting_amount(r#"10 VEUR {20 EUR, 2023-05-01, "first"}"#).unwrap()
+        );
+        assert_eq!(
+            "10 VEUR {20 EUR}",
+            posting_amount("10 VEUR {{200 EUR}}").unwrap()
+        );
+        assert_eq!(
+            "-5 VEUR @ 21 EUR",
+            posting_amount("-5 VEUR {} @ 21 EUR").unwrap()
+        );
+        assert!(posting_amount("10 VEUR {20 EUR").is_err());
+    }
+
+    #[test]
+    fn test_read_entries_order() {
+        let source = r#"
+2023-05-02 * "Shop" "Groceries" #food
+  Expenses:Food  10 EUR
+  Assets:Cash
+
+2023-05-01 open Assets:Cash EUR
+2023-05-03 balance Assets:Cash  -10 EUR
+"#;
+        let entries = read_entries(source).unwrap();
+
+        assert_eq!(3, entries.len());
+        assert_eq!("account Assets:Cash  ; type: Asset\n", entries[0].text);
+        assert_eq!(
+            "2023-05-02 * Shop\n    ; Groceries :food:\n    Expenses:Food  10 EUR\n    Assets:Cash\n",
+            entries[1].text
+        );
+        assert_eq!(3, entries[1].source_line(3));
+        assert_eq!(
+            "2023-05-02 Balance\n    Assets:Cash  0 EUR =* -10 EUR\n",
+            entries[2].text
+        );
+    }
+
+    #[test]
+    fn test_unknown_directive() {
+        assert_eq!(
+            Err((2, "Unknown directive: opened".to_owned())),
+            read_entries("\n2023-05-01 opened Assets:Cash\n")
+        );
+    }
+
+    #[test]
+    fn test_write_journal() {
+        let mut journal = Journal::new();
+        let text = "P 2023-05-01 VEUR 20 EUR\n\n2023-05-01 * (12) Shop  ; Groceries :food:\n    Expenses:Food  10 €  ; receipt: 123\n    Cash  = -10 €\n";
+        parse_sources([("test.ledger", text.as_bytes())], &mut journal).unwrap();
+
+        let actual = write_journal(&journal);
+
+        assert_eq!(
+            vec![
+                "2023-05-01 open Equity:Cash",
+                "2023-05-01 open Expenses:Food",
+                "",
+                "2023-05-01 price VEUR 20 EUR",
+                "",
+                r#"2023-05-01 * "Shop" "Groceries" #food"#,
+                r#"  code: "12""#,
+                "  Expenses:Food  10 EUR",
+                r#"    receipt: "123""#,
+                "  Equity:Cash  -10 EUR",
+                "2023-05-02 balance Equity:Cash  -10 EUR",
+            ],
+            actual
+        );
+    }
+
+    /// The sub-accounts have the type of the parent, `account Spending  ; type: X`.
+    #[test]
+    fn test_write_inherited_account_type() {
+        let mut journal = Journal::new();
+        parse_file("tests/sample.hledger", &mut journal).unwrap();
+
+        let actual = write_journal(&journal);
+
+        assert!(actual.contains(&"2023-01-05 open Expenses:Spending:Food".to_owned()));
+        assert!(actual.contains(&"  Expenses:Spending:Food  45.30 EUR".to_owned()));
+    }
+}
@@ -0,0 +1,13 @@
+//! Ledger language server.
+//! Speaks the Language Server Protocol over stdin and stdout. The log goes to stderr.
+
+use std::io;
+
+fn main() {
+    env_logger::init();
+
+    let code = ledger_rs_lib::lsp::run(io::stdin().lock(), io::stdout().lock())
+        .expect("language server I/O");
+
+    std::process::exit(code);
+}
@@ -5,19 +5,51 @@
  */
 
 use chrono::NaiveDateTime;
+use serde::Serialize;
+
+use crate::{amount::Amount, pool::CommodityIndex, scanner};
+
+// Commodity flags, as in commodity.h
+
+/// The symbol is written after the quantity, i.e. `10 EUR`.
+pub const COMMODITY_STYLE_SUFFIXED: u16 = 0x001;
+/// There is a space between the symbol and the quantity.
+pub const COMMODITY_STYLE_SEPARATED: u16 = 0x002;
+/// The comma is the decimal mark, i.e. `1.000,00`.
+pub const COMMODITY_STYLE_DECIMAL_COMMA: u16 = 0x004;
+/// The thousands are separated, i.e. `1,000.00`.
+pub const COMMODITY_STYLE_THOUSANDS: u16 = 0x008;
+/// The style is set with the `format` directive and is not changed by the amounts in the journal.
+pub const COMMODITY_STYLE_NO_MIGRATE: u16 = 0x010;
+/// The commodity is declared with the `commodity` directive.
+pub const COMMODITY_KNOWN: u16 = 0x080;
+/// The commodity is not valued at market prices.
+pub const COMMODITY_NOMARKET: u16 = 0x100;
+
+/// Index of the commodity in the commodity pool.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+pub struct CommodityId(pub(crate) usize);
+
+impl CommodityId {
+    pub fn index(&self) -> usize {
+        self.0
+    }
+}
 
-use crate::{amount::Amount, pool::CommodityIndex};
-
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize)]
 pub struct Commodity {
     pub symbol: String,
     /// Index in the commodity graph.
+    #[serde(skip)]
     pub graph_index: Option<CommodityIndex>,
-    // precision
+    pub precision: u16,
+    pub flags: u16,
     pub name: Option<String>,
     pub note: Option<String>,
-    // smaller: Option<Amount>
-    // larger: Option<Amount>
+    /// The amount of the smaller unit in one of this, from `C 1h = 60m`, i.e. `60m` for `h`.
+    pub smaller: Option<Amount>,
+    /// One of the larger unit, i.e. `1h` for `m`.
+    pub larger: Option<Amount>,
     // value_expr: Option<>
 
     // commodity_pool
@@ -32,11 +64,94 @@ impl Commodity {
         Self {
             symbol: symbol.to_owned(),
             graph_index: None,
+            precision: 0,
+            flags: 0,
             name: None,
             note: None,
+            smaller: None,
+            larger: None,
             annotated: false,
         }
     }
+
+    pub fn has_flags(&self, flags: u16) -> bool {
+        self.flags & flags == flags
+    }
+
+    pub fn add_flags(&mut self, flags: u16) {
+        self.flags |= flags;
+    }
+
+    pub fn drop_flags(&mut self, flags: u16) {
+        self.flags &= !flags;
+    }
+
+    /// Sets the display style from a sample amount, i.e. `1.000,00 EUR`.
+    ///
+    /// void instance_t::commodity_format_directive(commodity_t& comm, string format)
+    pub fn set_format(&mut self, sample: &str) -> Result<(), String> {
+        let sample = sample.trim();
+        let (tokens, rest) = scanner::scan_amount(sample);
+        if tokens.quantity.is_empty() || !rest.is_empty() {
+            return Err(format!("Invalid commodity format: {}", sample));
+        }
+        if tokens.symbol != self.symbol {
+            return Err(format!(
+                "commodity directive symbol {} and format directive symbol {} should be the same",
+                self.symbol, tokens.symbol
+            ));
+        }
+
+        self.drop_flags(
+            COMMODITY_STYLE_SUFFIXED
+                | COMMODITY_STYLE_SEPARATED
+                | COMMODITY_STYLE_DECIMAL_COMMA
+                | COMMODITY_STYLE_THOUSANDS,
+        );
+
+        // symbol placement
+        if sample.starts_with(tokens.quantity) {
+            self.add_flags(COMMODITY_STYLE_SUFFIXED);
+            if sample[tokens.quantity.len()..].starts_with(char::is_whitespace) {
+                self.add_flags(COMMODITY_STYLE_SEPARATED);
+            }
+        } else if sample[tokens.symbol.len()..].starts_with(char::is_whitespace) {
+            self.add_flags(COMMODITY_STYLE_SEPARATED);
+        }
+
+        // The last separator is the decimal mark, unless it is the only one and is repeated.
+        let quantity = tokens.quantity;
+        let decimal_mark = match (quantity.rfind('.'), quantity.rfind(',')) {
+            (Some(dot), Some(comma)) => {
+                self.add_flags(COMMODITY_STYLE_THOUSANDS);
+                Some(if dot > comma { dot } else { comma })
+            }
+            (Some(index), None) | (None, Some(index)) => {
+                let mark = &quantity[index..index + 1];
+                if quantity.matches(mark).count() > 1 {
+                    self.add_flags(COMMODITY_STYLE_THOUSANDS);
+                    None
+                } else {
+                    Some(index)
+                }
+            }
+            (None, None) => None,
+        };
+
+        self.precision = 0;
+        if let Some(index) = decimal_mark {
+            if quantity[index..].starts_with(',') {
+                self.add_flags(COMMODITY_STYLE_DECIMAL_COMMA);
+            }
+            self.precision = (quantity.len() - index - 1) as u16;
+        } else if quantity.contains('.') {
+            // `1.000.000` uses the comma as the decimal mark.
+            self.add_flags(COMMODITY_STYLE_DECIMAL_COMMA);
+        }
+
+        self.add_flags(COMMODITY_STYLE_NO_MIGRATE);
+        Ok(())
+    }
 }
 
 /// commodity.cc
@@ -53,12 +168,6 @@ pub(crate) fn find_price(commodity: &Commodity, moment: NaiveDateTime, oldest: N
     todo!()
 }
 
-pub(crate) fn from_ptr<'a>(ptr: *const Commodity) -> &'a Commodity {
-    unsafe {
-        &*ptr
-    }
-}
-
 #[derive(Debug, PartialEq, Eq)]
 pub(crate) struct PricePoint {
     pub when: NaiveDateTime,
@@ -73,7 +182,7 @@ impl PricePoint {
 
 #[cfg(test)]
 mod tests {
-    use super::Commodity;
+    use super::*;
 
     #[test]
     fn test_comparison() {
@@ -83,6 +192,42 @@ mod tests {
         assert!(c1 == c2);
     }
 
+    #[test]
+    fn test_set_format_suffixed() {
+        let mut eur = Commodity::new("EUR");
+
+        eur.set_format("1.000,00 EUR").unwrap();
+
+        assert!(eur.has_flags(
+            COMMODITY_STYLE_SUFFIXED
+                | COMMODITY_STYLE_SEPARATED
+                | COMMODITY_STYLE_THOUSANDS
+                | COMMODITY_STYLE_DECIMAL_COMMA
+                | COMMODITY_STYLE_NO_MIGRATE
+        ));
+        assert_eq!(2, eur.precision);
+    }
+
+    #[test]
+    fn test_set_format_prefixed() {
+        let mut usd = Commodity::new("$");
+
+        usd.set_format("$1,000.000").unwrap();
+
+        assert!(!usd.has_flags(COMMODITY_STYLE_SUFFIXED));
+        assert!(!usd.has_flags(COMMODITY_STYLE_SEPARATED));
+        assert!(!usd.has_flags(COMMODITY_STYLE_DECIMAL_COMMA));
+        assert!(usd.has_flags(COMMODITY_STYLE_THOUSANDS));
+        assert_eq!(3, usd.precision);
+    }
+
+    #[test]
+    fn test_set_format_symbol_mismatch() {
+        let mut eur = Commodity::new("EUR");
+
+        assert!(eur.set_format("1.00 USD").is_err());
+    }
+
     #[test]
     fn test_comparison_ne() {
         let c1 = Commodity::new("EUR");
@@ -0,0 +1,288 @@
+/*!
+ * Sorting of the report items
+ *
+ * compare.h + .cc
+ *
+ * The sort order comes from `-S` or `--sort`, as a comma-separated list of the values to
+ * sort by. A `-` in front of the value reverses the order, i.e.
+ *
+ * `date`
+ * `-amount`
+ * `payee, -date`
+ *
+ * The items with equal values keep their order from the journal.
+ */
+
+use std::cmp::Ordering;
+
+use crate::{
+    account::Account, amount::Amount, journal::Journal, post::Post,
+    xact::Xact,
+};
+
+/// The value to sort by.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Term {
+    Date,
+    AuxDate,
+    Payee,
+    Account,
+    Amount,
+    AbsAmount,
+    Commodity,
+    Note,
+    Total,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct SortKey {
+    term: Term,
+    descending: bool,
+}
+
+/// The parsed sort expression.
+///
+/// template <typename T> class compare_items
+#[derive(Debug, Clone, PartialEq)]
+pub struct SortOrder {
+    keys: Vec<SortKey>,
+}
+
+impl SortOrder {
+    /// Parses the sort expression, i.e. `-date, payee`.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut keys = vec![];
+
+        for part in text.split(',') {
+            let part = part.trim();
+            let (descending, name) = match part.strip_prefix('-') {
+                Some(name) => (true, name.trim()),
+                None => (false, part),
+            };
+            let term = match name {
+                "d" | "date" => Term::Date,
+                "aux_date" | "effective_date" => Term::AuxDate,
+                "payee" => Term::Payee,
+                "account" => Term::Account,
+                "amount" => Term::Amount,
+                "abs(amount)" => Term::AbsAmount,
+                "commodity" => Term::Commodity,
+                "note" => Term::Note,
+                "total" => Term::Total,
+                "" => return Err(format!("Missing sort value in: {}", text)),
+                other => return Err(format!("Unknown sort value: {}", other)),
+            };
+            keys.push(SortKey { term, descending });
+        }
+
+        Ok(Self { keys })
+    }
+
+    /// Compares the postings. The `total` is the posting amount.
+    pub fn compare_posts(&self, a: &Post, b: &Post, journal: &Journal) -> Ordering {
+        self.compare_by(|term| compare_posts_by(term, a, b, journal))
+    }
+
+    /// Compares the transactions. The posting values are taken from the first posting.
+    pub fn compare_xacts(&self, a: &Xact, b: &Xact, journal: &Journal) -> Ordering {
+        self.compare_by(|term| match term {
+            Term::Date => a.date.cmp(&b.date),
+            Term::AuxDate => a.aux_date.cmp(&b.aux_date),
+            Term::Payee => a.payee.cmp(&b.payee),
+            Term::Note => a.note.cmp(&b.note),
+            _ => match (a.posts.first(), b.posts.first()) {
+                (Some(post_a), Some(post_b)) => compare_posts_by(term, post_a, post_b, journal),
+                (post_a, post_b) => post_a.is_some().cmp(&post_b.is_some()),
+            },
+        })
+    }
+
+    /// Compares the accounts. The `amount` and the `total` are the account totals, as
+    /// shown in the balance report. The other values, i.e. `date`, leave the order as is.
+    pub fn compare_accounts(&self, a: &Account, b: &Account, journal: &Journal) -> Ordering {
+        self.compare_by(|term| match term {
+            Term::Account => a.fullname().cmp(b.fullname()),
+            Term::Amount | Term::Total => {
+                compare_balances(&a.total(journal).amounts, &b.total(journal).amounts, journal)
+            }
+            Term::AbsAmount => {
+                let abs = |account: &Account| -> Vec<Amount> {
+                    account.total(journal).amounts.iter().map(|x| x.abs()).collect()
+                };
+                compare_balances(&abs(a), &abs(b), journal)
+            }
+            _ => Ordering::Equal,
+        })
+    }
+
+    /// Compares by each key in turn, until there is a difference.
+    fn compare_by<F>(&self, compare: F) -> Ordering
+    where
+        F: Fn(Term) -> Ordering,
+    {
+        for key in &self.keys {
+            let ordering = compare(key.term);
+            let ordering = if key.descending {
+                ordering.reverse()
+            } else {
+                ordering
+            };
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+        Ordering::Equal
+    }
+}
+
+fn compare_posts_by(term: Term, a: &Post, b: &Post, journal: &Journal) -> Ordering {
+    let xact_a = journal.get_xact(a.xact);
+    let xact_b = journal.get_xact(b.xact);
+    match term {
+        Term::Date => xact_a.date.cmp(&xact_b.date),
+        Term::AuxDate => xact_a.aux_date.cmp(&xact_b.aux_date),
+        Term::Payee => xact_a.payee.cmp(&xact_b.payee),
+        Term::Account => journal
+            .get_account(a.account)
+            .fullname()
+            .cmp(journal.get_account(b.account).fullname()),
+        Term::Amount | Term::Total => compare_amounts(a.amount, b.amount, journal),
+        Term::AbsAmount => {
+            compare_amounts(a.amount.map(|x| x.abs()), b.amount.map(|x| x.abs()), journal)
+        }
+        Term::Commodity => commodity_symbol(a, journal).cmp(commodity_symbol(b, journal)),
+        Term::Note => a.note.cmp(&b.note),
+    }
+}
+
+/// Compares the amounts by the commodity symbol, then by the quantity, as Ledger does.
+fn compare_amounts(a: Option<Amount>, b: Option<Amount>, journal: &Journal) -> Ordering {
+    let key = |amount: Option<Amount>| amount.map(|x| (symbol(&x, journal), x.quantity));
+    key(a).cmp(&key(b))
+}
+
+/// Compares the amounts in the order of the commodities in the balances.
+fn compare_balances(a: &[Amount], b: &[Amount], journal: &Journal) -> Ordering {
+    let keys = |amounts: &[Amount]| -> Vec<_> {
+        amounts.iter().map(|x| (symbol(x, journal), x.quantity)).collect()
+    };
+    keys(a).cmp(&keys(b))
+}
+
+fn commodity_symbol<'a>(post: &Post, journal: &'a Journal) -> &'a str {
+    post.amount.map_or("", |amount| symbol(&amount, journal))
+}
+
+fn symbol<'a>(amount: &Amount, journal: &'a Journal) -> &'a str {
+    match amount.get_commodity() {
+        Some(id) => &journal.commodity_pool.get(id).symbol,
+        None => "",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::SortOrder;
+    use crate::{journal::Journal, parser};
+
+    fn journal() -> Journal {
+        let src = r#"2023-05-03 Bakery
+    Expenses:Food  5 EUR
+    Assets:Cash
+
+2023-05-01 Supermarket
+    Expenses:Food  20 EUR
+    Assets:Cash
+
+2023-05-02 Apartment
+    Expenses:Rent  500 EUR
+    Assets:Bank
+"#;
+        let mut journal = Journal::new();
+        parser::read_into_journal(Cursor::new(src), &mut journal).unwrap();
+        journal
+    }
+
+    fn sorted_payees<'a>(journal: &'a Journal, order: &SortOrder) -> Vec<&'a str> {
+        let mut xacts: Vec<_> = journal.xacts.iter().collect();
+        xacts.sort_by(|a, b| order.compare_xacts(a, b, journal));
+        xacts.iter().map(|xact| xact.payee.as_str()).collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        assert!(SortOrder::parse("date").is_ok());
+        assert!(SortOrder::parse("-amount, payee").is_ok());
+        assert!(SortOrder::parse("date,").is_err());
+        assert!(SortOrder::parse("size").is_err());
+    }
+
+    #[test]
+    fn test_sort_xacts_by_date() {
+        let journal = journal();
+
+        let order = SortOrder::parse("date").unwrap();
+
+        assert_eq!(
+            vec!["Supermarket", "Apartment", "Bakery"],
+            sorted_payees(&journal, &order)
+        );
+    }
+
+    #[test]
+    fn test_sort_xacts_descending() {
+        let journal = journal();
+
+        let order = SortOrder::parse("-amount").unwrap();
+
+        assert_eq!(
+            vec!["Apartment", "Supermarket", "Bakery"],
+            sorted_payees(&journal, &order)
+        );
+    }
+
+    #[test]
+    fn test_sort_accounts_by_total() {
+        let journal = journal();
+        let expenses = journal.find_account("Expenses").unwrap();
+        let mut accounts: Vec<_> = expenses
+            .accounts
+            .values()
+            .map(|id| journal.get_account(*id))
+            .collect();
+
+        let order = SortOrder::parse("-total").unwrap();
+        accounts.sort_by(|a, b| order.compare_accounts(a, b, &journal));
+
+        let names: Vec<&str> = accounts.iter().map(|a| a.fullname()).collect();
+        assert_eq!(vec!["Expenses:Rent", "Expenses:Food"], names);
+    }
+
+    #[test]
+    fn test_sort_by_amount_and_commodity() {
+        let src = r#"2023-05-01 Dollars
+    Expenses:Food  5 USD
+    Assets:Cash
+
+2023-05-02 Euros
+    Expenses:Food  5 EUR
+    Assets:Cash
+
+2023-05-03 More euros
+    Expenses:Food  7 EUR
+    Assets:Cash
+"#;
+        let mut journal = Journal::new();
+        parser::read_into_journal(Cursor::new(src), &mut journal).unwrap();
+
+        let order = SortOrder::parse("amount").unwrap();
+
+        // 5 EUR and 5 USD are not equal, and the commodities are not mixed.
+        assert_eq!(
+            vec!["Euros", "More euros", "Dollars"],
+            sorted_payees(&journal, &order)
+        );
+    }
+}
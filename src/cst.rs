@@ -0,0 +1,705 @@
+/*!
+ * Concrete syntax tree
+ *
+ * A lossless representation of the journal text, for the editor tooling. Every line,
+ * including the comments and the blank lines, is a node with its byte range in the
+ * source. The transactions, posts and directives have child nodes for their tokens.
+ *
+ * A node can be replaced with new text. Emitting the tree reproduces the rest of the
+ * source byte for byte.
+ *
+ * ```
+ * use ledger_rs_lib::cst::{NodeKind, SyntaxTree};
+ *
+ * let source = "; Groceries\n2023-05-01 Shop\n    Expenses:Food  20 EUR\n    Assets:Cash\n";
+ * let mut tree = SyntaxTree::parse(source);
+ *
+ * let payee = tree.nodes[1].find_mut(NodeKind::Payee).unwrap();
+ * payee.replace("Supermarket");
+ *
+ * assert_eq!(source.replace("Shop", "Supermarket"), tree.to_string());
+ * ```
+ */
+
+use std::{fmt, ops::Range};
+
+/// The types of the nodes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    /// A line with only whitespace.
+    Blank,
+    /// A comment line, or a `comment` ... `end comment` block.
+    Comment,
+    /// A transaction, the header and the indented lines.
+    Xact,
+    /// The first line of a transaction.
+    XactHeader,
+    Post,
+    /// A directive with its indented sub-directives.
+    Directive,
+    /// An indented line of a directive, i.e. `note` in an `account` directive.
+    SubDirective,
+    /// A line that could not be recognised.
+    Unknown,
+
+    // Tokens
+    Date,
+    AuxDate,
+    /// The state mark of the transaction, `*` or `!`.
+    State,
+    /// The code of the transaction, in the parentheses.
+    Code,
+    Payee,
+    Note,
+    Account,
+    /// The sign before the symbol, as in `-$20.00`.
+    Sign,
+    Quantity,
+    Symbol,
+    LotQuantity,
+    LotSymbol,
+    LotDate,
+    CostQuantity,
+    CostSymbol,
+    /// The directive or sub-directive keyword, i.e. `account`, `P`.
+    Name,
+    Argument,
+}
+
+/// A node in the syntax tree.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node {
+    pub kind: NodeKind,
+    /// The byte range in the source. The lines include the line ending.
+    pub range: Range<usize>,
+    /// The child nodes, in the source order.
+    pub children: Vec<Node>,
+    /// The new text of an edited node.
+    replacement: Option<String>,
+}
+
+impl Node {
+    fn new(kind: NodeKind, range: Range<usize>) -> Self {
+        Self {
+            kind,
+            range,
+            children: vec![],
+            replacement: None,
+        }
+    }
+
+    fn with_children(kind: NodeKind, range: Range<usize>, mut children: Vec<Node>) -> Self {
+        children.sort_by_key(|child| child.range.start);
+        Self {
+            kind,
+            range,
+            children,
+            replacement: None,
+        }
+    }
+
+    /// Replaces the text of the node, including its children. An empty text removes it.
+    pub fn replace(&mut self, text: &str) {
+        self.replacement = Some(text.to_owned());
+    }
+
+    pub fn is_edited(&self) -> bool {
+        self.replacement.is_some()
+    }
+
+    /// Finds the first descendant of the given kind.
+    pub fn find(&self, kind: NodeKind) -> Option<&Node> {
+        self.children.iter().find_map(|child| {
+            if child.kind == kind {
+                Some(child)
+            } else {
+                child.find(kind)
+            }
+        })
+    }
+
+    pub fn find_mut(&mut self, kind: NodeKind) -> Option<&mut Node> {
+        self.children.iter_mut().find_map(|child| {
+            if child.kind == kind {
+                Some(child)
+            } else {
+                child.find_mut(kind)
+            }
+        })
+    }
+
+    /// The children of the given kind.
+    pub fn children_of(&self, kind: NodeKind) -> impl Iterator<Item = &Node> {
+        self.children.iter().filter(move |child| child.kind == kind)
+    }
+
+    pub fn children_of_mut(&mut self, kind: NodeKind) -> impl Iterator<Item = &mut Node> {
+        self.children
+            .iter_mut()
+            .filter(move |child| child.kind == kind)
+    }
+
+    /// Writes the node. The untouched text between the children comes from the source.
+    fn emit(&self, source: &str, output: &mut String) {
+        if let Some(text) = &self.replacement {
+            output.push_str(text);
+            return;
+        }
+
+        let mut position = self.range.start;
+        for child in &self.children {
+            output.push_str(&source[position..child.range.start]);
+            child.emit(source, output);
+            position = child.range.end;
+        }
+        output.push_str(&source[position..self.range.end]);
+    }
+}
+
+/// The syntax tree of a journal file.
+#[derive(Debug, Clone)]
+pub struct SyntaxTree {
+    source: String,
+    /// The top-level nodes. They cover the whole source.
+    pub nodes: Vec<Node>,
+}
+
+impl SyntaxTree {
+    pub fn parse(source: &str) -> Self {
+        let nodes = parse_nodes(source);
+        Self {
+            source: source.to_owned(),
+            nodes,
+        }
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// The original text of the node.
+    pub fn text(&self, node: &Node) -> &str {
+        &self.source[node.range.clone()]
+    }
+
+    /// The nodes containing the byte offset, from the top-level one to the innermost.
+    pub fn nodes_at(&self, offset: usize) -> Vec<&Node> {
+        let mut result = vec![];
+        let mut nodes = &self.nodes;
+        while let Some(node) = nodes.iter().find(|node| node.range.contains(&offset)) {
+            result.push(node);
+            nodes = &node.children;
+        }
+        result
+    }
+}
+
+impl fmt::Display for SyntaxTree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut output = String::with_capacity(self.source.len());
+        for node in &self.nodes {
+            node.emit(&self.source, &mut output);
+        }
+        f.write_str(&output)
+    }
+}
+
+/// A line of the source: (range with the line ending, content without the line ending).
+struct Line<'a> {
+    range: Range<usize>,
+    content: &'a str,
+}
+
+impl Line<'_> {
+    fn is_blank(&self) -> bool {
+        self.content.trim().is_empty()
+    }
+
+    fn is_indented(&self) -> bool {
+        self.content.starts_with([' ', '\t'])
+    }
+}
+
+fn split_lines(source: &str) -> Vec<Line<'_>> {
+    let mut offset = 0;
+    source
+        .split_inclusive('\n')
+        .map(|line| {
+            let range = offset..offset + line.len();
+            offset = range.end;
+            Line {
+                range,
+                content: line.trim_end_matches(['\n', '\r']),
+            }
+        })
+        .collect()
+}
+
+fn parse_nodes(source: &str) -> Vec<Node> {
+    let lines = split_lines(source);
+    let mut nodes = vec![];
+
+    let mut i = 0;
+    while i < lines.len() {
+        let line = &lines[i];
+        i += 1;
+
+        if line.is_blank() {
+            nodes.push(Node::new(NodeKind::Blank, line.range.clone()));
+            continue;
+        }
+
+        match line.content.chars().next().expect("a non-blank line") {
+            ';' | '#' | '*' | '|' | '%' => {
+                nodes.push(Node::new(NodeKind::Comment, line.range.clone()))
+            }
+            '0'..='9' => {
+                let (node, next) = parse_xact(&lines, i - 1);
+                nodes.push(node);
+                i = next;
+            }
+            ' ' | '\t' => nodes.push(Node::new(NodeKind::Unknown, line.range.clone())),
+            _ => {
+                let (node, next) = parse_directive(&lines, i - 1);
+                nodes.push(node);
+                i = next;
+            }
+        }
+    }
+
+    nodes
+}
+
+/// Parses the transaction starting at the given line.
+/// Returns the node and the index of the next line.
+fn parse_xact(lines: &[Line], first: usize) -> (Node, usize) {
+    let header = &lines[first];
+    let mut children = vec![Node::with_children(
+        NodeKind::XactHeader,
+        header.range.clone(),
+        token_nodes(header, scan_xact_header(header.content)),
+    )];
+
+    let mut i = first + 1;
+    while i < lines.len() && lines[i].is_indented() && !lines[i].is_blank() {
+        let line = &lines[i];
+        let node = if line.content.trim_start().starts_with(';') {
+            Node::new(NodeKind::Comment, line.range.clone())
+        } else {
+            Node::with_children(
+                NodeKind::Post,
+                line.range.clone(),
+                token_nodes(line, scan_post(line.content)),
+            )
+        };
+        children.push(node);
+        i += 1;
+    }
+
+    let range = header.range.start..lines[i - 1].range.end;
+    (Node::with_children(NodeKind::Xact, range, children), i)
+}
+
+/// Parses the directive starting at the given line, with its indented lines.
+/// Returns the node and the index of the next line.
+fn parse_directive(lines: &[Line], first: usize) -> (Node, usize) {
+    let line = &lines[first];
+    let name = directive_name(line.content);
+
+    // The comment block runs until its `end`.
+    if name == "comment" || name == "test" {
+        let end = format!("end {}", name);
+        let last = lines[first + 1..]
+            .iter()
+            .position(|line| line.content.trim_end() == end)
+            .map(|index| first + 1 + index)
+            .unwrap_or(lines.len() - 1);
+        let range = line.range.start..lines[last].range.end;
+        return (Node::new(NodeKind::Comment, range), last + 1);
+    }
+
+    let mut children = name_and_argument(line);
+    let mut i = first + 1;
+    while i < lines.len() && lines[i].is_indented() && !lines[i].is_blank() {
+        let line = &lines[i];
+        children.push(Node::with_children(
+            NodeKind::SubDirective,
+            line.range.clone(),
+            name_and_argument(line),
+        ));
+        i += 1;
+    }
+
+    let range = line.range.start..lines[i - 1].range.end;
+    (Node::with_children(NodeKind::Directive, range, children), i)
+}
+
+/// The directive keyword, i.e. `account`, or the single letter, i.e. `P`.
+fn directive_name(content: &str) -> &str {
+    let content = content.trim_start_matches(['@', '!']);
+    let end = content.find(char::is_whitespace).unwrap_or(content.len());
+    let word = &content[..end];
+    match word.chars().next() {
+        Some(c @ ('A' | 'C' | 'D' | 'N' | 'P' | 'Y')) => &word[..c.len_utf8()],
+        _ => word,
+    }
+}
+
+/// The tokens of a directive line: the name and the argument.
+fn name_and_argument(line: &Line) -> Vec<Node> {
+    let mut scanner = LineScanner::new(line.content);
+    scanner.skip(|c| c.is_whitespace() || c == '@' || c == '!');
+    let name = directive_name(scanner.rest());
+    scanner.take(NodeKind::Name, name.len());
+    scanner.push_trimmed(NodeKind::Argument, scanner.position..scanner.end);
+    token_nodes(line, scanner.tokens)
+}
+
+/// Creates the nodes for the tokens, with the ranges in the line content.
+fn token_nodes(line: &Line, tokens: Vec<(NodeKind, Range<usize>)>) -> Vec<Node> {
+    let start = line.range.start;
+    tokens
+        .into_iter()
+        .map(|(kind, range)| Node::new(kind, start + range.start..start + range.end))
+        .collect()
+}
+
+/// Scans the transaction header, `DATE[=AUX_DATE] [STATE] [(CODE)] PAYEE  ; NOTE`.
+/// The note starts at `  ;`, as in the scanner.
+fn scan_xact_header(content: &str) -> Vec<(NodeKind, Range<usize>)> {
+    let mut scanner = LineScanner::new(content);
+    if let Some(index) = content.find("  ;") {
+        scanner.end = index;
+        scanner.push_trimmed(NodeKind::Note, index + 3..content.len());
+    }
+
+    scanner.take_while(NodeKind::Date, |c| c != '=' && !c.is_whitespace());
+    if scanner.rest().starts_with('=') {
+        scanner.position += 1;
+        scanner.take_while(NodeKind::AuxDate, |c| !c.is_whitespace());
+    }
+    scanner.skip(char::is_whitespace);
+
+    // The state is a mark on its own, `* Shop`.
+    let rest = scanner.rest();
+    if (rest.starts_with("* ") || rest.starts_with("! ")) && rest.len() > 2 {
+        scanner.take(NodeKind::State, 1);
+        scanner.skip(char::is_whitespace);
+    }
+    if let Some(close) = scanner.rest().strip_prefix('(').and_then(|rest| rest.find(')')) {
+        let code = scanner.position + 1..scanner.position + 1 + close;
+        scanner.push_trimmed(NodeKind::Code, code.clone());
+        scanner.position = code.end + 1;
+    }
+    scanner.push_trimmed(NodeKind::Payee, scanner.position..scanner.end);
+    scanner.tokens
+}
+
+/// Scans the post, `ACCOUNT  AMOUNT [{LOT}] [[DATE]] [@ COST] [= BALANCE]  ; NOTE`.
+/// The amount can start with the quantity, `-20 EUR`, or the symbol, `$-20` or `-$20`.
+fn scan_post(content: &str) -> Vec<(NodeKind, Range<usize>)> {
+    let mut scanner = LineScanner::new(content);
+    if let Some(index) = content.find(';') {
+        scanner.end = index;
+        scanner.push_trimmed(NodeKind::Note, index + 1..content.len());
+    }
+    scanner.skip(char::is_whitespace);
+
+    // Two spaces separate the account from the amount.
+    let account_end = match scanner.rest().find("  ") {
+        Some(index) => scanner.position + index,
+        None => scanner.end,
+    };
+    scanner.push_trimmed(NodeKind::Account, scanner.position..account_end);
+    scanner.position = account_end;
+
+    // The balance assertion ends the amount part. Its `=` starts a word.
+    let assertion = scanner
+        .rest()
+        .char_indices()
+        .find(|(i, c)| *c == '=' && scanner.rest()[..*i].ends_with(char::is_whitespace));
+    if let Some((index, _)) = assertion {
+        scanner.end = scanner.position + index;
+    }
+
+    scanner.skip(char::is_whitespace);
+    scanner.amount(NodeKind::Quantity, NodeKind::Symbol);
+    loop {
+        scanner.skip(char::is_whitespace);
+        let rest = scanner.rest();
+        let (kind, close) = match rest.chars().next() {
+            Some('{') => (NodeKind::LotQuantity, '}'),
+            Some('[') => (NodeKind::LotDate, ']'),
+            _ => break,
+        };
+        let Some(length) = rest.find(close) else { break };
+        let end = scanner.end;
+        scanner.position += 1;
+        scanner.end = scanner.position + length - 1;
+        match kind {
+            NodeKind::LotQuantity => {
+                scanner.skip(|c| c.is_whitespace() || c == '=' || c == '{');
+                scanner.amount(NodeKind::LotQuantity, NodeKind::LotSymbol);
+            }
+            _ => scanner.push_trimmed(NodeKind::LotDate, scanner.position..scanner.end),
+        }
+        scanner.position = scanner.end + 1;
+        scanner.end = end;
+    }
+    if scanner.rest().starts_with('@') {
+        scanner.skip(|c| c == '@');
+        scanner.skip(char::is_whitespace);
+        scanner.amount(NodeKind::CostQuantity, NodeKind::CostSymbol);
+    }
+    scanner.tokens
+}
+
+/// Scans a line and keeps the byte ranges of the tokens, in the line content.
+struct LineScanner<'a> {
+    content: &'a str,
+    position: usize,
+    /// The end of the part being scanned, i.e. before the note.
+    end: usize,
+    tokens: Vec<(NodeKind, Range<usize>)>,
+}
+
+impl<'a> LineScanner<'a> {
+    fn new(content: &'a str) -> Self {
+        Self {
+            content,
+            position: 0,
+            end: content.len(),
+            tokens: vec![],
+        }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.content[self.position..self.end]
+    }
+
+    /// Adds the token, without the surrounding whitespace. The empty tokens are skipped.
+    fn push_trimmed(&mut self, kind: NodeKind, range: Range<usize>) {
+        let text = &self.content[range.clone()];
+        let start = range.start + text.len() - text.trim_start().len();
+        let end = range.start + text.trim_end().len();
+        if start < end {
+            self.tokens.push((kind, start..end));
+        }
+    }
+
+    fn take(&mut self, kind: NodeKind, length: usize) {
+        self.push_trimmed(kind, self.position..self.position + length);
+        self.position += length;
+    }
+
+    fn take_while(&mut self, kind: NodeKind, predicate: impl Fn(char) -> bool) {
+        let rest = self.rest();
+        let length = rest.find(|c| !predicate(c)).unwrap_or(rest.len());
+        self.take(kind, length);
+    }
+
+    fn skip(&mut self, predicate: impl Fn(char) -> bool) {
+        let rest = self.rest();
+        self.position += rest.find(|c| !predicate(c)).unwrap_or(rest.len());
+    }
+
+    /// Scans the amount, with the quantity first or the symbol first, as the scanner does.
+    fn amount(&mut self, quantity: NodeKind, symbol: NodeKind) {
+        let is_number = |c: char| c.is_ascii_digit() || c == '-' || c == '.' || c == ',';
+        let is_symbol =
+            |c: char| !c.is_whitespace() && !is_number(c) && !"@{}[]=".contains(c);
+        let rest = self.rest();
+        let mut chars = rest.chars();
+        match (chars.next(), chars.next()) {
+            // `-$20.00`
+            (Some('-'), Some(c)) if is_symbol(c) => {
+                self.take(NodeKind::Sign, 1);
+                self.take_while(symbol, is_symbol);
+                self.skip(char::is_whitespace);
+                self.take_while(quantity, is_number);
+            }
+            (Some(c), _) if is_number(c) => {
+                self.take_while(quantity, is_number);
+                self.skip(char::is_whitespace);
+                self.take_while(symbol, is_symbol);
+            }
+            _ => {
+                self.take_while(symbol, is_symbol);
+                self.skip(char::is_whitespace);
+                self.take_while(quantity, is_number);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{NodeKind, SyntaxTree};
+
+    const SOURCE: &str = r#"; A comment
+account Expenses:Food
+    note Food
+
+2023-05-01 Shop  ; :groceries:
+    Expenses:Food  20 EUR
+    ; after the post
+    Assets:Cash
+
+P 2023-05-01 EUR 1.10 USD
+"#;
+
+    #[test]
+    fn test_round_trip() {
+        let tree = SyntaxTree::parse(SOURCE);
+
+        assert_eq!(SOURCE, tree.to_string());
+    }
+
+    #[test]
+    fn test_structure() {
+        let tree = SyntaxTree::parse(SOURCE);
+
+        let kinds: Vec<NodeKind> = tree.nodes.iter().map(|node| node.kind).collect();
+        assert_eq!(
+            vec![
+                NodeKind::Comment,
+                NodeKind::Directive,
+                NodeKind::Blank,
+                NodeKind::Xact,
+                NodeKind::Blank,
+                NodeKind::Directive
+            ],
+            kinds
+        );
+
+        let account = &tree.nodes[1];
+        assert_eq!("account", tree.text(account.find(NodeKind::Name).unwrap()));
+        assert_eq!("Expenses:Food", tree.text(account.find(NodeKind::Argument).unwrap()));
+        assert_eq!(1, account.children_of(NodeKind::SubDirective).count());
+
+        let xact = &tree.nodes[3];
+        assert_eq!("2023-05-01", tree.text(xact.find(NodeKind::Date).unwrap()));
+        assert_eq!("Shop", tree.text(xact.find(NodeKind::Payee).unwrap()));
+        assert_eq!(":groceries:", tree.text(xact.find(NodeKind::Note).unwrap()));
+        assert_eq!(2, xact.children_of(NodeKind::Post).count());
+        assert_eq!(1, xact.children_of(NodeKind::Comment).count());
+
+        let post = xact.children_of(NodeKind::Post).next().unwrap();
+        assert_eq!("    Expenses:Food  20 EUR\n", tree.text(post));
+        assert_eq!("20", tree.text(post.find(NodeKind::Quantity).unwrap()));
+        assert_eq!("EUR", tree.text(post.find(NodeKind::Symbol).unwrap()));
+
+        let price = &tree.nodes[5];
+        assert_eq!("P", tree.text(price.find(NodeKind::Name).unwrap()));
+    }
+
+    #[test]
+    fn test_edit_token() {
+        let mut tree = SyntaxTree::parse(SOURCE);
+
+        let xact = &mut tree.nodes[3];
+        let post = xact.children_of_mut(NodeKind::Post).next().unwrap();
+        post.find_mut(NodeKind::Quantity).unwrap().replace("25.50");
+
+        let expected = SOURCE.replace("20 EUR", "25.50 EUR");
+        assert_eq!(expected, tree.to_string());
+    }
+
+    #[test]
+    fn test_remove_post() {
+        let mut tree = SyntaxTree::parse(SOURCE);
+
+        let xact = &mut tree.nodes[3];
+        xact.children_of_mut(NodeKind::Post).nth(1).unwrap().replace("");
+
+        let expected = SOURCE.replace("    Assets:Cash\n", "");
+        assert_eq!(expected, tree.to_string());
+    }
+
+    #[test]
+    fn test_spacing_and_line_endings_kept() {
+        let source = "2023-05-01 Shop\r\n\tExpenses   1 EUR\r\n  Assets\r\n\r\n\r\n;end";
+        let mut tree = SyntaxTree::parse(source);
+        assert_eq!(source, tree.to_string());
+
+        tree.nodes[0].find_mut(NodeKind::Payee).unwrap().replace("Store");
+        assert_eq!(source.replace("Shop", "Store"), tree.to_string());
+    }
+
+    #[test]
+    fn test_header_tokens() {
+        let source = "2023-01-01=2023-01-02 * (123) Shop  ; note\n    Expenses  1 EUR\n    Assets\n";
+        let mut tree = SyntaxTree::parse(source);
+
+        let header = tree.nodes[0].children_of(NodeKind::XactHeader).next().unwrap();
+        let tokens: Vec<(NodeKind, &str)> =
+            header.children.iter().map(|n| (n.kind, tree.text(n))).collect();
+        assert_eq!(
+            vec![
+                (NodeKind::Date, "2023-01-01"),
+                (NodeKind::AuxDate, "2023-01-02"),
+                (NodeKind::State, "*"),
+                (NodeKind::Code, "123"),
+                (NodeKind::Payee, "Shop"),
+                (NodeKind::Note, "note"),
+            ],
+            tokens
+        );
+
+        tree.nodes[0].find_mut(NodeKind::Payee).unwrap().replace("Store");
+        assert_eq!(source.replace("Shop", "Store"), tree.to_string());
+    }
+
+    #[test]
+    fn test_amount_tokens() {
+        let source = "2023-05-01 Shop\n    Expenses  -$20.00\n    Assets  $-20.00\n    \
+                      Assets:Stocks  -10 VEUR {20 EUR} [2023-04-01] @ 25 EUR = 0 VEUR ; lot\n";
+        let tree = SyntaxTree::parse(source);
+
+        let tokens: Vec<Vec<(NodeKind, &str)>> = tree.nodes[0]
+            .children_of(NodeKind::Post)
+            .map(|post| post.children.iter().map(|n| (n.kind, tree.text(n))).collect())
+            .collect();
+        assert_eq!(
+            vec![
+                (NodeKind::Account, "Expenses"),
+                (NodeKind::Sign, "-"),
+                (NodeKind::Symbol, "$"),
+                (NodeKind::Quantity, "20.00"),
+            ],
+            tokens[0]
+        );
+        assert_eq!(
+            vec![
+                (NodeKind::Account, "Assets"),
+                (NodeKind::Symbol, "$"),
+                (NodeKind::Quantity, "-20.00"),
+            ],
+            tokens[1]
+        );
+        assert_eq!(
+            vec![
+                (NodeKind::Account, "Assets:Stocks"),
+                (NodeKind::Quantity, "-10"),
+                (NodeKind::Symbol, "VEUR"),
+                (NodeKind::LotQuantity, "20"),
+                (NodeKind::LotSymbol, "EUR"),
+                (NodeKind::LotDate, "2023-04-01"),
+                (NodeKind::CostQuantity, "25"),
+                (NodeKind::CostSymbol, "EUR"),
+                (NodeKind::Note, "lot"),
+            ],
+            tokens[2]
+        );
+    }
+
+    #[test]
+    fn test_nodes_at() {
+        let tree = SyntaxTree::parse(SOURCE);
+        let offset = SOURCE.find("20 EUR").unwrap();
+
+        let kinds: Vec<NodeKind> = tree.nodes_at(offset).iter().map(|n| n.kind).collect();
+
+        assert_eq!(vec![NodeKind::Xact, NodeKind::Post, NodeKind::Quantity], kinds);
+    }
+}
@@ -0,0 +1,597 @@
+/*!
+ * CSV bank statements
+ *
+ * Converts the CSV statements into transactions. A rules file maps the columns and
+ * selects the accounts:
+ *
+ * ```text
+ * skip 1
+ * separator ;
+ * fields date, code, payee, amount
+ * date-format %d.%m.%Y
+ * decimal-mark ,
+ * currency EUR
+ * account1 Assets:Bank:Checking
+ * account2 Expenses:Unknown
+ *
+ * if AMAZON|Amazon
+ *     account2 Expenses:Shopping
+ * ```
+ *
+ * The fields are `date`, `code`, `payee` (or `description`), `amount`, `amount-in`,
+ * `amount-out`, `currency` and `note` (or `comment`). `_` skips a column.
+ * The amount goes to `account1`, the bank account. The `if` rules match the payee,
+ * case-insensitively, and the first matching rule selects `account2`.
+ *
+ * The rules for `statement.csv` are read from `statement.csv.rules`, unless given.
+ */
+
+use std::{fs, io::Cursor, path::Path};
+
+use chrono::NaiveDate;
+use regex::{Regex, RegexBuilder};
+
+use crate::{
+    amount::Quantity,
+    import::Importer,
+    journal::Journal,
+    parser::{self, ISO_DATE_FORMAT},
+    xact::{Xact, XactId},
+};
+
+/// The field names in the `fields` rule. `_` skips the column.
+const FIELD_NAMES: [&str; 11] = [
+    "date",
+    "code",
+    "payee",
+    "description",
+    "amount",
+    "amount-in",
+    "amount-out",
+    "currency",
+    "note",
+    "comment",
+    "_",
+];
+
+/// The rules for reading a CSV statement.
+#[derive(Debug)]
+pub struct CsvRules {
+    /// The number of the header records to skip.
+    pub skip: usize,
+    pub separator: char,
+    /// The field name of each column.
+    pub fields: Vec<String>,
+    /// The date format, i.e. `%d.%m.%Y`. The default formats are as in the journal.
+    pub date_format: Option<String>,
+    /// `.` or `,`. The other one is the thousands separator.
+    pub decimal_mark: char,
+    /// The commodity of the amounts, unless there is a `currency` field.
+    pub currency: Option<String>,
+    /// The bank account, `Assets:Unknown` if not given. The OFX statements default to
+    /// the account of the statement instead.
+    pub account1: Option<String>,
+    /// The other account, unless selected by a payee rule.
+    pub account2: String,
+    /// (payee pattern, account2)
+    pub payee_rules: Vec<(Regex, String)>,
+}
+
+impl CsvRules {
+    /// Parses the rules file text.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let rules = Self::parse_accounts(text)?;
+        if !rules.fields.iter().any(|field| field == "date") {
+            return Err("The fields do not include the date".to_owned());
+        }
+        Ok(rules)
+    }
+
+    /// Parses the rules without requiring the columns. The OFX statements use only the
+    /// accounts and the payee rules.
+    pub(crate) fn parse_accounts(text: &str) -> Result<Self, String> {
+        let mut rules = Self {
+            skip: 0,
+            separator: ',',
+            fields: vec![],
+            date_format: None,
+            decimal_mark: '.',
+            currency: None,
+            account1: None,
+            account2: "Expenses:Unknown".to_owned(),
+            payee_rules: vec![],
+        };
+        // The pattern of the `if` block being read.
+        let mut condition: Option<Regex> = None;
+
+        for (index, line) in text.lines().enumerate() {
+            let error = |message: String| format!("line {}: {}", index + 1, message);
+            let indented = line.starts_with([' ', '\t']);
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(['#', ';']) {
+                continue;
+            }
+            let (name, value) = match line.split_once(char::is_whitespace) {
+                Some((name, value)) => (name, value.trim()),
+                None => (line, ""),
+            };
+
+            if indented {
+                let Some(pattern) = &condition else {
+                    return Err(error(format!("Unexpected indented rule: {}", line)));
+                };
+                match name {
+                    "account2" => rules.payee_rules.push((pattern.clone(), value.to_owned())),
+                    _ => return Err(error(format!("Unknown rule in the if block: {}", name))),
+                }
+                continue;
+            }
+
+            condition = None;
+            match name {
+                "skip" => {
+                    rules.skip = match value {
+                        "" => 1,
+                        _ => value.parse().map_err(|_| error(format!("Invalid skip: {}", value)))?,
+                    }
+                }
+                "separator" => {
+                    rules.separator = match value {
+                        "TAB" | "\\t" => '\t',
+                        _ => value.chars().next().ok_or_else(|| error("Missing separator".into()))?,
+                    }
+                }
+                "fields" => {
+                    rules.fields = value.split(',').map(|field| field.trim().to_owned()).collect();
+                    if let Some(field) =
+                        rules.fields.iter().find(|field| !FIELD_NAMES.contains(&field.as_str()))
+                    {
+                        return Err(error(format!("Unknown field: {}", field)));
+                    }
+                }
+                "date-format" => rules.date_format = Some(value.to_owned()),
+                "decimal-mark" => rules.decimal_mark = value.chars().next().unwrap_or('.'),
+                "currency" => rules.currency = Some(value.to_owned()),
+                "account1" => rules.account1 = Some(value.to_owned()),
+                "account2" => rules.account2 = value.to_owned(),
+                "if" => {
+                    let pattern = RegexBuilder::new(value)
+                        .case_insensitive(true)
+                        .build()
+                        .map_err(|e| error(format!("Invalid pattern {}: {}", value, e)))?;
+                    condition = Some(pattern);
+                }
+                _ => return Err(error(format!("Unknown rule: {}", name))),
+            }
+        }
+
+        Ok(rules)
+    }
+
+    /// Reads the rules file.
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Self::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// The rules file for the statement, i.e. `statement.csv.rules`.
+    pub fn path_for(statement: &Path) -> std::path::PathBuf {
+        let mut path = statement.as_os_str().to_owned();
+        path.push(".rules");
+        path.into()
+    }
+
+    /// The bank account, from the `account1` rule.
+    pub(crate) fn account1(&self) -> &str {
+        self.account1.as_deref().unwrap_or("Assets:Unknown")
+    }
+
+    /// The account for the payee, from the payee rules, or the default `account2`.
+    pub(crate) fn account2_for(&self, payee: &str) -> &str {
+        self.payee_rules
+            .iter()
+            .find(|(pattern, _)| pattern.is_match(payee))
+            .map_or(&self.account2, |(_, account)| account)
+    }
+}
+
+/// A transaction read from a CSV record.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsvXact {
+    pub date: NaiveDate,
+    pub code: Option<String>,
+    pub payee: String,
+    /// The amount for `account1`. Positive is received.
+    pub amount: Quantity,
+    pub commodity: Option<String>,
+    pub account1: String,
+    pub account2: String,
+    pub note: Option<String>,
+}
+
+impl CsvXact {
+    /// The transaction in the journal format. The line breaks in the values, from the
+    /// quoted fields, become spaces.
+    pub fn to_ledger(&self) -> String {
+        let mut header = self.date.format(ISO_DATE_FORMAT).to_string();
+        if let Some(code) = &self.code {
+            header += &format!(" ({})", single_line(code));
+        }
+        header += " ";
+        header += &single_line(&self.payee);
+        if let Some(note) = &self.note {
+            header += "  ; ";
+            header += &single_line(note);
+        }
+
+        let mut amount = self.amount.to_string();
+        if let Some(commodity) = &self.commodity {
+            amount += " ";
+            amount += &single_line(commodity);
+        }
+        format!("{}\n    {}  {}\n    {}\n", header, self.account1, amount, self.account2)
+    }
+
+    /// The transaction is in the journal if there is one with the same date and code, and
+    /// with a post of the same amount, in either direction.
+    pub fn exists_in(&self, journal: &Journal) -> bool {
+        let quantity = abs(self.amount);
+        journal.xacts.iter().any(|xact: &Xact| {
+            xact.date == Some(self.date)
+                && xact.code == self.code
+                && xact.posts.iter().filter_map(|post| post.amount).any(|amount| {
+                    let symbol = amount
+                        .get_commodity()
+                        .map(|id| journal.commodity_pool.get(id).symbol.as_str());
+                    abs(amount.quantity) == quantity && symbol == self.commodity.as_deref()
+                })
+        })
+    }
+}
+
+/// Reads the transactions from the CSV text.
+pub fn read_csv(source: &str, rules: &CsvRules) -> Result<Vec<CsvXact>, String> {
+    let mut xacts = vec![];
+
+    for (line, record) in csv_records(source, rules.separator)?.into_iter().skip(rules.skip) {
+        if record.iter().all(|value| value.trim().is_empty()) {
+            continue;
+        }
+        let error = |message: String| format!("line {}: {}", line, message);
+
+        let mut date = None;
+        let mut xact = CsvXact {
+            date: NaiveDate::MIN,
+            code: None,
+            payee: String::new(),
+            amount: Quantity::ZERO,
+            commodity: rules.currency.clone(),
+            account1: rules.account1().to_owned(),
+            account2: String::new(),
+            note: None,
+        };
+
+        for (field, value) in rules.fields.iter().zip(&record) {
+            let value = value.trim();
+            if value.is_empty() {
+                continue;
+            }
+            match field.as_str() {
+                "date" => {
+                    let parsed = parser::parse_date_in(value, None, rules.date_format.as_deref())
+                        .map_err(|e| error(e.to_string()))?;
+                    date = Some(parsed);
+                }
+                "code" => xact.code = Some(value.to_owned()),
+                "payee" | "description" => xact.payee = value.to_owned(),
+                "amount" | "amount-in" => {
+                    xact.amount += parse_quantity(value, rules.decimal_mark).map_err(error)?
+                }
+                "amount-out" => {
+                    xact.amount -= abs(parse_quantity(value, rules.decimal_mark).map_err(error)?)
+                }
+                "currency" => xact.commodity = Some(value.to_owned()),
+                "note" | "comment" => xact.note = Some(value.to_owned()),
+                _ => (),
+            }
+        }
+
+        xact.date = date.ok_or_else(|| error("Missing date".to_owned()))?;
+        xact.account2 = rules.account2_for(&xact.payee).to_owned();
+        xacts.push(xact);
+    }
+
+    Ok(xacts)
+}
+
+/// Reads the CSV statement into the journal, skipping the transactions that are
+/// already there. Returns the new transactions.
+pub fn import_csv(
+    source: &str,
+    rules: &CsvRules,
+    journal: &mut Journal,
+) -> Result<Vec<XactId>, String> {
+    let text: Vec<String> = read_csv(source, rules)?
+        .iter()
+        .filter(|xact| !xact.exists_in(journal))
+        .map(|xact| xact.to_ledger())
+        .collect();
+
+    let first = journal.xacts.len();
+    let mut parser = parser::Parser::new(Cursor::new(text.join("\n")), journal);
+    parser.try_parse().map_err(|(line, e)| format!("line {}: {}", line, e))?;
+
+    Ok((first..journal.xacts.len()).map(XactId).collect())
+}
+
+/// Converts the statements to the journal text. Command: `convert`.
+/// The transactions already in the journal, if given, are skipped.
+pub fn convert_files(
+    paths: &[String],
+    rules_path: Option<&str>,
+    journal: Option<&Journal>,
+) -> Result<Vec<String>, String> {
+    if paths.is_empty() {
+        return Err("No CSV file was given".to_owned());
+    }
+
+    let mut output: Vec<String> = vec![];
+    for path in paths {
+        let path = Path::new(path);
+        let rules = match rules_path {
+            Some(rules_path) => CsvRules::read(Path::new(rules_path))?,
+            None => CsvRules::read(&CsvRules::path_for(path))?,
+        };
+        let source = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let xacts = read_csv(&source, &rules).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+        for xact in xacts {
+            if journal.is_some_and(|journal| xact.exists_in(journal)) {
+                continue;
+            }
+            if !output.is_empty() {
+                output.push(String::new());
+            }
+            output.extend(xact.to_ledger().lines().map(|line| line.to_owned()));
+        }
+    }
+    Ok(output)
+}
+
+/// The importer for `import statement.csv`, with the rules from `statement.csv.rules`.
+pub struct CsvImporter;
+
+impl Importer for CsvImporter {
+    fn name(&self) -> &str {
+        "csv"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["csv"]
+    }
+
+    fn import(&self, path: &Path, source: &str, journal: &Journal) -> Result<String, String> {
+        let rules = CsvRules::read(&CsvRules::path_for(path))?;
+        let xacts = read_csv(source, &rules).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let text: Vec<String> = xacts
+            .iter()
+            .filter(|xact| !xact.exists_in(journal))
+            .map(|xact| xact.to_ledger())
+            .collect();
+        Ok(text.join("\n"))
+    }
+}
+
+/// Parses the amount, i.e. `-1.234,56` with the `,` decimal mark. The thousands
+/// separators, the spaces and the currency signs are ignored.
+fn parse_quantity(text: &str, decimal_mark: char) -> Result<Quantity, String> {
+    let thousands = if decimal_mark == ',' { '.' } else { ',' };
+    let number: String = text
+        .chars()
+        .filter(|c| c.is_ascii_digit() || ['-', '+', decimal_mark].contains(c))
+        .filter(|c| *c != thousands)
+        .map(|c| if c == decimal_mark { '.' } else { c })
+        .collect();
+    let number = number.strip_prefix('+').unwrap_or(&number);
+    Quantity::from_str(number).ok_or_else(|| format!("Invalid amount: {}", text))
+}
+
+/// The text on one line, with the line breaks as spaces.
+fn single_line(text: &str) -> String {
+    text.lines().map(str::trim).filter(|line| !line.is_empty()).collect::<Vec<_>>().join(" ")
+}
+
+fn abs(quantity: Quantity) -> Quantity {
+    let mut quantity = quantity;
+    quantity.set_sign_positive();
+    quantity
+}
+
+/// Splits the CSV text into the records, with the line number of each. The values can be
+/// quoted, with `""` for a quote inside.
+fn csv_records(text: &str, separator: char) -> Result<Vec<(usize, Vec<String>)>, String> {
+    let mut records = vec![];
+    let mut record = vec![];
+    let mut value = String::new();
+    let mut in_quotes = false;
+    let mut line = 1;
+    let mut record_line = 1;
+
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                value.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            '\n' if !in_quotes => {
+                record.push(std::mem::take(&mut value));
+                records.push((record_line, std::mem::take(&mut record)));
+                line += 1;
+                record_line = line;
+            }
+            '\r' if !in_quotes => (),
+            c if c == separator && !in_quotes => record.push(std::mem::take(&mut value)),
+            c => {
+                if c == '\n' {
+                    line += 1;
+                }
+                value.push(c);
+            }
+        }
+    }
+    if in_quotes {
+        return Err(format!("line {}: Unterminated quote", record_line));
+    }
+    if !value.is_empty() || !record.is_empty() {
+        record.push(value);
+        records.push((record_line, record));
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{convert_files, csv_records, import_csv, parse_quantity, read_csv, CsvRules};
+    use crate::{amount::Quantity, journal::Journal, parse_text};
+
+    const RULES: &str = r#"skip 1
+separator ;
+fields date, code, payee, amount, _
+date-format %d.%m.%Y
+decimal-mark ,
+currency EUR
+account1 Assets:Bank
+
+if amazon
+    account2 Expenses:Shopping
+if ^salary
+    account2 Income:Salary
+"#;
+
+    const STATEMENT: &str = r#"Date;Reference;Description;Amount;Balance
+01.05.2023;1001;"AMAZON MKTP; Order 1";-1.234,50;100
+02.05.2023;1002;Bakery;-5,00;95
+
+25.05.2023;;SALARY MAY;2.000,00;2095
+"#;
+
+    #[test]
+    fn test_csv_records() {
+        let actual = csv_records("a,\"b,\"\"c\"\"\"\n\"multi\nline\",d\r\n", ',').unwrap();
+
+        assert_eq!(
+            vec![
+                (1, vec!["a".to_owned(), "b,\"c\"".into()]),
+                (2, vec!["multi\nline".to_owned(), "d".into()])
+            ],
+            actual
+        );
+    }
+
+    #[test]
+    fn test_parse_quantity() {
+        assert_eq!(Quantity::from("-1234.50"), parse_quantity("-1.234,50", ',').unwrap());
+        assert_eq!(Quantity::from("1234.5"), parse_quantity("$1,234.5", '.').unwrap());
+        assert!(parse_quantity("n/a", '.').is_err());
+    }
+
+    #[test]
+    fn test_read_csv() {
+        let rules = CsvRules::parse(RULES).unwrap();
+
+        let actual = read_csv(STATEMENT, &rules).unwrap();
+
+        assert_eq!(3, actual.len());
+        assert_eq!(
+            "2023-05-01 (1001) AMAZON MKTP; Order 1\n    \
+             Assets:Bank  -1234.50 EUR\n    \
+             Expenses:Shopping\n",
+            actual[0].to_ledger()
+        );
+        assert_eq!("Expenses:Unknown", actual[1].account2);
+        assert_eq!(None, actual[2].code);
+        assert_eq!("Income:Salary", actual[2].account2);
+    }
+
+    #[test]
+    fn test_line_breaks_in_values() {
+        let rules = CsvRules::parse("fields date, payee, amount, note").unwrap();
+
+        let actual = read_csv("2023-05-01,\"Shop\n2023-05-02 Injected\",1,\"a\r\nb\"\n", &rules);
+
+        assert_eq!(
+            "2023-05-01 Shop 2023-05-02 Injected  ; a b\n    \
+             Assets:Unknown  1\n    \
+             Expenses:Unknown\n",
+            actual.unwrap()[0].to_ledger()
+        );
+    }
+
+    #[test]
+    fn test_invalid_rules() {
+        assert_eq!(
+            "line 2: Unknown rule: acount1",
+            CsvRules::parse("fields date\nacount1 Assets").unwrap_err()
+        );
+        assert!(CsvRules::parse("fields payee, amount").is_err());
+        assert_eq!(
+            "line 1: Unknown field: amout",
+            CsvRules::parse("fields date, payee, amout").unwrap_err()
+        );
+        let rules = CsvRules::parse("fields date, amount").unwrap();
+        assert_eq!(
+            "line 2: Invalid amount: n/a",
+            read_csv("2023-05-01,1\n2023-05-02,n/a\n", &rules).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_import_skips_existing() {
+        let rules = CsvRules::parse(RULES).unwrap();
+        let mut journal = Journal::new();
+        parse_text(
+            "2023-05-02 (1002) Bakery Vienna\n    Expenses:Food  5.00 EUR\n    Assets:Bank\n",
+            &mut journal,
+        )
+        .unwrap();
+
+        let added = import_csv(STATEMENT, &rules, &mut journal).unwrap();
+
+        assert_eq!(2, added.len());
+        let payees: Vec<&str> =
+            added.iter().map(|id| journal.get_xact(*id).payee.as_str()).collect();
+        assert_eq!(vec!["AMAZON MKTP; Order 1", "SALARY MAY"], payees);
+        assert_eq!(Some("1001"), journal.get_xact(added[0]).code.as_deref());
+
+        // The second import adds nothing.
+        assert!(import_csv(STATEMENT, &rules, &mut journal).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_convert_files() {
+        let dir = std::env::temp_dir()
+            .join(format!("ledger-rs-csv-convert-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let statement = dir.join("statement.csv");
+        std::fs::write(&statement, STATEMENT).unwrap();
+        std::fs::write(dir.join("statement.csv.rules"), RULES).unwrap();
+
+        let actual = convert_files(&[statement.display().to_string()], None, None).unwrap();
+
+        assert_eq!(11, actual.len());
+        assert_eq!("", actual[3]);
+        assert_eq!("2023-05-25 SALARY MAY", actual[8]);
+
+        // The same with the `import` directive.
+        let mut journal = Journal::new();
+        crate::parse_sources(
+            [(dir.join("main.ledger").display().to_string(), "import statement.csv\n".as_bytes())],
+            &mut journal,
+        )
+        .unwrap();
+        assert_eq!(3, journal.xacts.len());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,14 +1,177 @@
 /*!
  * Types of directives
+ *
+ * The directives, as read from the source by the [`crate::reader`]. They are independent
+ * of a Journal: the accounts and commodities are kept by name and are not registered
+ * anywhere.
  */
 
-use crate::xact::Xact;
+use std::collections::BTreeMap;
 
+use chrono::{NaiveDate, NaiveDateTime};
 
-/// Types of directives
+use crate::{amount::Quantity, scanner};
+
+/// The location of a directive in the source.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    /// The byte offset of the first character.
+    pub start: usize,
+    /// The byte offset after the last line, including its line ending.
+    pub end: usize,
+    /// The first line, 1-based.
+    pub line: usize,
+    /// The last line, 1-based.
+    pub end_line: usize,
+}
+
+/// A directive with its location in the source.
 #[derive(Debug)]
- pub enum DirectiveType {
-    Comment,
-    Price,
-    Xact(Xact)
+pub struct Directive {
+    pub kind: DirectiveType,
+    pub span: Span,
+}
+
+/// Types of directives
+#[derive(Debug, PartialEq)]
+pub enum DirectiveType {
+    /// `; text`, or a `comment` ... `end comment` block. The text without the comment
+    /// character.
+    Comment(String),
+    /// `P 2023-05-01 EUR 1.10 USD`
+    Price(PriceDirective),
+    Xact(XactDirective),
+    /// `include other.ledger`, the path as written.
+    Include(String),
+    Account(AccountDirective),
+    Commodity(CommodityDirective),
+    Payee(PayeeDirective),
+    /// `tag Name`
+    Tag(String),
+    /// `Y 2023` or `year 2023`
+    Year(i32),
+    /// Any other directive, i.e. `alias`, `apply`, `end`, `D`, with its argument.
+    Other { name: String, argument: String },
+}
+
+/// An amount with the commodity symbol, not registered in any commodity pool.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RawAmount {
+    pub quantity: Quantity,
+    /// The symbol, empty if the amount has no commodity.
+    pub symbol: String,
+}
+
+impl RawAmount {
+    /// Parses the amount parts, i.e. "25", "AUD".
+    pub fn parse(quantity: &str, symbol: &str) -> Option<Self> {
+        let quantity = Quantity::from_str(quantity)?;
+        Some(Self {
+            quantity,
+            symbol: symbol.to_owned(),
+        })
+    }
+}
+
+/// The transaction, with the postings as written. There are no generated postings.
+#[derive(Debug, Default, PartialEq)]
+pub struct XactDirective {
+    pub date: Option<NaiveDate>,
+    pub aux_date: Option<NaiveDate>,
+    pub payee: String,
+    pub note: Option<String>,
+    /// Tags and metadata. (tag, value)
+    pub metadata: BTreeMap<String, Option<String>>,
+    pub posts: Vec<PostDirective>,
+}
+
+impl XactDirective {
+    /// Sets the note and reads any tags from it.
+    pub fn add_note(&mut self, note: &str) {
+        add_note(&mut self.note, &mut self.metadata, note);
+    }
+}
+
+/// The posting line of a transaction.
+///
+/// `    Assets:Stocks  -10 VEUR {20 EUR} [2023-04-01] @ 25 EUR`
+#[derive(Debug, Default, PartialEq)]
+pub struct PostDirective {
+    pub account: String,
+    pub amount: Option<RawAmount>,
+    /// The lot price, `{20 EUR}`.
+    pub lot_price: Option<RawAmount>,
+    /// The lot date, `[2023-04-01]`.
+    pub lot_date: Option<NaiveDate>,
+    /// The cost as written, `@ 25 EUR` or `@@ 250 EUR`.
+    pub cost: Option<RawAmount>,
+    /// Whether the cost is per unit (`@`) or the total (`@@`).
+    pub is_per_unit: bool,
+    pub note: Option<String>,
+    /// Tags and metadata. (tag, value)
+    pub metadata: BTreeMap<String, Option<String>>,
+    /// The location of the posting line and its notes.
+    pub span: Span,
+}
+
+impl PostDirective {
+    /// Sets the note and reads any tags from it.
+    pub fn add_note(&mut self, note: &str) {
+        add_note(&mut self.note, &mut self.metadata, note);
+    }
+}
+
+/// `P 2023-05-01 EUR 1.10 USD`
+#[derive(Debug, PartialEq)]
+pub struct PriceDirective {
+    pub datetime: NaiveDateTime,
+    pub symbol: String,
+    pub price: RawAmount,
+}
+
+/// `account Expenses:Food` with its sub-directives.
+#[derive(Debug, Default, PartialEq)]
+pub struct AccountDirective {
+    pub name: String,
+    pub note: Option<String>,
+    pub aliases: Vec<String>,
+    /// The payee patterns, `payee ^(KFC|Popeyes)$`.
+    pub payees: Vec<String>,
+    /// The `check` expressions.
+    pub checks: Vec<String>,
+    /// The `assert` expressions.
+    pub assertions: Vec<String>,
+    pub default: bool,
+    /// The text of the `;` comments, i.e. hledger's `type: A`.
+    pub comments: Vec<String>,
+}
+
+/// `commodity EUR` with its sub-directives.
+#[derive(Debug, Default, PartialEq)]
+pub struct CommodityDirective {
+    pub symbol: String,
+    pub note: Option<String>,
+    pub format: Option<String>,
+    pub aliases: Vec<String>,
+    pub nomarket: bool,
+    pub default: bool,
+}
+
+/// `payee Name` with its aliases.
+#[derive(Debug, Default, PartialEq)]
+pub struct PayeeDirective {
+    pub name: String,
+    pub aliases: Vec<String>,
+}
+
+/// Sets the note and reads any tags from it.
+fn add_note(
+    target: &mut Option<String>,
+    metadata: &mut BTreeMap<String, Option<String>>,
+    note: &str,
+) {
+    for (tag, value) in scanner::scan_tags(note) {
+        metadata.insert(tag.to_owned(), value.map(|v| v.to_owned()));
+    }
+    *target = Some(note.into());
 }
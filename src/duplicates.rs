@@ -0,0 +1,346 @@
+/*!
+ * Duplicate transactions
+ *
+ * Finds the transactions that are likely duplicates of an earlier one, i.e. after importing
+ * the same bank statement twice. The duplicate has a date within a few days of the original,
+ * the same amounts, and a similar payee.
+ *
+ * In the fix-up mode, the duplicates are removed from the journal files.
+ */
+
+use std::{collections::BTreeMap, fs};
+
+use crate::{
+    amount::Amount,
+    balance::Balance,
+    journal::Journal,
+    parser::ISO_DATE_FORMAT,
+    xact::{Xact, XactId},
+};
+
+/// The default date window, in days.
+pub const DEFAULT_DAYS: i64 = 3;
+
+/// The payees with at least this similarity (0 to 1) are considered the same.
+const PAYEE_SIMILARITY: f64 = 0.8;
+
+/// A transaction that duplicates an earlier one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Duplicate {
+    pub original: XactId,
+    pub duplicate: XactId,
+}
+
+/// Finds the transactions that duplicate an earlier transaction within the given number of
+/// days. The transactions without a date are skipped.
+pub fn find_duplicates(journal: &Journal, days: i64) -> Vec<Duplicate> {
+    let mut xacts: Vec<(usize, &Xact)> = journal
+        .xacts
+        .iter()
+        .enumerate()
+        .filter(|(_, xact)| xact.date.is_some())
+        .collect();
+    // The sort is stable, so the earlier transaction of the same date is the original.
+    xacts.sort_by_key(|(_, xact)| xact.date);
+
+    let amounts: Vec<Vec<Amount>> = xacts.iter().map(|(_, xact)| xact_amounts(xact)).collect();
+    let mut is_duplicate = vec![false; xacts.len()];
+    let mut result = vec![];
+
+    for i in 0..xacts.len() {
+        if is_duplicate[i] || amounts[i].is_empty() {
+            continue;
+        }
+        let (original_index, original) = xacts[i];
+
+        for j in i + 1..xacts.len() {
+            let (index, xact) = xacts[j];
+            let distance = (xact.date.unwrap() - original.date.unwrap()).num_days();
+            if distance > days {
+                break;
+            }
+            if is_duplicate[j]
+                || amounts[i] != amounts[j]
+                || !similar_payees(&original.payee, &xact.payee)
+            {
+                continue;
+            }
+
+            is_duplicate[j] = true;
+            result.push(Duplicate {
+                original: XactId(original_index),
+                duplicate: XactId(index),
+            });
+        }
+    }
+
+    result.sort_by_key(|duplicate| duplicate.duplicate);
+    result
+}
+
+/// Duplicates report. Command: `duplicates`.
+/// Lists each duplicate with the transaction it duplicates.
+pub fn duplicates_report(journal: &Journal, days: i64) -> Vec<String> {
+    find_duplicates(journal, days)
+        .iter()
+        .map(|duplicate| {
+            format!(
+                "{} duplicates {}",
+                describe(journal.get_xact(duplicate.duplicate), journal),
+                describe(journal.get_xact(duplicate.original), journal)
+            )
+        })
+        .collect()
+}
+
+/// Removes the duplicate transactions from the files they were read from, together with
+/// the blank line that follows each. The duplicates that were not read from a file, i.e.
+/// the imported ones, are skipped and reported.
+pub fn drop_duplicates(journal: &Journal, duplicates: &[Duplicate]) -> Result<Vec<String>, String> {
+    let mut output = vec![];
+    // pathname -> the line ranges to remove
+    let mut files: BTreeMap<&str, Vec<(usize, usize)>> = BTreeMap::new();
+    for duplicate in duplicates {
+        let xact = journal.get_xact(duplicate.duplicate);
+        let Some(pos) = xact
+            .pos
+            .as_ref()
+            .filter(|pos| !pos.pathname.is_empty() && pos.pathname != crate::STDIN_PATHNAME)
+        else {
+            output.push(format!("Skipped {}, not read from a file", describe(xact, journal)));
+            continue;
+        };
+        files
+            .entry(&pos.pathname)
+            .or_default()
+            .push((pos.beg_line, pos.end_line));
+    }
+
+    for (pathname, ranges) in files {
+        let source = fs::read_to_string(pathname).map_err(|e| format!("{}: {}", pathname, e))?;
+        fs::write(pathname, remove_lines(&source, &ranges))
+            .map_err(|e| format!("{}: {}", pathname, e))?;
+        output.push(format!("Dropped {} duplicates from {}", ranges.len(), pathname));
+    }
+    Ok(output)
+}
+
+/// Removes the line ranges (1-based, inclusive), and a blank line after each range.
+fn remove_lines(source: &str, ranges: &[(usize, usize)]) -> String {
+    let lines: Vec<&str> = source.split_inclusive('\n').collect();
+    let mut keep = vec![true; lines.len()];
+
+    for &(beg, end) in ranges {
+        for line in beg..=end.min(lines.len()) {
+            keep[line - 1] = false;
+        }
+        if lines.get(end).is_some_and(|line| line.trim().is_empty()) {
+            keep[end] = false;
+        }
+    }
+
+    lines
+        .iter()
+        .zip(keep)
+        .filter(|(_, keep)| *keep)
+        .map(|(line, _)| *line)
+        .collect()
+}
+
+/// The amounts that change hands in the transaction: the sum of the positive amounts
+/// in each commodity, ordered by commodity.
+fn xact_amounts(xact: &Xact) -> Vec<Amount> {
+    let mut balance = Balance::new();
+    for amount in xact.posts.iter().filter_map(|post| post.amount) {
+        if amount.quantity.is_sign_positive() && !amount.is_zero() {
+            balance.add(&amount);
+        }
+    }
+    let mut amounts = balance.amounts;
+    amounts.sort_by_key(|amount| amount.get_commodity());
+    amounts
+}
+
+/// Compares the payees, ignoring the case and the punctuation.
+fn similar_payees(a: &str, b: &str) -> bool {
+    let a = normalize_payee(a);
+    let b = normalize_payee(b);
+    if a.is_empty() || b.is_empty() {
+        return false;
+    }
+    if a == b {
+        return true;
+    }
+    // i.e. "amazon" in "amazon mktp 1234"
+    let (shorter, longer) = if a.len() < b.len() { (&a, &b) } else { (&b, &a) };
+    if shorter.len() >= 3 && longer.starts_with(shorter.as_str()) {
+        return true;
+    }
+
+    let distance = levenshtein(&a, &b) as f64;
+    let length = a.chars().count().max(b.chars().count()) as f64;
+    1.0 - distance / length >= PAYEE_SIMILARITY
+}
+
+/// Lowercase words, separated by a single space.
+fn normalize_payee(payee: &str) -> String {
+    payee
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The number of the single-character edits that turn one text into the other.
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+/// The location, the date and the payee of the transaction.
+fn describe(xact: &Xact, journal: &Journal) -> String {
+    let mut text = String::new();
+    if let Some(pos) = &xact.pos {
+        text += &format!("\"{}\", line {}: ", pos.pathname, pos.beg_line);
+    }
+    if let Some(date) = xact.date {
+        text += &date.format(ISO_DATE_FORMAT).to_string();
+        text += " ";
+    }
+    text += &xact.payee;
+    for amount in xact_amounts(xact) {
+        text += " ";
+        text += &amount.display(&journal.commodity_pool).to_string();
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, io::Cursor};
+
+    use super::{drop_duplicates, find_duplicates, levenshtein, similar_payees, Duplicate};
+    use crate::{journal::Journal, parser, xact::XactId};
+
+    const SOURCE: &str = r#"2023-05-01 AMAZON MKTP US*1234
+    Expenses:Shopping  20 EUR
+    Assets:Bank
+
+2023-05-01 Bakery
+    Expenses:Food  5 EUR
+    Assets:Cash
+
+2023-05-03 Amazon Mktp
+    Assets:Bank  -20 EUR
+    Expenses:Shopping
+
+2023-05-20 Amazon Mktp
+    Expenses:Shopping  20 EUR
+    Assets:Bank
+"#;
+
+    #[test]
+    fn test_similar_payees() {
+        assert!(similar_payees("Supermarket", "SUPERMARKET"));
+        assert!(similar_payees("Amazon", "AMAZON.COM*A1B2"));
+        assert!(similar_payees("Supermarket", "Supermarkt"));
+        assert!(!similar_payees("Bakery", "Bank"));
+        assert_eq!(3, levenshtein("kitten", "sitting"));
+    }
+
+    #[test]
+    fn test_find_duplicates() {
+        let mut journal = Journal::new();
+        parser::read_into_journal(Cursor::new(SOURCE), &mut journal).unwrap();
+
+        let actual = find_duplicates(&journal, 3);
+
+        assert_eq!(
+            vec![Duplicate {
+                original: XactId(0),
+                duplicate: XactId(2)
+            }],
+            actual
+        );
+    }
+
+    #[test]
+    fn test_date_window() {
+        let mut journal = Journal::new();
+        parser::read_into_journal(Cursor::new(SOURCE), &mut journal).unwrap();
+
+        let actual = find_duplicates(&journal, 30);
+
+        assert_eq!(2, actual.len());
+        assert_eq!(XactId(3), actual[1].duplicate);
+    }
+
+    #[test]
+    fn test_drop_duplicates() {
+        let path = std::env::temp_dir()
+            .join(format!("ledger-rs-duplicates-{}.ledger", std::process::id()));
+        fs::write(&path, SOURCE).unwrap();
+        let mut journal = Journal::new();
+        crate::parse_file(&path.display().to_string(), &mut journal).unwrap();
+
+        let duplicates = find_duplicates(&journal, 3);
+        let output = drop_duplicates(&journal, &duplicates).unwrap();
+
+        assert_eq!(
+            vec![format!("Dropped 1 duplicates from {}", path.display())],
+            output
+        );
+        let expected = SOURCE.replace(
+            "2023-05-03 Amazon Mktp\n    Assets:Bank  -20 EUR\n    Expenses:Shopping\n\n",
+            "",
+        );
+        assert_eq!(expected, fs::read_to_string(&path).unwrap());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_drop_from_text_source() {
+        let mut journal = Journal::new();
+        parser::read_into_journal(Cursor::new(SOURCE), &mut journal).unwrap();
+
+        let duplicates = find_duplicates(&journal, 3);
+        let output = drop_duplicates(&journal, &duplicates).unwrap();
+
+        assert_eq!(1, output.len());
+        assert!(output[0].starts_with("Skipped "));
+        assert!(output[0].ends_with("2023-05-03 Amazon Mktp 20 EUR, not read from a file"));
+    }
+
+    /// The imported transactions have no position.
+    #[test]
+    fn test_drop_skips_imported() {
+        let path = std::env::temp_dir()
+            .join(format!("ledger-rs-duplicates-imported-{}.ledger", std::process::id()));
+        fs::write(&path, SOURCE).unwrap();
+        let mut journal = Journal::new();
+        crate::parse_file(&path.display().to_string(), &mut journal).unwrap();
+        journal.xacts[2].pos = None;
+
+        let duplicates = find_duplicates(&journal, 3);
+        let output = drop_duplicates(&journal, &duplicates).unwrap();
+
+        assert_eq!(
+            vec!["Skipped 2023-05-03 Amazon Mktp 20 EUR, not read from a file"],
+            output
+        );
+        assert_eq!(SOURCE, fs::read_to_string(&path).unwrap());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,262 @@
+/*!
+ * Value expressions
+ *
+ * expr.h + .cc
+ *
+ * Only a small subset of Ledger's value expressions is supported: a single
+ * comparison of a posting property against a literal. This is enough for the
+ * `check` and `assert` sub-directives of the `account` directive, i.e.
+ *
+ * `commodity == "EUR"`
+ * `abs(amount) <= 100`
+ * `payee =~ /Supermarket/`
+ */
+
+use regex::Regex;
+
+use crate::{amount::Quantity, post::Post};
+
+/// The value the expression is evaluated against.
+#[derive(Debug, Clone, PartialEq)]
+enum Term {
+    Account,
+    Amount,
+    AbsAmount,
+    Commodity,
+    Note,
+    Payee,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Operator {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Match,
+    NotMatch,
+}
+
+#[derive(Debug, Clone)]
+enum Literal {
+    Number(Quantity),
+    Text(String),
+    Mask(Regex),
+}
+
+/// A parsed value expression.
+#[derive(Debug, Clone)]
+pub struct Expr {
+    /// The original expression text.
+    pub text: String,
+    term: Term,
+    operator: Operator,
+    literal: Literal,
+}
+
+impl PartialEq for Expr {
+    fn eq(&self, other: &Self) -> bool {
+        self.text == other.text
+    }
+}
+
+/// The values available to an expression when it is evaluated for a Post.
+pub struct Scope<'a> {
+    pub post: &'a Post,
+    pub account: &'a str,
+    pub payee: &'a str,
+    /// The symbol of the post amount's commodity.
+    pub commodity: &'a str,
+}
+
+impl Expr {
+    /// Parses the expression text, i.e. `commodity == "EUR"`.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+
+        let operators: [(&str, Operator); 8] = [
+            ("==", Operator::Eq),
+            ("!=", Operator::Ne),
+            ("<=", Operator::Le),
+            (">=", Operator::Ge),
+            ("=~", Operator::Match),
+            ("!~", Operator::NotMatch),
+            ("<", Operator::Lt),
+            (">", Operator::Gt),
+        ];
+        // The first operator in the text wins, so that the literal may contain operator characters.
+        let Some((index, symbol, operator)) = text.char_indices().find_map(|(i, _)| {
+            operators
+                .iter()
+                .find(|(symbol, _)| text[i..].starts_with(symbol))
+                .map(|(symbol, op)| (i, *symbol, op.clone()))
+        }) else {
+            return Err(format!("Unsupported value expression: {}", text));
+        };
+
+        let term = match text[..index].trim() {
+            "account" => Term::Account,
+            "amount" => Term::Amount,
+            "abs(amount)" => Term::AbsAmount,
+            "commodity" => Term::Commodity,
+            "note" => Term::Note,
+            "payee" => Term::Payee,
+            other => return Err(format!("Unknown identifier in value expression: {}", other)),
+        };
+
+        let literal = parse_literal(text[index + symbol.len()..].trim())?;
+
+        // Validate the combinations.
+        let is_numeric = matches!(term, Term::Amount | Term::AbsAmount);
+        let valid = match (&operator, &literal) {
+            (Operator::Match | Operator::NotMatch, Literal::Mask(_)) => !is_numeric,
+            (Operator::Match | Operator::NotMatch, _) => false,
+            (_, Literal::Number(_)) => is_numeric,
+            (Operator::Eq | Operator::Ne, Literal::Text(_)) => !is_numeric,
+            _ => false,
+        };
+        if !valid {
+            return Err(format!("Invalid comparison in value expression: {}", text));
+        }
+
+        Ok(Self {
+            text: text.to_owned(),
+            term,
+            operator,
+            literal,
+        })
+    }
+
+    /// Evaluates the expression against the given posting.
+    pub fn calc(&self, scope: &Scope) -> bool {
+        match &self.literal {
+            Literal::Number(expected) => {
+                let Some(amount) = scope.post.amount else {
+                    return false;
+                };
+                let mut actual = amount.quantity;
+                if self.term == Term::AbsAmount {
+                    actual.set_sign_positive();
+                }
+                match self.operator {
+                    Operator::Eq => actual == *expected,
+                    Operator::Ne => actual != *expected,
+                    Operator::Lt => actual < *expected,
+                    Operator::Le => actual <= *expected,
+                    Operator::Gt => actual > *expected,
+                    Operator::Ge => actual >= *expected,
+                    _ => false,
+                }
+            }
+            Literal::Text(expected) => {
+                let actual = self.text_value(scope);
+                match self.operator {
+                    Operator::Eq => actual == expected,
+                    Operator::Ne => actual != expected,
+                    _ => false,
+                }
+            }
+            Literal::Mask(mask) => {
+                let actual = self.text_value(scope);
+                match self.operator {
+                    Operator::Match => mask.is_match(actual),
+                    Operator::NotMatch => !mask.is_match(actual),
+                    _ => false,
+                }
+            }
+        }
+    }
+
+    fn text_value<'a>(&self, scope: &'a Scope) -> &'a str {
+        match self.term {
+            Term::Account => scope.account,
+            Term::Commodity => scope.commodity,
+            Term::Note => scope.post.note.as_deref().unwrap_or(""),
+            Term::Payee => scope.payee,
+            Term::Amount | Term::AbsAmount => "",
+        }
+    }
+}
+
+fn parse_literal(input: &str) -> Result<Literal, String> {
+    if input.len() >= 2 && input.starts_with('"') && input.ends_with('"') {
+        return Ok(Literal::Text(input[1..input.len() - 1].to_owned()));
+    }
+    if input.len() >= 2 && input.starts_with('/') && input.ends_with('/') {
+        return match Regex::new(&input[1..input.len() - 1]) {
+            Ok(mask) => Ok(Literal::Mask(mask)),
+            Err(e) => Err(format!("Invalid regular expression {}: {}", input, e)),
+        };
+    }
+    match Quantity::from_str(input) {
+        Some(quantity) => Ok(Literal::Number(quantity)),
+        None => Err(format!("Invalid value in expression: {}", input)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Expr, Scope};
+    use crate::{amount::Amount, pool::CommodityPool, post::Post};
+
+    fn scope_for<'a>(post: &'a Post) -> Scope<'a> {
+        Scope {
+            post,
+            account: "Expenses:Food",
+            payee: "Supermarket",
+            commodity: "",
+        }
+    }
+
+    #[test]
+    fn test_commodity_check() {
+        let mut pool = CommodityPool::new();
+        let eur = pool.create("EUR", None);
+        let post = Post {
+            amount: Some(Amount::new(20.into(), Some(eur))),
+            ..Default::default()
+        };
+        let scope = Scope {
+            commodity: &pool.get(eur).symbol,
+            ..scope_for(&post)
+        };
+
+        let expr = Expr::parse(r#"commodity == "EUR""#).unwrap();
+
+        assert!(expr.calc(&scope));
+        assert!(!Expr::parse(r#"commodity == "USD""#)
+            .unwrap()
+            .calc(&scope));
+    }
+
+    #[test]
+    fn test_amount_comparison() {
+        let post = Post {
+            amount: Some(Amount::new((-150).into(), None)),
+            ..Default::default()
+        };
+
+        assert!(Expr::parse("amount < 0").unwrap().calc(&scope_for(&post)));
+        assert!(!Expr::parse("abs(amount) <= 100")
+            .unwrap()
+            .calc(&scope_for(&post)));
+    }
+
+    #[test]
+    fn test_payee_mask() {
+        let post = Post::default();
+
+        let expr = Expr::parse("payee =~ /^Super/").unwrap();
+
+        assert!(expr.calc(&scope_for(&post)));
+    }
+
+    #[test]
+    fn test_unsupported_expression() {
+        assert!(Expr::parse("amount").is_err());
+        assert!(Expr::parse(r#"amount == "EUR""#).is_err());
+        assert!(Expr::parse("total > 0").is_err());
+    }
+}
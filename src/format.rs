@@ -0,0 +1,447 @@
+/*!
+ * Journal formatter
+ *
+ * Rewrites the journal text with a consistent layout: the posts and the sub-directives
+ * are indented with four spaces, the amounts are right-aligned to one column, the dates
+ * are written as `YYYY-MM-DD`, and the transactions are separated by one blank line.
+ * The comments are kept.
+ *
+ * The formatter works on the syntax tree, so it never changes the meaning of the text.
+ * `format_files` verifies that, by reading the directives before and after.
+ */
+
+use std::{fs, ops::Range};
+
+use chrono::NaiveDate;
+
+use crate::{
+    cst::{Node, NodeKind, SyntaxTree},
+    directives::{DirectiveType, Span},
+    parser::{parse_date_in, ISO_DATE_FORMAT},
+    reader,
+};
+
+/// The indentation of the posts and the sub-directives.
+const INDENT: &str = "    ";
+
+/// A post line, split into (account, amount, rest), or a comment line in a transaction.
+struct Row {
+    account: String,
+    amount: String,
+    rest: String,
+}
+
+/// Formats the journal text.
+pub fn format_journal(source: &str) -> String {
+    format_journal_in(source, None)
+}
+
+/// Formats the journal text, with the dates written in the given input date format.
+pub fn format_journal_in(source: &str, input_date_format: Option<&str>) -> String {
+    let mut tree = SyntaxTree::parse(source);
+    let newline = if source.contains("\r\n") { "\r\n" } else { "\n" };
+
+    // The transactions: (header, rows).
+    let xacts: Vec<Option<(String, Vec<Row>)>> = tree
+        .nodes
+        .iter()
+        .map(|node| {
+            (node.kind == NodeKind::Xact).then(|| split_xact(&tree, node, input_date_format))
+        })
+        .collect();
+
+    // The right edge of the amounts, the same for the whole file.
+    let amount_end = xacts
+        .iter()
+        .flatten()
+        .flat_map(|(_, rows)| rows)
+        .filter(|row| !row.amount.is_empty())
+        .map(|row| width(&row.account) + 2 + width(&row.amount))
+        .max()
+        .unwrap_or(0);
+
+    let mut formatted: Vec<Option<String>> = vec![];
+    for (i, (node, xact)) in tree.nodes.iter().zip(xacts).enumerate() {
+        let text = match node.kind {
+            // Collapse the blank lines, and drop the ones at the start and at the end.
+            NodeKind::Blank => {
+                let previous_blank = i == 0 || tree.nodes[i - 1].kind == NodeKind::Blank;
+                let at_end = tree.nodes[i..].iter().all(|n| n.kind == NodeKind::Blank);
+                if previous_blank || at_end {
+                    Some(String::new())
+                } else {
+                    Some(newline.to_owned())
+                }
+            }
+            NodeKind::Xact => {
+                let (header, rows) = xact.expect("split transaction");
+                let mut text = format_xact(header, rows, amount_end, newline);
+                // Separate the transaction from whatever follows.
+                if tree.nodes.get(i + 1).is_some_and(|n| n.kind != NodeKind::Blank) {
+                    text.push_str(newline);
+                }
+                Some(text)
+            }
+            NodeKind::Directive => Some(format_directive(&tree, node, newline)),
+            _ => None,
+        };
+        formatted.push(text);
+    }
+
+    for (node, text) in tree.nodes.iter_mut().zip(formatted) {
+        if let Some(text) = text {
+            node.replace(&text);
+        }
+    }
+
+    let mut output = tree.to_string();
+    if !output.is_empty() && !output.ends_with('\n') {
+        output.push_str(newline);
+    }
+    output
+}
+
+/// Formats the files in place. With `check`, only finds the files that are not formatted.
+/// Returns the messages about the reformatted files, or an error with the files that
+/// need formatting in the `check` mode.
+pub fn format_files(
+    paths: &[String],
+    check: bool,
+    input_date_format: Option<&str>,
+) -> Result<Vec<String>, String> {
+    let mut changed: Vec<&str> = vec![];
+
+    for path in paths {
+        let source = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let formatted = format_journal_in(&source, input_date_format);
+        if formatted == source {
+            continue;
+        }
+        verify_meaning(&source, &formatted, input_date_format)
+            .map_err(|e| format!("{}: {}", path, e))?;
+
+        if !check {
+            fs::write(path, &formatted).map_err(|e| format!("{}: {}", path, e))?;
+        }
+        changed.push(path);
+    }
+
+    if check && !changed.is_empty() {
+        let lines: Vec<String> = changed.iter().map(|p| format!("Would reformat {}", p)).collect();
+        return Err(lines.join("\n"));
+    }
+
+    Ok(changed.iter().map(|p| format!("Formatted {}", p)).collect())
+}
+
+/// Checks that the formatted text has the same directives as the source.
+fn verify_meaning(
+    source: &str,
+    formatted: &str,
+    input_date_format: Option<&str>,
+) -> Result<(), String> {
+    let before = read_directives(source, input_date_format)?;
+    let after = read_directives(formatted, input_date_format)?;
+    if before != after {
+        return Err("Formatting would change the meaning of the journal".to_owned());
+    }
+    Ok(())
+}
+
+/// The directives, without the locations.
+fn read_directives(
+    text: &str,
+    input_date_format: Option<&str>,
+) -> Result<Vec<DirectiveType>, String> {
+    let mut iter = reader::create_str_reader(text);
+    if let Some(format) = input_date_format {
+        iter = iter.with_input_date_format(format);
+    }
+    iter.map(|directive| {
+        let mut kind = directive.map_err(|e| e.to_string())?.kind;
+        if let DirectiveType::Xact(xact) = &mut kind {
+            for post in &mut xact.posts {
+                post.span = Span::default();
+            }
+        }
+        Ok(kind)
+    })
+    .collect()
+}
+
+/// The first line as is, the indented lines re-indented.
+fn format_directive(tree: &SyntaxTree, node: &Node, newline: &str) -> String {
+    let mut lines: Vec<String> = tree
+        .text(node)
+        .lines()
+        .map(|line| line.trim_end().to_owned())
+        .collect();
+    for line in lines.iter_mut().skip(1) {
+        *line = format!("{}{}", INDENT, line.trim());
+    }
+    join_lines(&lines, newline)
+}
+
+fn format_xact(header: String, rows: Vec<Row>, amount_end: usize, newline: &str) -> String {
+    let mut lines = vec![header];
+
+    for row in rows {
+        let line = if row.account.is_empty() {
+            // comment
+            format!("{}{}", INDENT, row.rest)
+        } else if row.amount.is_empty() {
+            format!("{}{}{}", INDENT, row.account, row.rest)
+        } else {
+            let padding = amount_end - width(&row.account) - width(&row.amount);
+            format!("{}{}{}{}{}", INDENT, row.account, " ".repeat(padding), row.amount, row.rest)
+        };
+        lines.push(line);
+    }
+
+    join_lines(&lines, newline)
+}
+
+/// Splits the transaction into the header and the rows, with the dates normalised.
+fn split_xact(tree: &SyntaxTree, node: &Node, input_date_format: Option<&str>) -> (String, Vec<Row>) {
+    let edits = date_edits(tree, node, input_date_format);
+
+    let header = node.children_of(NodeKind::XactHeader).next().expect("xact header");
+    let header = edited_text(tree, line_range(tree, header), &edits)
+        .trim_end()
+        .to_owned();
+
+    let mut rows = vec![];
+    for child in &node.children {
+        match child.kind {
+            NodeKind::Post => rows.push(split_post(tree, child, &edits)),
+            NodeKind::Comment => rows.push(Row {
+                account: String::new(),
+                amount: String::new(),
+                rest: tree.text(child).trim().to_owned(),
+            }),
+            _ => (),
+        }
+    }
+
+    (header, rows)
+}
+
+/// Splits the post line into the account, the amount and the rest of the line.
+fn split_post(tree: &SyntaxTree, post: &Node, edits: &[(Range<usize>, String)]) -> Row {
+    let line_end = line_range(tree, post).end;
+    let account = post.find(NodeKind::Account).expect("post account");
+
+    let amount_tokens: Vec<&Node> = post
+        .children
+        .iter()
+        .filter(|n| matches!(n.kind, NodeKind::Sign | NodeKind::Quantity | NodeKind::Symbol))
+        .collect();
+    let (Some(first), Some(last)) = (amount_tokens.first(), amount_tokens.last()) else {
+        // No amount. Keep the note as is.
+        return Row {
+            account: tree.text(account).to_owned(),
+            amount: String::new(),
+            rest: edited_text(tree, account.range.end..line_end, edits),
+        };
+    };
+
+    Row {
+        account: tree.text(account).to_owned(),
+        amount: tree.source()[first.range.start..last.range.end].to_owned(),
+        rest: edited_text(tree, last.range.end..line_end, edits),
+    }
+}
+
+/// The new text of the dates in the transaction: (range, date).
+fn date_edits(
+    tree: &SyntaxTree,
+    node: &Node,
+    input_date_format: Option<&str>,
+) -> Vec<(Range<usize>, String)> {
+    let mut edits = vec![];
+    let mut stack = vec![node];
+    while let Some(node) = stack.pop() {
+        if matches!(node.kind, NodeKind::Date | NodeKind::AuxDate | NodeKind::LotDate) {
+            let text = tree.text(node);
+            if let Some(date) = normalise_date(text, input_date_format) {
+                if date != text {
+                    edits.push((node.range.clone(), date));
+                }
+            }
+        }
+        stack.extend(&node.children);
+    }
+    edits.sort_by_key(|(range, _)| range.start);
+    edits
+}
+
+/// Writes the date as `YYYY-MM-DD`. The dates without a year are left as they are,
+/// since they depend on the `Y` directive.
+fn normalise_date(text: &str, input_date_format: Option<&str>) -> Option<String> {
+    let date = match input_date_format {
+        Some(format) => NaiveDate::parse_from_str(text, format).ok()?,
+        None => {
+            let parts: Vec<&str> = text.split(['-', '/', '.']).collect();
+            if parts.len() != 3 || parts[0].len() != 4 {
+                return None;
+            }
+            parse_date_in(text, None, None).ok()?
+        }
+    };
+    Some(date.format(ISO_DATE_FORMAT).to_string())
+}
+
+/// The source text in the range, with the edits applied.
+fn edited_text(tree: &SyntaxTree, range: Range<usize>, edits: &[(Range<usize>, String)]) -> String {
+    let source = tree.source();
+    let mut result = String::new();
+    let mut position = range.start;
+    for (edit_range, text) in edits {
+        if edit_range.start < position || edit_range.end > range.end {
+            continue;
+        }
+        result.push_str(&source[position..edit_range.start]);
+        result.push_str(text);
+        position = edit_range.end;
+    }
+    result.push_str(&source[position..range.end]);
+    result
+}
+
+/// The range of the line node, without the line ending and the trailing whitespace.
+fn line_range(tree: &SyntaxTree, node: &Node) -> Range<usize> {
+    node.range.start..node.range.start + tree.text(node).trim_end().len()
+}
+
+fn join_lines(lines: &[String], newline: &str) -> String {
+    lines.iter().map(|line| format!("{}{}", line, newline)).collect()
+}
+
+fn width(text: &str) -> usize {
+    text.chars().count()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{format_files, format_journal, format_journal_in};
+
+    #[test]
+    fn test_alignment_and_indentation() {
+        let source = r#"2023-05-01 Shop
+  Expenses:Food  20 EUR
+	Assets:Cash   -120.50 EUR ; paid
+ ; comment
+    Assets:Other
+"#;
+        let expected = r#"2023-05-01 Shop
+    Expenses:Food     20 EUR
+    Assets:Cash  -120.50 EUR ; paid
+    ; comment
+    Assets:Other
+"#;
+
+        assert_eq!(expected, format_journal(source));
+    }
+
+    #[test]
+    fn test_alignment_sign_before_symbol() {
+        let source = "2023-05-01 Shop\n    Expenses:Food  $20.00\n    Assets  -$20.00\n";
+        let expected = "2023-05-01 Shop\n    Expenses:Food  $20.00\n    Assets        -$20.00\n";
+
+        assert_eq!(expected, format_journal(source));
+    }
+
+    #[test]
+    fn test_alignment_across_transactions() {
+        let source = "2023-05-01 Shop\n    Expenses  1 EUR\n    Assets\n\n2023-05-02 Shop\n    Expenses:Food:Fruit  1 EUR\n    Assets\n";
+        let expected = "2023-05-01 Shop\n    Expenses             1 EUR\n    Assets\n\n2023-05-02 Shop\n    Expenses:Food:Fruit  1 EUR\n    Assets\n";
+
+        assert_eq!(expected, format_journal(source));
+    }
+
+    #[test]
+    fn test_blank_lines() {
+        let source = "\n\n; header\n2023-05-01 Shop\n    Expenses  1 EUR\n    Assets\n2023-05-02 Shop\n    Expenses  1 EUR\n    Assets\n\n\n\naccount Assets\n  note Cash\n\n\n";
+        let expected = "; header\n2023-05-01 Shop\n    Expenses  1 EUR\n    Assets\n\n2023-05-02 Shop\n    Expenses  1 EUR\n    Assets\n\naccount Assets\n    note Cash\n";
+
+        assert_eq!(expected, format_journal(source));
+    }
+
+    #[test]
+    fn test_dates() {
+        let source = "2023/5/1=2023.05.03 Shop\n    Assets:Stocks  1 VEUR {20 EUR} [2023/04/01]\n    Assets\n\n05/02 Shop\n    Expenses  1 EUR\n    Assets\n";
+        let expected = "2023-05-01=2023-05-03 Shop\n    Assets:Stocks  1 VEUR {20 EUR} [2023-04-01]\n    Assets\n\n05/02 Shop\n    Expenses        1 EUR\n    Assets\n";
+
+        assert_eq!(expected, format_journal(source));
+    }
+
+    #[test]
+    fn test_input_date_format() {
+        let source = "01.05.2023 Shop\n    Expenses  1 EUR\n    Assets\n";
+
+        assert_eq!(
+            "2023-05-01 Shop\n    Expenses  1 EUR\n    Assets\n",
+            format_journal_in(source, Some("%d.%m.%Y"))
+        );
+    }
+
+    #[test]
+    fn test_formatted_is_stable() {
+        let source = fs::read_to_string("tests/trade-buy-sell-lot.ledger").unwrap();
+
+        let once = format_journal(&source);
+
+        assert_eq!(once, format_journal(&once));
+    }
+
+    #[test]
+    fn test_crlf_kept() {
+        let source = "2023-05-01 Shop\r\n  Expenses  1 EUR\r\n  Assets\r\n";
+
+        assert_eq!(
+            "2023-05-01 Shop\r\n    Expenses  1 EUR\r\n    Assets\r\n",
+            format_journal(source)
+        );
+    }
+
+    #[test]
+    fn test_format_files() {
+        let path = std::env::temp_dir()
+            .join(format!("ledger-rs-format-test-{}.ledger", std::process::id()));
+        let path_str = path.display().to_string();
+        fs::write(&path, "2023/05/01 Shop\n  Expenses  1 EUR\n  Assets\n").unwrap();
+        let paths = vec![path_str.clone()];
+
+        // check
+        let error = format_files(&paths, true, None).unwrap_err();
+        assert_eq!(format!("Would reformat {}", path_str), error);
+
+        // format
+        let output = format_files(&paths, false, None).unwrap();
+        assert_eq!(vec![format!("Formatted {}", path_str)], output);
+        assert_eq!(
+            "2023-05-01 Shop\n    Expenses  1 EUR\n    Assets\n",
+            fs::read_to_string(&path).unwrap()
+        );
+
+        // formatted
+        assert!(format_files(&paths, true, None).unwrap().is_empty());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_invalid_journal_not_formatted() {
+        let path = std::env::temp_dir()
+            .join(format!("ledger-rs-format-invalid-{}.ledger", std::process::id()));
+        fs::write(&path, "account\n 2023-05-01 Shop\n").unwrap();
+
+        let result = format_files(&[path.display().to_string()], false, None);
+
+        assert!(result.unwrap_err().contains("requires an argument"));
+        assert_eq!("account\n 2023-05-01 Shop\n", fs::read_to_string(&path).unwrap());
+        fs::remove_file(&path).unwrap();
+    }
+}
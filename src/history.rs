@@ -17,11 +17,12 @@ use std::{
 };
 
 use chrono::{Local, NaiveDateTime};
-use petgraph::{algo::astar, stable_graph::NodeIndex, Graph};
+use petgraph::{algo::astar, stable_graph::NodeIndex, visit::EdgeRef, Graph};
+use serde::Serialize;
 
 use crate::{
     amount::{Amount, Quantity},
-    commodity::{self, Commodity, PricePoint},
+    commodity::{CommodityId, PricePoint},
     pool::CommodityIndex,
 };
 
@@ -33,7 +34,7 @@ type PriceMap = BTreeMap<NaiveDateTime, Quantity>;
 // }
 
 // pub(crate) type CommodityHistory = Graph<Commodity, PriceMap>;
-pub(crate) struct CommodityHistory(Graph<*const Commodity, PriceMap>);
+pub(crate) struct CommodityHistory(Graph<CommodityId, PriceMap>);
 
 impl CommodityHistory {
     pub fn new() -> Self {
@@ -41,52 +42,64 @@ impl CommodityHistory {
     }
 
     /// Adds the commodity to the commodity graph.
-    pub fn add_commodity(&mut self, commodity: *const Commodity) -> CommodityIndex {
+    pub fn add_commodity(&mut self, commodity: CommodityId) -> CommodityIndex {
         self.add_node(commodity)
     }
 
     /// Adds a new price point.
     /// i.e. 1 EUR = 1.12 USD
     /// source: EUR
+    /// target: USD
     /// date
-    /// price: 1.12 USD
+    /// quantity: 1.12
     pub fn add_price(
         &mut self,
-        source_ptr: *const Commodity,
+        source: CommodityIndex,
+        target: CommodityIndex,
         datetime: NaiveDateTime,
-        price: Amount,
+        quantity: Quantity,
     ) {
-        let source = commodity::from_ptr(source_ptr);
-        assert!(Some(source) != price.get_commodity());
+        assert_ne!(source, target);
 
         log::debug!(
-            "adding price for {:?}, date: {:?}, price: {:?}",
-            source.symbol,
+            "adding price for {:?}, date: {:?}, price: {:?} of {:?}",
+            source,
             datetime,
-            price
+            quantity,
+            target
         );
 
-        let index = match self.0.find_edge(
-            source.graph_index.unwrap(),
-            price.get_commodity().unwrap().graph_index.unwrap(),
-        ) {
+        let index = match self.0.find_edge(source, target) {
             Some(index) => index,
-            None => {
-                let dest = price.get_commodity().unwrap().graph_index.unwrap();
-                self.add_edge(source.graph_index.unwrap(), dest, PriceMap::new())
-            }
+            None => self.add_edge(source, target, PriceMap::new()),
         };
 
         let prices = self.edge_weight_mut(index).unwrap();
 
         // Add the price to the price history.
         // prices.entry(key) ?
-        prices.insert(datetime, price.quantity);
+        prices.insert(datetime, quantity);
+    }
+
+    /// All the price points, by date.
+    pub fn prices(&self) -> Vec<Price> {
+        let mut prices: Vec<Price> = self
+            .edge_references()
+            .flat_map(|edge| {
+                let commodity = self.get_commodity(edge.source());
+                let target = self.get_commodity(edge.target());
+                edge.weight().iter().map(move |(datetime, quantity)| {
+                    Price::new(commodity, *datetime, Amount::new(*quantity, Some(target)))
+                })
+            })
+            .collect();
+        prices.sort_by_key(|price| price.datetime);
+        prices
     }
 
-    pub fn get_commodity(&self, index: NodeIndex) -> &Commodity {
-        let ptr = self.node_weight(index).expect("index should be valid");
-        unsafe { &**ptr }
+    /// Returns the id of the commodity at the given node.
+    pub fn get_commodity(&self, index: NodeIndex) -> CommodityId {
+        *self.node_weight(index).expect("index should be valid")
     }
 
     // pub fn get_commodity_mut(&mut self, index: NodeIndex) -> &mut Commodity {
@@ -101,15 +114,12 @@ impl CommodityHistory {
     /// find_price(source, target, moment, oldest);
     pub fn find_price(
         &self,
-        source_ptr: *const Commodity,
-        target_ptr: *const Commodity,
+        source: CommodityIndex,
+        target: CommodityIndex,
         moment: NaiveDateTime,
         oldest: NaiveDateTime,
     ) -> Option<PricePoint> {
-        assert_ne!(source_ptr, target_ptr);
-
-        let source: CommodityIndex = commodity::from_ptr(source_ptr).graph_index.unwrap();
-        let target: CommodityIndex = commodity::from_ptr(target_ptr).graph_index.unwrap();
+        assert_ne!(source, target);
 
         // Search for the shortest path using a*.
         let shortest_path = astar(&self.0, source, |finish| finish == target, |e| 1, |_| 0);
@@ -138,11 +148,11 @@ impl CommodityHistory {
             quantity = y;
         } else {
             // else calculate the rate
-            let (x, y) = self.calculate_rate(source, target_ptr, path);
+            let (x, y) = self.calculate_rate(source, target, path);
             date = x;
             quantity = y;
         }
-        let pp = PricePoint::new(date, Amount::new(quantity, Some(target_ptr)));
+        let pp = PricePoint::new(date, Amount::new(quantity, Some(self.get_commodity(target))));
         return Some(pp);
 }
 
@@ -152,8 +162,8 @@ impl CommodityHistory {
     /// 
     /// The final date of the price, when multiple hops involved, is the least recent date of the available
     /// intermediate rates.
-    fn calculate_rate(&self, source: CommodityIndex, target_ptr: *const Commodity, path: Vec<NodeIndex>) -> (NaiveDateTime, Quantity) {
-        let mut result = Amount::new(Quantity::ONE, Some(target_ptr));
+    fn calculate_rate(&self, source: CommodityIndex, target: CommodityIndex, path: Vec<NodeIndex>) -> (NaiveDateTime, Quantity) {
+        let mut result = Amount::new(Quantity::ONE, Some(self.get_commodity(target)));
         let mut temp_source = source;
         let mut least_recent: NaiveDateTime = Local::now().naive_local();
 
@@ -226,7 +236,7 @@ impl CommodityHistory {
 
 impl Deref for CommodityHistory {
     // specify the target type as i32
-    type Target = Graph<*const Commodity, PriceMap>;
+    type Target = Graph<CommodityId, PriceMap>;
 
     // define the deref method that returns a reference to the inner value
     fn deref(&self) -> &Self::Target {
@@ -259,10 +269,10 @@ fn get_latest_price(
 ///
 /// TODO: Compare with price_point_t, which does not have the commodity_index,
 /// if one type would be enough.
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct Price {
     /// The commodity being priced.
-    pub commodity: *const Commodity,
+    pub commodity: CommodityId,
     /// Point in time at which the price is valid.
     pub datetime: NaiveDateTime,
     /// Price of the commodity. i.e. 1.20 AUD
@@ -270,16 +280,13 @@ pub struct Price {
 }
 
 impl Price {
-    pub fn new(commodity: *const Commodity, datetime: NaiveDateTime, cost: Amount) -> Self {
+    pub fn new(commodity: CommodityId, datetime: NaiveDateTime, cost: Amount) -> Self {
         Self {
             commodity,
             datetime,
             price: cost,
         }
     }
-    pub fn get_commodity(&self) -> &Commodity {
-        unsafe { &*self.commodity }
-    }
 }
 
 #[cfg(test)]
@@ -290,7 +297,7 @@ mod tests {
     use super::{get_latest_price, CommodityHistory, PriceMap};
     use crate::{
         amount::{Amount, Quantity},
-        commodity::{self, Commodity, PricePoint},
+        commodity::CommodityId,
         journal::Journal,
         parser::{parse_amount, parse_datetime},
     };
@@ -298,25 +305,26 @@ mod tests {
     #[test]
     fn test_adding_commodity() {
         let mut hist = CommodityHistory::new();
-        let c = Commodity::new("EUR");
+        let c = CommodityId(0);
 
         // Act
-        let cdty_index = hist.add_commodity(&c);
+        let cdty_index = hist.add_commodity(c);
 
         // Assert
         assert_eq!(1, hist.node_count());
-        // TODO: assert_eq!("EUR", hist.node_weight(cdty_index).unwrap().symbol);
+        assert_eq!(c, *hist.node_weight(cdty_index).unwrap());
     }
 
     #[test]
     fn test_get_commodity() {
-        let mut hist = CommodityHistory::new();
-        let c = Commodity::new("EUR");
-        let id = hist.add_commodity(&c);
+        let mut journal = Journal::new();
+        let eur = journal.commodity_pool.create("EUR", None);
+        let id = journal.commodity_pool.get(eur).graph_index.unwrap();
 
-        let actual = hist.get_commodity(id);
+        let actual = journal.commodity_pool.commodity_history.get_commodity(id);
 
-        assert_eq!("EUR", actual.symbol);
+        assert_eq!(eur, actual);
+        assert_eq!("EUR", journal.commodity_pool.get(actual).symbol);
     }
 
     #[test]
@@ -328,12 +336,12 @@ mod tests {
         let local = Local::now();
         let today = local.naive_local();
         let price = Amount::new(25.into(), Some(usd));
-        let hist = &mut journal.commodity_pool.commodity_history;
 
         // Act
-        hist.add_price(eur, today, price);
+        journal.commodity_pool.add_price(eur, today, price);
 
         // Assert
+        let hist = &journal.commodity_pool.commodity_history;
         assert_eq!(2, hist.node_count());
         assert_eq!(1, hist.edge_count());
 
@@ -344,8 +352,7 @@ mod tests {
     #[test]
     fn test_index() {
         let mut graph = CommodityHistory::new();
-        let eur = Commodity::new("EUR");
-        let x = graph.add_commodity(&eur);
+        let x = graph.add_commodity(CommodityId(0));
         let y = x.index();
         let z = NodeIndex::new(y);
 
@@ -375,28 +382,25 @@ mod tests {
     #[test]
     fn test_get_direct_price() {
         let journal = &mut Journal::new();
-        let eur_ptr = journal.commodity_pool.create("EUR", None);
-        let usd_ptr = journal.commodity_pool.create("USD", None);
+        let eur = journal.commodity_pool.create("EUR", None);
+        let usd = journal.commodity_pool.create("USD", None);
         // add price
         let date = parse_datetime("2023-05-01").unwrap();
         let price = parse_amount("1.20 USD", journal).unwrap();
-        assert!(!price.get_commodity().unwrap().symbol.is_empty());
-        journal.commodity_pool.add_price(eur_ptr, date, price);
+        assert_eq!(Some(usd), price.get_commodity());
+        journal.commodity_pool.add_price(eur, date, price);
 
         // act
-        let (actual_date, actual_quantity) = journal
-            .commodity_pool
+        let pool = &journal.commodity_pool;
+        let (actual_date, actual_quantity) = pool
             .commodity_history
             .get_direct_price(
-                commodity::from_ptr(eur_ptr).graph_index.unwrap(),
-                commodity::from_ptr(usd_ptr).graph_index.unwrap(),
+                pool.get(eur).graph_index.unwrap(),
+                pool.get(usd).graph_index.unwrap(),
             )
             .unwrap();
 
         // assert
-        // assert_eq!(eur_ptr, actual.price.commodity);
-        // assert_eq!("2023-05-01 00:00:00", actual.datetime.to_string());
-        // assert_eq!(actual.price.quantity, 1.20.into());
         assert_eq!(actual_date, &date);
         assert_eq!(Quantity::from("1.20"), *actual_quantity);
     }
@@ -406,28 +410,30 @@ mod tests {
     fn test_find_price_1_hop() {
         let mut journal = Journal::new();
         // add commodities
-        let eur_ptr = journal.commodity_pool.create("EUR", None);
-        let usd_ptr = journal.commodity_pool.create("USD", None);
+        let eur = journal.commodity_pool.create("EUR", None);
+        let usd = journal.commodity_pool.create("USD", None);
         // add price
         let date = parse_datetime("2023-05-01").unwrap();
         let price = parse_amount("1.20 USD", &mut journal).unwrap();
         let oldest = Local::now().naive_local();
-        journal.commodity_pool.add_price(eur_ptr, date, price);
+        journal.commodity_pool.add_price(eur, date, price);
 
         // act
-        let actual = journal
-            .commodity_pool
+        let pool = &journal.commodity_pool;
+        let actual = pool
             .commodity_history
-            .find_price(eur_ptr, usd_ptr, date, oldest)
+            .find_price(
+                pool.get(eur).graph_index.unwrap(),
+                pool.get(usd).graph_index.unwrap(),
+                date,
+                oldest,
+            )
             .expect("price found");
 
         // assert
         assert_eq!(actual.when, date);
         assert_eq!(actual.price.quantity, "1.20".into());
-        assert_eq!(
-            actual.price.get_commodity().unwrap(),
-            commodity::from_ptr(usd_ptr)
-        );
+        assert_eq!(actual.price.get_commodity(), Some(usd));
     }
 
     /// Test calculating the rate.
@@ -437,22 +443,23 @@ mod tests {
     fn test_calculate_rate() {
         // arrange
         let mut journal = Journal::new();
-        let eur_ptr = journal.commodity_pool.create("EUR", None);
-        let aud_ptr = journal.commodity_pool.create("AUD", None);
-        let usd_ptr = journal.commodity_pool.create("USD", None);
-        let source = commodity::from_ptr(eur_ptr).graph_index.unwrap();
+        let eur = journal.commodity_pool.create("EUR", None);
+        let aud = journal.commodity_pool.create("AUD", None);
+        let usd = journal.commodity_pool.create("USD", None);
+        let source = journal.commodity_pool.get(eur).graph_index.unwrap();
+        let target = journal.commodity_pool.get(usd).graph_index.unwrap();
         let path = vec![NodeIndex::new(0), NodeIndex::new(1), NodeIndex::new(2)];
         // prices
         let date = parse_datetime("2023-05-01").unwrap();
         // 1 EUR = 2 AUD
         let two_aud = parse_amount("2 AUD", &mut journal).unwrap();
-        journal.commodity_pool.add_price(eur_ptr, date, two_aud);
+        journal.commodity_pool.add_price(eur, date, two_aud);
         // 1 AUD = 3 USD
         let three_usd = parse_amount("3 USD", &mut journal).unwrap();
-        journal.commodity_pool.add_price(aud_ptr, date, three_usd);
+        journal.commodity_pool.add_price(aud, date, three_usd);
 
         // act
-        let (actual_date, actual_quantity) = journal.commodity_pool.commodity_history.calculate_rate(source, usd_ptr, path);
+        let (actual_date, actual_quantity) = journal.commodity_pool.commodity_history.calculate_rate(source, target, path);
 
         // assert
         assert_eq!(date, actual_date);
@@ -463,30 +470,35 @@ mod tests {
     #[test_log::test]
     fn test_find_price_2_hops() {
         let mut journal = Journal::new();
-        let eur_ptr = journal.commodity_pool.create("EUR", None);
-        let aud_ptr = journal.commodity_pool.create("AUD", None);
-        let usd_ptr = journal.commodity_pool.create("USD", None);
+        let eur = journal.commodity_pool.create("EUR", None);
+        let aud = journal.commodity_pool.create("AUD", None);
+        let usd = journal.commodity_pool.create("USD", None);
         // prices
         let date = parse_datetime("2023-05-01").unwrap();
         // 1 EUR = 2 AUD
         let euraud = parse_amount("2 AUD", &mut journal).unwrap();
-        journal.commodity_pool.add_price(eur_ptr, date, euraud);
+        journal.commodity_pool.add_price(eur, date, euraud);
         // 1 AUD = 3 USD
         let audusd = parse_amount("3 USD", &mut journal).unwrap();
-        journal.commodity_pool.add_price(aud_ptr, date, audusd);
+        journal.commodity_pool.add_price(aud, date, audusd);
         let oldest = Local::now().naive_local();
 
         // act
-        let actual = journal
-            .commodity_pool
+        let pool = &journal.commodity_pool;
+        let actual = pool
             .commodity_history
-            .find_price(eur_ptr, usd_ptr, date, oldest)
+            .find_price(
+                pool.get(eur).graph_index.unwrap(),
+                pool.get(usd).graph_index.unwrap(),
+                date,
+                oldest,
+            )
             .unwrap();
 
         // assert
         assert_eq!(actual.when, date);
         // 1 EUR = 2 AUD = 6 USD
         assert_eq!(actual.price.quantity, 6.into());
-        assert_eq!(actual.price.get_commodity().unwrap().symbol, "USD");
+        assert_eq!("USD", pool.get(actual.price.get_commodity().unwrap()).symbol);
     }
 }
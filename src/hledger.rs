@@ -0,0 +1,151 @@
+/*!
+ * hledger journals
+ *
+ * hledger reads most of the journal syntax, with a few differences. The tags are
+ * `name:value`, separated by commas, and the account types are declared with
+ * `account Assets  ; type: A`. The reading is done by the parser, with `Syntax::Hledger`.
+ *
+ * `write_journal` writes the journal in hledger's syntax.
+ */
+
+use std::collections::BTreeMap;
+
+use crate::{journal::Journal, parser::ISO_DATE_FORMAT, scanner};
+
+/// Writes the journal in hledger's syntax: the account types, the prices and the
+/// transactions, with the tags and the balance assertions.
+pub fn write_journal(journal: &Journal) -> Vec<String> {
+    let pool = &journal.commodity_pool;
+    let mut output = vec![];
+
+    let mut accounts: Vec<_> = journal
+        .accounts
+        .iter()
+        .filter_map(|account| Some((account.fullname(), account.kind?)))
+        .collect();
+    accounts.sort_by_key(|(name, _)| *name);
+    for (name, kind) in accounts {
+        output.push(format!("account {}  ; type: {}", name, kind.name()));
+    }
+
+    let prices = pool.prices();
+    if !output.is_empty() && !prices.is_empty() {
+        output.push(String::new());
+    }
+    for price in prices {
+        output.push(format!(
+            "P {} {} {}",
+            price.datetime.date().format(ISO_DATE_FORMAT),
+            pool.get(price.commodity).symbol,
+            price.price.display(pool)
+        ));
+    }
+
+    for xact in &journal.xacts {
+        if !output.is_empty() {
+            output.push(String::new());
+        }
+
+        let mut header = match xact.date {
+            Some(date) => date.format(ISO_DATE_FORMAT).to_string(),
+            None => String::new(),
+        };
+        header += " ";
+        match (&xact.code, xact.payee.split_once(' ')) {
+            (Some(code), Some((state @ ("*" | "!"), payee))) => {
+                header += &format!("{} ({}) {}", state, code, payee)
+            }
+            (Some(code), _) => header += &format!("({}) {}", code, xact.payee),
+            (None, _) => header += &xact.payee,
+        }
+        if let Some(comment) = comment(xact.note.as_deref(), &xact.metadata) {
+            header += "  ; ";
+            header += &comment;
+        }
+        output.push(header);
+
+        for post in &xact.posts {
+            let mut line = format!("    {}", journal.get_account(post.account).fullname());
+            if let Some(amount) = post.amount {
+                line += "  ";
+                line += &amount.display(pool).to_string();
+                if let Some(cost) = post.cost {
+                    if cost.get_commodity() != amount.get_commodity() {
+                        line += " @@ ";
+                        line += &cost.abs().display(pool).to_string();
+                    }
+                }
+            }
+            if let Some(assertion) = post.assertion {
+                line += " ";
+                line += assertion.operator();
+                line += " ";
+                line += &assertion.amount.display(pool).to_string();
+            }
+            if let Some(comment) = comment(post.note.as_deref(), &post.metadata) {
+                line += "  ; ";
+                line += &comment;
+            }
+            output.push(line);
+        }
+    }
+
+    output
+}
+
+/// The comment with the text of the note and the tags, `name:value`.
+fn comment(note: Option<&str>, metadata: &BTreeMap<String, Option<String>>) -> Option<String> {
+    let mut parts = vec![];
+    if let Some(text) = note.map(scanner::note_text).filter(|text| !text.is_empty()) {
+        parts.push(text);
+    }
+    for (name, value) in metadata {
+        parts.push(format!("{}:{}", name, value.as_deref().unwrap_or_default()));
+    }
+    match parts.is_empty() {
+        true => None,
+        false => Some(parts.join(", ")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::write_journal;
+    use crate::{journal::Journal, parse_sources};
+
+    #[test]
+    fn test_write_journal() {
+        let mut journal = Journal::new();
+        let text = "account Assets:Cash  ; type: C\n\n2023-05-01 * (12) Shop  ; Groceries :food:\n    ; receipt: R-1\n    Expenses:Food  10 EUR\n    Assets:Cash  = -10 EUR\n";
+        parse_sources([("test.ledger", text.as_bytes())], &mut journal).unwrap();
+
+        let actual = write_journal(&journal);
+
+        assert_eq!(
+            vec![
+                "account Assets:Cash  ; type: Cash",
+                "",
+                "2023-05-01 * (12) Shop  ; food:, receipt:R-1",
+                "    Expenses:Food  10 EUR",
+                "    Assets:Cash  -10 EUR = -10 EUR",
+            ],
+            actual
+        );
+    }
+
+    /// The written journal is read back the same.
+    #[test]
+    fn test_round_trip() {
+        let mut journal = Journal::new();
+        let text = "2023-05-01 Shop  ; lunch, project:home\n    Expenses:Food  10 EUR  ; diet:\n    Assets:Cash\n";
+        parse_sources([("test.hledger", text.as_bytes())], &mut journal).unwrap();
+
+        let written = write_journal(&journal).join("\n");
+        let mut read = Journal::new();
+        parse_sources([("written.hledger", written.as_bytes())], &mut read).unwrap();
+
+        assert_eq!(written, write_journal(&read).join("\n"));
+        assert_eq!(Some("home"), read.xacts[0].get_tag("project"));
+        assert!(read.xacts[0].posts[0].metadata.contains_key("diet"));
+    }
+}
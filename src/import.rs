@@ -0,0 +1,240 @@
+/*!
+ * Importers
+ *
+ * The `import` directive reads the transactions from the files in other formats, i.e. the
+ * CSV bank statements. An importer converts the file into the journal text, which is then
+ * parsed as if it was written in place of the directive.
+ *
+ * ```ledger
+ * import statement.csv
+ * import csv statement.txt
+ * ```
+ *
+ * The importer is selected by the explicit type, or by the file extension. The custom
+ * importers are registered in `Journal::importers`, before parsing.
+ */
+
+use std::{fs, path::Path};
+
+use crate::{
+    csv::{self, CsvImporter},
+    journal::Journal,
+    ofx::OfxImporter,
+};
+
+/// Converts a file in another format into the journal text.
+pub trait Importer: Send + Sync {
+    /// The type, i.e. `csv` in `import csv statement.txt`.
+    fn name(&self) -> &str;
+
+    /// The file extensions, without the dot, i.e. `csv`. Compared case-insensitively.
+    fn extensions(&self) -> &[&str];
+
+    /// Converts the contents of the file into the journal text. The path is for the
+    /// messages and for the related files, i.e. the rules file next to the statement.
+    /// The transactions already in the journal are skipped, so that importing the same
+    /// statement again adds nothing.
+    fn import(&self, path: &Path, source: &str, journal: &Journal) -> Result<String, String>;
+}
+
+/// The importers, by type and by file extension.
+pub struct ImporterRegistry {
+    importers: Vec<Box<dyn Importer>>,
+}
+
+impl ImporterRegistry {
+    /// A registry without any importers.
+    pub fn empty() -> Self {
+        Self { importers: vec![] }
+    }
+
+    /// Adds the importer. It replaces the importer of the same type, and takes precedence
+    /// for its extensions.
+    pub fn register(&mut self, importer: Box<dyn Importer>) {
+        self.importers.retain(|existing| existing.name() != importer.name());
+        self.importers.push(importer);
+    }
+
+    /// The importer of the given type, i.e. `csv`.
+    pub fn find(&self, name: &str) -> Option<&dyn Importer> {
+        self.importers
+            .iter()
+            .rev()
+            .find(|importer| importer.name() == name)
+            .map(|importer| importer.as_ref())
+    }
+
+    /// The importer for the extension of the file.
+    pub fn find_for_path(&self, path: &Path) -> Option<&dyn Importer> {
+        let extension = path.extension()?.to_str()?;
+        self.importers
+            .iter()
+            .rev()
+            .find(|importer| {
+                importer
+                    .extensions()
+                    .iter()
+                    .any(|e| e.eq_ignore_ascii_case(extension))
+            })
+            .map(|importer| importer.as_ref())
+    }
+
+    /// The registered types.
+    pub fn names(&self) -> Vec<&str> {
+        self.importers.iter().map(|importer| importer.name()).collect()
+    }
+}
+
+impl Default for ImporterRegistry {
+    /// The registry with the built-in importers.
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry.register(Box::new(CsvImporter));
+        registry.register(Box::new(OfxImporter));
+        registry
+    }
+}
+
+/// Converts the statements to the journal text. Command: `convert`.
+/// With the rules file, the statements are read as CSV. Otherwise, the importer is selected
+/// by the file extension. The transactions already in the journal, if given, are skipped.
+pub fn convert_files(
+    paths: &[String],
+    rules_path: Option<&str>,
+    journal: Option<&Journal>,
+) -> Result<Vec<String>, String> {
+    if rules_path.is_some() {
+        return csv::convert_files(paths, rules_path, journal);
+    }
+    if paths.is_empty() {
+        return Err("No statement file was given".to_owned());
+    }
+
+    let empty = Journal::new();
+    let journal = journal.unwrap_or(&empty);
+    let mut output: Vec<String> = vec![];
+    for path in paths {
+        let path = Path::new(path);
+        let importer = journal
+            .importers
+            .find_for_path(path)
+            .ok_or_else(|| format!("No importer for \"{}\"", path.display()))?;
+        let source = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let text = importer.import(path, &source, journal)?;
+
+        if text.trim().is_empty() {
+            continue;
+        }
+        if !output.is_empty() {
+            output.push(String::new());
+        }
+        output.extend(text.trim_end().lines().map(|line| line.to_owned()));
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{Importer, ImporterRegistry};
+    use crate::{journal::Journal, parse_sources};
+
+    /// `2023-05-01;Shop;5 EUR` lines, paid in cash.
+    struct LinesImporter;
+
+    impl Importer for LinesImporter {
+        fn name(&self) -> &str {
+            "lines"
+        }
+
+        fn extensions(&self) -> &[&str] {
+            &["txt"]
+        }
+
+        fn import(&self, path: &Path, source: &str, _: &Journal) -> Result<String, String> {
+            let mut text = String::new();
+            for (index, line) in source.lines().enumerate() {
+                let fields: Vec<&str> = line.split(';').collect();
+                let [date, payee, amount] = fields[..] else {
+                    return Err(format!("{}:{}: expected 3 fields", path.display(), index + 1));
+                };
+                text += &format!("{date} {payee}\n    Expenses  {amount}\n    Assets:Cash\n\n");
+            }
+            Ok(text)
+        }
+    }
+
+    fn journal_with(files: &[(&str, &str)]) -> (Journal, std::path::PathBuf) {
+        let dir = std::env::temp_dir()
+            .join(format!("ledger-rs-import-{}-{}", files[0].0, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for (name, content) in files {
+            std::fs::write(dir.join(name), content).unwrap();
+        }
+        let mut journal = Journal::new();
+        journal.importers.register(Box::new(LinesImporter));
+        (journal, dir)
+    }
+
+    fn parse(journal: &mut Journal, path: &Path) -> Result<(), String> {
+        let file = std::fs::File::open(path).unwrap();
+        parse_sources([(path.display().to_string(), file)], journal)
+    }
+
+    #[test]
+    fn test_registry() {
+        let mut registry = ImporterRegistry::empty();
+        registry.register(Box::new(LinesImporter));
+        registry.register(Box::new(LinesImporter));
+
+        assert_eq!(vec!["lines"], registry.names());
+        assert!(registry.find("lines").is_some());
+        assert!(registry.find("csv").is_none());
+        assert!(registry.find_for_path(Path::new("statement.TXT")).is_some());
+        assert!(registry.find_for_path(Path::new("statement")).is_none());
+        assert_eq!(vec!["csv", "ofx"], ImporterRegistry::default().names());
+    }
+
+    #[test]
+    fn test_import_directive() {
+        let (mut journal, dir) = journal_with(&[
+            (
+                "main.ledger",
+                "apply account Personal\nimport cash.txt\nimport lines cash.dat\nend apply\n",
+            ),
+            ("cash.txt", "2023-05-01;Shop;5 EUR\n2023-05-02;Bakery;2 EUR\n"),
+            ("cash.dat", "2023-05-03;Kiosk;1 EUR\n"),
+        ]);
+
+        parse(&mut journal, &dir.join("main.ledger")).unwrap();
+
+        let payees: Vec<&str> = journal.xacts.iter().map(|x| x.payee.as_str()).collect();
+        assert_eq!(vec!["Shop", "Bakery", "Kiosk"], payees);
+        // As if written inline, within the `apply account`.
+        assert!(journal.find_account("Personal:Expenses").is_some());
+        // The positions are not in the imported file.
+        assert!(journal.xacts[0].pos.is_none());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_import_errors() {
+        let (mut journal, dir) = journal_with(&[
+            ("errors.ledger", "import bad.txt\n"),
+            ("bad.txt", "2023-05-01;Shop\n"),
+            ("unknown.ledger", "import statement.qif\n"),
+            ("statement.qif", "!Type:Bank\n"),
+        ]);
+
+        let bad = parse(&mut journal, &dir.join("errors.ledger")).unwrap_err();
+        let unknown = parse(&mut journal, &dir.join("unknown.ledger")).unwrap_err();
+
+        assert!(bad.ends_with(&format!("{}:1: expected 3 fields", dir.join("bad.txt").display())));
+        assert!(unknown.ends_with(&format!(
+            "No importer for \"{}\"",
+            dir.join("statement.qif").display()
+        )));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -4,56 +4,157 @@
  * Provides methods for fetching and iterating over the contained elements
  * (transactions, posts, accounts...).
  */
-use std::io::Read;
+use std::{
+    collections::{HashMap, HashSet},
+    io::Read,
+};
+
+use regex::Regex;
+use serde::{ser::SerializeStruct, Serialize, Serializer};
 
 use crate::{
-    account::Account,
-    commodity::Commodity,
-    parser,
+    account::{Account, AccountId},
+    commodity::{Commodity, COMMODITY_KNOWN},
+    history::CommodityHistory,
+    import::ImporterRegistry,
+    parser::{self, Syntax},
     pool::{CommodityIndex, CommodityPool},
-    post::Post,
-    xact::Xact,
+    post::{Post, PostId},
+    xact::{Xact, XactId},
 };
 
-// pub type XactIndex = usize;
+/// How to treat the accounts, commodities, payees and tags that were not declared.
+///
+/// journal_t::checking_style
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckingStyle {
+    /// Accept everything. The default.
+    Permissive,
+    /// Warn about the undeclared names, `--strict`.
+    Warning,
+    /// Stop with an error on the undeclared names, `--pedantic`.
+    Error,
+}
 
+/// The journal owns all the model objects. They refer to each other by their ids,
+/// which are the indices in the journal's collections.
+///
+/// A parsed Journal is `Send + Sync`, so it can be shared as `Arc<Journal>` and
+/// queried from several threads at once.
 pub struct Journal {
-    pub master: Box<Account>,
+    /// All the accounts, indexed by AccountId. The first one is the master account.
+    pub(crate) accounts: Vec<Account>,
 
     pub commodity_pool: CommodityPool,
     pub xacts: Vec<Xact>,
+
+    /// The default account, used to balance the single-post transactions.
+    pub bucket: Option<AccountId>,
+    /// Map (alias, account full name)
+    pub(crate) account_aliases: HashMap<String, String>,
+    /// Regular expression aliases (pattern, replacement), applied to the full account name.
+    pub(crate) regex_aliases: Vec<(Regex, String)>,
+    /// Expand the aliases repeatedly, until there is no alias for the result.
+    pub recursive_aliases: bool,
+    /// Payee patterns, which select the account for the postings to `Unknown`.
+    pub(crate) payees_for_unknown_accounts: Vec<(Regex, String)>,
+
+    pub checking_style: CheckingStyle,
+    /// The date format of the journal, from `--input-date-format`, i.e. `%d.%m.%Y`.
+    pub input_date_format: Option<String>,
+    /// Payees declared with the `payee` directive.
+    pub(crate) known_payees: HashSet<String>,
+    /// Payee aliases (pattern, payee) from the `alias` sub-directive of `payee`.
+    pub(crate) payee_aliases: Vec<(Regex, String)>,
+    /// Tags declared with the `tag` directive.
+    pub(crate) known_tags: HashSet<String>,
+    /// The importers for the `import` directive.
+    pub importers: ImporterRegistry,
+    /// The syntax of all the sources, from `--input-format`. By default, it depends on the
+    /// file extension.
+    pub syntax: Option<Syntax>,
 }
 
+// Fails to compile if any of the model types stops being shareable between threads.
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Journal>();
+    assert_send_sync::<CommodityPool>();
+    assert_send_sync::<CommodityHistory>();
+};
+
 impl Journal {
     pub fn new() -> Self {
         Self {
-            master: Box::new(Account::new("")),
+            accounts: vec![Account::new("")],
 
             commodity_pool: CommodityPool::new(),
             xacts: vec![],
             // sources: Vec<fileinfo?>
+            bucket: None,
+            account_aliases: HashMap::new(),
+            regex_aliases: vec![],
+            recursive_aliases: false,
+            payees_for_unknown_accounts: vec![],
+            checking_style: CheckingStyle::Permissive,
+            input_date_format: None,
+            known_payees: HashSet::new(),
+            payee_aliases: vec![],
+            known_tags: HashSet::new(),
+            importers: ImporterRegistry::default(),
+            syntax: None,
         }
     }
 
-    pub fn add_xact(&mut self, xact: Xact) -> &Xact {
+    pub fn add_xact(&mut self, xact: Xact) -> XactId {
         self.xacts.push(xact);
-        //self.xacts.len() - 1
-        self.xacts.last().unwrap()
+        XactId(self.xacts.len() - 1)
+    }
+
+    /// Adds the post to the transaction and registers it with its account.
+    pub fn add_post(&mut self, xact_id: XactId, mut post: Post) -> PostId {
+        post.xact = xact_id;
+        let account = post.account;
+
+        let posts = &mut self.xacts[xact_id.0].posts;
+        posts.push(post);
+        let post_id = PostId {
+            xact: xact_id,
+            index: posts.len() - 1,
+        };
+
+        self.get_account_mut(account).posts.push(post_id);
+
+        post_id
     }
 
     pub fn all_posts(&self) -> Vec<&Post> {
         self.xacts.iter().flat_map(|x| x.posts.iter()).collect()
     }
 
-    pub fn get_account(&self, acct_ptr: *const Account) -> &Account {
-        unsafe { &*acct_ptr }
+    pub fn get_xact(&self, id: XactId) -> &Xact {
+        &self.xacts[id.0]
     }
 
-    pub fn get_account_mut(&self, acct_ptr: *const Account) -> &mut Account {
-        unsafe {
-            let mut_ptr = acct_ptr as *mut Account;
-            &mut *mut_ptr
-        }
+    pub fn get_xact_mut(&mut self, id: XactId) -> &mut Xact {
+        &mut self.xacts[id.0]
+    }
+
+    pub fn get_post(&self, id: PostId) -> &Post {
+        &self.xacts[id.xact.0].posts[id.index]
+    }
+
+    /// The root of the account tree.
+    pub fn master(&self) -> &Account {
+        self.get_account(AccountId::MASTER)
+    }
+
+    pub fn get_account(&self, id: AccountId) -> &Account {
+        &self.accounts[id.0]
+    }
+
+    pub fn get_account_mut(&mut self, id: AccountId) -> &mut Account {
+        &mut self.accounts[id.0]
     }
 
     pub fn get_commodity(&self, index: CommodityIndex) -> &Commodity {
@@ -65,52 +166,313 @@ impl Journal {
     /// account_t * journal_t::register_account(const string& name, post_t * post,
     ///                                         account_t * master_account)
     ///
-    pub fn register_account(&mut self, name: &str) -> Option<*const Account> {
+    pub fn register_account(&mut self, name: &str) -> Option<AccountId> {
+        self.register_account_in(name, "")
+    }
+
+    /// Registers the account below the given parent account, i.e. from `apply account`.
+    /// An alias replaces the whole account name, regardless of the parent.
+    pub fn register_account_in(&mut self, name: &str, parent: &str) -> Option<AccountId> {
         if name.is_empty() {
             panic!("Invalid account name {:?}", name);
         }
 
-        // todo: expand_aliases
-        // account_t * result = expand_aliases(name);
-
-        let master_account: &mut Account = &mut self.master;
+        // If there are any account aliases, substitute before creating an account object.
+        let name = match self.expand_aliases(name) {
+            Ok(expanded) if expanded == name && !parent.is_empty() => {
+                format!("{}:{}", parent, name)
+            }
+            Ok(expanded) => expanded,
+            Err(e) => {
+                log::error!("{}", e);
+                return None;
+            }
+        };
 
         // Create the account object and associate it with the journal; this
         // is registering the account.
 
-        let Some(account_ptr) = master_account.find_or_create(name, true)
-        else { return None };
+        let account = self.find_or_create_account(AccountId::MASTER, &name, true);
 
         // todo: add any validity checks here.
 
-        let account = self.get_account(account_ptr);
+        account
+    }
+
+    /// Finds the account by its name, relative to the parent account, i.e. "Assets:Cash".
+    /// The missing accounts are created with `auto_create`.
+    ///
+    /// account_t * find_account(const string& name, bool auto_create = true);
+    pub fn find_or_create_account(
+        &mut self,
+        parent: AccountId,
+        name: &str,
+        auto_create: bool,
+    ) -> Option<AccountId> {
+        let mut account = parent;
+
+        // search for name parts in between the `:`
+        for part in name.split(':') {
+            account = match self.get_account(account).accounts.get(part) {
+                Some(&child) => child,
+                None if auto_create => self.create_account(account, part),
+                None => return None,
+            };
+        }
+
         Some(account)
     }
 
+    /// Creates the sub-account with the given name.
+    fn create_account(&mut self, parent: AccountId, name: &str) -> AccountId {
+        let id = AccountId(self.accounts.len());
+
+        let mut new_account = Account::new(name);
+        new_account.parent = Some(parent);
+        // skip the master account.
+        let parent_name = self.get_account(parent).fullname();
+        new_account.fullname = if parent_name.is_empty() {
+            name.to_owned()
+        } else {
+            format!("{}:{}", parent_name, name)
+        };
+
+        log::debug!("The new account {:?} id: {:?}", new_account.fullname, id);
+
+        self.accounts.push(new_account);
+        self.get_account_mut(parent).accounts.insert(name.to_owned(), id);
+
+        id
+    }
+
+    /// Returns the account name with the aliases replaced.
+    /// With `recursive_aliases`, the result is expanded again until no alias applies.
+    ///
+    /// account_t * journal_t::expand_aliases(string name)
+    ///
+    pub(crate) fn expand_aliases(&self, name: &str) -> Result<String, String> {
+        let mut name = name.to_owned();
+        let mut already_seen: Vec<String> = vec![];
+
+        while let Some((alias, expanded)) = self.expand_alias(&name) {
+            if already_seen.contains(&alias) {
+                return Err(format!("Infinite recursion on alias expansion for {}", alias));
+            }
+            already_seen.push(alias);
+            name = expanded;
+
+            if !self.recursive_aliases {
+                break;
+            }
+        }
+
+        Ok(name)
+    }
+
+    /// Applies the first matching alias to the name.
+    /// Returns (alias, expanded name), or None if there is no alias for the name.
+    fn expand_alias(&self, name: &str) -> Option<(String, String)> {
+        // There is an alias for the full account name, including colons.
+        if let Some(fullname) = self.account_aliases.get(name) {
+            return Some((name.to_owned(), fullname.to_owned()));
+        }
+
+        // Only check the very first account for alias expansion.
+        if let Some(colon) = name.find(':') {
+            let first = &name[..colon];
+            if let Some(fullname) = self.account_aliases.get(first) {
+                return Some((first.to_owned(), format!("{}{}", fullname, &name[colon..])));
+            }
+        }
+
+        for (mask, replacement) in &self.regex_aliases {
+            if !mask.is_match(name) {
+                continue;
+            }
+            let expanded = mask.replace_all(name, replacement.as_str()).into_owned();
+            if expanded != name {
+                return Some((mask.as_str().to_owned(), expanded));
+            }
+        }
+
+        None
+    }
+
+    /// Registers an alias for the account with the given full name.
+    pub fn add_account_alias(&mut self, alias: &str, account_name: &str) {
+        self.account_aliases
+            .insert(alias.to_owned(), account_name.to_owned());
+    }
+
+    /// Registers an alias from the `alias` directive or the `--alias` option.
+    ///
+    /// `checking=Assets:Bank:Checking` is a plain alias, and
+    /// `/^(.+):Chk$/=Assets:$1:Checking` replaces the matches of the regular expression
+    /// in the full account name. `\1`-style group references are also accepted.
+    pub fn add_alias(&mut self, definition: &str) -> Result<(), String> {
+        let definition = definition.trim();
+
+        if let Some(rest) = definition.strip_prefix('/') {
+            // Regular expression alias. The pattern can contain `=`.
+            let Some(end) = rest.rfind('/') else {
+                return Err(format!("Invalid alias definition: {}", definition));
+            };
+            let Some(replacement) = rest[end + 1..].trim_start().strip_prefix('=') else {
+                return Err(format!("Invalid alias definition: {}", definition));
+            };
+            let mask = Regex::new(&rest[..end])
+                .map_err(|e| format!("Invalid alias pattern {:?}: {}", &rest[..end], e))?;
+            let replacement = Regex::new(r"\\(\d)")
+                .unwrap()
+                .replace_all(replacement.trim(), "$${$1}")
+                .into_owned();
+
+            self.regex_aliases.push((mask, replacement));
+            return Ok(());
+        }
+
+        match definition.split_once('=') {
+            Some((alias, account_name)) if !alias.trim().is_empty() && !account_name.trim().is_empty() => {
+                self.add_account_alias(alias.trim(), account_name.trim());
+                Ok(())
+            }
+            _ => Err(format!("Invalid alias definition: {}", definition)),
+        }
+    }
+
+    /// Postings to the `Unknown` account, with the payee matching the pattern, will be
+    /// assigned to the given account.
+    pub fn add_payee_mapping(&mut self, pattern: &str, account_name: &str) -> Result<(), String> {
+        let mask = Regex::new(pattern)
+            .map_err(|e| format!("Invalid payee pattern {:?}: {}", pattern, e))?;
+        self.payees_for_unknown_accounts
+            .push((mask, account_name.to_owned()));
+        Ok(())
+    }
+
+    /// Finds the account for the given payee, from the `payee` sub-directives.
+    pub fn find_account_for_payee(&self, payee: &str) -> Option<&str> {
+        self.payees_for_unknown_accounts
+            .iter()
+            .find(|(mask, _)| mask.is_match(payee))
+            .map(|(_, account_name)| account_name.as_str())
+    }
+
+    /// Declares the payee, as with the `payee` directive.
+    pub fn register_payee(&mut self, name: &str) {
+        self.known_payees.insert(name.to_owned());
+    }
+
+    pub fn add_payee_alias(&mut self, pattern: &str, payee: &str) -> Result<(), String> {
+        let mask = Regex::new(pattern)
+            .map_err(|e| format!("Invalid payee alias {:?}: {}", pattern, e))?;
+        self.payee_aliases.push((mask, payee.to_owned()));
+        Ok(())
+    }
+
+    /// Returns the payee for the name, after applying the payee aliases.
+    ///
+    /// string journal_t::validate_payee(const string& name_or_alias)
+    pub fn translate_payee<'a>(&'a self, name: &'a str) -> &'a str {
+        self.payee_aliases
+            .iter()
+            .find(|(mask, _)| mask.is_match(name))
+            .map(|(_, payee)| payee.as_str())
+            .unwrap_or(name)
+    }
+
+    /// Declares the tag, as with the `tag` directive.
+    pub fn register_tag(&mut self, name: &str) {
+        self.known_tags.insert(name.to_owned());
+    }
+
+    /// Lists the names in the transaction which were not declared, for `--strict` and
+    /// `--pedantic`. Returns (index of the post, message). The messages about the
+    /// transaction itself have no post index.
+    pub fn find_undeclared(&self, xact: &Xact) -> Vec<(Option<usize>, String)> {
+        let mut result = vec![];
+
+        if !self.known_payees.contains(&xact.payee) {
+            result.push((None, format!("Unknown payee '{}'", xact.payee)));
+        }
+        for tag in xact.metadata.keys() {
+            if !self.known_tags.contains(tag) {
+                result.push((None, format!("Unknown metadata tag '{}'", tag)));
+            }
+        }
+
+        for (i, post) in xact.posts.iter().enumerate() {
+            let account = self.get_account(post.account);
+            if !account.known {
+                result.push((Some(i), format!("Unknown account '{}'", account.fullname())));
+            }
+            for amount in [post.amount, post.cost].iter().flatten() {
+                let Some(commodity) = amount.get_commodity() else {
+                    continue;
+                };
+                let commodity = self.commodity_pool.get(commodity);
+                if !commodity.has_flags(COMMODITY_KNOWN) {
+                    result.push((Some(i), format!("Unknown commodity '{}'", commodity.symbol)));
+                }
+            }
+            for tag in post.metadata.keys() {
+                // The transaction's tags are reported once, above.
+                if !self.known_tags.contains(tag) && !xact.metadata.contains_key(tag) {
+                    result.push((Some(i), format!("Unknown metadata tag '{}'", tag)));
+                }
+            }
+        }
+
+        result
+    }
+
+    /// Finds account by full name.
+    /// i.e. "Assets:Cash"
     pub fn find_account(&self, name: &str) -> Option<&Account> {
-        self.master.find_account(name)
+        self.find_account_id(name).map(|id| self.get_account(id))
+    }
+
+    pub fn find_account_id(&self, name: &str) -> Option<AccountId> {
+        let mut account = AccountId::MASTER;
+        for part in name.split(':') {
+            account = *self.get_account(account).accounts.get(part)?;
+        }
+        Some(account)
     }
 
     /// Read journal source (file or string).
     ///
     /// std::size_t journal_t::read(parse_context_stack_t& context)
     ///
-    /// returns number of transactions parsed
-    pub fn read<T: Read>(&mut self, source: T) -> usize {
+    /// returns number of transactions parsed, or the first parsing error
+    pub fn read<T: Read>(&mut self, source: T) -> Result<usize, String> {
         // read_textual
-        parser::read_into_journal(source, self);
+        parser::read_into_journal(source, self)?;
+
+        Ok(self.xacts.len())
+    }
+}
 
-        self.xacts.len()
+/// The model as JSON: the accounts, the commodities, the prices and the transactions.
+/// The ids in the items are the indices in these arrays. See the `json` module.
+impl Serialize for Journal {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut journal = serializer.serialize_struct("Journal", 4)?;
+        journal.serialize_field("accounts", &self.accounts)?;
+        journal.serialize_field("commodities", &self.commodity_pool.commodities)?;
+        journal.serialize_field("prices", &self.commodity_pool.prices())?;
+        journal.serialize_field("xacts", &self.xacts)?;
+        journal.end()
     }
 }
 
 #[cfg(test)]
 mod tests {
     use core::panic;
-    use std::{io::Cursor, ptr::addr_of};
+    use std::io::Cursor;
 
     use super::Journal;
-    use crate::{account::Account, parse_file};
+    use crate::{account::AccountId, amount::Quantity, parse_file, parse_text};
 
     #[test]
     fn test_add_account() {
@@ -132,13 +494,13 @@ mod tests {
         let Some(ptr) = journal.register_account(NAME) else {panic!("unexpected")};
         let actual = journal.get_account(ptr);
 
-        assert_eq!(&*journal.master as *const Account, actual.parent);
+        assert_eq!(Some(AccountId::MASTER), actual.parent);
     }
 
     #[test]
     fn test_find_account() {
         let mut journal = Journal::new();
-        parse_file("tests/basic.ledger", &mut journal);
+        parse_file("tests/basic.ledger", &mut journal).unwrap();
 
         let actual = journal.find_account("Assets:Cash");
 
@@ -152,32 +514,95 @@ mod tests {
 
         // act
         let new_acct = j.register_account(NAME).unwrap();
-        let actual = j.get_account_mut(new_acct);
-        
+
         let journal = &j;
+        let actual = journal.get_account(new_acct);
 
         // Asserts
-        assert_eq!(4, journal.master.flatten_account_tree().len());
+        assert_eq!(4, journal.master().flatten_account_tree(journal).len());
         assert_eq!(NAME, actual.fullname());
 
         // tree structure
-        let master = &journal.master;
+        let master = journal.master();
         assert_eq!("", master.name);
 
-        let assets = master.find_account("Assets").unwrap();
-        // let assets = journal.get_account(assets_ptr);
+        let assets_id = master.accounts["Assets"];
+        let assets = journal.get_account(assets_id);
         assert_eq!("Assets", assets.name);
-        assert_eq!(&*journal.master as *const Account, assets.parent);
+        assert_eq!(Some(AccountId::MASTER), assets.parent);
 
-        let inv = assets.find_account("Investments").unwrap();
-        // let inv = journal.get_account_mut(inv_ix);
+        let inv_id = assets.accounts["Investments"];
+        let inv = journal.get_account(inv_id);
         assert_eq!("Investments", inv.name);
-        assert_eq!(addr_of!(*assets), inv.parent);
+        assert_eq!(Some(assets_id), inv.parent);
 
-        let broker = inv.find_account("Broker").unwrap();
-        // let broker = journal.get_account(broker_ix);
+        let broker = journal.get_account(inv.accounts["Broker"]);
         assert_eq!("Broker", broker.name);
-        assert_eq!(inv as *const Account, broker.parent);
+        assert_eq!(Some(inv_id), broker.parent);
+        assert_eq!(Some(new_acct), journal.find_account_id(NAME));
+    }
+
+    #[test]
+    fn test_expand_aliases() {
+        let mut journal = Journal::new();
+        journal.add_alias("checking=Assets:Bank:Checking").unwrap();
+
+        assert_eq!("Assets:Bank:Checking", journal.expand_aliases("checking").unwrap());
+        // Only the first account in the name gets expanded.
+        assert_eq!(
+            "Assets:Bank:Checking:Joint",
+            journal.expand_aliases("checking:Joint").unwrap()
+        );
+        assert_eq!("Assets:checking", journal.expand_aliases("Assets:checking").unwrap());
+    }
+
+    #[test]
+    fn test_regex_aliases() {
+        let mut journal = Journal::new();
+        journal.add_alias(r"/^(.+):Chk$/ = Assets:\1:Checking").unwrap();
+
+        let actual = journal.expand_aliases("Bank:Chk").unwrap();
+
+        assert_eq!("Assets:Bank:Checking", actual);
+    }
+
+    #[test]
+    fn test_recursive_aliases() {
+        let mut journal = Journal::new();
+        journal.add_alias("chk=checking").unwrap();
+        journal.add_alias("checking=Assets:Bank:Checking").unwrap();
+
+        assert_eq!("checking", journal.expand_aliases("chk").unwrap());
+
+        journal.recursive_aliases = true;
+        assert_eq!("Assets:Bank:Checking", journal.expand_aliases("chk").unwrap());
+
+        // infinite recursion
+        journal.add_alias("Assets=chk").unwrap();
+        assert!(journal.expand_aliases("chk").is_err());
+    }
+
+    #[test]
+    fn test_register_account_in_parent() {
+        let mut journal = Journal::new();
+        journal.add_alias("cash=Assets:Cash").unwrap();
+
+        let food = journal.register_account_in("Expenses:Food", "Personal").unwrap();
+        let cash = journal.register_account_in("cash", "Personal").unwrap();
+
+        assert_eq!("Personal:Expenses:Food", journal.get_account(food).fullname());
+        assert_eq!("Assets:Cash", journal.get_account(cash).fullname());
+    }
+
+    #[test]
+    fn test_register_account_with_alias() {
+        let mut journal = Journal::new();
+        journal.add_alias("cash=Assets:Cash").unwrap();
+
+        let ptr = journal.register_account("cash").unwrap();
+
+        assert_eq!("Assets:Cash", journal.get_account(ptr).fullname());
+        assert!(journal.find_account("cash").is_none());
     }
 
     /// The master account needs to be created in the Journal automatically.
@@ -185,9 +610,10 @@ mod tests {
     fn test_master_gets_created() {
         let j = Journal::new();
 
-        let actual = j.master;
+        let actual = j.master();
 
         assert_eq!("", actual.name);
+        assert_eq!(None, actual.parent);
     }
 
     #[test]
@@ -199,9 +625,55 @@ mod tests {
         let mut j = Journal::new();
 
         // Act
-        let num_xact = j.read(Cursor::new(src));
+        let num_xact = j.read(Cursor::new(src)).unwrap();
 
         // Assert
         assert_eq!(1, num_xact);
     }
+
+    /// The ids stay valid while the collections grow.
+    #[test]
+    fn test_ids_after_growing() {
+        let input: String = (1..=50)
+            .map(|i| format!("2023-05-01 Payee {}\n    Expenses:Food  {} EUR\n    Assets:Cash\n\n", i, i))
+            .collect();
+        let mut journal = Journal::new();
+
+        parse_text(&input, &mut journal).unwrap();
+
+        let cash = journal.find_account("Assets:Cash").unwrap();
+        assert_eq!(50, cash.posts.len());
+        let post = journal.get_post(cash.posts[49]);
+        assert_eq!("Payee 50", journal.get_xact(post.xact).payee);
+        assert_eq!(Quantity::from(-50), post.amount.unwrap().quantity);
+    }
+
+    #[test]
+    fn test_find_undeclared() {
+        let input = r#"account Expenses:Food
+account Assets:Cash
+commodity EUR
+payee Supermarket
+tag food
+
+2023-05-01 Supermarket
+    Expenses:Fodo  20 USD ; :food:
+    Assets:Cash ; :cash:
+"#;
+        let mut journal = Journal::new();
+
+        parse_text(input, &mut journal).unwrap();
+
+        let actual = journal.find_undeclared(&journal.xacts[0]);
+
+        assert_eq!(
+            vec![
+                (Some(0), "Unknown account 'Expenses:Fodo'".to_string()),
+                (Some(0), "Unknown commodity 'USD'".to_string()),
+                (Some(1), "Unknown commodity 'USD'".to_string()),
+                (Some(1), "Unknown metadata tag 'cash'".to_string()),
+            ],
+            actual
+        );
+    }
 }
@@ -0,0 +1,356 @@
+/*!
+ * JSON output
+ *
+ * The reports as structured data, for `--output-format json`. The amounts are objects with
+ * the quantity as text, to keep the precision, and the commodity symbol, or null:
+ *
+ * ```json
+ * { "quantity": "-20.00", "commodity": "EUR" }
+ * ```
+ *
+ * The balances are arrays of amounts, one for each commodity. The dates are `YYYY-MM-DD`.
+ *
+ * - `accounts`: the full names of the accounts, `["Assets", "Assets:Cash"]`;
+ * - `balance`: the account tree, from the master account, with the sub-accounts in
+ *   `accounts`: `{ "account": "Assets:Cash", "name": "Cash", "amount": [...],
+ *   "total": [...], "accounts": [...] }`. `amount` is the balance of the account's own
+ *   posts, and `total` includes the sub-accounts;
+ * - `register`: the postings, `{ "date", "payee", "account", "amount", "total" }`, with
+ *   the running total. With `-M`, the totals of each account in each month,
+ *   `{ "begin", "end", "account", "amount", "total" }`, where `amount` is a balance;
+ * - `prices`: `{ "date", "commodity", "price" }`;
+ * - `print`: the transactions, `{ "date", "aux_date", "code", "payee", "note",
+ *   "metadata", "position", "posts" }`, with the posts as `{ "account", "amount", "cost",
+ *   "assertion", "note", "metadata" }`. The assertion is `{ "amount", "inclusive",
+ *   "total" }`, where `inclusive` is the `=*` form, with the sub-accounts, and `total`
+ *   is the `==` form. The metadata is an object, with null for the tags without a value.
+ *
+ * The model is serialized with serde, i.e. `serde_json::to_value(&journal)`. The
+ * Journal is `{ "accounts", "commodities", "prices", "xacts" }`, and the items refer to
+ * each other with the ids, which are the indices in these arrays. The first account is
+ * the master account. The amounts are `{ "quantity", "commodity" }`, with the id of the
+ * commodity. A post is identified by the transaction and its index there,
+ * `{ "xact", "index" }`.
+ */
+
+use serde_json::{json, Value};
+
+use crate::{
+    account::Account,
+    amount::Amount,
+    balance::Balance,
+    compare::SortOrder,
+    journal::Journal,
+    parser::ISO_DATE_FORMAT,
+    pool::CommodityPool,
+    report::{monthly_totals, AccountFilter},
+    xact::Xact,
+};
+
+/// The accounts that match the filter, by their full names. Command: `accounts`.
+pub fn accounts_report(journal: &Journal, filter: &AccountFilter) -> Value {
+    let accounts: Vec<&str> = journal
+        .master()
+        .flatten_account_tree(journal)
+        .iter()
+        .map(|account| account.fullname())
+        .filter(|name| !name.is_empty() && filter.matches(name))
+        .collect();
+    json!(accounts)
+}
+
+/// The account tree with the balances. Command: `balance`.
+/// Only the accounts that match the filter, and their parents, are in the tree.
+pub fn balance_report(
+    journal: &Journal,
+    filter: &AccountFilter,
+    sort: Option<&SortOrder>,
+) -> Value {
+    account_node(journal.master(), journal, filter, sort).unwrap_or(Value::Null)
+}
+
+fn account_node(
+    account: &Account,
+    journal: &Journal,
+    filter: &AccountFilter,
+    sort: Option<&SortOrder>,
+) -> Option<Value> {
+    let mut children: Vec<&Account> = account
+        .accounts
+        .values()
+        .map(|id| journal.get_account(*id))
+        .collect();
+    children.sort_by(|a, b| a.name.cmp(&b.name));
+    if let Some(sort) = sort {
+        children.sort_by(|a, b| sort.compare_accounts(a, b, journal));
+    }
+    let children: Vec<Value> = children
+        .into_iter()
+        .filter_map(|child| account_node(child, journal, filter, sort))
+        .collect();
+
+    if children.is_empty() && !filter.matches(account.fullname()) {
+        return None;
+    }
+
+    let pool = &journal.commodity_pool;
+    Some(json!({
+        "account": account.fullname(),
+        "name": account.name,
+        "amount": balance(&account.amount(journal), pool),
+        "total": balance(&account.total(journal), pool),
+        "accounts": children,
+    }))
+}
+
+/// The postings to the accounts that match the filter, with the running total.
+/// Command: `register`.
+pub fn register_report(
+    journal: &Journal,
+    filter: &AccountFilter,
+    sort: Option<&SortOrder>,
+) -> Value {
+    let pool = &journal.commodity_pool;
+    let mut posts = journal.all_posts();
+    posts.retain(|post| filter.matches(journal.get_account(post.account).fullname()));
+    if let Some(sort) = sort {
+        posts.sort_by(|a, b| sort.compare_posts(a, b, journal));
+    }
+
+    let mut total = Balance::new();
+    let mut rows = vec![];
+    for post in posts {
+        let xact = journal.get_xact(post.xact);
+        if let Some(amount) = &post.amount {
+            total.add(amount);
+        }
+        rows.push(json!({
+            "date": date(xact),
+            "payee": xact.payee,
+            "account": journal.get_account(post.account).fullname(),
+            "amount": post.amount.map(|amount| amount_value(&amount, pool)),
+            "total": balance(&total, pool),
+        }));
+    }
+    json!(rows)
+}
+
+/// The total of the postings to each account in each month, with the running total.
+/// Command: `register -M`.
+pub fn monthly_register_report(journal: &Journal, filter: &AccountFilter) -> Value {
+    let pool = &journal.commodity_pool;
+    let rows: Vec<Value> = monthly_totals(journal, filter)
+        .iter()
+        .map(|row| {
+            json!({
+                "begin": row.first.format(ISO_DATE_FORMAT).to_string(),
+                "end": row.last.format(ISO_DATE_FORMAT).to_string(),
+                "account": row.account,
+                "amount": balance(&row.amount, pool),
+                "total": balance(&row.total, pool),
+            })
+        })
+        .collect();
+    json!(rows)
+}
+
+/// The price points, by date. Command: `prices`.
+pub fn prices_report(journal: &Journal) -> Value {
+    let pool = &journal.commodity_pool;
+    let prices: Vec<Value> = pool
+        .prices()
+        .iter()
+        .map(|price| {
+            json!({
+                "date": price.datetime.date().format(ISO_DATE_FORMAT).to_string(),
+                "commodity": pool.get(price.commodity).symbol,
+                "price": amount_value(&price.price, pool),
+            })
+        })
+        .collect();
+    json!(prices)
+}
+
+/// The transactions, with the accounts and the commodities by name. Command: `print`.
+pub fn print_report(journal: &Journal, sort: Option<&SortOrder>) -> Value {
+    let pool = &journal.commodity_pool;
+    let mut xacts: Vec<&Xact> = journal.xacts.iter().collect();
+    if let Some(sort) = sort {
+        xacts.sort_by(|a, b| sort.compare_xacts(a, b, journal));
+    }
+
+    let xacts: Vec<Value> = xacts
+        .into_iter()
+        .map(|xact| {
+            let posts: Vec<Value> = xact
+                .posts
+                .iter()
+                .map(|post| {
+                    json!({
+                        "account": journal.get_account(post.account).fullname(),
+                        "amount": post.amount.map(|amount| amount_value(&amount, pool)),
+                        "cost": post.cost.map(|cost| amount_value(&cost, pool)),
+                        "assertion": post.assertion.map(|assertion| {
+                            json!({
+                                "amount": amount_value(&assertion.amount, pool),
+                                "inclusive": assertion.inclusive,
+                                "total": assertion.total,
+                            })
+                        }),
+                        "note": post.note,
+                        "metadata": post.metadata,
+                    })
+                })
+                .collect();
+            json!({
+                "date": date(xact),
+                "aux_date": xact.aux_date.map(|date| date.format(ISO_DATE_FORMAT).to_string()),
+                "code": xact.code,
+                "payee": xact.payee,
+                "note": xact.note,
+                "metadata": xact.metadata,
+                "position": xact.pos,
+                "posts": posts,
+            })
+        })
+        .collect();
+    json!(xacts)
+}
+
+/// The amount, `{ "quantity": "10.00", "commodity": "EUR" }`.
+pub fn amount_value(amount: &Amount, pool: &CommodityPool) -> Value {
+    json!({
+        "quantity": amount.quantity,
+        "commodity": amount.get_commodity().map(|id| pool.get(id).symbol.as_str()),
+    })
+}
+
+fn balance(balance: &Balance, pool: &CommodityPool) -> Value {
+    let amounts: Vec<Value> = balance
+        .amounts
+        .iter()
+        .map(|amount| amount_value(amount, pool))
+        .collect();
+    json!(amounts)
+}
+
+fn date(xact: &Xact) -> Option<String> {
+    xact.date
+        .map(|date| date.format(ISO_DATE_FORMAT).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{balance_report, print_report, register_report};
+    use crate::{journal::Journal, parse_sources, report::AccountFilter};
+
+    fn journal() -> Journal {
+        let mut journal = Journal::new();
+        let text = "P 2023-05-01 VEUR 20 EUR\n\n2023-05-01 * Shop  ; :food:\n    Expenses:Food  10.50 EUR\n    Assets:Cash\n";
+        parse_sources([("test.ledger", text.as_bytes())], &mut journal).unwrap();
+        journal
+    }
+
+    #[test]
+    fn test_balance_tree() {
+        let journal = journal();
+        let filter = AccountFilter::new(&["Food".to_owned()]).unwrap();
+
+        let actual = balance_report(&journal, &filter, None);
+
+        assert_eq!(
+            json!({
+                "account": "",
+                "name": "",
+                "amount": [],
+                "total": [{ "quantity": "0.00", "commodity": "EUR" }],
+                "accounts": [{
+                    "account": "Expenses",
+                    "name": "Expenses",
+                    "amount": [],
+                    "total": [{ "quantity": "10.50", "commodity": "EUR" }],
+                    "accounts": [{
+                        "account": "Expenses:Food",
+                        "name": "Food",
+                        "amount": [{ "quantity": "10.50", "commodity": "EUR" }],
+                        "total": [{ "quantity": "10.50", "commodity": "EUR" }],
+                        "accounts": [],
+                    }],
+                }],
+            }),
+            actual
+        );
+    }
+
+    #[test]
+    fn test_register() {
+        let journal = journal();
+
+        let actual = register_report(&journal, &AccountFilter::default(), None);
+
+        assert_eq!(2, actual.as_array().unwrap().len());
+        assert_eq!(
+            json!({
+                "date": "2023-05-01",
+                "payee": "* Shop",
+                "account": "Assets:Cash",
+                "amount": { "quantity": "-10.50", "commodity": "EUR" },
+                "total": [{ "quantity": "0.00", "commodity": "EUR" }],
+            }),
+            actual[1]
+        );
+    }
+
+    #[test]
+    fn test_print() {
+        let journal = journal();
+
+        let actual = print_report(&journal, None);
+
+        let xact = &actual[0];
+        assert_eq!("* Shop", xact["payee"]);
+        assert_eq!(json!({ "food": null }), xact["metadata"]);
+        assert_eq!(json!(3), xact["position"]["beg_line"]);
+        assert_eq!("Expenses:Food", xact["posts"][0]["account"]);
+        assert_eq!(json!(null), xact["posts"][0]["cost"]);
+    }
+
+    #[test]
+    fn test_print_assertion_kinds() {
+        let mut journal = Journal::new();
+        let text = "2023-05-01 Opening\n    Assets:Cash  5 EUR = 5 EUR\n    Equity  -5 EUR ==* -5 EUR\n";
+        parse_sources([("test.ledger", text.as_bytes())], &mut journal).unwrap();
+
+        let actual = print_report(&journal, None);
+
+        let posts = &actual[0]["posts"];
+        assert_eq!(
+            json!({
+                "amount": { "quantity": "5", "commodity": "EUR" },
+                "inclusive": false,
+                "total": false,
+            }),
+            posts[0]["assertion"]
+        );
+        assert_eq!(json!(true), posts[1]["assertion"]["inclusive"]);
+        assert_eq!(json!(true), posts[1]["assertion"]["total"]);
+    }
+
+    /// The model refers to the accounts and the commodities by their ids.
+    #[test]
+    fn test_serialize_journal() {
+        let journal = journal();
+
+        let actual = serde_json::to_value(&journal).unwrap();
+
+        let post = &actual["xacts"][0]["posts"][0];
+        let account = post["account"].as_u64().unwrap() as usize;
+        assert_eq!("Food", actual["accounts"][account]["name"]);
+        let commodity = post["amount"]["commodity"].as_u64().unwrap() as usize;
+        assert_eq!("EUR", actual["commodities"][commodity]["symbol"]);
+        assert_eq!("20", actual["prices"][0]["price"]["quantity"]);
+        assert_eq!("2023-05-01", actual["xacts"][0]["date"]);
+    }
+}
@@ -30,10 +30,17 @@ report on account balances
     assert_eq!("Account Expenses:Food has balance 20 EUR", actual[4]);
 ```
 */
-use std::{fs::File, io::Cursor};
+use std::{
+    collections::BTreeMap,
+    env,
+    fs::File,
+    io::{self, Cursor, Read},
+    path::Path,
+};
 
 use journal::Journal;
 use option::InputOptions;
+use parser::Syntax;
 
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
@@ -42,17 +49,29 @@ pub mod account;
 mod annotate;
 pub mod amount;
 mod balance;
+pub mod beancount;
 pub mod commodity;
-mod directives;
-mod reader;
+pub mod compare;
+pub mod cst;
+pub mod csv;
+pub mod directives;
+pub mod duplicates;
+pub mod expr;
+pub mod format;
+pub mod hledger;
+pub mod reader;
 pub mod history;
-mod iterator;
+pub mod import;
 pub mod journal;
+pub mod json;
+pub mod lsp;
 mod journalreader;
+pub mod ofx;
 mod option;
 pub mod parser;
 pub mod pool;
 pub mod post;
+pub mod repl;
 pub mod report;
 pub mod scanner;
 pub mod utilities;
@@ -63,14 +82,55 @@ pub mod xact;
 /// The commands and arguments sent to the CLI are processed here. This is
 /// so that 3rd-party clients can pass argv and get the same result.
 /// The arguments should be compatible with Ledger, so that the functionality is comparable.
+/// Panics on errors. See [`try_run`] for the error handling.
 ///
 pub fn run(args: Vec<String>) -> Vec<String> {
+    match try_run(args) {
+        Ok(output) => output,
+        Err(e) => panic!("{}", e),
+    }
+}
+
+/// Same as [`run`] but returns the errors, i.e. for a missing file or a parsing error.
+/// The arguments do not include the program name. The default options come from
+/// `~/.ledgerrc` and the `LEDGER_*` environment variables, i.e. `LEDGER_FILE`.
+pub fn try_run(args: Vec<String>) -> Result<Vec<String>, String> {
     // separates commands from the options
-    let (commands, options) = option::process_arguments(args);
+    let (commands, options) = option::process_arguments_with_env(args, &environment())?;
+
+    execute_command(commands, options, None)
+}
 
-    execute_command(commands, options)
+/// The environment variables, for the `LEDGER_*` options and `HOME`.
+fn environment() -> BTreeMap<String, String> {
+    env::vars_os()
+        .filter_map(|(key, value)| Some((key.into_string().ok()?, value.into_string().ok()?)))
+        .collect()
 }
 
+/// The help for the command line, `--help`.
+pub fn usage() -> Vec<String> {
+    let mut lines: Vec<String> = USAGE.lines().map(|line| line.to_owned()).collect();
+    lines.extend(option::options_help());
+    lines
+}
+
+const USAGE: &str = "Usage: ledger-rs [options] <command> [arguments]
+
+Commands:
+  accounts [filter]      List the accounts
+  balance, bal, b        Show the account balances
+  register, reg, r       List the postings with the running total
+  print, p               Print the transactions in the journal format
+  prices                 List the price points
+  duplicates             List the likely duplicate transactions
+  convert <files>        Convert the CSV or OFX statements, skipping the transactions in the journal
+  fmt [files]            Format the journal files in place
+
+Without a command, reads the journal and starts the interactive mode.
+
+Options:";
+
 /// A convenient entry point if you want to use a command string directly.
 /// command: &str A Ledger-style command, i.e. "balance -f journal.ledger"
 ///
@@ -80,26 +140,91 @@ pub fn run_command(command: &str) -> Vec<String> {
 }
 
 /// global::execute_command equivalent
-fn execute_command(commands: Vec<String>, input_options: InputOptions) -> Vec<String> {
-    let verb = commands.iter().nth(0).unwrap();
+/// At the REPL, the journal is already in memory. Otherwise, it is read from the files.
+fn execute_command(
+    commands: Vec<String>,
+    input_options: InputOptions,
+    journal: Option<&Journal>,
+) -> Result<Vec<String>, String> {
+    if input_options.help {
+        return Ok(usage());
+    }
+    if input_options.version {
+        return Ok(vec![format!("ledger-rs {}", env!("CARGO_PKG_VERSION"))]);
+    }
 
-    // todo: look for pre-command
-    // look_for_precommand(verb);
+    let Some(verb) = commands.first() else {
+        return Err("No command was given".to_owned());
+    };
+
+    // The formatter works on the files, not on the journal.
+    if verb == "fmt" {
+        let mut paths = commands[1..].to_vec();
+        paths.extend(input_options.filenames.iter().cloned());
+        let input_date_format = input_options.input_date_format.as_deref();
+        return format::format_files(&paths, input_options.check, input_date_format);
+    }
+
+    // The statements are converted without a journal, or checked against it.
+    if verb == "convert" {
+        let read_journal;
+        let journal = match journal {
+            None if input_options.filenames.is_empty() => None,
+            None => {
+                read_journal = session_read_journal_files(&input_options)?;
+                Some(&read_journal)
+            }
+            journal => journal,
+        };
+        return import::convert_files(&commands[1..], input_options.rules.as_deref(), journal);
+    }
 
     // if !precommand
     //   if !at_repl
-    let journal = session_read_journal_files(&input_options);
+    let read_journal;
+    let journal = match journal {
+        Some(journal) => journal,
+        None => {
+            read_journal = session_read_journal_files(&input_options)?;
+            &read_journal
+        }
+    };
 
     // todo: lookup(COMMAND, verb)
 
     let command_args = &commands[1..];
+    let filter = report::AccountFilter::new(command_args)?;
+
+    let sort = match input_options.sort.as_deref() {
+        Some(text) => Some(compare::SortOrder::parse(text)?),
+        None => None,
+    };
+    let date_format = input_options.date_format.as_deref();
+    let unknown = || Err(format!("Unrecognized command '{}'", verb));
+
+    if input_options.output_format.as_deref() == Some("json") {
+        let value = match verb.as_str() {
+            "accounts" => json::accounts_report(journal, &filter),
+            "b" | "bal" | "balance" => json::balance_report(journal, &filter, sort.as_ref()),
+            "p" | "print" => json::print_report(journal, sort.as_ref()),
+            "prices" => json::prices_report(journal),
+            "r" | "reg" | "register" if input_options.monthly => {
+                json::monthly_register_report(journal, &filter)
+            }
+            "r" | "reg" | "register" => json::register_report(journal, &filter, sort.as_ref()),
+            _ => return Err(format!("No JSON output for '{}'", commands.join(" "))),
+        };
+        return serde_json::to_string_pretty(&value)
+            .map(|json| vec![json])
+            .map_err(|e| e.to_string());
+    }
 
     // execute command
-    match verb.chars().next().unwrap() {
+    let output = match verb.chars().next().unwrap() {
         'a' => {
             // accounts?
             // TODO: replace this temporary report
-            let mut output = report::report_accounts(&journal);
+            let mut output = report::filtered_accounts_report(journal, &filter);
             output.sort();
             output
         }
@@ -107,64 +232,134 @@ fn execute_command(commands: Vec<String>, input_options: InputOptions) -> Vec<St
             match verb.as_str() {
                 "b" | "bal" | "balance" => {
                     // balance report
-                    report::balance_report(&journal)
-                }
-                "budget" => {
-                    // budget
-                    todo!("budget!")
-                }
-                _ => {
-                    todo!("?")
+                    report::filtered_balance_report(journal, &filter, sort.as_ref())
                 }
+                _ => return unknown(),
             }
         }
-        _ => todo!("handle"),
-    }
-}
+        'd' => match verb.as_str() {
+            "duplicates" => {
+                let days = input_options.days.unwrap_or(duplicates::DEFAULT_DAYS);
+                if input_options.fix {
+                    let found = duplicates::find_duplicates(journal, days);
+                    duplicates::drop_duplicates(journal, &found)?
+                } else {
+                    duplicates::duplicates_report(journal, days)
+                }
+            }
+            _ => return unknown(),
+        },
+        'p' => match verb.as_str() {
+            "p" | "print" => match input_options.output_format.as_deref() {
+                None | Some("ledger") => report::print_report(journal, date_format, sort.as_ref()),
+                Some("hledger") => hledger::write_journal(journal),
+                Some("beancount") => beancount::write_journal(journal),
+                Some(format) => return Err(format!("Unknown output format: {}", format)),
+            },
+            "prices" => report::prices_report(journal, date_format),
+            _ => return unknown(),
+        },
+        'r' => match verb.as_str() {
+            "r" | "reg" | "register" if input_options.monthly => {
+                report::monthly_register_report(journal, &filter, date_format)
+            }
+            "r" | "reg" | "register" => {
+                report::register_report(journal, &filter, date_format, sort.as_ref())
+            }
+            _ => return unknown(),
+        },
+        _ => return unknown(),
+    };
 
-fn look_for_precommand(verb: &str) {
-    todo!()
+    Ok(output)
 }
 
-fn session_read_journal_files(options: &InputOptions) -> Journal {
+pub(crate) fn session_read_journal_files(options: &InputOptions) -> Result<Journal, String> {
     // Minimalistic approach:
     // get the file input
 
-    // multiple filenames
     let mut journal = Journal::new();
+
+    journal.checking_style = options.checking_style;
+    journal.input_date_format = options.input_date_format.clone();
+    if let Some(format) = &options.input_format {
+        journal.syntax = Some(Syntax::parse(format)?);
+    }
+
+    // account aliases
+    journal.recursive_aliases = options.recursive_aliases;
+    for alias in &options.aliases {
+        journal.add_alias(alias)?;
+    }
+
+    if options.filenames.is_empty() {
+        return Err("No journal file was specified (please use -f or LEDGER_FILE)".to_owned());
+    }
+
+    // multiple filenames. `-` is stdin.
+    let home = env::var("HOME").ok();
+    let mut sources: Vec<(String, Box<dyn Read>)> = vec![];
     for filename in &options.filenames {
-        // parse the journal file(s)
-        parse_file(filename, &mut journal);
+        if filename == "-" {
+            sources.push((STDIN_PATHNAME.to_owned(), Box::new(io::stdin())));
+            continue;
+        }
+        let path = utilities::expand_path(filename, home.as_deref());
+        let file = File::open(&path).map_err(|e| format!("{}: {}", filename, e))?;
+        sources.push((path.display().to_string(), Box::new(file)));
     }
 
-    journal
+    // parse the journal file(s)
+    parse_sources(sources, &mut journal)?;
+
+    Ok(journal)
+}
+
+/// The name of the standard input in the messages, for `-f -`.
+pub const STDIN_PATHNAME: &str = "/dev/stdin";
+
+/// Parses the named sources into the journal, in order. The sources can be the files,
+/// stdin, or the virtual files in memory. The names are used in the error messages,
+/// i.e. `"main.ledger", line 3: ...`, and in the transaction positions.
+pub fn parse_sources<I, N, R>(sources: I, journal: &mut Journal) -> Result<(), String>
+where
+    I: IntoIterator<Item = (N, R)>,
+    N: Into<String>,
+    R: Read,
+{
+    for (name, source) in sources {
+        let name: String = name.into();
+        let syntax = journal.syntax.or(Syntax::from_path(Path::new(&name)));
+        let mut parser = parser::Parser::new(source, journal);
+        parser.pathname = name;
+        parser.syntax = syntax.unwrap_or_default();
+        parser.parse()?;
+    }
+    Ok(())
 }
 
 /// Parse input and return the model structure.
-pub fn parse_file(file_path: &str, journal: &mut Journal) {
-    let file = File::open(file_path).expect("file opened");
-    parser::read_into_journal(file, journal);
+/// `-` reads stdin. Returns the error for the file that cannot be opened, or the first
+/// parsing error, with the location.
+pub fn parse_file(file_path: &str, journal: &mut Journal) -> Result<(), String> {
+    let source: (String, Box<dyn Read>) = match file_path {
+        "-" => (STDIN_PATHNAME.to_owned(), Box::new(io::stdin())),
+        _ => {
+            let file = File::open(file_path).map_err(|e| format!("{}: {}", file_path, e))?;
+            (file_path.to_owned(), Box::new(file))
+        }
+    };
+    parse_sources([source], journal)
 }
 
 /// Parses text containing Ledger-style journal.
 /// text: &str  A Ledger-style journal. The same content that is normally
 ///             stored in text files
 /// journal: &mut Journal  The result are stored in the given Journal instance.
-pub fn parse_text(text: &str, journal: &mut Journal) {
+/// Returns the first parsing error, with the line number.
+pub fn parse_text(text: &str, journal: &mut Journal) -> Result<(), String> {
     let source = Cursor::new(text);
-    parser::read_into_journal(source, journal);
-}
-
-pub fn parser_experiment() {
-    // read line from the Journal
-    // determine the type
-    // DirectiveType
-    // scan the line
-    // parse into a model instance
-    // read additional lines, as needed. Ie for Xact/Posts.
-    // return the directive with the entity, if created
-
-    todo!("try the new approach")
+    parser::read_into_journal(source, journal)
 }
 
 #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
@@ -176,7 +371,11 @@ pub fn wasm_test() -> String {
 mod lib_tests {
     use std::assert_eq;
 
-    use crate::{amount::{Amount, Quantity}, option, run};
+    use crate::{
+        amount::{Amount, Quantity},
+        journal::Journal,
+        option, parse_file, parse_sources, parse_text, run, try_run,
+    };
 
     // Try to understand why this test fails when dereferencing.
     #[test]
@@ -203,11 +402,11 @@ Account Expenses has balance 20"#;
         // arrange
         let args =
             shell_words::split("accounts -f tests/minimal.ledger -f tests/basic.ledger").unwrap();
-        let (_commands, input_options) = option::process_arguments(args);
+        let (_commands, input_options) = option::process_arguments(args).unwrap();
         // let cdty = Commodity::new("EUR");
 
         // Act
-        let journal = super::session_read_journal_files(&input_options);
+        let journal = super::session_read_journal_files(&input_options).unwrap();
 
         // Assert
         let xact0 = &journal.xacts[0];
@@ -225,10 +424,11 @@ Account Expenses has balance 20"#;
         assert_eq!(Some(Amount::new(20.into(), None)), xact0.posts[0].amount);
         // amount
         assert_eq!(xact1.posts[0].amount.unwrap().quantity, Quantity::from_str("20").unwrap());
-        assert_eq!(xact1.posts[0].amount.unwrap().get_commodity().unwrap().symbol, "EUR");
+        let commodity = xact1.posts[0].amount.unwrap().get_commodity().unwrap();
+        assert_eq!(journal.commodity_pool.get(commodity).symbol, "EUR");
 
         // accounts
-        let mut accounts = journal.master.flatten_account_tree();
+        let mut accounts = journal.master().flatten_account_tree(&journal);
         // hack for the test, since the order of items in a hashmap is not guaranteed.
         accounts.sort_unstable_by_key(|acc| &acc.name);
 
@@ -245,4 +445,62 @@ Account Expenses has balance 20"#;
             journal.commodity_pool.find("EUR").unwrap().symbol
         );
     }
+
+    #[test]
+    fn test_parse_sources() {
+        let main = "2023-04-21 Supermarket\n    Expenses:Food  20 EUR\n    Assets:Cash\n";
+        let other = "2023-04-22 Bakery\n    Expenses:Food  5 EUR\n    Assets:Cash\n";
+        let mut journal = Journal::new();
+
+        let sources = [("main.ledger", main.as_bytes()), ("other.ledger", other.as_bytes())];
+        parse_sources(sources, &mut journal).unwrap();
+
+        assert_eq!(2, journal.xacts.len());
+        let pos = journal.xacts[1].pos.as_ref().unwrap();
+        assert_eq!("other.ledger", pos.pathname);
+        assert_eq!(1, pos.beg_line);
+    }
+
+    #[test]
+    fn test_parse_sources_error_location() {
+        let mut journal = Journal::new();
+
+        let actual = parse_sources(
+            [("virtual.ledger", "; comment\nY abc\n".as_bytes())],
+            &mut journal,
+        );
+
+        assert!(actual.unwrap_err().starts_with("\"virtual.ledger\", line 2: "));
+    }
+
+    #[test]
+    fn test_parse_missing_file() {
+        let mut journal = Journal::new();
+
+        let actual = parse_file("tests/missing.ledger", &mut journal);
+
+        assert!(actual.unwrap_err().starts_with("tests/missing.ledger: "));
+    }
+
+    #[test]
+    fn test_parse_text_returns_the_error() {
+        let mut journal = Journal::new();
+        let text = "2023-05-01 Shop\n    Expenses  10 EUR\n    Assets  -5 EUR\n";
+
+        let actual = parse_text(text, &mut journal);
+
+        assert_eq!(
+            "\"\", line 3: Transaction does not balance, the remainder is 5 EUR",
+            actual.unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_budget_not_supported() {
+        let args = shell_words::split("budget -f tests/minimal.ledger").unwrap();
+
+        let actual = try_run(args);
+
+        assert_eq!(Err("Unrecognized command 'budget'".to_string()), actual);
+    }
 }
@@ -0,0 +1,783 @@
+/*!
+ * Language server
+ *
+ * A Language Server Protocol server for the journal files, over the stdio transport.
+ * It provides
+ * - diagnostics for the parse errors and the unbalanced transactions,
+ * - completion of the account names, payees and commodities,
+ * - hover with the balance of an account,
+ * - go-to-definition for the `include` and `account` directives,
+ * - document formatting.
+ *
+ * The messages are JSON-RPC, with the `Content-Length` header. The `ledger-lsp` binary
+ * runs the server on stdin and stdout.
+ */
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    io::{self, BufRead, Cursor, Write},
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+};
+
+use serde_json::{json, Value};
+
+use crate::{
+    cst::{Node, NodeKind, SyntaxTree},
+    format::format_journal,
+    journal::Journal,
+    parser::Parser,
+};
+
+// JSON-RPC error codes
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INTERNAL_ERROR: i64 = -32603;
+
+// LSP constants
+const SEVERITY_ERROR: u32 = 1;
+const KIND_MODULE: u32 = 9;
+const KIND_UNIT: u32 = 11;
+const KIND_VALUE: u32 = 12;
+
+/// Runs the server until the `exit` notification or the end of the input.
+/// Returns the exit code: 0 if the client asked for a `shutdown` first, 1 otherwise.
+pub fn run<R: BufRead, W: Write>(mut input: R, mut output: W) -> io::Result<i32> {
+    let mut server = Server::new();
+
+    while let Some(content) = read_message(&mut input)? {
+        let replies = match serde_json::from_str::<Value>(&content) {
+            Ok(message) => server.handle(&message),
+            Err(e) => vec![error_response(Value::Null, PARSE_ERROR, &e.to_string())],
+        };
+        for reply in replies {
+            write_message(&mut output, &reply)?;
+        }
+        if server.exited {
+            break;
+        }
+    }
+
+    Ok(if server.shutdown { 0 } else { 1 })
+}
+
+/// Reads one message: the headers, a blank line and the content.
+/// Returns None at the end of the input.
+pub fn read_message<R: BufRead>(input: &mut R) -> io::Result<Option<String>> {
+    let mut content_length: Option<usize> = None;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let header = line.trim();
+        if header.is_empty() {
+            if content_length.is_some() {
+                break;
+            }
+            continue;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                content_length = value.trim().parse().ok();
+            }
+        }
+    }
+
+    let mut content = vec![0; content_length.unwrap_or_default()];
+    input.read_exact(&mut content)?;
+    String::from_utf8(content)
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+pub fn write_message<W: Write>(output: &mut W, message: &Value) -> io::Result<()> {
+    let content = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", content.len(), content)?;
+    output.flush()
+}
+
+/// An open document and the Journal parsed from it.
+struct Document {
+    text: String,
+    journal: Journal,
+}
+
+/// The state of the language server: the open documents.
+pub struct Server {
+    documents: HashMap<String, Document>,
+    shutdown: bool,
+    exited: bool,
+}
+
+impl Server {
+    pub fn new() -> Self {
+        Self {
+            documents: HashMap::new(),
+            shutdown: false,
+            exited: false,
+        }
+    }
+
+    /// Handles a request or a notification.
+    /// Returns the messages to send back: the response and/or notifications.
+    pub fn handle(&mut self, message: &Value) -> Vec<Value> {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+        let id = message.get("id").cloned();
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| self.dispatch(method, params)));
+
+        match (id, result) {
+            // notification
+            (None, Ok(Dispatch::Notify(messages))) => messages,
+            (None, _) => vec![],
+            // request
+            (Some(id), Ok(Dispatch::Reply(result))) => {
+                vec![json!({"jsonrpc": "2.0", "id": id, "result": result})]
+            }
+            (Some(id), Ok(Dispatch::Notify(_))) | (Some(id), Ok(Dispatch::Unknown)) => {
+                vec![error_response(id, METHOD_NOT_FOUND, &format!("Unknown method {}", method))]
+            }
+            (Some(id), Err(panic)) => {
+                vec![error_response(id, INTERNAL_ERROR, &panic_message(&panic))]
+            }
+        }
+    }
+
+    fn dispatch(&mut self, method: &str, params: &Value) -> Dispatch {
+        match method {
+            "initialize" => Dispatch::Reply(initialize_result()),
+            "initialized" => Dispatch::Notify(vec![]),
+            "shutdown" => {
+                self.shutdown = true;
+                Dispatch::Reply(Value::Null)
+            }
+            "exit" => {
+                self.exited = true;
+                Dispatch::Notify(vec![])
+            }
+
+            "textDocument/didOpen" => {
+                let document = &params["textDocument"];
+                let uri = str_param(&document["uri"]);
+                self.update(uri, str_param(&document["text"]))
+            }
+            "textDocument/didChange" => {
+                // Full synchronisation; the last change has the whole text.
+                let uri = str_param(&params["textDocument"]["uri"]);
+                match params["contentChanges"].as_array().and_then(|c| c.last()) {
+                    Some(change) => self.update(uri, str_param(&change["text"])),
+                    None => Dispatch::Notify(vec![]),
+                }
+            }
+            "textDocument/didClose" => {
+                let uri = str_param(&params["textDocument"]["uri"]);
+                self.documents.remove(uri);
+                Dispatch::Notify(vec![publish_diagnostics(uri, vec![])])
+            }
+
+            "textDocument/completion" => Dispatch::Reply(self.completion(params)),
+            "textDocument/hover" => Dispatch::Reply(self.hover(params)),
+            "textDocument/definition" => Dispatch::Reply(self.definition(params)),
+            "textDocument/formatting" => Dispatch::Reply(self.formatting(params)),
+
+            _ => Dispatch::Unknown,
+        }
+    }
+
+    /// Stores the new text of the document and publishes its diagnostics.
+    fn update(&mut self, uri: &str, text: &str) -> Dispatch {
+        let path = uri_to_path(uri);
+        let (journal, diagnostics) = analyze(text, &path);
+        self.documents.insert(
+            uri.to_owned(),
+            Document {
+                text: text.to_owned(),
+                journal,
+            },
+        );
+        Dispatch::Notify(vec![publish_diagnostics(uri, diagnostics)])
+    }
+
+    /// The document and the byte offset of the position in the request.
+    fn document_at(&self, params: &Value) -> Option<(&Document, usize)> {
+        let document = self.documents.get(str_param(&params["textDocument"]["uri"]))?;
+        let position = &params["position"];
+        let offset = offset_at(
+            &document.text,
+            position["line"].as_u64()? as usize,
+            position["character"].as_u64()? as usize,
+        );
+        Some((document, offset))
+    }
+
+    fn completion(&self, params: &Value) -> Value {
+        let Some((document, offset)) = self.document_at(params) else {
+            return json!([]);
+        };
+        let line_start = document.text[..offset].rfind('\n').map_or(0, |i| i + 1);
+        let prefix = &document.text[line_start..offset];
+        let journal = &document.journal;
+
+        let (names, kind, detail) = match completion_context(prefix) {
+            Some(Completion::Account) => (account_names(journal), KIND_MODULE, "account"),
+            Some(Completion::Commodity) => (commodity_symbols(journal), KIND_UNIT, "commodity"),
+            Some(Completion::Payee) => (payee_names(journal), KIND_VALUE, "payee"),
+            None => return json!([]),
+        };
+
+        Value::Array(
+            names
+                .into_iter()
+                .map(|label| json!({"label": label, "kind": kind, "detail": detail}))
+                .collect(),
+        )
+    }
+
+    /// The balance of the account under the cursor.
+    fn hover(&self, params: &Value) -> Value {
+        let Some((document, offset)) = self.document_at(params) else {
+            return Value::Null;
+        };
+        let tree = SyntaxTree::parse(&document.text);
+        let Some((name, node)) = account_at(&tree, offset) else {
+            return Value::Null;
+        };
+        let journal = &document.journal;
+        let Some(account) = journal.find_account(name) else {
+            return Value::Null;
+        };
+
+        let balance: Vec<String> = account
+            .total(journal)
+            .amounts
+            .iter()
+            .map(|amount| amount.display(&journal.commodity_pool).to_string())
+            .collect();
+        let balance = if balance.is_empty() {
+            "0".to_owned()
+        } else {
+            balance.join(", ")
+        };
+
+        json!({
+            "contents": {
+                "kind": "markdown",
+                "value": format!("**{}**\n\nBalance: {}", account.fullname(), balance),
+            },
+            "range": range_of(&document.text, node.range.start, node.range.end),
+        })
+    }
+
+    /// The included file, or the `account` directive of the account under the cursor.
+    fn definition(&self, params: &Value) -> Value {
+        let Some((document, offset)) = self.document_at(params) else {
+            return Value::Null;
+        };
+        let uri = str_param(&params["textDocument"]["uri"]);
+        let tree = SyntaxTree::parse(&document.text);
+        let nodes = tree.nodes_at(offset);
+
+        // include
+        if let Some(directive) = nodes.first().filter(|n| directive_name(&tree, n) == Some("include")) {
+            let Some(argument) = directive.find(NodeKind::Argument) else {
+                return Value::Null;
+            };
+            let path = resolve_include(&uri_to_path(uri), tree.text(argument));
+            return json!({
+                "uri": path_to_uri(&path),
+                "range": {"start": {"line": 0, "character": 0}, "end": {"line": 0, "character": 0}},
+            });
+        }
+
+        // account
+        let Some((name, _)) = account_at(&tree, offset) else {
+            return Value::Null;
+        };
+        let mut uris: Vec<&String> = self.documents.keys().collect();
+        uris.sort();
+        for other_uri in uris {
+            let other = &self.documents[other_uri];
+            let other_tree = SyntaxTree::parse(&other.text);
+            let declaration = other_tree.nodes.iter().find(|node| {
+                directive_name(&other_tree, node) == Some("account")
+                    && node
+                        .find(NodeKind::Argument)
+                        .is_some_and(|argument| other_tree.text(argument) == name)
+            });
+            if let Some(node) = declaration {
+                let line_end = other.text[node.range.start..]
+                    .find(['\r', '\n'])
+                    .map_or(other.text.len(), |i| node.range.start + i);
+                return json!({
+                    "uri": other_uri,
+                    "range": range_of(&other.text, node.range.start, line_end),
+                });
+            }
+        }
+
+        Value::Null
+    }
+
+    /// Replaces the whole document with the formatted text.
+    fn formatting(&self, params: &Value) -> Value {
+        let Some(document) = self.documents.get(str_param(&params["textDocument"]["uri"])) else {
+            return json!([]);
+        };
+        let formatted = format_journal(&document.text);
+        if formatted == document.text {
+            return json!([]);
+        }
+        json!([{
+            "range": range_of(&document.text, 0, document.text.len()),
+            "newText": formatted,
+        }])
+    }
+}
+
+impl Default for Server {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The outcome of a message.
+enum Dispatch {
+    Reply(Value),
+    Notify(Vec<Value>),
+    Unknown,
+}
+
+fn initialize_result() -> Value {
+    json!({
+        "capabilities": {
+            // full document synchronisation
+            "textDocumentSync": 1,
+            "completionProvider": {"triggerCharacters": [":"]},
+            "hoverProvider": true,
+            "definitionProvider": true,
+            "documentFormattingProvider": true,
+        },
+        "serverInfo": {
+            "name": "ledger-lsp",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+    })
+}
+
+fn error_response(id: Value, code: i64, message: &str) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}})
+}
+
+fn publish_diagnostics(uri: &str, diagnostics: Vec<Value>) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": {"uri": uri, "diagnostics": diagnostics},
+    })
+}
+
+fn str_param(value: &Value) -> &str {
+    value.as_str().unwrap_or_default()
+}
+
+fn panic_message(panic: &Box<dyn std::any::Any + Send>) -> String {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Internal error".to_owned()
+    }
+}
+
+/// Parses the document into a Journal and collects the diagnostics.
+fn analyze(text: &str, path: &Path) -> (Journal, Vec<Value>) {
+    let mut diagnostics = vec![];
+
+    // The syntax errors, the unbalanced transactions and the failed assertions.
+    // The parsing continues after each one, so the journal has the rest of the document.
+    let mut journal = Journal::new();
+    let mut parser = Parser::new(Cursor::new(text), &mut journal);
+    parser.pathname = path.display().to_string();
+    for (line, message) in parser.try_parse_all() {
+        let line = line.max(1);
+        diagnostics.push(diagnostic(text, line, line, &message));
+    }
+
+    (journal, diagnostics)
+}
+
+/// A diagnostic for the (1-based) lines.
+fn diagnostic(text: &str, line: usize, end_line: usize, message: &str) -> Value {
+    let end_line_text = text.lines().nth(end_line.saturating_sub(1)).unwrap_or_default();
+    json!({
+        "range": {
+            "start": {"line": line.saturating_sub(1), "character": 0},
+            "end": {"line": end_line.saturating_sub(1), "character": utf16_len(end_line_text)},
+        },
+        "severity": SEVERITY_ERROR,
+        "source": "ledger",
+        "message": message,
+    })
+}
+
+/// What to complete at the cursor.
+#[derive(Debug, PartialEq)]
+enum Completion {
+    Account,
+    Commodity,
+    Payee,
+}
+
+/// Decides what to complete from the line up to the cursor.
+fn completion_context(prefix: &str) -> Option<Completion> {
+    if prefix.starts_with([' ', '\t']) {
+        // A post: the account, then the amount after two spaces or a tab.
+        let post = prefix.trim_start();
+        if post.starts_with(';') {
+            return None;
+        }
+        return if post.contains("  ") || post.contains('\t') {
+            Some(Completion::Commodity)
+        } else {
+            Some(Completion::Account)
+        };
+    }
+    if prefix.starts_with(|c: char| c.is_ascii_digit()) {
+        // The transaction header, after the date.
+        return prefix.contains(' ').then_some(Completion::Payee);
+    }
+
+    let (directive, _) = prefix.split_once(' ')?;
+    match directive {
+        "account" | "apply" | "bucket" | "A" => Some(Completion::Account),
+        "commodity" | "D" | "N" | "P" => Some(Completion::Commodity),
+        "payee" => Some(Completion::Payee),
+        _ => None,
+    }
+}
+
+fn account_names(journal: &Journal) -> Vec<String> {
+    journal
+        .master()
+        .flatten_account_tree(journal)
+        .iter()
+        .map(|account| account.fullname().to_owned())
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+fn commodity_symbols(journal: &Journal) -> Vec<String> {
+    let symbols: BTreeSet<String> = journal
+        .commodity_pool
+        .commodities
+        .iter()
+        .map(|commodity| commodity.symbol.clone())
+        .filter(|symbol| !symbol.is_empty())
+        .collect();
+    symbols.into_iter().collect()
+}
+
+fn payee_names(journal: &Journal) -> Vec<String> {
+    let payees: BTreeSet<String> = journal
+        .xacts
+        .iter()
+        .map(|xact| xact.payee.clone())
+        .chain(journal.known_payees.iter().cloned())
+        .collect();
+    payees.into_iter().collect()
+}
+
+/// The name of the directive node, i.e. `account`.
+fn directive_name<'a>(tree: &'a SyntaxTree, node: &Node) -> Option<&'a str> {
+    if node.kind != NodeKind::Directive {
+        return None;
+    }
+    let name = node.children.iter().find(|n| n.kind == NodeKind::Name)?;
+    Some(tree.text(name))
+}
+
+/// The account name under the cursor, in a post or in an `account` directive, and its node.
+fn account_at(tree: &SyntaxTree, offset: usize) -> Option<(&str, &Node)> {
+    let nodes = tree.nodes_at(offset);
+    let innermost = nodes.last()?;
+    match innermost.kind {
+        NodeKind::Account => Some((tree.text(innermost), innermost)),
+        NodeKind::Argument if directive_name(tree, nodes[0]) == Some("account") && nodes.len() == 2 => {
+            Some((tree.text(innermost), innermost))
+        }
+        _ => None,
+    }
+}
+
+/// The path of the included file, relative to the including one.
+fn resolve_include(document: &Path, argument: &str) -> PathBuf {
+    let path = PathBuf::from(argument);
+    if path.is_absolute() {
+        return path;
+    }
+    match document.parent() {
+        Some(directory) => directory.join(path),
+        None => path,
+    }
+}
+
+/// `file:///home/user/journal.ledger` -> `/home/user/journal.ledger`
+fn uri_to_path(uri: &str) -> PathBuf {
+    let path = uri.strip_prefix("file://").unwrap_or(uri);
+
+    // percent-decoding
+    let bytes = path.as_bytes();
+    let mut decoded: Vec<u8> = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|h| std::str::from_utf8(h).ok());
+        match (bytes[i], hex.and_then(|h| u8::from_str_radix(h, 16).ok())) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    PathBuf::from(String::from_utf8_lossy(&decoded).into_owned())
+}
+
+fn path_to_uri(path: &Path) -> String {
+    let mut uri = String::from("file://");
+    for byte in path.display().to_string().bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => {
+                uri.push(byte as char)
+            }
+            _ => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    uri
+}
+
+/// The byte offset of the LSP position (0-based line, UTF-16 character).
+fn offset_at(text: &str, line: usize, character: usize) -> usize {
+    let mut line_start = 0;
+    for _ in 0..line {
+        match text[line_start..].find('\n') {
+            Some(i) => line_start += i + 1,
+            None => return text.len(),
+        }
+    }
+
+    let mut units = 0;
+    for (i, c) in text[line_start..].char_indices() {
+        if units >= character || c == '\n' {
+            return line_start + i;
+        }
+        units += c.len_utf16();
+    }
+    text.len()
+}
+
+/// The LSP position of the byte offset.
+fn position_at(text: &str, offset: usize) -> Value {
+    let before = &text[..offset];
+    let line = before.matches('\n').count();
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    json!({"line": line, "character": utf16_len(&before[line_start..])})
+}
+
+fn range_of(text: &str, start: usize, end: usize) -> Value {
+    json!({"start": position_at(text, start), "end": position_at(text, end)})
+}
+
+fn utf16_len(text: &str) -> usize {
+    text.chars().map(char::len_utf16).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::{completion_context, offset_at, Completion, Server};
+
+    const URI: &str = "file:///tmp/journal.ledger";
+    const TEXT: &str = r#"account Expenses:Food
+
+2023-05-01 Shop
+    Expenses:Food  20 EUR
+    Assets:Cash
+
+2023-05-02 Shop
+    Expenses:Food  20 EUR
+    Assets:Cash  -10 EUR
+"#;
+
+    fn open(server: &mut Server, text: &str) -> Vec<Value> {
+        server.handle(&json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didOpen",
+            "params": {"textDocument": {"uri": URI, "languageId": "ledger", "version": 1, "text": text}},
+        }))
+    }
+
+    fn request(server: &mut Server, method: &str, line: u32, character: u32) -> Value {
+        let replies = server.handle(&json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": method,
+            "params": {"textDocument": {"uri": URI}, "position": {"line": line, "character": character}},
+        }));
+        replies[0]["result"].clone()
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let mut server = Server::new();
+
+        let messages = open(&mut server, TEXT);
+
+        assert_eq!("textDocument/publishDiagnostics", messages[0]["method"]);
+        let diagnostics = messages[0]["params"]["diagnostics"].as_array().unwrap();
+        assert_eq!(1, diagnostics.len());
+        assert_eq!(8, diagnostics[0]["range"]["start"]["line"]);
+        assert_eq!(8, diagnostics[0]["range"]["end"]["line"]);
+        assert_eq!(
+            "Transaction does not balance, the remainder is 10 EUR",
+            diagnostics[0]["message"]
+        );
+    }
+
+    #[test]
+    fn test_parse_error_diagnostic() {
+        let mut server = Server::new();
+
+        let messages = open(&mut server, "2023-05-01 Shop\n    Expenses  1 EUR\n    Assets\n\naccount\n");
+
+        let diagnostics = messages[0]["params"]["diagnostics"].as_array().unwrap();
+        assert_eq!(1, diagnostics.len());
+        assert_eq!(4, diagnostics[0]["range"]["start"]["line"]);
+    }
+
+    #[test]
+    fn test_diagnostics_after_errors() {
+        let mut server = Server::new();
+        let text = "2023-05-01 Shop\n    Expenses  10 EUR\n    Assets  -5 EUR\n\n\
+                    Y abc\n\n2023-05-02 Bakery\n    Expenses:Bread  1 EUR\n    Assets:Cash\n";
+
+        let messages = open(&mut server, text);
+
+        // Both errors, and the journal after them.
+        let diagnostics = messages[0]["params"]["diagnostics"].as_array().unwrap();
+        let lines: Vec<u64> = diagnostics
+            .iter()
+            .map(|d| d["range"]["start"]["line"].as_u64().unwrap())
+            .collect();
+        assert_eq!(vec![2, 4], lines);
+        let accounts = request(&mut server, "textDocument/completion", 7, 6);
+        let labels: Vec<&str> = accounts
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["label"].as_str().unwrap())
+            .collect();
+        assert!(labels.contains(&"Expenses:Bread"));
+    }
+
+    #[test]
+    fn test_completion() {
+        let mut server = Server::new();
+        open(&mut server, TEXT);
+
+        let accounts = request(&mut server, "textDocument/completion", 3, 6);
+        let labels: Vec<&str> = accounts
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["label"].as_str().unwrap())
+            .collect();
+        assert!(labels.contains(&"Expenses:Food"));
+        assert!(labels.contains(&"Assets:Cash"));
+
+        let payees = request(&mut server, "textDocument/completion", 2, 12);
+        assert_eq!("Shop", payees[0]["label"]);
+
+        let commodities = request(&mut server, "textDocument/completion", 3, 21);
+        assert_eq!("EUR", commodities[0]["label"]);
+    }
+
+    #[test]
+    fn test_completion_context() {
+        assert_eq!(Some(Completion::Account), completion_context("    Exp"));
+        assert_eq!(Some(Completion::Commodity), completion_context("    Expenses  20 "));
+        assert_eq!(Some(Completion::Payee), completion_context("2023-05-01 Sh"));
+        assert_eq!(Some(Completion::Account), completion_context("account Ex"));
+        assert_eq!(None, completion_context("; comment"));
+    }
+
+    #[test]
+    fn test_hover() {
+        let mut server = Server::new();
+        open(&mut server, TEXT);
+
+        let hover = request(&mut server, "textDocument/hover", 3, 8);
+
+        assert_eq!("**Expenses:Food**\n\nBalance: 40 EUR", hover["contents"]["value"]);
+        assert_eq!(4, hover["range"]["start"]["character"]);
+    }
+
+    #[test]
+    fn test_definition() {
+        let mut server = Server::new();
+        open(&mut server, "include other ledger.ledger\n\n2023-05-01 Shop\n    Expenses:Food  1 EUR\n    Assets\n\naccount Expenses:Food\n");
+
+        let include = request(&mut server, "textDocument/definition", 0, 10);
+        assert_eq!("file:///tmp/other%20ledger.ledger", include["uri"]);
+
+        let account = request(&mut server, "textDocument/definition", 3, 6);
+        assert_eq!(URI, account["uri"]);
+        assert_eq!(6, account["range"]["start"]["line"]);
+    }
+
+    #[test]
+    fn test_formatting() {
+        let mut server = Server::new();
+        open(&mut server, "2023-05-01 Shop\n  Expenses  1 EUR\n  Assets\n");
+
+        let replies = server.handle(&json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "textDocument/formatting",
+            "params": {"textDocument": {"uri": URI}, "options": {"tabSize": 4, "insertSpaces": true}},
+        }));
+
+        let edits = replies[0]["result"].as_array().unwrap();
+        assert_eq!(1, edits.len());
+        assert_eq!(
+            "2023-05-01 Shop\n    Expenses  1 EUR\n    Assets\n",
+            edits[0]["newText"]
+        );
+    }
+
+    #[test]
+    fn test_unknown_method() {
+        let mut server = Server::new();
+
+        let replies = server.handle(&json!({"jsonrpc": "2.0", "id": 3, "method": "foo/bar"}));
+
+        assert_eq!(-32601, replies[0]["error"]["code"]);
+    }
+
+    #[test]
+    fn test_offset_at() {
+        let text = "ab\n€x\n";
+
+        assert_eq!(0, offset_at(text, 0, 0));
+        assert_eq!(2, offset_at(text, 0, 5));
+        assert_eq!(6, offset_at(text, 1, 1));
+        assert_eq!(text.len(), offset_at(text, 5, 0));
+    }
+}
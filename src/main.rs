@@ -1,30 +1,191 @@
-use std::env;
+//! Ledger-rs command-line interface.
+//! Runs the command from the arguments and prints the output lines to stdout.
+//! The errors go to stderr, with a non-zero exit code. The warnings, i.e. from `--strict`,
+//! go to stderr as well. `RUST_LOG` selects more of the log.
+//! Without a command, reads the journal and runs the commands entered interactively.
 
-use ledger_rs_lib;
+use std::{
+    env,
+    io::{self, Write},
+    panic,
+    process::ExitCode,
+};
 
 /// Main entry point for the CLI.
-fn main() {
-    println!("Hello, Ledger-rs!");
+fn main() -> ExitCode {
+    init_logger();
 
-    let args: Vec<String> = env::args().collect();
-    println!("You requested {:?}", args);
+    // Skip the program name.
+    let args: Vec<String> = env::args().skip(1).collect();
 
-    // let args = read_command_arguments(args);
+    // The unfinished functionality panics. Report it as an error.
+    panic::set_hook(Box::new(|info| {
+        let payload = info.payload();
+        let message = payload
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| payload.downcast_ref::<String>().map(|s| s.as_str()))
+            .unwrap_or("unknown error");
+        eprintln!("Error: {}", message);
+    }));
 
-    if !args.is_empty() {
-        execute_command(args);
-    } else {
-        // repl.
-        panic!("not implemented")
+    // rustyline is not available on wasm32.
+    #[cfg(not(target_arch = "wasm32"))]
+    if ledger_rs_lib::repl::Repl::is_interactive(&args) {
+        return repl::run_repl(args);
     }
+
+    match panic::catch_unwind(|| ledger_rs_lib::try_run(args)) {
+        Ok(Ok(output)) => print_lines(&output),
+        Ok(Err(e)) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
+        // The message has been printed by the hook.
+        Err(_) => ExitCode::FAILURE,
+    }
+}
+
+/// Shows the warnings and the errors from the library on stderr, i.e. `Warning: "x.ledger",
+/// line 5: Unknown account 'Expenses:Fodo'`, unless `RUST_LOG` says otherwise.
+fn init_logger() {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn"))
+        .format(|buf, record| match record.level() {
+            log::Level::Warn => writeln!(buf, "Warning: {}", record.args()),
+            log::Level::Error => writeln!(buf, "Error: {}", record.args()),
+            level => writeln!(buf, "{} {}: {}", level, record.target(), record.args()),
+        })
+        .init();
+}
+
+/// Writes the output to stdout. A closed pipe, i.e. from `head`, is not an error.
+fn print_lines(lines: &[String]) -> ExitCode {
+    let mut stdout = io::stdout().lock();
+    for line in lines {
+        if let Err(e) = writeln!(stdout, "{}", line) {
+            if e.kind() == io::ErrorKind::BrokenPipe {
+                break;
+            }
+            eprintln!("Error: {}", e);
+            return ExitCode::FAILURE;
+        }
+    }
+    ExitCode::SUCCESS
 }
 
-// fn output_to_stdin(content: Vec<String>) {
-//     content.try_for_each(|s| writeln!(stdout, "{}", s))
-// }
+#[cfg(not(target_arch = "wasm32"))]
+mod repl {
+    use std::{
+        env,
+        panic::{self, AssertUnwindSafe},
+        path::PathBuf,
+        process::ExitCode,
+    };
+
+    use ledger_rs_lib::repl::Repl;
+    use rustyline::{
+        completion::Completer, error::ReadlineError, highlight::Highlighter, hint::Hinter,
+        history::FileHistory, validate::Validator, Context, Editor, Helper,
+    };
+
+    use super::print_lines;
+
+    /// The interactive mode. The journal is read once, and each line is a command.
+    /// The errors are reported, and the session continues. `quit`, `exit` or Ctrl-D end it.
+    pub fn run_repl(args: Vec<String>) -> ExitCode {
+        let repl = match Repl::new(args) {
+            Ok(repl) => repl,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                return ExitCode::FAILURE;
+            }
+        };
+
+        let mut editor: Editor<ReplHelper, FileHistory> = match Editor::new() {
+            Ok(editor) => editor,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                return ExitCode::FAILURE;
+            }
+        };
+        editor.set_helper(Some(ReplHelper { repl }));
+        let history = history_path();
+        if let Some(path) = &history {
+            // There is no history on the first run.
+            let _ = editor.load_history(path);
+        }
+
+        loop {
+            let line = match editor.readline("ledger-rs> ") {
+                Ok(line) => line,
+                // Ctrl-C cancels the line.
+                Err(ReadlineError::Interrupted) => continue,
+                Err(ReadlineError::Eof) => break,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    return ExitCode::FAILURE;
+                }
+            };
+            let command = line.trim();
+            if command.is_empty() {
+                continue;
+            }
+            let _ = editor.add_history_entry(command);
+            if command == "quit" || command == "exit" {
+                break;
+            }
+
+            let repl = &mut editor.helper_mut().expect("helper set").repl;
+            // The unfinished commands panic. The hook prints the message.
+            match panic::catch_unwind(AssertUnwindSafe(|| repl.execute(command))) {
+                Ok(Ok(output)) => {
+                    if print_lines(&output) == ExitCode::FAILURE {
+                        return ExitCode::FAILURE;
+                    }
+                }
+                Ok(Err(e)) => eprintln!("Error: {}", e),
+                Err(_) => (),
+            }
+        }
+
+        if let Some(path) = &history {
+            if let Err(e) = editor.save_history(path) {
+                eprintln!("Error: {}: {}", path.display(), e);
+            }
+        }
+        ExitCode::SUCCESS
+    }
+
+    /// The history file, `~/.ledger-rs_history`.
+    fn history_path() -> Option<PathBuf> {
+        env::var_os("HOME").map(|home| PathBuf::from(home).join(".ledger-rs_history"))
+    }
+
+    /// Completes the commands, the options and the account names.
+    struct ReplHelper {
+        repl: Repl,
+    }
+
+    impl Completer for ReplHelper {
+        type Candidate = String;
+
+        fn complete(
+            &self,
+            line: &str,
+            pos: usize,
+            _ctx: &Context<'_>,
+        ) -> rustyline::Result<(usize, Vec<String>)> {
+            Ok(self.repl.complete(line, pos))
+        }
+    }
+
+    impl Hinter for ReplHelper {
+        type Hint = String;
+    }
+
+    impl Highlighter for ReplHelper {}
+
+    impl Validator for ReplHelper {}
 
-fn execute_command(args: Vec<String>) {
-    let output = ledger_rs_lib::run(args);
-    // output_to_stdin(output);
-    println!("{:?}", output);
+    impl Helper for ReplHelper {}
 }
@@ -0,0 +1,477 @@
+/*!
+ * OFX bank statements
+ *
+ * Converts the OFX and QFX statements, both the SGML (OFX 1.x) and the XML (OFX 2.x)
+ * ones, into transactions:
+ *
+ * ```ledger
+ * 2023-05-05 (1001) Landlord
+ *     ; FITID: 202305052
+ *     Assets:Bank:Checking  -1200.00 USD
+ *     Expenses:Rent
+ * ```
+ *
+ * `FITID`, the bank's id of the transaction, is kept as metadata. The transactions with an
+ * id that is already in the journal, on the same account, are skipped, so importing a
+ * statement again adds nothing. The ledger balance of the statement, `LEDGERBAL`, becomes
+ * a balance assertion on the account, at its date.
+ *
+ * The accounts are selected with the rules from `statement.ofx.rules`, if it exists. These
+ * are `account1`, `account2`, `currency` and the `if` payee rules of the CSV rules.
+ * Without `account1`, the account is named after the statement's `ACCTID`,
+ * `Assets:Bank:<ACCTID>`, or `Liabilities:CreditCard:<ACCTID>` for the credit cards.
+ */
+
+use std::{collections::HashMap, fs, path::Path};
+
+use chrono::NaiveDate;
+
+use crate::{
+    amount::Quantity, csv::CsvRules, import::Importer, journal::Journal, parser::ISO_DATE_FORMAT,
+};
+
+/// A bank or credit card statement, `STMTRS` or `CCSTMTRS`.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct OfxStatement {
+    /// `CURDEF`, the currency of the amounts.
+    pub currency: Option<String>,
+    /// `ACCTID`
+    pub account_id: Option<String>,
+    /// A credit card statement, `CCSTMTRS`.
+    pub credit_card: bool,
+    pub transactions: Vec<OfxTransaction>,
+    /// `LEDGERBAL`, the balance and its date.
+    pub balance: Option<(Quantity, NaiveDate)>,
+}
+
+/// A transaction, `STMTTRN`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OfxTransaction {
+    /// `DTPOSTED`
+    pub date: NaiveDate,
+    /// `TRNAMT`. Positive is received.
+    pub amount: Quantity,
+    /// `FITID`, the bank's id of the transaction.
+    pub id: String,
+    /// `NAME`, or `MEMO` without the name.
+    pub payee: String,
+    pub memo: Option<String>,
+    /// `CHECKNUM`, the code of the transaction.
+    pub check_number: Option<String>,
+}
+
+impl OfxTransaction {
+    /// The transaction is in the journal if there is one with the same `FITID` that posts
+    /// to the account. The ids are unique only within an account.
+    pub fn exists_in(&self, account: &str, journal: &Journal) -> bool {
+        let Some(account) = journal.find_account_id(account) else {
+            return false;
+        };
+        journal.xacts.iter().any(|xact| {
+            xact.get_tag("FITID") == Some(self.id.as_str())
+                && xact.posts.iter().any(|post| post.account == account)
+        })
+    }
+}
+
+impl OfxStatement {
+    /// The account of the statement, `account1` from the rules, or the account named after
+    /// `ACCTID`.
+    pub fn account(&self, rules: &CsvRules) -> String {
+        match (&rules.account1, &self.account_id) {
+            (Some(account), _) => account.to_owned(),
+            (None, Some(id)) if self.credit_card => format!("Liabilities:CreditCard:{}", id),
+            (None, Some(id)) => format!("Assets:Bank:{}", id),
+            (None, None) => rules.account1().to_owned(),
+        }
+    }
+
+    /// The transactions in the journal format, without the ones already in the journal,
+    /// and the balance assertion. The statement that is already imported gives nothing.
+    pub fn to_ledger(&self, rules: &CsvRules, journal: &Journal) -> Vec<String> {
+        let account1 = self.account(rules);
+        let currency = rules.currency.as_ref().or(self.currency.as_ref());
+        let amount = |quantity: &Quantity| match currency {
+            Some(currency) => format!("{} {}", quantity, currency),
+            None => quantity.to_string(),
+        };
+
+        let new: Vec<&OfxTransaction> = self
+            .transactions
+            .iter()
+            .filter(|trn| !trn.exists_in(&account1, journal))
+            .collect();
+        if new.is_empty() && !self.transactions.is_empty() {
+            return vec![];
+        }
+
+        let mut xacts: Vec<String> = new
+            .iter()
+            .map(|trn| {
+                let mut header = trn.date.format(ISO_DATE_FORMAT).to_string();
+                if let Some(code) = &trn.check_number {
+                    header += &format!(" ({})", code);
+                }
+                let mut post = format!("{}  {}", account1, amount(&trn.amount));
+                if let Some(memo) = &trn.memo {
+                    post += &format!("  ; {}", memo);
+                }
+                format!(
+                    "{} {}\n    ; FITID: {}\n    {}\n    {}\n",
+                    header,
+                    trn.payee,
+                    trn.id,
+                    post,
+                    rules.account2_for(&trn.payee)
+                )
+            })
+            .collect();
+
+        if let Some((balance, date)) = &self.balance {
+            xacts.push(format!(
+                "{} Statement balance\n    {}  {} = {}\n",
+                date.format(ISO_DATE_FORMAT),
+                account1,
+                amount(&Quantity::ZERO),
+                amount(balance)
+            ));
+        }
+        xacts
+    }
+}
+
+/// Reads the statements from the OFX text, SGML or XML.
+pub fn read_ofx(source: &str) -> Result<Vec<OfxStatement>, String> {
+    let Some(start) = source.find("<OFX>") else {
+        return Err("Not an OFX statement, <OFX> was not found".to_owned());
+    };
+
+    let mut statements = vec![];
+    let mut statement: Option<OfxStatement> = None;
+    // The values in the STMTTRN or LEDGERBAL being read.
+    let mut fields: Option<HashMap<&str, String>> = None;
+
+    for (tag, value) in elements(&source[start..])? {
+        match tag {
+            "STMTRS" | "CCSTMTRS" => {
+                statement = Some(OfxStatement {
+                    credit_card: tag == "CCSTMTRS",
+                    ..Default::default()
+                })
+            }
+            "/STMTRS" | "/CCSTMTRS" => statements.extend(statement.take()),
+            "STMTTRN" | "LEDGERBAL" => fields = Some(HashMap::new()),
+            "/STMTTRN" | "/LEDGERBAL" => {
+                let (Some(statement), Some(fields)) = (&mut statement, fields.take()) else {
+                    continue;
+                };
+                if tag == "/STMTTRN" {
+                    statement.transactions.push(read_transaction(&fields)?);
+                } else {
+                    let balance = required(&fields, "LEDGERBAL", "BALAMT")?;
+                    let date = required(&fields, "LEDGERBAL", "DTASOF")?;
+                    statement.balance = Some((parse_amount(&balance)?, parse_date(&date)?));
+                }
+            }
+            _ if tag.starts_with('/') => (),
+            _ => {
+                if let Some(fields) = &mut fields {
+                    fields.insert(tag, value);
+                } else if let Some(statement) = &mut statement {
+                    match tag {
+                        "CURDEF" => statement.currency = Some(value),
+                        "ACCTID" => statement.account_id = Some(value),
+                        _ => (),
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(statements)
+}
+
+/// The importer for `import statement.ofx`, with the rules from `statement.ofx.rules`.
+/// Also reads the `.qfx` files.
+pub struct OfxImporter;
+
+impl Importer for OfxImporter {
+    fn name(&self) -> &str {
+        "ofx"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["ofx", "qfx"]
+    }
+
+    fn import(&self, path: &Path, source: &str, journal: &Journal) -> Result<String, String> {
+        let rules_path = CsvRules::path_for(path);
+        let rules = match rules_path.is_file() {
+            true => fs::read_to_string(&rules_path).map_err(|e| e.to_string()),
+            false => Ok(String::new()),
+        }
+        .and_then(|text| CsvRules::parse_accounts(&text))
+        .map_err(|e| format!("{}: {}", rules_path.display(), e))?;
+
+        let statements = read_ofx(source).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let text: Vec<String> = statements
+            .iter()
+            .flat_map(|statement| statement.to_ledger(&rules, journal))
+            .collect();
+        Ok(text.join("\n"))
+    }
+}
+
+fn read_transaction(fields: &HashMap<&str, String>) -> Result<OfxTransaction, String> {
+    let field = |name: &str| fields.get(name).filter(|value| !value.is_empty()).cloned();
+    let (payee, memo) = match (field("NAME"), field("MEMO")) {
+        (Some(name), memo) => (name, memo),
+        (None, Some(memo)) => (memo, None),
+        (None, None) => (
+            field("TRNTYPE").unwrap_or_else(|| "Unknown".to_owned()),
+            None,
+        ),
+    };
+
+    Ok(OfxTransaction {
+        date: parse_date(&required(fields, "STMTTRN", "DTPOSTED")?)?,
+        amount: parse_amount(&required(fields, "STMTTRN", "TRNAMT")?)?,
+        id: required(fields, "STMTTRN", "FITID")?,
+        payee,
+        memo,
+        check_number: field("CHECKNUM"),
+    })
+}
+
+fn required(fields: &HashMap<&str, String>, aggregate: &str, name: &str) -> Result<String, String> {
+    match fields.get(name) {
+        Some(value) if !value.is_empty() => Ok(value.to_owned()),
+        _ => Err(format!("{} without {}", aggregate, name)),
+    }
+}
+
+/// The date of `20230501120000.000[-5:EST]`, the first 8 digits.
+fn parse_date(value: &str) -> Result<NaiveDate, String> {
+    value
+        .get(..8)
+        .and_then(|date| NaiveDate::parse_from_str(date, "%Y%m%d").ok())
+        .ok_or_else(|| format!("Invalid date: {}", value))
+}
+
+/// The amount, i.e. `-45.30`. Some banks use the `,` decimal mark.
+fn parse_amount(value: &str) -> Result<Quantity, String> {
+    let number = value.replace(',', ".");
+    let number = number.strip_prefix('+').unwrap_or(&number);
+    Quantity::from_str(number).ok_or_else(|| format!("Invalid amount: {}", value))
+}
+
+/// The elements, as (tag, value), in the order of the text. The closing tags start with
+/// `/`. The value runs to the next tag, as the leaf elements in SGML are not closed.
+fn elements(text: &str) -> Result<Vec<(&str, String)>, String> {
+    let mut elements = vec![];
+    let mut rest = text;
+
+    while let Some(start) = rest.find('<') {
+        let Some(end) = rest[start..].find('>') else {
+            return Err(format!("Unterminated tag: {}", rest[start..].trim_end()));
+        };
+        let tag = rest[start + 1..start + end].trim();
+        rest = &rest[start + end + 1..];
+        let value_end = rest.find('<').unwrap_or(rest.len());
+        let value = decode(rest[..value_end].trim());
+        rest = &rest[value_end..];
+
+        // XML processing instructions and comments.
+        if tag.starts_with(['?', '!']) {
+            continue;
+        }
+        // The empty element, `<MEMO/>`.
+        let tag = tag.strip_suffix('/').map_or(tag, str::trim_end);
+        elements.push((tag, value));
+    }
+
+    Ok(elements)
+}
+
+/// Replaces the character references, i.e. `&amp;`.
+fn decode(text: &str) -> String {
+    let mut decoded = String::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest.find(';').map(|end| &rest[1..end]);
+        let c = match entity {
+            Some("amp") => Some('&'),
+            Some("lt") => Some('<'),
+            Some("gt") => Some('>'),
+            Some("quot") => Some('"'),
+            Some("apos") => Some('\''),
+            Some("nbsp") => Some(' '),
+            Some(code) if code.starts_with("#x") => u32::from_str_radix(&code[2..], 16)
+                .ok()
+                .and_then(char::from_u32),
+            Some(code) if code.starts_with('#') => code[1..].parse().ok().and_then(char::from_u32),
+            _ => None,
+        };
+        match (c, entity) {
+            (Some(c), Some(entity)) => {
+                decoded.push(c);
+                rest = &rest[entity.len() + 2..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::{decode, read_ofx, OfxStatement, OfxTransaction};
+    use crate::{amount::Quantity, csv::CsvRules, journal::Journal, parse_sources};
+
+    const SGML: &str = include_str!("../tests/statement.ofx");
+    const XML: &str = include_str!("../tests/statement.qfx");
+
+    fn import(journal: &mut Journal, text: &str) -> Result<(), String> {
+        parse_sources([("tests/main.ledger", text.as_bytes())], journal)
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(
+            "Smith & Sons <Shop>",
+            decode("Smith &amp; Sons &lt;Shop&#62;")
+        );
+        assert_eq!("AT&T; é", decode("AT&T; &#xe9;"));
+    }
+
+    #[test]
+    fn test_read_sgml() {
+        let statements = read_ofx(SGML).unwrap();
+
+        assert_eq!(1, statements.len());
+        let statement = &statements[0];
+        assert_eq!(Some("USD"), statement.currency.as_deref());
+        assert_eq!(Some("1234567890"), statement.account_id.as_deref());
+        assert_eq!(3, statement.transactions.len());
+        let check = &statement.transactions[1];
+        assert_eq!(NaiveDate::from_ymd_opt(2023, 5, 5).unwrap(), check.date);
+        assert_eq!(Quantity::from("-1200.00"), check.amount);
+        assert_eq!("202305052", check.id);
+        assert_eq!(Some("1001"), check.check_number.as_deref());
+        assert_eq!("Smith & Sons Grocery", statement.transactions[2].payee);
+        assert_eq!(
+            Some((
+                Quantity::from("1254.70"),
+                NaiveDate::from_ymd_opt(2023, 5, 31).unwrap()
+            )),
+            statement.balance
+        );
+    }
+
+    #[test]
+    fn test_read_xml() {
+        let statements = read_ofx(XML).unwrap();
+
+        assert_eq!(1, statements.len());
+        let statement = &statements[0];
+        assert_eq!(Some("4111111111111111"), statement.account_id.as_deref());
+        assert_eq!(None, statement.transactions[0].memo);
+        assert_eq!("Refund <online>", statement.transactions[1].payee);
+        assert_eq!(Quantity::from("5.00"), statement.transactions[1].amount);
+        assert_eq!(Quantity::from("-14.99"), statement.balance.unwrap().0);
+    }
+
+    #[test]
+    fn test_invalid_ofx() {
+        assert!(read_ofx("Date,Amount\n").is_err());
+        assert_eq!(
+            "STMTTRN without FITID",
+            read_ofx("<OFX><STMTRS><STMTTRN><DTPOSTED>20230501<TRNAMT>1</STMTTRN>").unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_import_is_idempotent() {
+        let mut journal = Journal::new();
+
+        import(&mut journal, "import statement.ofx\nimport statement.ofx\n").unwrap();
+
+        // 3 transactions and the balance assertion, once.
+        assert_eq!(4, journal.xacts.len());
+        let rent = &journal.xacts[1];
+        assert_eq!(Some("202305052"), rent.get_tag("FITID"));
+        assert_eq!(Some("1001"), rent.code.as_deref());
+        assert_eq!(
+            "Expenses:Rent",
+            journal.get_account(rent.posts[1].account).fullname()
+        );
+        assert_eq!("Statement balance", journal.xacts[3].payee);
+    }
+
+    /// The same `FITID` on another account is a different transaction.
+    #[test]
+    fn test_exists_in_account() {
+        let mut journal = Journal::new();
+        import(
+            &mut journal,
+            "2023-05-01 Transfer\n    ; FITID: 1\n    Assets:Savings  10 USD\n    Equity\n",
+        )
+        .unwrap();
+        let trn = OfxTransaction {
+            date: NaiveDate::from_ymd_opt(2023, 5, 1).unwrap(),
+            amount: Quantity::from(10),
+            id: "1".to_owned(),
+            payee: "Transfer".to_owned(),
+            memo: None,
+            check_number: None,
+        };
+
+        assert!(trn.exists_in("Assets:Savings", &journal));
+        assert!(!trn.exists_in("Assets:Bank:Checking", &journal));
+    }
+
+    /// Without the `account1` rule, the account is named after `ACCTID`.
+    #[test]
+    fn test_statement_account() {
+        let statement = &read_ofx(SGML).unwrap()[0];
+        let rules = CsvRules::parse_accounts("").unwrap();
+        let account1 = CsvRules::parse_accounts("account1 Assets:Bank:Checking\n").unwrap();
+
+        assert_eq!("Assets:Bank:1234567890", statement.account(&rules));
+        assert_eq!("Assets:Bank:Checking", statement.account(&account1));
+        assert_eq!("Assets:Unknown", OfxStatement::default().account(&rules));
+    }
+
+    #[test]
+    fn test_import_balance_assertion() {
+        let mut journal = Journal::new();
+
+        import(&mut journal, "import statement.qfx\n").unwrap();
+        let actual = import(
+            &mut Journal::new(),
+            "2023-04-30 Opening\n    Assets:Bank:Checking  10 USD\n    Equity\n\n\
+             import statement.ofx\n",
+        )
+        .unwrap_err();
+
+        assert_eq!(3, journal.xacts.len());
+        assert_eq!(
+            "Liabilities:CreditCard:4111111111111111",
+            journal
+                .get_account(journal.xacts[2].posts[0].account)
+                .fullname()
+        );
+        assert!(
+            actual.ends_with("Balance assertion off by -10.00 USD (expected to see 1254.70 USD)")
+        );
+    }
+}
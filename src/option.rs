@@ -9,336 +9,447 @@
  * - report
  */
 
-pub enum Kind {
-    UNKNOWN,
-    FUNCTION,
-    OPTION,
-    PRECOMMAND,
-    COMMAND,
-    DIRECTIVE,
-    FORMAT,
+use std::{collections::BTreeMap, fs, path::Path};
+
+use crate::{journal::CheckingStyle, utilities::expand_path};
+
+/// The scope of an option. Ledger looks up the options in the global scope, then in the
+/// session, and then in the report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    Global,
+    Session,
+    Report,
+}
+
+/// A command-line option.
+#[derive(Debug)]
+pub struct OptionDef {
+    /// The long name, without the dashes.
+    pub name: &'static str,
+    pub short: Option<char>,
+    /// The placeholder for the value, shown in the help. None for a flag.
+    pub value: Option<&'static str>,
+    pub scope: Scope,
+    pub description: &'static str,
+    /// Where the option goes in the InputOptions.
+    pub target: Target,
+}
+
+/// The field of the InputOptions that an option sets.
+#[derive(Debug)]
+pub enum Target {
+    /// A flag, set to true.
+    Flag(fn(&mut InputOptions) -> &mut bool),
+    /// A value. The last one wins.
+    Value(fn(&mut InputOptions) -> &mut Option<String>),
+    /// A value that can be given more than once, i.e. `--file`.
+    List(fn(&mut InputOptions) -> &mut Vec<String>),
+    /// Any other setting, with the value, if the option takes one.
+    Set(fn(&mut InputOptions, String) -> Result<(), String>),
+}
+
+const fn option(
+    name: &'static str,
+    short: Option<char>,
+    value: Option<&'static str>,
+    scope: Scope,
+    description: &'static str,
+    target: Target,
+) -> OptionDef {
+    OptionDef {
+        name,
+        short,
+        value,
+        scope,
+        description,
+        target,
+    }
+}
+
+/// The known options.
+///
+/// lookup_option() in global.cc, session.cc and report.cc
+pub const OPTIONS: &[OptionDef] = &[
+    // global
+    option("help", Some('h'), None, Scope::Global, "Show this help",
+        Target::Flag(|o| &mut o.help)),
+    option("version", None, None, Scope::Global, "Show the version",
+        Target::Flag(|o| &mut o.version)),
+    // session
+    option("file", Some('f'), Some("FILE"), Scope::Session, "Read the journal from FILE",
+        Target::List(|o| &mut o.filenames)),
+    option("init-file", None, Some("FILE"), Scope::Session, "Read the default options from FILE",
+        Target::Value(|o| &mut o.init_file)),
+    option("input-date-format", None, Some("FORMAT"), Scope::Session, "Read the dates in FORMAT",
+        Target::Value(|o| &mut o.input_date_format)),
+    option("input-format", None, Some("FORMAT"), Scope::Session, "Read the journal as ledger, hledger or beancount",
+        Target::Value(|o| &mut o.input_format)),
+    option("alias", None, Some("ALIAS=ACCOUNT"), Scope::Session, "Define an account alias",
+        Target::List(|o| &mut o.aliases)),
+    option("recursive-aliases", None, None, Scope::Session, "Expand the aliases recursively",
+        Target::Flag(|o| &mut o.recursive_aliases)),
+    option("strict", None, None, Scope::Session, "Warn about the undeclared items",
+        Target::Set(set_strict)),
+    option("pedantic", None, None, Scope::Session, "Fail on the undeclared items",
+        Target::Set(set_pedantic)),
+    // report
+    option("date-format", Some('y'), Some("FORMAT"), Scope::Report, "Print the dates in FORMAT",
+        Target::Value(|o| &mut o.date_format)),
+    option("output-format", None, Some("FORMAT"), Scope::Report, "Write as ledger, hledger, beancount (print) or json",
+        Target::Value(|o| &mut o.output_format)),
+    option("sort", Some('S'), Some("EXPR"), Scope::Report, "Sort the report by EXPR, i.e. -amount",
+        Target::Value(|o| &mut o.sort)),
+    option("monthly", Some('M'), None, Scope::Report, "register: the totals for each month",
+        Target::Flag(|o| &mut o.monthly)),
+    option("check", None, None, Scope::Report, "fmt: only list the files to format",
+        Target::Flag(|o| &mut o.check)),
+    option("days", None, Some("N"), Scope::Report, "duplicates: the date window, in days",
+        Target::Set(set_days)),
+    option("fix", None, None, Scope::Report, "duplicates: remove the duplicates",
+        Target::Flag(|o| &mut o.fix)),
+    option("rules", None, Some("FILE"), Scope::Report, "convert: the CSV rules file",
+        Target::Value(|o| &mut o.rules)),
+];
+
+fn set_strict(options: &mut InputOptions, _: String) -> Result<(), String> {
+    options.checking_style = CheckingStyle::Warning;
+    Ok(())
+}
+
+fn set_pedantic(options: &mut InputOptions, _: String) -> Result<(), String> {
+    options.checking_style = CheckingStyle::Error;
+    Ok(())
+}
+
+fn set_days(options: &mut InputOptions, days: String) -> Result<(), String> {
+    let Ok(days) = days.parse() else {
+        return Err(format!("Invalid number of days: {}", days));
+    };
+    options.days = Some(days);
+    Ok(())
 }
 
 /// Recognize arguments.
 /// returns (commands, options)
 /// Commands are application commands, with optional arguments, ie "accounts Asset"
 /// Options are the options with '-' or "--" prefix, ie "-f <file>"
-pub fn process_arguments(args: Vec<String>) -> (Vec<String>, InputOptions) {
+/// `--` ends the options. The rest of the arguments are commands.
+pub fn process_arguments(args: Vec<String>) -> Result<(Vec<String>, InputOptions), String> {
+    // The options with their long names, each followed by its value, if any.
     let mut options: Vec<String> = vec![];
     let mut commands: Vec<String> = vec![];
 
     // iterate through the list
-    let mut iter = args.iter();
+    let mut iter = args.into_iter();
     while let Some(arg) = iter.next() {
-        if !arg.starts_with('-') {
-            // otherwise return
-            commands.push(arg.to_owned());
-            continue;
+        if arg == "--" {
+            // ends the options processing
+            commands.extend(iter.by_ref());
+            break;
         }
 
-        // otherwise, it's an argument
-        // if an item contains "-f", it is a real argument
-
-        // long option
-        if arg.starts_with("--") {
-            // long argument
-            if arg.len() == 2 {
-                // it's a --, ending options processing
-                todo!("handle this case?")
-            } else if arg.len() == 1 {
-                panic!("illegar option {}", arg)
+        if let Some(long) = arg.strip_prefix("--") {
+            // `--name=value` or `--name value`
+            let (name, inline_value) = match long.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_owned())),
+                None => (long, None),
+            };
+            let Some(option) = find_option(name) else {
+                return Err(format!("Illegal option --{}", name));
+            };
+
+            options.push(format!("--{}", option.name));
+            match (option.value, inline_value) {
+                (Some(_), Some(value)) => options.push(value),
+                (Some(_), None) => match iter.next() {
+                    Some(value) => options.push(value),
+                    None => return Err(format!("Missing option argument for --{}", name)),
+                },
+                (None, Some(_)) => return Err(format!("Option --{} takes no argument", name)),
+                (None, None) => (),
             }
-
-            // todo: check if there is '=' contained
-            // else
-            let option_name = &arg[2..];
-
-            // TODO: find_option(option_name);
-
-            // TODO: get argument value
-
-            // TODO: process_option();
-
-            todo!("complete")
-        } else {
-            // single-char option
-
-            let mut option_queue = vec![];
-
-            // iterate through all characters and identify options,
-            for (i, c) in arg.char_indices() {
-                if i == 0 {
-                    // skipping the first ('-').
-                    continue;
-                }
-
-                // check for a valid option and if it requires an argument?
-                // Also links to a handler.
-                // option = find_option(c);
-                // ^^^ This is done later. Just parse here.
-
-                let mut temp_option = String::from('-');
-                temp_option.push(c);
-
-                // add option to the option queue
-                option_queue.push(temp_option);
-            }
-
-            // multiple arguments are possible after "-".
-            // The values come after the options.
-            // Iterate through the option_queue and retrieve the value if required.
-            for option in option_queue {
-                // todo: there needs to be an indicator if the option requires a value.
-                // if requires_value &&
-                if let Some(value) = iter.next() {
-                    // let mut whence = String::from("-");
-                    // whence.push(arg.chars().nth(0).unwrap());
-
-                    // TODO: check for validity, etc.
-                    // the magic seems to be happening here.
-                    // process_option(whence, Some(value.to_owned()));
-
-                    // for now, just add
-                    options.push(option);
-                    options.push(value.to_owned());
-                } else {
-                    panic!("Missing option argument for {}", arg);
+        } else if arg.len() > 1 && arg.starts_with('-') {
+            // Single-char options. Multiple are possible after "-", i.e. `-Sf date file`.
+            // The values come after the options, in the same order.
+            for c in arg.chars().skip(1) {
+                let Some(option) = find_short_option(c) else {
+                    return Err(format!("Illegal option -{}", c));
+                };
+
+                options.push(format!("--{}", option.name));
+                if option.value.is_some() {
+                    match iter.next() {
+                        Some(value) => options.push(value),
+                        None => return Err(format!("Missing option argument for -{}", c)),
+                    }
                 }
             }
+        } else {
+            // A command or its argument. `-` alone is an argument, i.e. a file name.
+            commands.push(arg);
         }
     }
 
     // Convert input options
-    let input_options = get_input_options(options);
+    let input_options = get_input_options(options)?;
 
-    (commands, input_options)
+    Ok((commands, input_options))
 }
 
-/// Searches through scopes for the option with the given letter.
-/// Then links to a handler function(?).
-fn find_option(letter: char) {
-    let mut name = String::from(letter);
-    name.push('_');
-
-    // lookup first checks Session
-    session_lookup(Kind::OPTION, &name);
-
-    todo!()
-}
-
-/// find_option() from global.cc
-fn lookup_option_global(kind: Kind, letter: char) {
-    match kind {
-        Kind::PRECOMMAND => {
-            // p => push, pop
+/// Reads the options from the init file, the environment variables and the arguments.
+/// The command line overrides the environment, which overrides the init file.
+///
+/// The environment variables are the long option names with the `LEDGER_` prefix, i.e.
+/// `LEDGER_FILE` for `--file`. The init file is from `--init-file`, `LEDGER_INIT_FILE`,
+/// or `~/.ledgerrc`.
+pub fn process_arguments_with_env(
+    args: Vec<String>,
+    vars: &BTreeMap<String, String>,
+) -> Result<(Vec<String>, InputOptions), String> {
+    let (commands, command_line) = process_arguments(args)?;
+    let environment = get_input_options(environment_options(vars))?;
+    let home = vars.get("HOME").map(|home| home.as_str());
+
+    let init_file = command_line.init_file.as_ref().or(environment.init_file.as_ref());
+    let init = match (init_file, home) {
+        (Some(path), _) => read_init_file(&expand_path(path, home))?,
+        (None, Some(home)) if Path::new(home).join(".ledgerrc").is_file() => {
+            read_init_file(&Path::new(home).join(".ledgerrc"))?
         }
-        _ => todo!(),
-    }
+        _ => InputOptions::new(),
+    };
 
-    // adhiostv
-    match letter {
-        's' => todo!("script"),
-        't' => todo!("trace"),
-        _ => todo!("other chars"),
-    }
+    Ok((commands, init.override_with(environment).override_with(command_line)))
 }
 
-fn process_option(whence: String, value: Option<String>) {
-    let mut args = vec![];
-
-    // add the argument and the value to a collection
-    args.push(whence);
+/// The options from the `LEDGER_*` environment variables. The variables that are not
+/// options, i.e. `LEDGER_PAGER`, are skipped.
+fn environment_options(vars: &BTreeMap<String, String>) -> Vec<String> {
+    let mut options = vec![];
+    for (key, value) in vars {
+        let Some(name) = key.strip_prefix("LEDGER_") else {
+            continue;
+        };
+        let name = name.to_lowercase().replace('_', "-");
+        // --help and --version are not defaults.
+        let Some(option) = find_option(&name).filter(|o| o.scope != Scope::Global) else {
+            continue;
+        };
 
-    match value {
-        Some(val) => args.push(val),
-        None => (),
+        options.push(format!("--{}", option.name));
+        if option.value.is_some() {
+            options.push(value.to_owned());
+        }
     }
-
-    // TODO: check for validity
-    // if wants_arg ...
-    // there have to be 2 args.
+    options
 }
 
-/// Lookup options for session
-fn session_lookup(kind: Kind, name: &str) {
-    let option = name.chars().nth(0).unwrap();
+/// Reads the options from the init file, one per line, i.e. `--file ~/journal.ledger`.
+/// The value is the rest of the line. The lines starting with `#` or `;` are comments.
+///
+/// session.cc: void session_t::read_init()
+fn read_init_file(path: &Path) -> Result<InputOptions, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
 
-    match kind {
-        Kind::FUNCTION => todo!(),
-        Kind::OPTION => {
-            // handler =
-            session_lookup_option(option)
-            // TODO: make_opt_handler(Session, handler)
+    let mut args = vec![];
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with(['#', ';']) {
+            continue;
+        }
+        match line.split_once(char::is_whitespace) {
+            Some((option, value)) => {
+                args.push(option.to_owned());
+                args.push(value.trim().to_owned());
+            }
+            None => args.push(line.to_owned()),
         }
-        _ => todo!(),
     }
-}
 
-/// Searches for a short-version option. i.e. -f for file
-fn session_lookup_option(option: char) {
-    match option {
-        'Q' => todo!(),
-        'Z' => todo!(),
-        'c' => todo!(),
-        'd' => todo!(),
-        'e' => todo!(),
-        'f' => {
-            // OPT_(file_)
-            todo!("option file_")
-        }
-        'i' => todo!(),
-        'l' => todo!(),
-        'm' => todo!(),
-        'n' => todo!(),
-        'p' => todo!(),
-        'r' => todo!(),
-        's' => todo!(),
-        't' => todo!(),
-        'v' => todo!(),
-        _ => todo!("return NULL"),
+    let (commands, options) =
+        process_arguments(args).map_err(|e| format!("{}: {}", path.display(), e))?;
+    if let Some(command) = commands.first() {
+        return Err(format!("{}: {} is not an option", path.display(), command));
     }
+    Ok(options)
 }
 
-/// Lookup options for reports
-fn lookup_report(kind: Kind, name: &str) {
-    let letter: char = name.chars().nth(0).unwrap();
-
-    match kind {
-        Kind::FUNCTION => {
-            todo!()
-        }
-        Kind::COMMAND => {
-            match letter {
-                'a' => {
-                    if name == "accounts" {
-                        todo!("accounts")
-                        // POSTS_REPORTER(report_accounts)
-                    }
-                }
-                'b' => {
-                    // FORMATTED_ACCOUNTS_REPORTER(balance_format_)
-                    todo!("balance")
-                    // or budget
-                }
-
-                // cdel
-                'p' => {
-                    // print,
-                    // POSTS_REPORTER(print_xacts)
-
-                    // prices,
-                    // pricedb,
-                    // FORMATTED_COMMODITIES_REPORTER(pricedb_format_)
-
-                    // pricemap,
-                    // report_t::pricemap_command
-
-                    // payees
-                    // POSTS_REPORTER(report_payees)
-                }
-                'r' => {
-                    // r, reg, register
-                    // FORMATTED_POSTS_REPORTER(register_format_)
-
-                    // reload
-                    // report_t::reload_command
-
-                    todo!("register")
-                }
-
-                // stx
-                _ => todo!("the rest"),
-            }
-        }
-        Kind::PRECOMMAND => {
-            match letter {
-                'a' => {
-                    todo!("args")
-                    // WRAP_FUNCTOR(query_command)
-                }
-                // efgpqst
-                _ => todo!("handle pre-command"),
-            }
-        }
-        _ => todo!("handle"),
-    }
+/// Finds the option by its long name, searching the scopes in order.
+pub fn find_option(name: &str) -> Option<&'static OptionDef> {
+    [Scope::Global, Scope::Session, Scope::Report]
+        .iter()
+        .find_map(|scope| {
+            OPTIONS
+                .iter()
+                .find(|option| option.scope == *scope && option.name == name)
+        })
+}
 
-    todo!("go through the report options")
+/// Finds the option by its letter, i.e. 'f' for `--file`.
+pub fn find_short_option(letter: char) -> Option<&'static OptionDef> {
+    OPTIONS.iter().find(|option| option.short == Some(letter))
 }
 
-fn lookup_option_report(letter: char) {
-    // t:
-    // amount, tail, total, total_data, truncate, total_width, time_report
-
-    match letter {
-        // %ABCDEFGHIJLMOPRSTUVWXYabcdefghijlmnopqrstuvwy
-        'G' => todo!("gain"),     // OPT_CH(gain)
-        'S' => todo!("sort"),     // OPT_CH(sort_)
-        'X' => todo!("exchange"), // OPT_CH(exchange_)
-        'a' => {
-            // OPT(abbrev_len_);
-            // else OPT_(account_);
-            // else OPT(actual);
-            // else OPT(add_budget);
-            // else OPT(amount_);
-            // else OPT(amount_data);
-            // else OPT_ALT(primary_date, actual_dates);
-            // else OPT(anon);
-            // else OPT_ALT(color, ansi);
-            // else OPT(auto_match);
-            // else OPT(aux_date);
-            // else OPT(average);
-            // else OPT(account_width_);
-            // else OPT(amount_width_);
-            // else OPT(average_lot_prices);
-            todo!()
-        }
-        _ => todo!("the rest"),
-    }
+/// The help lines for the options.
+pub fn options_help() -> Vec<String> {
+    OPTIONS
+        .iter()
+        .map(|option| {
+            let short = match option.short {
+                Some(c) => format!("-{}, ", c),
+                None => "    ".to_owned(),
+            };
+            let mut usage = format!("{}--{}", short, option.name);
+            if let Some(value) = option.value {
+                usage += " ";
+                usage += value;
+            }
+            format!("  {:<34} {}", usage, option.description)
+        })
+        .collect()
 }
 
+#[derive(Clone)]
 pub struct InputOptions {
+    /// `-h` or `--help`.
+    pub help: bool,
+    /// `--version`.
+    pub version: bool,
     pub filenames: Vec<String>,
+    /// Account aliases from `--alias`, i.e. `checking=Assets:Bank:Checking`.
+    pub aliases: Vec<String>,
+    pub recursive_aliases: bool,
+    /// `--strict` or `--pedantic`.
+    pub checking_style: CheckingStyle,
+    /// The date format of the journal files, `--input-date-format`.
+    pub input_date_format: Option<String>,
+    /// The date format for the reports, `-y` or `--date-format`.
+    pub date_format: Option<String>,
+    /// The syntax of the journal files, `--input-format`.
+    pub input_format: Option<String>,
+    /// The syntax of the `print` output, `--output-format`.
+    pub output_format: Option<String>,
+    /// `--check` for `fmt`: only report the files that need formatting.
+    pub check: bool,
+    /// The sort expression for the reports, `-S` or `--sort`.
+    pub sort: Option<String>,
+    /// `-M` or `--monthly`: group the register by month.
+    pub monthly: bool,
+    /// The date window for `duplicates`, `--days`.
+    pub days: Option<i64>,
+    /// `--fix` for `duplicates`: remove the duplicates from the files.
+    pub fix: bool,
+    /// The file with the default options, `--init-file`.
+    pub init_file: Option<String>,
+    /// The CSV rules file for `convert`, `--rules`.
+    pub rules: Option<String>,
 }
 
 impl InputOptions {
     pub fn new() -> Self {
-        Self { filenames: vec![] }
+        Self {
+            help: false,
+            version: false,
+            filenames: vec![],
+            aliases: vec![],
+            recursive_aliases: false,
+            checking_style: CheckingStyle::Permissive,
+            input_date_format: None,
+            date_format: None,
+            input_format: None,
+            output_format: None,
+            check: false,
+            sort: None,
+            monthly: false,
+            days: None,
+            fix: false,
+            init_file: None,
+            rules: None,
+        }
+    }
+
+    /// These options, overridden by the ones set in `higher`. The files from `higher`
+    /// replace these files, while the aliases are added.
+    pub(crate) fn override_with(mut self, higher: InputOptions) -> InputOptions {
+        self.help |= higher.help;
+        self.version |= higher.version;
+        if !higher.filenames.is_empty() {
+            self.filenames = higher.filenames;
+        }
+        self.aliases.extend(higher.aliases);
+        self.recursive_aliases |= higher.recursive_aliases;
+        if higher.checking_style != CheckingStyle::Permissive {
+            self.checking_style = higher.checking_style;
+        }
+        self.input_date_format = higher.input_date_format.or(self.input_date_format);
+        self.date_format = higher.date_format.or(self.date_format);
+        self.input_format = higher.input_format.or(self.input_format);
+        self.output_format = higher.output_format.or(self.output_format);
+        self.check |= higher.check;
+        self.sort = higher.sort.or(self.sort);
+        self.monthly |= higher.monthly;
+        self.days = higher.days.or(self.days);
+        self.fix |= higher.fix;
+        self.init_file = higher.init_file.or(self.init_file);
+        self.rules = higher.rules.or(self.rules);
+        self
     }
 }
 
-pub(crate) fn get_input_options(options: Vec<String>) -> InputOptions {
+/// Converts the options, as collected by `process_arguments`, into the InputOptions.
+/// Each option sets its target, from the `OPTIONS` table.
+pub(crate) fn get_input_options(options: Vec<String>) -> Result<InputOptions, String> {
     let mut result = InputOptions::new();
 
     let mut iter = options.into_iter();
-    loop {
-        match iter.next() {
-            Some(opt) => {
-                match opt.as_str() {
-                    "-f" => {
-                        let Some(filename) = iter.next() else { panic!("missing filename argument!"); };
-                        result.filenames.push(filename);
-                    }
-                    _ => panic!("Unrecognized argument!")
-                }
-            },
-            None => break,
+    while let Some(opt) = iter.next() {
+        let option = match opt.strip_prefix("--") {
+            Some(name) => find_option(name),
+            None => opt.strip_prefix('-').and_then(|letter| {
+                let mut chars = letter.chars();
+                chars.next().filter(|_| chars.next().is_none()).and_then(find_short_option)
+            }),
+        };
+        let Some(option) = option else {
+            return Err(format!("Illegal option {}", opt));
+        };
+        let mut value = || iter.next().ok_or(format!("Missing option argument for {}", opt));
+
+        match option.target {
+            Target::Flag(field) => *field(&mut result) = true,
+            Target::Value(field) => *field(&mut result) = Some(value()?),
+            Target::List(field) => field(&mut result).push(value()?),
+            Target::Set(set) => {
+                let value = match option.value {
+                    Some(_) => value()?,
+                    None => String::new(),
+                };
+                set(&mut result, value)?
+            }
         }
     }
-    result
+    Ok(result)
 }
 
 #[cfg(test)]
 mod tests {
+    use std::{collections::BTreeMap, fs};
+
     use shell_words::split;
 
-    use crate::option::{get_input_options, process_arguments};
+    use crate::{
+        journal::CheckingStyle,
+        option::{
+            find_option, find_short_option, get_input_options, process_arguments,
+            process_arguments_with_env, Scope, OPTIONS,
+        },
+    };
 
     #[test]
     fn test_process_arguments() {
         let args = split("accounts -f basic.ledger").unwrap();
 
-        let (commands, options) = process_arguments(args);
+        let (commands, options) = process_arguments(args).unwrap();
 
         assert_eq!(1, commands.len());
         assert_eq!("accounts", commands[0]);
@@ -352,7 +463,7 @@ mod tests {
     // fn test_process_multiple_arguments() {
     //     let args = split("cmd -ab value_a value_b").unwrap();
 
-    //     let (commands, options) = process_arguments(args);
+    //     let (commands, options) = process_arguments(args).unwrap();
 
     //     assert_eq!(1, commands.len());
     //     assert_eq!("cmd", commands[0]);
@@ -371,7 +482,7 @@ mod tests {
     fn test_multiple_commands() {
         let args: Vec<String> = shell_words::split("accounts b -f tests/minimal.ledger").unwrap();
 
-        let (commands, options) = process_arguments(args);
+        let (commands, _options) = process_arguments(args).unwrap();
 
         assert_eq!(2, commands.len());
         assert_eq!("accounts", commands[0]);
@@ -384,7 +495,7 @@ mod tests {
         let args = shell_words::split(command).expect("arguments parsed");
         let expected = "tests/minimal.ledger";
 
-        let (commands, options) = process_arguments(args);
+        let (_commands, options) = process_arguments(args).unwrap();
 
         let actual = options.filenames.first().unwrap();
         assert_eq!(expected, actual.as_str());
@@ -394,18 +505,221 @@ mod tests {
     fn test_multiple_filenames() {
         let args = split("accounts -f one -f two").unwrap();
 
-        let (commands, options) = process_arguments(args);
+        let (_commands, options) = process_arguments(args).unwrap();
 
         assert_eq!(2, options.filenames.len());
         assert_eq!("one", options.filenames[0]);
         assert_eq!("two", options.filenames[1]);
     }
 
+    #[test]
+    fn test_long_options() {
+        let args = split("accounts --file one --alias=a=Assets --alias c=Assets:Cash --recursive-aliases").unwrap();
+
+        let (commands, options) = process_arguments(args).unwrap();
+
+        assert_eq!(vec!["accounts"], commands);
+        assert_eq!(vec!["one"], options.filenames);
+        assert_eq!(vec!["a=Assets", "c=Assets:Cash"], options.aliases);
+        assert!(options.recursive_aliases);
+    }
+
+    #[test]
+    fn test_date_format_options() {
+        let args = split("print --input-date-format %d.%m.%Y -y %Y/%m/%d").unwrap();
+
+        let (_, options) = process_arguments(args).unwrap();
+
+        assert_eq!(Some("%d.%m.%Y".to_string()), options.input_date_format);
+        assert_eq!(Some("%Y/%m/%d".to_string()), options.date_format);
+    }
+
+    #[test]
+    fn test_format_options() {
+        let args = split("print --input-format beancount --output-format=hledger").unwrap();
+
+        let (_, options) = process_arguments(args).unwrap();
+
+        assert_eq!(Some("beancount".to_string()), options.input_format);
+        assert_eq!(Some("hledger".to_string()), options.output_format);
+    }
+
+    #[test]
+    fn test_checking_style_options() {
+        let (_, strict) = process_arguments(split("accounts --strict").unwrap()).unwrap();
+        let (_, pedantic) = process_arguments(split("accounts --pedantic").unwrap()).unwrap();
+        let (_, permissive) = process_arguments(split("accounts").unwrap()).unwrap();
+
+        assert_eq!(CheckingStyle::Warning, strict.checking_style);
+        assert_eq!(CheckingStyle::Error, pedantic.checking_style);
+        assert_eq!(CheckingStyle::Permissive, permissive.checking_style);
+    }
+
+    #[test]
+    fn test_inline_option_values() {
+        let args = split("print --file=one -S date --sort=-amount").unwrap();
+
+        let (commands, options) = process_arguments(args).unwrap();
+
+        assert_eq!(vec!["print"], commands);
+        assert_eq!(vec!["one"], options.filenames);
+        assert_eq!(Some("-amount".to_string()), options.sort);
+    }
+
+    #[test]
+    fn test_short_flags_take_no_value() {
+        let args = split("-h accounts -hf one").unwrap();
+
+        let (commands, options) = process_arguments(args).unwrap();
+
+        assert_eq!(vec!["accounts"], commands);
+        assert!(options.help);
+        assert_eq!(vec!["one"], options.filenames);
+    }
+
+    #[test]
+    fn test_end_of_options() {
+        let args = split("accounts -f one -- -Assets --strict").unwrap();
+
+        let (commands, options) = process_arguments(args).unwrap();
+
+        assert_eq!(vec!["accounts", "-Assets", "--strict"], commands);
+        assert_eq!(CheckingStyle::Permissive, options.checking_style);
+    }
+
+    #[test]
+    fn test_option_errors() {
+        let error = |command: &str| process_arguments(split(command).unwrap()).err().unwrap();
+
+        assert_eq!("Illegal option --frobnicate", error("b --frobnicate"));
+        assert_eq!("Illegal option -Q", error("b -Q"));
+        assert_eq!("Missing option argument for --file", error("b --file"));
+        assert_eq!("Missing option argument for -S", error("b -fS one"));
+        assert_eq!("Option --strict takes no argument", error("b --strict=yes"));
+        assert_eq!("Invalid number of days: x", error("duplicates --days x"));
+    }
+
+    /// Every option in the table is accepted, with its value.
+    #[test]
+    fn test_all_options_accepted() {
+        for option in OPTIONS {
+            let mut args = vec![format!("--{}", option.name)];
+            if option.value.is_some() {
+                args.push("1".to_owned());
+            }
+
+            assert!(process_arguments(args).is_ok(), "--{}", option.name);
+        }
+    }
+
+    #[test]
+    fn test_option_scopes() {
+        assert_eq!(Scope::Global, find_option("version").unwrap().scope);
+        assert_eq!(Scope::Session, find_short_option('f').unwrap().scope);
+        assert_eq!(Scope::Report, find_option("sort").unwrap().scope);
+        assert!(find_option("fil").is_none());
+    }
+
+    /// A temporary home directory with the given `.ledgerrc`.
+    fn temp_home(name: &str, ledgerrc: Option<&str>) -> String {
+        let home = std::env::temp_dir()
+            .join(format!("ledger-rs-home-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&home).unwrap();
+        let rc = home.join(".ledgerrc");
+        match ledgerrc {
+            Some(content) => fs::write(&rc, content).unwrap(),
+            None => {
+                let _ = fs::remove_file(&rc);
+            }
+        }
+        home.display().to_string()
+    }
+
+    fn vars(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_init_file_in_home() {
+        let home = temp_home("rc", Some("# defaults\n--file ~/main.ledger\n-y %d.%m.%Y\n--strict\n"));
+
+        let (commands, options) =
+            process_arguments_with_env(split("b").unwrap(), &vars(&[("HOME", &home)])).unwrap();
+
+        assert_eq!(vec!["b"], commands);
+        assert_eq!(vec!["~/main.ledger"], options.filenames);
+        assert_eq!(Some("%d.%m.%Y".to_string()), options.date_format);
+        assert_eq!(CheckingStyle::Warning, options.checking_style);
+        fs::remove_dir_all(&home).unwrap();
+    }
+
+    #[test]
+    fn test_environment_overrides_init_file() {
+        let home = temp_home("env", Some("--file init.ledger\n--date-format %Y\n"));
+        let vars = vars(&[
+            ("HOME", &home),
+            ("LEDGER_FILE", "env.ledger"),
+            ("LEDGER_PEDANTIC", "1"),
+            ("LEDGER_PAGER", "less"),
+        ]);
+
+        let (_, options) = process_arguments_with_env(vec![], &vars).unwrap();
+
+        assert_eq!(vec!["env.ledger"], options.filenames);
+        assert_eq!(Some("%Y".to_string()), options.date_format);
+        assert_eq!(CheckingStyle::Error, options.checking_style);
+        fs::remove_dir_all(&home).unwrap();
+    }
+
+    #[test]
+    fn test_command_line_overrides_environment() {
+        let home = temp_home("args", Some("--alias a=Assets\n--date-format %Y\n"));
+        let vars = vars(&[("HOME", &home), ("LEDGER_FILE", "env.ledger")]);
+        let args = split("b -f one -f two -y %m --alias c=Assets:Cash").unwrap();
+
+        let (_, options) = process_arguments_with_env(args, &vars).unwrap();
+
+        assert_eq!(vec!["one", "two"], options.filenames);
+        assert_eq!(Some("%m".to_string()), options.date_format);
+        assert_eq!(vec!["a=Assets", "c=Assets:Cash"], options.aliases);
+        fs::remove_dir_all(&home).unwrap();
+    }
+
+    #[test]
+    fn test_explicit_init_file() {
+        let home = temp_home("explicit", None);
+        let rc = format!("{}/other.rc", home);
+        fs::write(&rc, "--file other.ledger\n").unwrap();
+
+        let (_, options) =
+            process_arguments_with_env(vec!["--init-file".into(), rc], &vars(&[("HOME", &home)]))
+                .unwrap();
+        let missing =
+            process_arguments_with_env(split("--init-file ~/missing.rc").unwrap(), &vars(&[("HOME", &home)]));
+
+        assert_eq!(vec!["other.ledger"], options.filenames);
+        assert!(missing.is_err());
+        fs::remove_dir_all(&home).unwrap();
+    }
+
+    #[test]
+    fn test_invalid_init_file() {
+        let home = temp_home("invalid", Some("balance\n"));
+
+        let result = process_arguments_with_env(vec![], &vars(&[("HOME", &home)]));
+
+        assert!(result.err().unwrap().ends_with("balance is not an option"));
+        fs::remove_dir_all(&home).unwrap();
+    }
+
     #[test]
     fn test_creating_input_options() {
         let options: Vec<String> = vec!["-f".into(), "one".into(), "-f".into(), "two".into()];
 
-        let actual = get_input_options(options);
+        let actual = get_input_options(options).unwrap();
 
         assert_eq!(2, actual.filenames.len());
         assert_eq!("one", actual.filenames[0]);
@@ -15,41 +15,108 @@
  * the collections in the Journal.
  * It also creates links among the models. This functionality is from finalize() function.
  */
-use core::panic;
 use std::{
-    env,
-    io::{BufRead, BufReader, Read},
-    path::PathBuf,
-    str::FromStr,
-    todo,
+    env, fmt,
+    fs::{self, File},
+    io::{BufRead, BufReader, Cursor, Read},
+    path::{Path, PathBuf},
 };
 
-use anyhow::Error;
-use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use anyhow::{anyhow, Error};
+use chrono::{Datelike, NaiveDate, NaiveDateTime, NaiveTime};
 
 use crate::{
+    account::{AccountCheck, AccountId, AccountType},
     amount::{Amount, Quantity},
     annotate::Annotation,
-    journal::Journal,
-    post::Post,
+    beancount,
+    commodity::{Commodity, COMMODITY_KNOWN, COMMODITY_NOMARKET},
+    directives::{
+        AccountDirective, CommodityDirective, DirectiveType, PayeeDirective, PriceDirective,
+    },
+    expr::{Expr, Scope},
+    journal::{CheckingStyle, Journal},
+    post::{Assertion, Post},
+    reader::{self, LineSource},
     scanner::{self, PostTokens},
-    xact::Xact, parse_file,
+    utilities::expand_path,
+    xact::{Position, Xact, XactId},
 };
 
 pub const ISO_DATE_FORMAT: &str = "%Y-%m-%d";
 pub const ISO_TIME_FORMAT: &str = "%H:%M:%S";
 
-pub(crate) fn read_into_journal<T: Read>(source: T, journal: &mut Journal) {
+/// An error on an earlier line than the current one, i.e. on a post of the transaction.
+/// It is reported at its own line.
+#[derive(Debug)]
+struct LineError {
+    line: usize,
+    message: String,
+}
+
+impl fmt::Display for LineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for LineError {}
+
+pub(crate) fn read_into_journal<T: Read>(source: T, journal: &mut Journal) -> Result<(), String> {
     let mut parser = Parser::new(source, journal);
 
-    parser.parse();
+    parser.parse()
 }
 
-/// Parses ISO-formatted date string, like 2023-07-23
-pub(crate) fn parse_date(date_str: &str) -> NaiveDate {
-    // todo: support more date formats?
+/// Parses the date with the `--input-date-format`, if given, or in one of the formats
+/// Ledger accepts: `2023-05-01`, `23-05-01`, `2023-05` and `05-01`. The separator can
+/// be `-`, `/` or `.`. The dates without a year get the given year, or the current one.
+///
+/// date_t parse_date_mask(const char * date_str, optional<date_traits_t&> traits)
+pub fn parse_date_in(
+    date_str: &str,
+    year: Option<i32>,
+    input_format: Option<&str>,
+) -> Result<NaiveDate, Error> {
+    let year = year.unwrap_or_else(|| chrono::Local::now().year());
+
+    if let Some(format) = input_format {
+        if let Ok(date) = parse_date_with_format(date_str, format, year) {
+            return Ok(date);
+        }
+    }
+
+    // Normalise the separators.
+    let normalised = date_str.replace(['.', '/'], "-");
+    let parts: Vec<&str> = normalised.split('-').collect();
+    let format = match parts.as_slice() {
+        [y, _, _] if y.len() == 4 => "%Y-%m-%d",
+        [_, _, _] => "%y-%m-%d",
+        [y, _] if y.len() == 4 => "%Y-%m",
+        [_, _] => "%m-%d",
+        _ => return Err(anyhow!("Invalid date: {}", date_str)),
+    };
+
+    parse_date_with_format(&normalised, format, year)
+        .map_err(|_| anyhow!("Invalid date: {}", date_str))
+}
 
-    NaiveDate::parse_from_str(date_str, ISO_DATE_FORMAT).expect("date parsed")
+/// Parses the date, adding the year or the day when the format has none.
+fn parse_date_with_format(
+    date_str: &str,
+    format: &str,
+    year: i32,
+) -> Result<NaiveDate, chrono::ParseError> {
+    let has_year = format.contains("%Y") || format.contains("%y");
+    let has_day = format.contains("%d") || format.contains("%e");
+
+    match (has_year, has_day) {
+        (true, true) => NaiveDate::parse_from_str(date_str, format),
+        (true, false) => NaiveDate::parse_from_str(&format!("{} 1", date_str), &format!("{} %d", format)),
+        (false, _) => {
+            NaiveDate::parse_from_str(&format!("{} {}", date_str, year), &format!("{} %Y", format))
+        }
+    }
 }
 
 /// Create DateTime from date string only.
@@ -74,20 +141,90 @@ pub fn parse_amount_parts(
     journal: &mut Journal,
 ) -> Option<Amount> {
     // Create Commodity, add to collection
-    let commodity_ptr = journal.commodity_pool.find_or_create(commodity, None);
+    let mut commodity_id = journal.commodity_pool.find_or_create(commodity, None);
+    // The amounts without a symbol use the default commodity, if one is set.
+    if commodity_id.is_none() {
+        commodity_id = journal.commodity_pool.default_commodity();
+    }
 
     if let Some(quantity) = Quantity::from_str(quantity) {
-        Some(Amount::new(quantity, Some(commodity_ptr)))
+        Some(Amount::new(quantity, commodity_id))
     } else {
         None
     }
 }
 
+/// The scope opened by an `apply` directive. It affects all the transactions and postings
+/// until the matching `end apply`.
+#[derive(Debug, Clone)]
+pub(crate) enum ApplyScope {
+    /// `apply account Personal`, the parent account for the postings.
+    Account(String),
+    /// `apply tag hastag` or `apply tag key: value`, the tags added to the transactions.
+    Tag(String),
+    /// `apply fixed CAD $0.90`, the fixed rate for the commodity.
+    Fixed(String, Amount),
+}
+
+impl ApplyScope {
+    fn kind(&self) -> &str {
+        match self {
+            ApplyScope::Account(_) => "account",
+            ApplyScope::Tag(_) => "tag",
+            ApplyScope::Fixed(_, _) => "fixed",
+        }
+    }
+}
+
+/// The syntax of the journal. hledger's reads the tags as `name:value`, while Beancount's
+/// is converted to the journal text, entry by entry, in the order of the dates.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Syntax {
+    #[default]
+    Ledger,
+    Hledger,
+    Beancount,
+}
+
+impl Syntax {
+    /// `--input-format ledger|hledger|beancount`
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.to_lowercase().as_str() {
+            "ledger" => Ok(Self::Ledger),
+            "hledger" => Ok(Self::Hledger),
+            "beancount" => Ok(Self::Beancount),
+            _ => Err(format!("Unknown input format: {}", name)),
+        }
+    }
+
+    /// The syntax for the file extension, `.hledger`, `.beancount` or `.bean`.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "hledger" => Some(Self::Hledger),
+            "beancount" | "bean" => Some(Self::Beancount),
+            "ledger" => Some(Self::Ledger),
+            _ => None,
+        }
+    }
+}
+
 pub(crate) struct Parser<'j, T: Read> {
     pub journal: &'j mut Journal,
 
     reader: BufReader<T>,
     buffer: String,
+    /// The active `apply` directives, the innermost last.
+    apply_stack: Vec<ApplyScope>,
+    /// The name of the source, for the messages.
+    pub(crate) pathname: String,
+    /// The number of the line in the buffer.
+    pub(crate) linenum: usize,
+    /// The year for the dates without one, from the `Y` directive.
+    pub(crate) year: Option<i32>,
+    /// The chain of the including files, from the top one, for the cycle detection.
+    includes: Vec<PathBuf>,
+    pub(crate) syntax: Syntax,
 }
 
 impl<'j, T: Read> Parser<'j, T> {
@@ -100,43 +237,118 @@ impl<'j, T: Read> Parser<'j, T> {
             reader,
             buffer,
             journal,
+            apply_stack: vec![],
+            pathname: String::new(),
+            linenum: 0,
+            year: None,
+            includes: vec![],
+            syntax: Syntax::Ledger,
         }
     }
 
+    /// The source location for the messages, i.e. `"journal.ledger", line 5`.
+    fn location(&self, linenum: usize) -> String {
+        format!("\"{}\", line {}", self.pathname, linenum)
+    }
+
     /// Parse given input.
-    /// Fill the Journal with parsed elements.
-    pub fn parse(&mut self) {
+    /// Fill the Journal with parsed elements. Stops at the first error and returns it with
+    /// the location, i.e. `"journal.ledger", line 5: ...`.
+    pub fn parse(&mut self) -> Result<(), String> {
+        self.try_parse().map_err(|(line, e)| {
+            // Some errors already carry the location.
+            let location = self.location(line);
+            if e.starts_with(&location) {
+                e
+            } else {
+                format!("{}: {}", location, e)
+            }
+        })
+    }
+
+    /// Parses the input until the end or the first error.
+    /// Returns the error with the number of the line where it happened.
+    pub(crate) fn try_parse(&mut self) -> Result<(), (usize, String)> {
+        match self.parse_directives(false).pop() {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+
+    /// Parses the whole input, for the editor. After an error, the rest of the directive is
+    /// skipped and the parsing continues with the next one.
+    /// Returns all the errors, with their line numbers.
+    pub(crate) fn try_parse_all(&mut self) -> Vec<(usize, String)> {
+        self.parse_directives(true)
+    }
+
+    fn parse_directives(&mut self, recover: bool) -> Vec<(usize, String)> {
+        if self.syntax == Syntax::Beancount {
+            return self.parse_beancount().err().into_iter().collect();
+        }
+
+        let mut errors = vec![];
         loop {
-            match self.reader.read_line(&mut self.buffer) {
+            match self.read_line() {
                 Err(err) => {
-                    println!("Error: {:?}", err);
-                    break;
+                    errors.push((self.linenum, err.to_string()));
+                    return errors;
                 }
                 Ok(0) => {
                     // end of file
-                    break;
+                    return errors;
                 }
                 Ok(_) => {
-                    // Remove the trailing newline characters
-                    // let trimmed = &line.trim_end();
-
-                    match self.read_next_directive() {
-                        Ok(_) => (), // continue
-                        Err(err) => {
-                            log::error!("Error: {:?}", err);
-                            println!("Error: {:?}", err);
+                    let Err(err) = self.read_next_directive() else {
+                        continue;
+                    };
+                    errors.push(match err.downcast_ref::<LineError>() {
+                        Some(e) => (e.line, e.message.clone()),
+                        None => (self.linenum, err.to_string()),
+                    });
+                    if !recover {
+                        return errors;
+                    }
+                    // The indented lines of the failed directive.
+                    while self.peek_whitespace_line() {
+                        if !matches!(self.read_line(), Ok(length) if length > 0) {
                             break;
                         }
-                    };
+                    }
                 }
             }
+        }
+    }
 
-            // clear the buffer before reading the next line.
-            self.buffer.clear();
+    /// Reads the Beancount source. The entries, sorted by date, are converted to the
+    /// journal text and parsed one by one, with the lines of the source.
+    fn parse_beancount(&mut self) -> Result<(), (usize, String)> {
+        let mut source = String::new();
+        self.reader
+            .read_to_string(&mut source)
+            .map_err(|e| (self.linenum, e.to_string()))?;
+        let pathname = PathBuf::from(&self.pathname);
+
+        for entry in beancount::read_entries(&source)? {
+            let first = self.journal.xacts.len();
+            let mut parser = self.nested(Cursor::new(entry.text.as_str()), &pathname);
+            parser.syntax = Syntax::Ledger;
+            parser
+                .try_parse()
+                .map_err(|(line, err)| (entry.source_line(line), err))?;
+
+            for xact in &mut self.journal.xacts[first..] {
+                if let Some(pos) = &mut xact.pos {
+                    pos.beg_line = entry.source_line(pos.beg_line);
+                    pos.end_line = entry.source_line(pos.end_line);
+                }
+            }
         }
+
+        Ok(())
     }
 
-    fn read_next_directive(&mut self) -> Result<(), String> {
+    fn read_next_directive(&mut self) -> Result<(), Error> {
         // if self.buffer.is_empty() {
         //     return Ok(());
         // }
@@ -149,7 +361,7 @@ impl<'j, T: Read> Parser<'j, T> {
         // determine what the line is
         match self.buffer.chars().peekable().peek().unwrap() {
             // comments
-            ';' | '#' | '*' | '|' => {
+            c if reader::is_comment_line(*c) => {
                 // ignore
                 return Ok(());
             }
@@ -160,227 +372,613 @@ impl<'j, T: Read> Parser<'j, T> {
 
             '0' | '1' | '2' | '3' | '4' | '5' | '6' | '7' | '8' | '9' => {
                 // Starts with date/number.
-                let _ = self.xact_directive();
+                self.xact_directive()?;
             }
 
             ' ' | '\t' => {
-                todo!("complete")
+                return Err(anyhow!("Unexpected indented line"));
             }
 
             // The rest
             _ => {
                 // 4.7.2 command directives
+                self.general_directive().map_err(Error::msg)?;
+            }
+        }
+
+        Ok(())
+    }
 
-                if self.general_directive() {
-                    return Ok(());
+    /// textual.cc
+    /// bool instance_t::general_directive(char *line)
+    ///
+    /// The directive is read by the [`reader`] and applied to the Journal here.
+    fn general_directive(&mut self) -> Result<(), String> {
+        let input_format = self.journal.input_date_format.clone();
+        let directive = reader::general_directive(self, self.year, input_format.as_deref())
+            .map_err(|e| e.to_string())?;
+
+        match directive {
+            DirectiveType::Account(account) => self.account_directive(account),
+            DirectiveType::Commodity(commodity) => self.commodity_directive(commodity),
+            DirectiveType::Comment(_) => Ok(()),
+            DirectiveType::Include(path) => self.include_directive(&path),
+            DirectiveType::Payee(payee) => self.payee_directive(payee),
+            DirectiveType::Price(price) => self.price_directive(price),
+            DirectiveType::Tag(name) => {
+                self.journal.register_tag(&name);
+                Ok(())
+            }
+            DirectiveType::Year(year) => {
+                self.year = Some(year);
+                Ok(())
+            }
+            DirectiveType::Other { name, argument } => {
+                let required = |argument: &str| require_argument(&name, Some(argument.to_owned()));
+                match name.as_str() {
+                    "alias" => self.journal.add_alias(&required(&argument)?),
+                    "apply" => self.apply_directive(&required(&argument)?),
+                    "bucket" | "A" => self.bucket_directive(&required(&argument)?),
+                    "end" => self.end_apply_directive(&argument),
+                    "fixed" => self.apply_rate_directive(&required(&argument)?),
+                    "import" => self.import_directive(&required(&argument)?),
+                    "C" => self.commodity_conversion_directive(&argument),
+                    "D" => self.default_commodity_directive(&argument),
+                    "N" => self.nomarket_directive(&argument),
+                    _ => Err(format!("Unknown directive '{}'", self.buffer.trim_end())),
                 }
+            }
+            DirectiveType::Xact(_) => unreachable!("the transactions are read by the parser"),
+        }
+    }
 
-                let c = 'P';
-                match c {
-                        // ACDNPY
-                        'P' => {
-                            // a pricing xact
-                            self.price_xact_directive();
-                        }
+    /// textual.cc
+    /// void instance_t::account_directive(char * line)
+    ///
+    /// ```ledger
+    /// account Expenses:Food
+    ///     note This account is all about the chicken!
+    ///     alias food
+    ///     payee ^(KFC|Popeyes)$
+    ///     check commodity == "$"
+    ///     assert abs(amount) <= 100
+    ///     default
+    /// ```
+    fn account_directive(&mut self, directive: AccountDirective) -> Result<(), String> {
+        let parent = apply_account_name(&self.apply_stack);
+        let name = directive.name.as_str();
+        let Some(account_id) = self.journal.register_account_in(name, &parent) else {
+            return Err(format!("Invalid account name {:?}", name));
+        };
+        let fullname = self.journal.get_account(account_id).fullname().to_owned();
+        self.journal.get_account_mut(account_id).known = true;
+        for comment in &directive.comments {
+            self.account_type(account_id, comment)?;
+        }
 
-                        c => {
-                            log::warn!("not handled: {:?}", c);
-                            todo!("handle other directives");
-                        }
-                    }
-                    // TODO: todo!("the rest")
-            }
+        for alias in &directive.aliases {
+            self.journal.add_account_alias(alias, &fullname);
+        }
+        for payee in &directive.payees {
+            self.journal.add_payee_mapping(payee, &fullname)?;
+        }
+        if directive.default {
+            self.journal.bucket = Some(account_id);
+        }
+        let checks = directive.checks.iter().map(|expr| (expr, false));
+        let assertions = directive.assertions.iter().map(|expr| (expr, true));
+        for (expr, is_assertion) in checks.chain(assertions) {
+            let expr = Expr::parse(expr)?;
+            let account = self.journal.get_account_mut(account_id);
+            account.checks.push(AccountCheck::new(expr, is_assertion));
+        }
+        if let Some(note) = directive.note {
+            self.journal.get_account_mut(account_id).note = Some(note);
         }
 
         Ok(())
     }
 
-    /// textual.cc
-    /// bool instance_t::general_directive(char *line)
-    fn general_directive(&mut self) -> bool {
-        // todo: skip if (*p == '@' || *p == '!')
+    /// Sets the type of the account from `type:` in the comment of the declaration.
+    fn account_type(&mut self, account_id: AccountId, comment: &str) -> Result<(), String> {
+        let kind = self
+            .scan_tags(comment)
+            .into_iter()
+            .find(|(tag, _)| *tag == "type")
+            .map(|(_, value)| value.unwrap_or_default());
+        if let Some(kind) = kind {
+            let Some(kind) = AccountType::parse(kind) else {
+                return Err(format!("Invalid account type: {:?}", kind));
+            };
+            self.journal.get_account_mut(account_id).kind = Some(kind);
+        }
+        Ok(())
+    }
+
+    /// The tags of the comment, in the syntax of the journal.
+    fn scan_tags<'a>(&self, note: &'a str) -> Vec<(&'a str, Option<&'a str>)> {
+        match self.syntax {
+            Syntax::Hledger => scanner::scan_hledger_tags(note),
+            _ => scanner::scan_tags(note),
+        }
+    }
 
-        // split directive and argument
-        let mut iter = self.buffer.split_whitespace();
-        let Some(directive) = iter.next() else {
-            panic!("no directive?")
+    /// `commodity EUR` with the indented sub-directives `note`, `format`, `alias`,
+    /// `nomarket` and `default`.
+    ///
+    /// void instance_t::commodity_directive(char * line)
+    fn commodity_directive(&mut self, directive: CommodityDirective) -> Result<(), String> {
+        let pool = &mut self.journal.commodity_pool;
+        let Some(commodity_id) = pool.find_or_create(&directive.symbol, None) else {
+            return Err("Directive 'commodity' requires an argument".to_string());
         };
-        let argument = iter.next();
-
-        // todo: check arguments for directives that require one
-        // match directive {
-        //     "comment" | "end" | "python" | "test" | "year" | "Y" => {
-        //         //
-        //         // Year can also be specified with one letter?
-        //         ()
-        //     }
-        //     _ => {
-        //         panic!("The directive {:?} requires an argument!", directive);
-        //     }
-        // }
+        // The symbol can be an alias.
+        let symbol = pool.get(commodity_id).symbol.to_owned();
+        self.commodity_mut(&symbol).add_flags(COMMODITY_KNOWN);
 
-        match directive.chars().peekable().peek().unwrap() {
-            'a' => {
-                todo!("a");
-            }
+        for alias in &directive.aliases {
+            self.journal.commodity_pool.alias(alias, &symbol);
+        }
+        if let Some(format) = &directive.format {
+            self.commodity_mut(&symbol).set_format(format)?;
+        }
+        if directive.nomarket {
+            self.commodity_mut(&symbol).add_flags(COMMODITY_NOMARKET);
+        }
+        if directive.default {
+            self.journal
+                .commodity_pool
+                .set_default_commodity(commodity_id);
+        }
+        if let Some(note) = directive.note {
+            self.commodity_mut(&symbol).note = Some(note);
+        }
 
-            // bcde
-            'i' => match directive {
-                "include" => {
-                    let own_argument = argument.unwrap().to_owned();
-                    self.include_directive(&own_argument);
-                    return true;
-                }
-                "import" => {
-                    todo!("import directive")
-                }
-                _ => (),
-            },
+        Ok(())
+    }
 
-            // ptvy
-            _ => {
-                todo!("handle")
-            }
+    /// `payee Name` with the indented `alias` sub-directives.
+    ///
+    /// void instance_t::payee_directive(char * line)
+    fn payee_directive(&mut self, directive: PayeeDirective) -> Result<(), String> {
+        self.journal.register_payee(&directive.name);
+        for alias in &directive.aliases {
+            self.journal.add_payee_alias(alias, &directive.name)?;
         }
 
-        // lookup(DIRECTIVE, self.buffer)
+        Ok(())
+    }
 
-        false
+    /// `P 2023-05-01 EUR 1.10 USD`, the market price of the commodity.
+    ///
+    /// void instance_t::price_xact_directive(char * line)
+    fn price_directive(&mut self, directive: PriceDirective) -> Result<(), String> {
+        self.journal
+            .commodity_pool
+            .add_price_directive(&directive)
+            .map_err(|e| e.to_string())
     }
 
-    fn price_xact_directive(&mut self) {
-        // pass on to the commodity pool
+    fn commodity_mut(&mut self, symbol: &str) -> &mut Commodity {
         self.journal
             .commodity_pool
-            .parse_price_directive(&self.buffer);
+            .find_mut(symbol)
+            .expect("commodity created")
+    }
+
+    /// `apply account`, `apply tag`, `apply fixed`
+    ///
+    /// void instance_t::apply_directive(char * line)
+    fn apply_directive(&mut self, argument: &str) -> Result<(), String> {
+        let (kind, rest) = match argument.split_once(char::is_whitespace) {
+            Some((kind, rest)) => (kind, rest.trim()),
+            None => (argument, ""),
+        };
+        if rest.is_empty() {
+            return Err(format!("Directive 'apply {}' requires an argument", kind));
+        }
+
+        match kind {
+            "account" => self.apply_account_directive(rest),
+            "tag" => self.apply_tag_directive(rest),
+            "fixed" | "rate" => self.apply_rate_directive(rest),
+            _ => Err(format!("Unknown 'apply' directive: {}", kind)),
+        }
+    }
+
+    /// void instance_t::apply_account_directive(char * line)
+    fn apply_account_directive(&mut self, name: &str) -> Result<(), String> {
+        // The applied account is registered, so that the name gets validated.
+        let parent = apply_account_name(&self.apply_stack);
+        let Some(account_id) = self.journal.register_account_in(name, &parent) else {
+            return Err(format!("Invalid account name {:?}", name));
+        };
+        // The alias expansion can change the name so store the registered one.
+        let fullname = self.journal.get_account(account_id).fullname().to_owned();
+
+        self.apply_stack.push(ApplyScope::Account(fullname));
+        Ok(())
+    }
+
+    /// void instance_t::apply_tag_directive(char * line)
+    fn apply_tag_directive(&mut self, tag: &str) -> Result<(), String> {
+        let tag = if tag.contains(':') {
+            tag.to_owned()
+        } else {
+            format!(":{}:", tag)
+        };
+
+        self.apply_stack.push(ApplyScope::Tag(tag));
+        Ok(())
+    }
+
+    /// `apply fixed CAD $0.90`
+    ///
+    /// void instance_t::apply_rate_directive(char * line)
+    fn apply_rate_directive(&mut self, argument: &str) -> Result<(), String> {
+        let Some((symbol, price)) = argument.split_once(char::is_whitespace) else {
+            return Err(format!("Invalid fixed rate: {}", argument));
+        };
+        let Some(price) = parse_amount(price.trim(), self.journal) else {
+            return Err(format!("Invalid fixed rate: {}", argument));
+        };
+
+        self.apply_stack
+            .push(ApplyScope::Fixed(symbol.to_owned(), price));
+        Ok(())
+    }
+
+    /// `end`, `end apply`, or `end apply account`
+    ///
+    /// void instance_t::end_apply_directive(char * kind)
+    fn end_apply_directive(&mut self, argument: &str) -> Result<(), String> {
+        let name = match argument.split_once(char::is_whitespace) {
+            Some(("apply", name)) => name.trim(),
+            None if argument.is_empty() || argument == "apply" => "",
+            _ => return Err(format!("Unknown directive 'end {}'", argument)),
+        };
+
+        let Some(scope) = self.apply_stack.last() else {
+            return Err("'end' or 'end apply' found, but no enclosing 'apply' directive".into());
+        };
+        if !name.is_empty() && name != scope.kind() {
+            return Err(format!(
+                "'end apply {}' directive does not match 'apply {}' directive",
+                name,
+                scope.kind()
+            ));
+        }
+
+        self.apply_stack.pop();
+        Ok(())
+    }
+
+    /// The default account, used to balance the transactions with a single posting.
+    ///
+    /// void instance_t::bucket_directive(char * line)
+    fn bucket_directive(&mut self, name: &str) -> Result<(), String> {
+        let parent = apply_account_name(&self.apply_stack);
+        let Some(account_id) = self.journal.register_account_in(name, &parent) else {
+            return Err(format!("Invalid account name {:?}", name));
+        };
+
+        self.journal.bucket = Some(account_id);
+        Ok(())
+    }
+
+    /// `C 1h = 60m`
+    ///
+    /// void instance_t::commodity_conversion_directive(char * line)
+    fn commodity_conversion_directive(&mut self, argument: &str) -> Result<(), String> {
+        let Some((larger, smaller)) = argument.split_once('=') else {
+            return Err(format!("Invalid commodity conversion: {}", argument));
+        };
+        let (Some(larger), Some(smaller)) = (
+            parse_amount(larger.trim(), self.journal),
+            parse_amount(smaller.trim(), self.journal),
+        ) else {
+            return Err(format!("Invalid commodity conversion: {}", argument));
+        };
+        let (Some(larger_id), Some(smaller_id)) = (larger.commodity, smaller.commodity) else {
+            return Err(format!("Invalid commodity conversion: {}", argument));
+        };
+        if larger.quantity.is_zero() {
+            return Err(format!("Invalid commodity conversion: {}", argument));
+        }
+
+        // The smaller unit per one of the larger.
+        let smaller = Amount::new(smaller.quantity / larger.quantity, Some(smaller_id));
+
+        let pool = &mut self.journal.commodity_pool;
+        let larger_commodity = pool.get_mut(larger_id);
+        larger_commodity.smaller = Some(smaller);
+        larger_commodity.add_flags(COMMODITY_NOMARKET);
+        pool.get_mut(smaller_id).larger = Some(Amount::new(Quantity::ONE, Some(larger_id)));
+
+        Ok(())
+    }
+
+    /// `D $1,000.00`, sets the default commodity and its format.
+    ///
+    /// void instance_t::default_commodity_directive(char * line)
+    fn default_commodity_directive(&mut self, argument: &str) -> Result<(), String> {
+        let (tokens, _) = scanner::scan_amount(argument);
+        if tokens.symbol.is_empty() {
+            return Err(format!("Invalid default commodity: {}", argument));
+        }
+        let pool = &mut self.journal.commodity_pool;
+        let commodity_id = pool.find_or_create(tokens.symbol, None).expect("commodity symbol");
+
+        let commodity = pool.get_mut(commodity_id);
+        commodity.set_format(argument)?;
+        commodity.add_flags(COMMODITY_KNOWN);
+        pool.set_default_commodity(commodity_id);
+
+        Ok(())
+    }
+
+    /// `N SYMBOL`, excludes the commodity from the market prices.
+    ///
+    /// void instance_t::nomarket_directive(char * line)
+    fn nomarket_directive(&mut self, symbol: &str) -> Result<(), String> {
+        let symbol = symbol.trim_matches('"');
+        if symbol.is_empty() {
+            return Err("Directive 'N' requires an argument".to_string());
+        }
+        let pool = &mut self.journal.commodity_pool;
+        let commodity_id = pool.find_or_create(symbol, None).expect("commodity symbol");
+
+        pool.get_mut(commodity_id).add_flags(COMMODITY_NOMARKET);
+
+        Ok(())
     }
 
-    fn create_xact(&mut self) -> *const Xact {
+    fn create_xact(&mut self) -> Result<XactId, Error> {
         let tokens = scanner::tokenize_xact_header(&self.buffer);
-        let xact = Xact::create(tokens[0], tokens[1], tokens[2], tokens[3]);
+        let date = self.parse_date(tokens[0])?;
+        let aux_date = self.parse_date(tokens[1])?;
+        let (code, payee) = scanner::tokenize_code(tokens[2]);
+        let mut xact = Xact::create(date, aux_date, &payee, tokens[3]);
+        if self.syntax == Syntax::Hledger {
+            xact.metadata.clear();
+            for (tag, value) in scanner::scan_hledger_tags(tokens[3]) {
+                xact.set_tag(tag, value);
+            }
+        }
+        xact.code = code.map(|code| code.to_owned());
+        xact.payee = self.journal.translate_payee(&xact.payee).to_owned();
 
         // Add xact to the journal
-        let new_ref = self.journal.add_xact(xact);
+        let xact_id = self.journal.add_xact(xact);
 
-        new_ref
+        Ok(xact_id)
+    }
+
+    /// Parses the date with the current year and the input date format.
+    fn parse_date(&self, date_str: &str) -> Result<Option<NaiveDate>, Error> {
+        if date_str.is_empty() {
+            return Ok(None);
+        }
+        let input_format = self.journal.input_date_format.as_deref();
+        let date = parse_date_in(date_str, self.year, input_format)
+            .map_err(|e| anyhow!("{}: {}", self.location(self.linenum), e))?;
+        Ok(Some(date))
     }
 
     fn xact_directive(&mut self) -> Result<(), Error> {
-        let xact_ptr = self.create_xact() as *mut Xact;
+        let xact_id = self.create_xact()?;
+        let xact_line = self.linenum;
+        // The line numbers of the posts, for the messages.
+        let mut post_lines: Vec<usize> = vec![];
+        // The last line that belongs to the transaction.
+        let mut end_line = xact_line;
 
         // Read the Xact contents (Posts, Comments, etc.)
-        // Read until separator (empty line).
-        loop {
-            self.buffer.clear(); // empty the buffer before reading
-            match self.reader.read_line(&mut self.buffer) {
-                Err(e) => {
-                    println!("Error: {:?}", e);
-                    break;
-                }
-                Ok(0) => {
-                    // end of file
-                    log::debug!("0-length buffer");
-                    break;
-                }
-                Ok(_) => {
-                    if self.buffer.is_empty() {
-                        // panic!("Unexpected whitespace at the beginning of line!")
-                        todo!("Check what happens here")
-                    }
+        // The lines are indented. The transaction ends at a blank line or at the next
+        // directive, as in the reader.
+        while self.peek_whitespace_line() {
+            if self.read_line()? == 0 {
+                break;
+            }
+            let input = self.buffer.trim_start();
 
-                    // parse line
-                    match self.buffer.chars().peekable().peek() {
-                        Some(' ') => {
-                            // valid line, starts with space.
-                            let input = self.buffer.trim_start();
-
-                            // if the line is blank after trimming, exit (end the transaction).
-                            if input.is_empty() {
-                                break;
-                            }
-
-                            // Process the Xact content line. Could be a Comment or a Post.
-                            match input.chars().peekable().peek() {
-                                Some(';') => {
-                                    self.parse_trailing_note(xact_ptr);
-                                }
-                                _ => {
-                                    parse_post(input, xact_ptr, &mut self.journal)?;
-                                }
-                            }
-                        }
-                        Some('\r') | Some('\n') => {
-                            // empty line "\r\n". Exit.
-                            break;
-                        }
-                        _ => {
-                            panic!("should not happen")
-                        }
+            // if the line is blank after trimming, exit (end the transaction).
+            if input.is_empty() {
+                break;
+            }
+
+            end_line = self.linenum;
+
+            // Process the Xact content line. Could be a Comment or a Post.
+            match input.chars().peekable().peek() {
+                Some(';') => {
+                    self.parse_trailing_note(xact_id);
+                }
+                _ => {
+                    parse_post(input, xact_id, self.journal, &self.apply_stack, self.year)?;
+                    post_lines.push(self.linenum);
+                    if self.syntax == Syntax::Hledger {
+                        self.hledger_post_tags(xact_id);
                     }
                 }
             }
-
-            // empty the buffer before exiting.
-            self.buffer.clear();
         }
+        self.buffer.clear();
+
+        self.journal.get_xact_mut(xact_id).pos = Some(Position {
+            pathname: self.pathname.clone(),
+            beg_line: xact_line,
+            end_line,
+        });
 
         // "finalize" transaction
-        crate::xact::finalize(xact_ptr, &mut self.journal);
+        crate::xact::finalize(xact_id, self.journal).map_err(|e| anyhow!(e))?;
+
+        apply_tags(self.journal.get_xact_mut(xact_id), &self.apply_stack);
+
+        verify_account_checks(xact_id, self.journal)?;
+
+        self.check_declarations(xact_id, xact_line, &post_lines)?;
+
+        Ok(())
+    }
+
+    /// Reports the undeclared accounts, commodities, payees and tags in `--strict` and
+    /// `--pedantic` modes.
+    fn check_declarations(
+        &self,
+        xact_id: XactId,
+        xact_line: usize,
+        post_lines: &[usize],
+    ) -> Result<(), Error> {
+        if self.journal.checking_style == CheckingStyle::Permissive {
+            return Ok(());
+        }
+
+        let xact = self.journal.get_xact(xact_id);
+        for (post_index, message) in self.journal.find_undeclared(xact) {
+            // The generated posts have no line of their own.
+            let line = post_index
+                .and_then(|i| post_lines.get(i))
+                .copied()
+                .unwrap_or(xact_line);
+            match self.journal.checking_style {
+                CheckingStyle::Error => return Err(LineError { line, message }.into()),
+                _ => log::warn!("{}: {}", self.location(line), message),
+            }
+        }
 
         Ok(())
     }
 
     /// textual.cc
     /// void instance_t::include_directive(char *line)
-    fn include_directive(&mut self, argument: &str) {
-        let mut filename: PathBuf;
+    /// `include other.ledger`. The relative paths are from the directory of the including
+    /// file. The path can start with `~` and can be a glob, i.e. `2023/*.ledger`.
+    ///
+    /// textual.cc: void instance_t::include_directive(char * line)
+    fn include_directive(&mut self, argument: &str) -> Result<(), String> {
+        let files = self.find_files(argument, "include")?;
+
+        // The files being read, from the top one, for the cycle detection.
+        let mut chain = self.includes.clone();
+        if chain.is_empty() {
+            chain.extend(fs::canonicalize(&self.pathname));
+        }
 
-        // if (line[0] != '/' && line[0] != '\\' && line[0] != '~')
-        if argument.starts_with('/') || argument.starts_with('\\') || argument.starts_with('~') {
-            filename = PathBuf::from_str(argument).unwrap();
-        } else {
-            // relative path
-            /*
-            filename = PathBuf::from_str(argument).unwrap();
-            // get the parent path?
-            match filename.parent() {
-                Some(parent) => {
-                    // absolute path
-                    filename = parent.to_path_buf();
-                },
-                None => {
-                    // no parent path, use current directory
-                    filename = env::current_dir().unwrap();
-                },
+        for filename in files {
+            let canonical =
+                fs::canonicalize(&filename).map_err(|e| format!("{}: {}", filename.display(), e))?;
+            if chain.contains(&canonical) {
+                let mut names: Vec<String> =
+                    chain.iter().map(|p| p.display().to_string()).collect();
+                names.push(canonical.display().to_string());
+                return Err(format!("Include cycle: {}", names.join(" -> ")));
             }
-            */
-            filename = env::current_dir().unwrap();
-            filename.push(argument);
+
+            // read file. The included file is within the current `apply` scopes.
+            let file = File::open(&filename).map_err(|e| format!("{}: {}", filename.display(), e))?;
+            let mut parser = self.nested(file, &filename);
+            parser.includes = chain.clone();
+            parser.includes.push(canonical);
+            parser
+                .try_parse()
+                .map_err(|(line, err)| format!("{}: {}", parser.location(line), err))?;
         }
 
-        // TODO: resolve glob, i.e *.ledger
+        Ok(())
+    }
 
-        // let mut file_found = false;
-        let parent_path = filename.parent().unwrap();
-        if parent_path.exists() {
-            if filename.is_file() {
-                // let base = filename.file_name();
+    /// `import statement.csv` or `import csv statement.txt`. The file is converted by the
+    /// importer for its type, or extension, and the result is read as if it was written
+    /// in place of the directive. The paths are as in `include`.
+    fn import_directive(&mut self, argument: &str) -> Result<(), String> {
+        let (kind, pattern) = match argument.split_once(char::is_whitespace) {
+            Some((kind, pattern)) if self.journal.importers.find(kind).is_some() => {
+                (Some(kind), pattern.trim())
+            }
+            _ => (None, argument),
+        };
 
-                // read file.
-                parse_file(filename.to_str().unwrap(), self.journal);
+        for filename in self.find_files(pattern, "import")? {
+            let importer = match kind {
+                Some(kind) => self.journal.importers.find(kind),
+                None => self.journal.importers.find_for_path(&filename),
+            }
+            .ok_or_else(|| format!("No importer for \"{}\"", filename.display()))?;
+            let source = fs::read_to_string(&filename)
+                .map_err(|e| format!("{}: {}", filename.display(), e))?;
+            let text = importer.import(&filename, &source, self.journal)?;
+
+            let first = self.journal.xacts.len();
+            let mut parser = self.nested(Cursor::new(text), &filename);
+            parser.syntax = Syntax::Ledger;
+            parser.try_parse().map_err(|(line, err)| {
+                format!("{} (imported), line {}: {}", filename.display(), line, err)
+            })?;
+            // The positions are in the converted text, not in the file.
+            for xact in &mut self.journal.xacts[first..] {
+                xact.pos = None;
             }
         }
 
-        // if !file_found {
-        //     panic!("Include file not found");
-        // }
+        Ok(())
+    }
+
+    /// The parser for the nested source, within the current `apply` scopes and year.
+    fn nested<S: Read>(&mut self, source: S, filename: &Path) -> Parser<'_, S> {
+        let mut parser = Parser::new(source, self.journal);
+        parser.apply_stack = self.apply_stack.clone();
+        parser.pathname = filename.display().to_string();
+        parser.year = self.year;
+        parser.includes = self.includes.clone();
+        parser.syntax = Syntax::from_path(filename).unwrap_or(self.syntax);
+        parser
+    }
+
+    /// The files for the `include` or `import` argument. The relative paths are from the
+    /// directory of the current file. Expands `~` and the globs.
+    fn find_files(&self, argument: &str, directive: &str) -> Result<Vec<PathBuf>, String> {
+        let home = env::var("HOME").ok();
+        let mut filename = expand_path(argument, home.as_deref());
+        if filename.is_relative() {
+            filename = self.directory().join(filename);
+        }
+
+        let pattern = filename.display().to_string();
+        let files: Vec<PathBuf> = if pattern.contains(['*', '?', '[']) {
+            let paths = glob::glob(&pattern)
+                .map_err(|e| format!("Invalid {} pattern \"{}\": {}", directive, argument, e))?;
+            let mut files: Vec<PathBuf> =
+                paths.filter_map(Result::ok).filter(|p| p.is_file()).collect();
+            files.sort();
+            files
+        } else if filename.is_file() {
+            vec![filename]
+        } else {
+            vec![]
+        };
+        if files.is_empty() {
+            return Err(format!("File to {} was not found: \"{}\"", directive, pattern));
+        }
+        Ok(files)
+    }
+
+    /// The directory of the source, for the relative includes. The current directory for
+    /// the text and stdin.
+    fn directory(&self) -> PathBuf {
+        let path = Path::new(&self.pathname);
+        match path.parent() {
+            Some(parent) if self.pathname != crate::STDIN_PATHNAME => parent.to_path_buf(),
+            _ => env::current_dir().unwrap_or_default(),
+        }
     }
 
     /// Parses the trailing note from the buffer.
-    /// xact_index = The index of the current transaction, being parsed.
+    /// xact_id = The id of the current transaction, being parsed.
     /// The note is added either to the transaction or the last post, based on it's position.
     ///
-    fn parse_trailing_note(&mut self, xact_ptr: *mut Xact) {
+    fn parse_trailing_note(&mut self, xact_id: XactId) {
         // This is a trailing note, and possibly a metadata info tag
         // It is added to the previous element (xact/post).
 
@@ -391,44 +989,170 @@ impl<'j, T: Read> Parser<'j, T> {
             return;
         }
 
-        // let xact = self.journal.xacts.get_mut(xact_index).unwrap();
-        let xact: &mut Xact;
-        unsafe {
-            xact = &mut *xact_ptr;
-        }
+        let tags = self.scan_tags(note);
+        let xact = self.journal.get_xact_mut(xact_id);
         if xact.posts.is_empty() {
             // The first comment. Add to the xact.
             // let xact_mut = self.journal.xacts.get_mut(xact_index).unwrap();
-            xact.add_note(note);
+            xact.note = Some(note.into());
+            for (tag, value) in tags {
+                xact.set_tag(tag, value);
+            }
         } else {
             // Post comment. Add to the previous posting.
             // let last_post_index = xact.posts.last().unwrap();
             let last_post = xact.posts.last_mut().unwrap();
             // let post = self.journal.get_post_mut(*last_post_index);
-            last_post.add_note(note);
+            last_post.note = Some(note.into());
+            for (tag, value) in tags {
+                last_post.set_tag(tag, value);
+            }
+        }
+    }
+
+    /// Reads the tags of the last post's note in hledger's syntax.
+    fn hledger_post_tags(&mut self, xact_id: XactId) {
+        let xact = self.journal.get_xact_mut(xact_id);
+        let Some(post) = xact.posts.last_mut() else {
+            return;
+        };
+        post.metadata.clear();
+        if let Some(note) = post.note.clone() {
+            for (tag, value) in scanner::scan_hledger_tags(&note) {
+                post.set_tag(tag, value);
+            }
         }
     }
 }
 
-/// Parses Post from the buffer, adds it to the Journal and links
-/// to Xact, Account, etc.
-fn parse_post(input: &str, xact_ptr: *const Xact, journal: &mut Journal) -> Result<(), Error> {
-    let tokens = scanner::scan_post(input);
+impl<T: Read> LineSource for Parser<'_, T> {
+    fn read_line(&mut self) -> std::io::Result<usize> {
+        self.buffer.clear();
+        let n = self.reader.read_line(&mut self.buffer)?;
+        if n > 0 {
+            self.linenum += 1;
+        }
+        Ok(n)
+    }
 
-    // TODO: Make this more like Ledger now that we have pointers.
+    fn buffer(&self) -> &str {
+        &self.buffer
+    }
 
-    // Create Account, add to collection
-    let account_ptr = journal.register_account(tokens.account).unwrap();
+    /// bool instance_t::peek_whitespace_line()
+    fn peek_whitespace_line(&mut self) -> bool {
+        match self.reader.fill_buf() {
+            Ok(buf) => matches!(buf.first(), Some(b' ') | Some(b'\t')),
+            Err(_) => false,
+        }
+    }
+}
 
-    // create amount
-    let amount_opt = parse_amount_parts(tokens.quantity, tokens.symbol, journal);
+/// The full name of the innermost applied account, or an empty string.
+fn apply_account_name(apply_stack: &[ApplyScope]) -> String {
+    apply_stack
+        .iter()
+        .rev()
+        .find_map(|scope| match scope {
+            ApplyScope::Account(name) => Some(name.to_owned()),
+            _ => None,
+        })
+        .unwrap_or_default()
+}
 
-    // parse and add annotations.
-    {
-        let annotation = Annotation::parse(
-            tokens.price_date,
-            tokens.price_quantity,
-            tokens.price_commodity,
+/// Adds the tags from the enclosing `apply tag` directives to the transaction and its postings.
+fn apply_tags(xact: &mut Xact, apply_stack: &[ApplyScope]) {
+    for scope in apply_stack {
+        let ApplyScope::Tag(tag) = scope else {
+            continue;
+        };
+        xact.parse_tags(tag);
+        for post in &mut xact.posts {
+            post.parse_tags(tag);
+        }
+    }
+}
+
+/// Returns the argument of a directive, or an error if there is none.
+fn require_argument(directive: &str, argument: Option<String>) -> Result<String, String> {
+    match argument {
+        Some(argument) if !argument.is_empty() => Ok(argument),
+        _ => Err(format!("Directive '{}' requires an argument", directive)),
+    }
+}
+
+/// Evaluates the `check` and `assert` expressions of the accounts used in the transaction.
+/// A failed check is reported as a warning, a failed assertion as an error.
+fn verify_account_checks(xact_id: XactId, journal: &Journal) -> Result<(), Error> {
+    let xact = journal.get_xact(xact_id);
+
+    for post in &xact.posts {
+        let account = journal.get_account(post.account);
+        if account.checks.is_empty() {
+            continue;
+        }
+
+        let commodity = match post.amount.and_then(|amount| amount.get_commodity()) {
+            Some(id) => journal.commodity_pool.get(id).symbol.as_str(),
+            None => "",
+        };
+        let scope = Scope {
+            post,
+            account: account.fullname(),
+            payee: &xact.payee,
+            commodity,
+        };
+        for check in &account.checks {
+            if check.expr.calc(&scope) {
+                continue;
+            }
+            if check.is_assertion {
+                return Err(anyhow!("Transaction assertion failed: {}", check.expr.text));
+            }
+            log::warn!("Transaction check failed: {}", check.expr.text);
+        }
+    }
+
+    Ok(())
+}
+
+/// Parses Post from the buffer, adds it to the Journal and links
+/// to Xact, Account, etc.
+fn parse_post(
+    input: &str,
+    xact_id: XactId,
+    journal: &mut Journal,
+    apply_stack: &[ApplyScope],
+    year: Option<i32>,
+) -> Result<(), Error> {
+    let tokens = scanner::scan_post(input);
+
+    // Create Account, add to collection
+    let parent = apply_account_name(apply_stack);
+    let Some(mut account_id) = journal.register_account_in(tokens.account, &parent) else {
+        return Err(anyhow!("Invalid account name {:?}", tokens.account));
+    };
+
+    // If the account name being registered is "Unknown", check whether
+    // the payee indicates an account that should be used.
+    if journal.get_account(account_id).name == "Unknown" {
+        let payee = &journal.get_xact(xact_id).payee;
+        if let Some(account_name) = journal.find_account_for_payee(payee) {
+            let account_name = account_name.to_owned();
+            account_id = journal.register_account(&account_name).unwrap();
+        }
+    }
+
+    // create amount
+    let amount_opt = parse_amount_parts(tokens.quantity, tokens.symbol, journal);
+
+    // parse and add annotations.
+    {
+        let annotation = Annotation::parse(
+            tokens.price_date,
+            tokens.price_quantity,
+            tokens.price_commodity,
+            year,
             journal,
         )?;
 
@@ -442,36 +1166,114 @@ fn parse_post(input: &str, xact_ptr: *const Xact, journal: &mut Journal) -> Resu
     }
 
     // handle cost (2nd amount)
-    let cost_option = parse_cost(&tokens, &amount_opt, journal);
+    let mut cost_option = parse_cost(&tokens, &amount_opt, journal);
+
+    // Without an explicit cost, use the fixed rate from `apply fixed`, if any.
+    let mut cost_fixated = false;
+    if cost_option.is_none() && !tokens.symbol.is_empty() {
+        let fixed_rate = apply_stack.iter().rev().find_map(|scope| match scope {
+            ApplyScope::Fixed(symbol, price) if symbol == tokens.symbol => Some(*price),
+            _ => None,
+        });
+        if let (Some(price), Some(amount)) = (fixed_rate, amount_opt) {
+            cost_option = Some(price * amount);
+            cost_fixated = true;
+        }
+    }
+
+    // balance assertion or assignment
+    let mut assertion = None;
+    let amount_opt = match tokens.assertion_quantity.is_empty() {
+        true => amount_opt,
+        false => {
+            let (amount, checked) = assert_balance(&tokens, account_id, amount_opt, journal)?;
+            assertion = Some(checked);
+            amount
+        }
+    };
 
     // note
-    // TODO: parse note
-    let note = None;
+    let note = if tokens.note.is_empty() {
+        None
+    } else {
+        Some(tokens.note)
+    };
 
     // Create Post, link Xact, Account, Commodity
-    let post_ref: &Post;
-    {
-        let post: Post;
-        post = Post::new(account_ptr, xact_ptr, amount_opt, cost_option, note);
+    let mut post = Post::new(account_id, xact_id, amount_opt, cost_option, note);
+    post.cost_fixated = cost_fixated;
+    post.assertion = assertion;
+
+    // add Post to Xact and Account.posts
+    journal.add_post(xact_id, post);
+
+    Ok(())
+}
+
+/// textual.cc: the balance assertion in instance_t::parse_post
+/// `Assets  10 EUR = 100 EUR` checks the balance of the account, in the commodity, after the
+/// posting. Without the amount, `Assets  = 100 EUR` assigns the balance, and the amount of
+/// the posting is the difference. hledger's `==` also checks that there are no other
+/// commodities, and `=*` includes the sub-accounts.
+/// Returns the amount of the posting and the assertion.
+fn assert_balance(
+    tokens: &PostTokens,
+    account_id: AccountId,
+    amount: Option<Amount>,
+    journal: &mut Journal,
+) -> Result<(Option<Amount>, Assertion), Error> {
+    let Some(expected) =
+        parse_amount_parts(tokens.assertion_quantity, tokens.assertion_symbol, journal)
+    else {
+        return Err(anyhow!("Invalid balance assertion {:?}", tokens.assertion_quantity));
+    };
+    let commodity = expected.get_commodity();
 
-        // add Post to Xact.
-        // let xact = journal.xacts.get_mut(xact_ptr).unwrap();
-        let xact: &mut Xact;
-        unsafe {
-            xact = &mut *(xact_ptr.cast_mut());
+    let account = journal.get_account(account_id);
+    let mut balance = match tokens.assertion_inclusive {
+        true => account.total(journal),
+        false => account.amount(journal),
+    };
+    if let Some(amount) = &amount {
+        balance.add(amount);
+    }
+    let total = balance
+        .amounts
+        .iter()
+        .find(|amount| amount.get_commodity() == commodity)
+        .map_or(Quantity::ZERO, |amount| amount.quantity);
+
+    let mut difference = expected.quantity;
+    difference -= total;
+    let difference = Amount::new(difference, commodity);
+
+    if tokens.assertion_total {
+        let other = balance
+            .amounts
+            .iter()
+            .find(|amount| amount.get_commodity() != commodity && !amount.is_zero());
+        if let Some(other) = other {
+            return Err(anyhow!(
+                "Balance assertion failed, the account also has {}",
+                other.display(&journal.commodity_pool)
+            ));
         }
-        // xact.post_indices.push(post_index);
-        post_ref = xact.add_post(post);
     }
 
-    // add Post to Account.posts
-    {
-        //let account = journal.accounts.get_mut(account_ptr).unwrap();
-        let account = journal.get_account_mut(account_ptr);
-        account.posts.push(post_ref);
+    let assertion = Assertion {
+        amount: expected,
+        inclusive: tokens.assertion_inclusive,
+        total: tokens.assertion_total,
+    };
+    match amount {
+        None => Ok((Some(difference), assertion)),
+        Some(_) if difference.is_zero() => Ok((amount, assertion)),
+        Some(_) => Err(anyhow!(
+            "Balance assertion off by {} (expected to see {})",
+            difference.display(&journal.commodity_pool),
+            expected.display(&journal.commodity_pool)
+        )),
     }
-
-    Ok(())
 }
 
 fn parse_cost(
@@ -516,10 +1318,9 @@ mod tests {
 
         let mut parser = Parser::new(source, &mut journal);
 
-        parser.parse();
-        //parser.general_directive();
+        parser.parse().unwrap();
 
-        todo!("assert")
+        assert_eq!(1, journal.xacts.len());
     }
 
     /// A transaction record, after which comes a line with spaces only.
@@ -537,10 +1338,53 @@ mod tests {
         let mut parser = Parser::new(source, &mut journal);
 
         // Act
-        parser.parse();
+        parser.parse().unwrap();
 
         // Assert
-        assert_eq!(3, journal.master.flatten_account_tree().len());
+        assert_eq!(3, journal.master().flatten_account_tree(&journal).len());
+    }
+}
+
+#[cfg(test)]
+mod date_tests {
+    use chrono::NaiveDate;
+
+    use super::parse_date_in;
+
+    fn ymd(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn test_separators() {
+        assert_eq!(ymd(2019, 5, 1), parse_date_in("2019-05-01", None, None).unwrap());
+        assert_eq!(ymd(2019, 5, 1), parse_date_in("2019/05/01", None, None).unwrap());
+        assert_eq!(ymd(2019, 5, 1), parse_date_in("2019.05.01", None, None).unwrap());
+        assert_eq!(ymd(2019, 5, 1), parse_date_in("2019/5/1", None, None).unwrap());
+    }
+
+    #[test]
+    fn test_short_dates() {
+        assert_eq!(ymd(2019, 5, 1), parse_date_in("19/05/01", None, None).unwrap());
+        assert_eq!(ymd(2019, 5, 1), parse_date_in("2019/05", None, None).unwrap());
+        assert_eq!(ymd(2023, 5, 1), parse_date_in("05/01", Some(2023), None).unwrap());
+    }
+
+    #[test]
+    fn test_input_date_format() {
+        let format = Some("%d.%m.%Y");
+
+        assert_eq!(ymd(2019, 1, 5), parse_date_in("05.01.2019", None, format).unwrap());
+        // The other formats still work.
+        assert_eq!(ymd(2019, 5, 1), parse_date_in("2019-05-01", None, format).unwrap());
+        // A format without the year.
+        assert_eq!(ymd(2020, 1, 5), parse_date_in("05.01", Some(2020), Some("%d.%m")).unwrap());
+    }
+
+    #[test]
+    fn test_invalid_date() {
+        assert!(parse_date_in("2019-13-01", None, None).is_err());
+        assert!(parse_date_in("yesterday", None, None).is_err());
     }
 }
 
@@ -561,7 +1405,7 @@ mod full_tests {
         let mut journal = Journal::new();
 
         // Act
-        super::read_into_journal(cursor, &mut journal);
+        super::read_into_journal(cursor, &mut journal).unwrap();
 
         // Assert
         assert_eq!(1, journal.xacts.len());
@@ -590,7 +1434,7 @@ mod full_tests {
         let mut journal = Journal::new();
 
         // Act
-        read_into_journal(cursor, &mut journal);
+        read_into_journal(cursor, &mut journal).unwrap();
 
         // Assert
         assert_eq!(2, journal.commodity_pool.commodities.len());
@@ -617,7 +1461,7 @@ mod parser_tests {
         let mut journal = Journal::new();
 
         // Act
-        parser::read_into_journal(cursor, &mut journal);
+        parser::read_into_journal(cursor, &mut journal).unwrap();
 
         // Assert
 
@@ -648,7 +1492,7 @@ mod parser_tests {
         let cursor = Cursor::new(input);
         let mut journal = Journal::new();
 
-        super::read_into_journal(cursor, &mut journal);
+        super::read_into_journal(cursor, &mut journal).unwrap();
 
         // Assert
         // Xact
@@ -684,7 +1528,7 @@ mod parser_tests {
         let mut journal = Journal::new();
 
         // Act
-        read_into_journal(cursor, &mut journal);
+        read_into_journal(cursor, &mut journal).unwrap();
 
         // Assert
 
@@ -697,17 +1541,17 @@ mod parser_tests {
         let p1 = &posts[0];
         let account = journal.get_account(p1.account);
         assert_eq!("Investment", account.name);
-        let parent = journal.get_account(account.parent);
+        let parent = journal.get_account(account.parent.unwrap());
         assert_eq!("Assets", parent.name);
         // amount
         let Some(a1) = &p1.amount else { panic!() };
         assert_eq!("20", a1.quantity.to_string());
-        let comm1 = a1.get_commodity().unwrap();
+        let comm1 = journal.commodity_pool.get(a1.get_commodity().unwrap());
         assert_eq!("VEUR", comm1.symbol);
         let Some(ref cost1) = p1.cost else { panic!() };
         // cost
-        assert_eq!(200, cost1.quantity.into());
-        assert_eq!("EUR", cost1.get_commodity().unwrap().symbol);
+        assert_eq!(200, Into::<i32>::into(cost1.quantity));
+        assert_eq!("EUR", journal.commodity_pool.get(cost1.get_commodity().unwrap()).symbol);
 
         // post 2
         let p2 = &posts[1];
@@ -715,7 +1559,7 @@ mod parser_tests {
         // amount
         let Some(a2) = &p2.amount else { panic!() };
         assert_eq!("-200", a2.quantity.to_string());
-        let comm2 = a2.get_commodity().unwrap();
+        let comm2 = journal.commodity_pool.get(a2.get_commodity().unwrap());
         assert_eq!("EUR", comm2.symbol);
 
         assert!(p2.cost.is_none());
@@ -735,13 +1579,13 @@ mod parser_tests {
         let mut journal = Journal::new();
 
         // Act
-        read_into_journal(cursor, &mut journal);
+        read_into_journal(cursor, &mut journal).unwrap();
 
         // Assert
         let xact = &journal.xacts[0];
         assert_eq!(1, journal.xacts.len());
         assert_eq!(4, xact.posts.len());
-        assert_eq!(7, journal.master.flatten_account_tree().len());
+        assert_eq!(7, journal.master().flatten_account_tree(&journal).len());
         assert_eq!(2, journal.commodity_pool.commodities.len());
     }
 }
@@ -759,11 +1603,11 @@ mod posting_parsing_tests {
         let mut j = Journal::new();
 
         // Act
-        parse_file(file_path, &mut j);
+        parse_file(file_path, &mut j).unwrap();
 
         // Assert
         assert_eq!(1, j.xacts.len());
-        assert_eq!(4, j.master.flatten_account_tree().len());
+        assert_eq!(4, j.master().flatten_account_tree(&j).len());
         assert_eq!(2, j.commodity_pool.commodities.len());
         // price
         assert_eq!(2, j.commodity_pool.commodity_history.node_count());
@@ -787,7 +1631,7 @@ mod posting_parsing_tests {
         let mut j = Journal::new();
 
         // Act
-        parse_file(file_path, &mut j);
+        parse_file(file_path, &mut j).unwrap();
 
         // Assert the price of "10 VEUR @ 12.75 EUR" must to be 127.50 EUR
         let xact = j.xacts.get(0).unwrap();
@@ -795,8 +1639,8 @@ mod posting_parsing_tests {
         let cost = post.cost.unwrap();
         assert_eq!(cost.quantity, 127.5.into());
 
-        let eur = j.commodity_pool.find("EUR").unwrap();
-        assert_eq!(cost.commodity, eur);
+        let eur = j.commodity_pool.find_id("EUR").unwrap();
+        assert_eq!(cost.commodity, Some(eur));
     }
 
     #[test]
@@ -809,7 +1653,7 @@ mod posting_parsing_tests {
         let journal = &mut Journal::new();
         let mut parser = Parser::new(Cursor::new(input), journal);
 
-        parser.parse();
+        parser.parse().unwrap();
 
         // Assert
         assert!(journal.xacts[0].note.is_some());
@@ -829,7 +1673,7 @@ mod posting_parsing_tests {
         let journal = &mut Journal::new();
         let mut parser = Parser::new(Cursor::new(input), journal);
 
-        parser.parse();
+        parser.parse().unwrap();
 
         // Assert
         let xact = &journal.xacts[0];
@@ -842,11 +1686,13 @@ mod posting_parsing_tests {
 #[cfg(test)]
 mod amount_parsing_tests {
     use super::Amount;
-    use crate::{amount::Quantity, journal::Journal, parser::parse_post, xact::Xact};
+    use chrono::NaiveDate;
+
+    use crate::{amount::Quantity, journal::Journal, parser::parse_post, xact::{Xact, XactId}};
 
     fn setup() -> Journal {
         let mut journal = Journal::new();
-        let xact = Xact::create("2023-05-02", "", "Supermarket", "");
+        let xact = Xact::create(NaiveDate::from_ymd_opt(2023, 5, 2), None, "Supermarket", "");
         journal.add_xact(xact);
 
         journal
@@ -872,13 +1718,13 @@ mod amount_parsing_tests {
     fn test_pos_w_commodity_separated() {
         const SYMBOL: &str = "EUR";
         let mut journal = setup();
-        let eur_ptr = journal.commodity_pool.create(SYMBOL, None);
-        let xact_ptr = &journal.xacts[0] as *const Xact;
-        let expected = Amount::new(20.into(), Some(eur_ptr));
+        let eur = journal.commodity_pool.create(SYMBOL, None);
+        let xact_id = XactId(0);
+        let expected = Amount::new(20.into(), Some(eur));
 
         // Act
 
-        let _ = parse_post("  Assets  20 EUR", xact_ptr, &mut journal);
+        let _ = parse_post("  Assets  20 EUR", xact_id, &mut journal, &[], None);
 
         // Assert
         let xact = &journal.xacts[0];
@@ -889,7 +1735,7 @@ mod amount_parsing_tests {
         assert_eq!(expected, *amount);
 
         // commodity
-        let c = amount.get_commodity().unwrap();
+        let c = journal.commodity_pool.get(amount.get_commodity().unwrap());
         assert_eq!("EUR", c.symbol);
     }
 
@@ -897,12 +1743,12 @@ mod amount_parsing_tests {
     fn test_neg_commodity_separated() {
         const SYMBOL: &str = "EUR";
         let mut journal = setup();
-        let eur_ptr = journal.commodity_pool.create(SYMBOL, None);
-        let expected = Amount::new((-20).into(), Some(eur_ptr));
-        let xact_ptr = &journal.xacts[0] as *const Xact;
+        let eur = journal.commodity_pool.create(SYMBOL, None);
+        let expected = Amount::new((-20).into(), Some(eur));
+        let xact_id = XactId(0);
 
         // Act
-        let _ = parse_post("  Assets  -20 EUR", xact_ptr, &mut journal);
+        let _ = parse_post("  Assets  -20 EUR", xact_id, &mut journal, &[], None);
 
         // Assert
         let xact = &journal.xacts[0];
@@ -910,7 +1756,7 @@ mod amount_parsing_tests {
         let Some(amt) = &post.amount else { panic!() };
         assert_eq!(&expected, amt);
 
-        let commodity = amt.get_commodity().unwrap();
+        let commodity = journal.commodity_pool.get(amt.get_commodity().unwrap());
         assert_eq!("EUR", commodity.symbol);
     }
 
@@ -918,10 +1764,10 @@ mod amount_parsing_tests {
     fn test_full_w_commodity_separated() {
         // Arrange
         let mut journal = setup();
-        let xact_ptr = &journal.xacts[0] as *const Xact;
+        let xact_id = XactId(0);
 
         // Act
-        let _ = parse_post("  Assets  -20000.00 EUR", xact_ptr, &mut journal);
+        let _ = parse_post("  Assets  -20000.00 EUR", xact_id, &mut journal, &[], None);
         let xact = &journal.xacts[0];
         let post = xact.posts.first().unwrap();
         let Some(ref amount) = post.amount else {
@@ -930,17 +1776,17 @@ mod amount_parsing_tests {
 
         // Assert
         assert_eq!("-20000.00", amount.quantity.to_string());
-        assert_eq!("EUR", amount.get_commodity().unwrap().symbol);
+        assert_eq!("EUR", journal.commodity_pool.get(amount.get_commodity().unwrap()).symbol);
     }
 
     #[test]
     fn test_full_commodity_first() {
         // Arrange
         let mut journal = setup();
-        let xact_ptr = &journal.xacts[0] as *const Xact;
+        let xact_id = XactId(0);
 
         // Act
-        let _ = parse_post("  Assets  A$-20000.00", xact_ptr, &mut journal);
+        let _ = parse_post("  Assets  A$-20000.00", xact_id, &mut journal, &[], None);
         let xact = &journal.xacts[0];
         let post = xact.posts.first().unwrap();
         let Some(ref amount) = post.amount else {
@@ -949,7 +1795,7 @@ mod amount_parsing_tests {
 
         // Assert
         assert_eq!("-20000.00", amount.quantity.to_string());
-        assert_eq!("A$", amount.get_commodity().unwrap().symbol);
+        assert_eq!("A$", journal.commodity_pool.get(amount.get_commodity().unwrap()).symbol);
     }
 
     #[test]
@@ -1000,3 +1846,704 @@ mod amount_parsing_tests {
         // assert_eq!(None, actual.commodity);
     }
 }
+
+#[cfg(test)]
+mod directive_tests {
+    use std::io::{BufRead, Cursor};
+
+    use super::Parser;
+    use crate::{
+        commodity::{COMMODITY_KNOWN, COMMODITY_NOMARKET},
+        journal::{CheckingStyle, Journal},
+        parse_text,
+    };
+
+    #[test]
+    fn test_account_directive() {
+        let input = r#"account Expenses:Food
+    note This account is all about the chicken!
+    alias food
+    payee ^(KFC|Popeyes)$
+    check commodity == "EUR"
+    assert abs(amount) <= 100
+
+2023-05-01 Supermarket
+    food  20 EUR
+    Assets:Cash
+"#;
+        let mut journal = Journal::new();
+
+        parse_text(input, &mut journal).unwrap();
+
+        let food = journal.find_account("Expenses:Food").unwrap();
+        assert_eq!(
+            Some("This account is all about the chicken!".to_string()),
+            food.note
+        );
+        assert_eq!(2, food.checks.len());
+        assert!(!food.checks[0].is_assertion);
+        assert!(food.checks[1].is_assertion);
+        // alias
+        assert!(journal.find_account("food").is_none());
+        assert_eq!(1, food.posts.len());
+        // payee
+        assert_eq!(
+            Some("Expenses:Food"),
+            journal.find_account_for_payee("KFC")
+        );
+    }
+
+    #[test]
+    fn test_account_directive_followed_by_xact() {
+        let input = r#"account Assets:Cash
+2023-05-01 Supermarket
+    Expenses:Food  20 EUR
+    Assets:Cash
+"#;
+        let mut journal = Journal::new();
+
+        parse_text(input, &mut journal).unwrap();
+
+        assert_eq!(1, journal.xacts.len());
+        assert_eq!(5, journal.master().flatten_account_tree(&journal).len());
+    }
+
+    #[test]
+    fn test_account_empty_comments() {
+        let input = "account Expenses:Food\n    ;\n    #\n    alias food\n";
+        let mut journal = Journal::new();
+
+        parse_text(input, &mut journal).unwrap();
+
+        assert!(journal.find_account("Expenses:Food").is_some());
+        assert!(journal.find_account("food").is_none());
+    }
+
+    #[test]
+    fn test_xact_followed_by_directive() {
+        let input = "2023-05-01 Shop\n\tExpenses:Food  20 EUR\n\tAssets:Cash\naccount Assets:Bank\n";
+        let mut journal = Journal::new();
+
+        parse_text(input, &mut journal).unwrap();
+
+        assert_eq!(2, journal.xacts[0].posts.len());
+        assert!(journal.find_account("Assets:Bank").unwrap().known);
+    }
+
+    #[test]
+    fn test_account_payee_for_unknown() {
+        let input = r#"account Expenses:Food
+    payee ^KFC$
+
+2023-05-01 KFC
+    Expenses:Unknown  20 EUR
+    Assets:Cash
+"#;
+        let mut journal = Journal::new();
+
+        parse_text(input, &mut journal).unwrap();
+
+        let food = journal.find_account("Expenses:Food").unwrap();
+        assert_eq!(1, food.posts.len());
+        let unknown = journal.find_account("Expenses:Unknown").unwrap();
+        assert!(unknown.posts.is_empty());
+    }
+
+    #[test]
+    fn test_account_default() {
+        let input = r#"account Assets:Cash
+    default
+
+2023-05-01 Supermarket
+    Expenses:Food  20 EUR
+"#;
+        let mut journal = Journal::new();
+
+        parse_text(input, &mut journal).unwrap();
+
+        let xact = &journal.xacts[0];
+        assert_eq!(2, xact.posts.len());
+        let cash = journal.get_account(xact.posts[1].account);
+        assert_eq!("Assets:Cash", cash.fullname());
+        assert_eq!(
+            "-20",
+            xact.posts[1].amount.unwrap().quantity.to_string()
+        );
+    }
+
+    #[test]
+    fn test_account_assertion_fails() {
+        let input = r#"account Expenses:Food
+    assert abs(amount) <= 100
+
+2023-05-01 Supermarket
+    Expenses:Food  200 EUR
+    Assets:Cash
+
+2023-05-02 Supermarket
+    Expenses:Food  20 EUR
+    Assets:Cash
+"#;
+        let mut journal = Journal::new();
+        let mut parser = Parser::new(Cursor::new(input), &mut journal);
+
+        let actual = parser.parse();
+
+        assert_eq!(
+            "\"\", line 6: Transaction assertion failed: abs(amount) <= 100",
+            actual.unwrap_err()
+        );
+        // Parsing stops at the failed assertion.
+        assert_eq!(1, journal.xacts.len());
+    }
+
+    #[test]
+    fn test_alias_directive() {
+        let input = r#"alias checking=Assets:Bank:Checking
+
+2023-05-01 Supermarket
+    Expenses:Food  20 EUR
+    checking
+"#;
+        let mut journal = Journal::new();
+
+        parse_text(input, &mut journal).unwrap();
+
+        let account = journal.get_account(journal.xacts[0].posts[1].account);
+        assert_eq!("Assets:Bank:Checking", account.fullname());
+        assert!(journal.find_account("checking").is_none());
+    }
+
+    #[test]
+    fn test_unknown_account_sub_directive() {
+        let input = r#"account Expenses:Food
+    colour blue
+"#;
+        let mut journal = Journal::new();
+        let mut parser = Parser::new(Cursor::new(input), &mut journal);
+        parser.buffer = "account Expenses:Food\n".to_string();
+        parser.reader.read_line(&mut String::new()).unwrap();
+
+        let actual = parser.general_directive();
+
+        assert_eq!(
+            Err("Unknown account directive 'colour'".to_string()),
+            actual
+        );
+    }
+
+    #[test]
+    fn test_apply_account() {
+        let input = r#"apply account Personal
+2023-05-01 Supermarket
+    Expenses:Food  20 EUR
+    Assets:Cash
+
+end apply account
+
+2023-05-02 Supermarket
+    Expenses:Food  20 EUR
+    Assets:Cash
+"#;
+        let mut journal = Journal::new();
+
+        parse_text(input, &mut journal).unwrap();
+
+        let account = journal.get_account(journal.xacts[0].posts[0].account);
+        assert_eq!("Personal:Expenses:Food", account.fullname());
+        let account = journal.get_account(journal.xacts[1].posts[0].account);
+        assert_eq!("Expenses:Food", account.fullname());
+    }
+
+    #[test]
+    fn test_apply_account_with_include() {
+        let input = r#"apply account Personal:Alice
+include tests/basic.ledger
+end apply
+"#;
+        let mut journal = Journal::new();
+
+        parse_text(input, &mut journal).unwrap();
+
+        assert_eq!(1, journal.xacts.len());
+        assert!(journal.find_account("Personal:Alice:Expenses:Food").is_some());
+        assert!(journal.find_account("Expenses").is_none());
+    }
+
+    #[test]
+    fn test_apply_tag() {
+        let input = r#"apply tag trip
+apply tag Location: Vienna
+2023-05-01 Supermarket
+    Expenses:Food  20 EUR ; :food:
+    Assets:Cash
+
+end apply tag
+end apply tag
+"#;
+        let mut journal = Journal::new();
+
+        parse_text(input, &mut journal).unwrap();
+
+        let xact = &journal.xacts[0];
+        assert!(xact.has_tag("trip"));
+        assert_eq!(Some("Vienna"), xact.get_tag("Location"));
+        assert!(xact.posts[0].has_tag("trip"));
+        assert!(xact.posts[0].has_tag("food"));
+        assert!(xact.posts[1].has_tag("trip"));
+        assert!(!xact.posts[1].has_tag("food"));
+    }
+
+    #[test]
+    fn test_apply_fixed() {
+        let input = r#"apply fixed CAD $0.90
+2023-05-01 Store
+    Expenses:Food  10 CAD
+    Assets:Cash
+
+end apply fixed
+"#;
+        let mut journal = Journal::new();
+
+        parse_text(input, &mut journal).unwrap();
+
+        let post = &journal.xacts[0].posts[0];
+        assert!(post.cost_fixated);
+        assert_eq!("9.00", post.cost.unwrap().quantity.to_string());
+        // The fixed rate does not record a market price.
+        assert_eq!(0, journal.commodity_pool.commodity_history.edge_count());
+    }
+
+    #[test]
+    fn test_bucket_directive() {
+        let input = r#"bucket Assets:Checking
+2023-05-01 Supermarket
+    Expenses:Food  20 EUR
+"#;
+        let mut journal = Journal::new();
+
+        parse_text(input, &mut journal).unwrap();
+
+        let xact = &journal.xacts[0];
+        assert_eq!(2, xact.posts.len());
+        let account = journal.get_account(xact.posts[1].account);
+        assert_eq!("Assets:Checking", account.fullname());
+    }
+
+    #[test]
+    fn test_balance_assertion() {
+        let input = r#"2023-05-01 Salary
+    Assets:Bank  100 EUR = 100 EUR
+    Income
+
+2023-05-02 Shop
+    Expenses  20 EUR
+    Assets:Bank  = 80 EUR
+
+2023-05-31 Statement
+    Assets:Bank  0 EUR = 80 EUR
+"#;
+        let mut journal = Journal::new();
+
+        parse_text(input, &mut journal).unwrap();
+
+        let amount = journal.xacts[1].posts[1].amount.unwrap();
+        assert_eq!(crate::amount::Quantity::from(-20), amount.quantity);
+        assert_eq!(1, journal.xacts[2].posts.len());
+    }
+
+    #[test]
+    fn test_balance_assertion_fails() {
+        let input = "2023-05-01 Salary\n    Assets:Bank  100 EUR = 90 EUR\n    Income\n";
+        let mut journal = Journal::new();
+
+        let actual = Parser::new(Cursor::new(input), &mut journal).try_parse();
+
+        let (line, message) = actual.unwrap_err();
+        assert_eq!(2, line);
+        assert_eq!("Balance assertion off by -10 EUR (expected to see 90 EUR)", message);
+    }
+
+    #[test]
+    fn test_end_apply_mismatch() {
+        let input = "apply account Personal\n";
+        let mut journal = Journal::new();
+        let mut parser = Parser::new(Cursor::new(input), &mut journal);
+        parser.buffer = input.to_string();
+        parser.reader.read_line(&mut String::new()).unwrap();
+        parser.general_directive().unwrap();
+
+        parser.buffer = "end apply tag\n".to_string();
+        let actual = parser.general_directive();
+
+        assert_eq!(
+            Err("'end apply tag' directive does not match 'apply account' directive".to_string()),
+            actual
+        );
+
+        parser.buffer = "end\n".to_string();
+        assert!(parser.general_directive().is_ok());
+        assert_eq!(
+            Err("'end' or 'end apply' found, but no enclosing 'apply' directive".to_string()),
+            parser.general_directive()
+        );
+    }
+
+    #[test]
+    fn test_end_unknown() {
+        let input = "apply account Personal\nend comment\n";
+        let mut journal = Journal::new();
+        let mut parser = Parser::new(Cursor::new(input), &mut journal);
+
+        let actual = parser.try_parse();
+
+        assert_eq!(Err((2, "Unknown directive 'end comment'".to_string())), actual);
+    }
+
+    #[test]
+    fn test_comment_blocks() {
+        let input = r#"comment
+2023-05-01 Not a transaction
+    end apply
+end comment
+test
+2023-05-02 Neither
+end test
+
+2023-05-03 Shop
+    Expenses:Food  20 EUR
+    Assets:Cash
+"#;
+        let mut journal = Journal::new();
+
+        parse_text(input, &mut journal).unwrap();
+
+        assert_eq!(1, journal.xacts.len());
+        assert_eq!("Shop", journal.xacts[0].payee);
+    }
+
+    #[test]
+    fn test_commodity_directive() {
+        let input = r#"commodity EUR
+    note Euro
+    format 1.000,00 EUR
+    alias €
+    nomarket
+    default
+
+2023-05-01 Supermarket
+    Expenses:Food  €20
+    Assets:Cash  -20
+
+2023-05-02 Broker
+    Assets:Investments  10 EUR @ 1.10 USD
+    Assets:Cash
+"#;
+        let mut journal = Journal::new();
+
+        parse_text(input, &mut journal).unwrap();
+
+        let eur = journal.commodity_pool.find("EUR").unwrap();
+        assert_eq!(Some("Euro".to_string()), eur.note);
+        assert_eq!(2, eur.precision);
+        assert!(eur.has_flags(COMMODITY_KNOWN | COMMODITY_NOMARKET));
+        assert_eq!(Some(eur), journal.commodity_pool.find("€"));
+        let eur_id = journal.commodity_pool.find_id("EUR");
+        assert_eq!(eur_id, journal.commodity_pool.default_commodity());
+        // The alias and the default commodity.
+        let xact = &journal.xacts[0];
+        let pool = &journal.commodity_pool;
+        assert_eq!("20,00 EUR", xact.posts[0].amount.unwrap().display(pool).to_string());
+        assert_eq!("-20,00 EUR", xact.posts[1].amount.unwrap().display(pool).to_string());
+        // No market price is recorded for a `nomarket` commodity.
+        assert_eq!(0, journal.commodity_pool.commodity_history.edge_count());
+    }
+
+    #[test]
+    fn test_unknown_commodity_sub_directive() {
+        let input = "commodity EUR\n    value market\n";
+        let mut journal = Journal::new();
+        let mut parser = Parser::new(Cursor::new(input), &mut journal);
+        parser.buffer = "commodity EUR\n".to_string();
+        parser.reader.read_line(&mut String::new()).unwrap();
+
+        let actual = parser.general_directive();
+
+        assert_eq!(
+            Err("Unknown commodity directive 'value'".to_string()),
+            actual
+        );
+    }
+
+    #[test]
+    fn test_pedantic_unknown_account() {
+        let input = r#"2023-05-01 Supermarket
+    Expenses:Fodo  -20 EUR
+    Expenses:Food  20 EUR
+"#;
+        let mut journal = Journal::new();
+        let declarations = "account Expenses:Food\ncommodity EUR\npayee Supermarket\n";
+        parse_text(declarations, &mut journal).unwrap();
+        journal.checking_style = CheckingStyle::Error;
+        let mut parser = Parser::new(Cursor::new(input), &mut journal);
+        parser.pathname = "journal.ledger".to_string();
+
+        let actual = parser.parse();
+
+        // The location of the post, once.
+        assert_eq!(
+            r#""journal.ledger", line 2: Unknown account 'Expenses:Fodo'"#,
+            actual.unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_strict_mode_continues() {
+        let input = r#"2023-05-01 Supermarket
+    Expenses:Food  20 EUR
+    Assets:Cash
+
+2023-05-02 Supermarket
+    Expenses:Food  20 EUR
+    Assets:Cash
+"#;
+        let mut journal = Journal::new();
+        journal.checking_style = CheckingStyle::Warning;
+
+        parse_text(input, &mut journal).unwrap();
+
+        assert_eq!(2, journal.xacts.len());
+    }
+
+    #[test]
+    fn test_payee_directive_alias() {
+        let input = r#"payee Supermarket
+    alias ^SPAR\b
+
+2023-05-01 SPAR Vienna
+    Expenses:Food  20 EUR
+    Assets:Cash
+"#;
+        let mut journal = Journal::new();
+
+        parse_text(input, &mut journal).unwrap();
+
+        assert_eq!("Supermarket", journal.xacts[0].payee);
+        assert!(journal.known_payees.contains("Supermarket"));
+    }
+
+    #[test]
+    fn test_single_letter_directives() {
+        let input = r#"D $1,000.00
+N EUR
+C 1h = 60m
+C 1m = 60s
+A Assets:Cash
+P 2023-05-01 EUR 1.10 $
+
+2023-05-01 Supermarket
+    Expenses:Food  1234.5
+"#;
+        let mut journal = Journal::new();
+
+        parse_text(input, &mut journal).unwrap();
+
+        // D
+        let pool = &journal.commodity_pool;
+        let usd = pool.find_id("$").unwrap();
+        assert_eq!(Some(usd), pool.default_commodity());
+        let post = &journal.xacts[0].posts[0];
+        assert_eq!("$1,234.50", post.amount.unwrap().display(pool).to_string());
+        // A
+        let account = journal.get_account(journal.xacts[0].posts[1].account);
+        assert_eq!("Assets:Cash", account.fullname());
+        // N
+        let eur = journal.commodity_pool.find("EUR").unwrap();
+        assert!(eur.has_flags(COMMODITY_NOMARKET));
+        // P, not kept for the commodity without a market value
+        assert_eq!(0, journal.commodity_pool.commodity_history.edge_count());
+        // C
+        let hours = pool.find_id("h").unwrap();
+        let two_hours = crate::amount::Amount::new(2.into(), Some(hours));
+        let seconds = two_hours.reduce(pool);
+        assert_eq!("7200", seconds.quantity.to_string());
+        assert_eq!("s", pool.get(seconds.get_commodity().unwrap()).symbol);
+    }
+
+    #[test]
+    fn test_commodity_conversion_from_zero() {
+        let mut journal = Journal::new();
+
+        let actual = parse_text("C 0h = 60m\n", &mut journal);
+
+        assert_eq!(
+            "\"\", line 1: Invalid commodity conversion: 0h = 60m",
+            actual.unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_invalid_price_date() {
+        let input = "P 2023-13-01 EUR 1.10 USD\n";
+        let mut journal = Journal::new();
+        let mut parser = Parser::new(Cursor::new(input), &mut journal);
+
+        let actual = parser.try_parse();
+
+        assert_eq!(Err((1, "Invalid date: 2023-13-01".to_string())), actual);
+    }
+
+    #[test]
+    fn test_price_without_year() {
+        let input = "Y 2021\nP 05/01 EUR 1.10 USD\n";
+        let mut journal = Journal::new();
+
+        parse_text(input, &mut journal).unwrap();
+
+        let prices = journal.commodity_pool.prices();
+        assert_eq!("2021-05-01", prices[0].datetime.date().to_string());
+    }
+
+    #[test]
+    fn test_unknown_directive() {
+        let input = "2023-05-01 Shop\n    Expenses  1 EUR\n    Assets\n\nX unknown\n";
+        let mut journal = Journal::new();
+        let mut parser = Parser::new(Cursor::new(input), &mut journal);
+
+        let actual = parser.try_parse();
+
+        assert_eq!(Err((5, "Unknown directive 'X unknown'".to_string())), actual);
+    }
+
+    #[test]
+    fn test_year_directive() {
+        let input = "Y 2023\n";
+        let mut journal = Journal::new();
+        let mut parser = Parser::new(Cursor::new(input), &mut journal);
+
+        parser.parse().unwrap();
+
+        assert_eq!(Some(2023), parser.year);
+
+        let mut journal = Journal::new();
+        let mut parser = Parser::new(Cursor::new("year 2019\n"), &mut journal);
+        parser.parse().unwrap();
+        assert_eq!(Some(2019), parser.year);
+    }
+
+    #[test]
+    fn test_year_with_short_dates() {
+        let input = r#"Y 2021
+05/01 Supermarket
+    Expenses:Food  20 EUR
+    Assets:Cash
+
+2023/05/02=05/03 Supermarket
+    Expenses:Food  20 EUR
+    Assets:Cash
+"#;
+        let mut journal = Journal::new();
+
+        parse_text(input, &mut journal).unwrap();
+
+        assert_eq!("2021-05-01", journal.xacts[0].date.unwrap().to_string());
+        assert_eq!("2023-05-02", journal.xacts[1].date.unwrap().to_string());
+        assert_eq!("2021-05-03", journal.xacts[1].aux_date.unwrap().to_string());
+    }
+
+    /// A directory with the given files, for the include tests.
+    fn include_dir(name: &str, files: &[(&str, &str)]) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(name);
+        let _ = std::fs::remove_dir_all(&dir);
+        for (path, content) in files {
+            let path = dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        dir
+    }
+
+    fn parse_path(path: &std::path::Path, journal: &mut Journal) -> Result<(), String> {
+        let file = std::fs::File::open(path).unwrap();
+        crate::parse_sources([(path.display().to_string(), file)], journal)
+    }
+
+    #[test]
+    fn test_include_relative_to_including_file() {
+        let mut journal = Journal::new();
+
+        crate::parse_file("tests/include.ledger", &mut journal).unwrap();
+
+        assert_eq!(1, journal.xacts.len());
+        assert_eq!("Supermarket", journal.xacts[0].payee);
+    }
+
+    #[test]
+    fn test_include_glob() {
+        let xact = |payee: &str| format!("2023-05-01 {}\n    Expenses  1 EUR\n    Assets\n", payee);
+        let dir = include_dir(
+            "ledger-rs-include-glob",
+            &[
+                ("main.ledger", "include 2023/*.ledger\n"),
+                ("2023/02.ledger", &xact("February")),
+                ("2023/01.ledger", &xact("January")),
+                ("2023/notes.txt", &xact("Notes")),
+            ],
+        );
+        let mut journal = Journal::new();
+
+        parse_path(&dir.join("main.ledger"), &mut journal).unwrap();
+
+        let payees: Vec<&str> = journal.xacts.iter().map(|x| x.payee.as_str()).collect();
+        assert_eq!(vec!["January", "February"], payees);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_include_missing_file() {
+        let dir = include_dir(
+            "ledger-rs-include-missing",
+            &[
+                ("main.ledger", "\ninclude sub.ledger\n"),
+                ("sub.ledger", "include missing.ledger\n"),
+            ],
+        );
+        let mut journal = Journal::new();
+
+        let actual = parse_path(&dir.join("main.ledger"), &mut journal);
+
+        // The error has the include chain.
+        let main = dir.join("main.ledger").display().to_string();
+        let sub = dir.join("sub.ledger").display().to_string();
+        let missing = dir.join("missing.ledger").display().to_string();
+        assert_eq!(
+            Err(format!(
+                "\"{main}\", line 2: \"{sub}\", line 1: \
+                 File to include was not found: \"{missing}\""
+            )),
+            actual
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_include_cycle() {
+        let dir = include_dir(
+            "ledger-rs-include-cycle",
+            &[("a.ledger", "include b.ledger\n"), ("b.ledger", "include a.ledger\n")],
+        );
+        let mut journal = Journal::new();
+
+        let actual = parse_path(&dir.join("a.ledger"), &mut journal).unwrap_err();
+
+        let a = std::fs::canonicalize(dir.join("a.ledger")).unwrap();
+        let b = std::fs::canonicalize(dir.join("b.ledger")).unwrap();
+        assert!(actual.ends_with(&format!(
+            "Include cycle: {} -> {} -> {}",
+            a.display(),
+            b.display(),
+            a.display()
+        )));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -6,65 +6,79 @@
  */
 use std::collections::HashMap;
 
-use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use anyhow::{anyhow, Error};
+use chrono::NaiveDateTime;
 use petgraph::stable_graph::NodeIndex;
 
 use crate::{
-    amount::{Amount, Quantity},
+    amount::Amount,
     annotate::Annotation,
-    commodity::Commodity,
+    commodity::{Commodity, CommodityId, COMMODITY_NOMARKET},
+    directives::PriceDirective,
     history::{CommodityHistory, Price},
-    parser::{ISO_DATE_FORMAT, ISO_TIME_FORMAT},
-    scanner,
+    reader,
 };
 
 /// Commodity Index is the index of the node in the history graph.
 pub type CommodityIndex = NodeIndex;
 
 pub struct CommodityPool {
+    /// The commodities, indexed by CommodityId.
+    pub(crate) commodities: Vec<Commodity>,
     /// Map (symbol, commodity)
-    // pub(crate) commodities: HashMap<String, NodeIndex>,
-    pub(crate) commodities: HashMap<String, Commodity>,
-    // pub(crate) commodities: HashMap<String, Rc<Commodity>>,
+    pub(crate) symbols: HashMap<String, CommodityId>,
     /// Commodity annotations. symbol, annotation
     pub(crate) annotated_commodities: HashMap<String, Annotation>,
     pub(crate) commodity_history: CommodityHistory,
-    null_commodity: *const Commodity,
-    default_commodity: *const Commodity,
+    /// Map (alias, symbol) from the `alias` sub-directive of `commodity`.
+    pub(crate) aliases: HashMap<String, String>,
+    default_commodity: Option<CommodityId>,
     // pricedb
 }
 
 impl CommodityPool {
     pub fn new() -> Self {
         Self {
-            commodities: HashMap::new(),
+            commodities: vec![],
+            symbols: HashMap::new(),
             annotated_commodities: HashMap::new(),
             commodity_history: CommodityHistory::new(),
-            null_commodity: std::ptr::null(),
-            default_commodity: std::ptr::null(),
+            aliases: HashMap::new(),
+            default_commodity: None,
         }
     }
 
     pub fn add_price_struct(&mut self, price: Price) {
-        self.commodity_history
-            .add_price(price.get_commodity(), price.datetime, price.price);
+        self.add_price(price.commodity, price.datetime, price.price);
     }
 
     /// Adds a new price point.
     /// i.e. (1) EUR = 1.12 USD
-    /// commodity_index = index of the commodity, i.e. `EUR`
+    /// commodity = the commodity, i.e. `EUR`
     /// date = date of pricing
     /// price: Amount = the price of the commodity, i.e. `1.12 USD`
-    pub fn add_price(&mut self, commodity: *const Commodity, datetime: NaiveDateTime, price: Amount) {
-        self.commodity_history.add_price(commodity, datetime, price)
+    pub fn add_price(&mut self, commodity: CommodityId, datetime: NaiveDateTime, price: Amount) {
+        let source = self.get(commodity).graph_index.unwrap();
+        let target = self
+            .get(price.get_commodity().expect("price commodity"))
+            .graph_index
+            .unwrap();
+
+        self.commodity_history
+            .add_price(source, target, datetime, price.quantity)
+    }
+
+    /// The price points, by date.
+    pub fn prices(&self) -> Vec<Price> {
+        self.commodity_history.prices()
     }
 
     /// Creates a new Commodity for the given Symbol.
-    pub fn create(&mut self, symbol: &str, annotation_option: Option<Annotation>) -> *const Commodity {
+    pub fn create(&mut self, symbol: &str, annotation_option: Option<Annotation>) -> CommodityId {
         // todo: handle double quotes
-        
+
         let mut c = Commodity::new(symbol);
-        
+
         // Annotation
         if let Some(ann) = annotation_option {
             // Create an annotated commodity.
@@ -76,51 +90,91 @@ impl CommodityPool {
             self.annotated_commodities.insert(symbol.to_owned(), ann);
         }
 
-        // move to map
-        self.commodities.insert(symbol.to_string(), c);
-        // get the new address.
-        let new_commodity = self.commodities.get(symbol).unwrap();
+        let id = CommodityId(self.commodities.len());
 
         // add to price history graph.
-        let cdty_ptr = new_commodity as *const Commodity;
-        // log::debug!("commodity pointer: {:?} for {:?}", cdty_ptr, symbol);
-        let i = self.commodity_history.add_commodity(cdty_ptr);
-
-        unsafe {
-            let mut_ptr = cdty_ptr as *mut Commodity;
-            let mut_cdty = &mut *mut_ptr;
-            mut_cdty.graph_index = Some(i);
-        }
+        let i = self.commodity_history.add_commodity(id);
+        c.graph_index = Some(i);
 
-        log::debug!("Commodity {:?} created. index: {:?}, addr:{:?}", symbol, i, cdty_ptr);
+        self.commodities.push(c);
+        self.symbols.insert(symbol.to_string(), id);
 
-        cdty_ptr
+        log::debug!("Commodity {:?} created. index: {:?}, id:{:?}", symbol, i, id);
+
+        id
+    }
+
+    /// Returns the commodity with the given id.
+    pub fn get(&self, id: CommodityId) -> &Commodity {
+        &self.commodities[id.0]
+    }
+
+    pub fn get_mut(&mut self, id: CommodityId) -> &mut Commodity {
+        &mut self.commodities[id.0]
     }
 
     pub fn find(&self, symbol: &str) -> Option<&Commodity> {
-        self.commodities.get(symbol)
+        self.find_id(symbol).map(|id| self.get(id))
+    }
+
+    /// Returns the id of the commodity with the given symbol or alias.
+    pub fn find_id(&self, symbol: &str) -> Option<CommodityId> {
+        self.symbols.get(self.resolve_alias(symbol)).copied()
+    }
+
+    pub(crate) fn find_mut(&mut self, symbol: &str) -> Option<&mut Commodity> {
+        let id = self.find_id(symbol)?;
+        Some(self.get_mut(id))
+    }
+
+    /// Registers an alternative symbol for the commodity, i.e. `€` for `EUR`.
+    ///
+    /// commodity_t * commodity_pool_t::alias(const string& name, commodity_t& referent)
+    pub fn alias(&mut self, name: &str, symbol: &str) {
+        self.aliases.insert(name.to_owned(), symbol.to_owned());
+    }
+
+    fn resolve_alias<'a>(&'a self, symbol: &'a str) -> &'a str {
+        match self.aliases.get(symbol) {
+            Some(referent) => referent.as_str(),
+            None => symbol,
+        }
+    }
+
+    /// The commodity used for the amounts without a symbol. Set with the `D` directive or
+    /// with `default` in the `commodity` directive.
+    pub fn default_commodity(&self) -> Option<CommodityId> {
+        self.default_commodity
+    }
+
+    pub fn set_default_commodity(&mut self, commodity: CommodityId) {
+        self.default_commodity = Some(commodity);
     }
 
     pub fn find_index(&self, symbol: &str) -> Option<CommodityIndex> {
-        let x = self.commodities.get(symbol);
+        let x = self.find(symbol);
         x.unwrap().graph_index
     }
 
     /// Finds a commodity with the given symbol, or creates one.
+    /// Returns None for an empty symbol.
     ///
     pub fn find_or_create(
         &mut self,
         symbol: &str,
         annotation: Option<Annotation>,
-    ) -> *const Commodity {
+    ) -> Option<CommodityId> {
         if symbol.is_empty() {
-            return std::ptr::null();
+            return None;
         }
 
         // Try using entry?
         // self.commodities.entry(symbol).
 
-        if let Some(c) = self.commodities.get(symbol) {
+        let symbol = self.resolve_alias(symbol).to_owned();
+        let symbol = symbol.as_str();
+
+        if let Some(&id) = self.symbols.get(symbol) {
             // check if annotation exists and add if not.
             if annotation.is_some() && !self.annotated_commodities.contains_key(symbol) {
                 // append annotation
@@ -128,14 +182,14 @@ impl CommodityPool {
                     .insert(symbol.to_owned(), annotation.unwrap());
             }
 
-            c
+            Some(id)
         } else {
-            self.create(symbol, annotation)
+            Some(self.create(symbol, annotation))
         }
     }
 
     pub fn get_by_index(&self, index: CommodityIndex) -> &Commodity {
-        self.commodity_history.get_commodity(index)
+        self.get(self.commodity_history.get_commodity(index))
     }
 
     /// This is the exchange() method but, due to mutability of references, it **does not**
@@ -158,13 +212,9 @@ impl CommodityPool {
     ) -> (CostBreakdown, Option<Price>) {
 
         // annotations
+        let commodity = amount.get_commodity().expect("amount commodity");
         let annotation_opt: Option<&Annotation> =
-            if let Some(commodity_index) = amount.get_commodity().unwrap().graph_index {
-                let commodity = self.get_by_index(commodity_index);
-                self.annotated_commodities.get(&commodity.symbol)
-            } else {
-                None
-            };
+            self.annotated_commodities.get(&self.get(commodity).symbol);
 
         let mut per_unit_cost = if is_per_unit || amount.is_zero() {
             cost.abs()
@@ -181,13 +231,17 @@ impl CommodityPool {
         // Do not record commodity exchanges where amount's commodity has a
         // fixated price, since this does not establish a market value for the
         // base commodity.
+        // The commodities marked `nomarket` do not get their market value from the exchanges.
         let new_price: Option<Price>;
         // if add_price
-        if !per_unit_cost.is_zero() && amount.get_commodity() != per_unit_cost.get_commodity() {
+        if !per_unit_cost.is_zero()
+            && amount.get_commodity() != per_unit_cost.get_commodity()
+            && !self.get(commodity).has_flags(COMMODITY_NOMARKET)
+        {
             // self.add_price(amount.commodity_index.unwrap(), moment, per_unit_cost);
             // Instead, return the new price and have the caller store it.
             new_price = Some(Price::new(
-                amount.commodity,
+                commodity,
                 moment,
                 per_unit_cost,
             ));
@@ -221,34 +275,35 @@ impl CommodityPool {
         self.commodities.len()
     }
 
-    pub fn parse_price_directive(&mut self, line: &str) {
-        let tokens = scanner::scan_price_directive(line);
+    /// Parses a price directive, `P 2023-05-01 EUR 1.10 USD`, and adds the price.
+    /// The dates without a year get the given year.
+    pub fn parse_price_directive(
+        &mut self,
+        line: &str,
+        year: Option<i32>,
+        input_format: Option<&str>,
+    ) -> Result<(), Error> {
+        let directive = reader::price_directive(line, year, input_format)?;
+        self.add_price_directive(&directive)
+    }
 
-        // date
-        let date = NaiveDate::parse_from_str(tokens[0], ISO_DATE_FORMAT).expect("date parsed");
-        // time
-        let time = if !tokens[1].is_empty() {
-            NaiveTime::parse_from_str(tokens[1], ISO_TIME_FORMAT).expect("time parsed")
-        } else {
-            NaiveTime::MIN
+    /// Adds the price of the directive. The commodities without a market value,
+    /// `N EUR`, keep no prices.
+    pub fn add_price_directive(&mut self, directive: &PriceDirective) -> Result<(), Error> {
+        let Some(commodity) = self.find_or_create(&directive.symbol, None) else {
+            return Err(anyhow!("Missing commodity in the price"));
+        };
+        let Some(price_commodity) = self.find_or_create(&directive.price.symbol, None) else {
+            return Err(anyhow!("Missing commodity in the price"));
         };
-        let datetime = NaiveDateTime::new(date, time);
-
-        // commodity
-        let commodity_ptr = self.find_or_create(tokens[2], None);
-
-        // quantity
-        let quantity = Quantity::from_str(tokens[3]).expect("quantity parsed");
-
-        // cost commodity
-        let cost_commodity = self.find_or_create(tokens[4], None);
 
-        // cost
-        let cost = Amount::new(quantity, Some(cost_commodity));
+        if self.get(commodity).has_flags(COMMODITY_NOMARKET) {
+            return Ok(());
+        }
 
-        // Add price for commodity
-        self.commodity_history
-            .add_price(commodity_ptr, datetime, cost);
+        let price = Amount::new(directive.price.quantity, Some(price_commodity));
+        self.add_price(commodity, directive.datetime, price);
+        Ok(())
     }
 }
 
@@ -280,18 +335,15 @@ impl CostBreakdown {
 #[cfg(test)]
 mod tests {
     use super::CommodityPool;
-    use crate::{amount::Quantity, annotate::Annotation, journal::Journal, parse_file, parse_text, commodity::Commodity};
+    use crate::{amount::Quantity, annotate::Annotation, journal::Journal, parse_file, parse_text};
 
     #[test]
     fn test_create() {
         const SYMBOL: &str = "DHI";
         let mut pool = CommodityPool::new();
 
-        let cdty_ptr = pool.create(SYMBOL, None);
-        let cdty: &Commodity;
-        unsafe {
-            cdty = &*cdty_ptr;
-        }
+        let id = pool.create(SYMBOL, None);
+        let cdty = pool.get(id);
 
         assert_eq!(SYMBOL, cdty.symbol);
 
@@ -308,7 +360,7 @@ mod tests {
 
         // Assert
         assert_eq!(1, pool.commodities.len());
-        assert!(pool.commodities.contains_key("EUR"));
+        assert!(pool.find("EUR").is_some());
     }
 
     #[test]
@@ -317,7 +369,7 @@ mod tests {
         let mut pool = CommodityPool::new();
 
         // Act
-        pool.parse_price_directive(line);
+        pool.parse_price_directive(line, None, None).unwrap();
 
         // Assert
         assert_eq!(2, pool.commodities.len());
@@ -325,19 +377,19 @@ mod tests {
         assert_eq!(1, pool.commodity_history.edge_count());
 
         // Currencies in the map.
-        assert!(pool.commodities.contains_key("EUR"));
-        assert!(pool.commodities.contains_key("USD"));
+        assert!(pool.find("EUR").is_some());
+        assert!(pool.find("USD").is_some());
 
         // Currencies as nodes in the graph.
         assert_eq!(
             "EUR",
             // pool.commodity_history.node_weights().nth(0).unwrap().symbol
-            pool.commodities.get("EUR").unwrap().symbol
+            pool.find("EUR").unwrap().symbol
         );
         assert_eq!(
             "USD",
             // pool.commodity_history.node_weights().nth(1).unwrap().symbol
-            pool.commodities.get("USD").unwrap().symbol
+            pool.find("USD").unwrap().symbol
         );
 
         // Rate, edge
@@ -378,7 +430,7 @@ mod tests {
 "#;
         let journal = &mut Journal::new();
 
-        parse_text(input, journal);
+        parse_text(input, journal).unwrap();
 
         // assert
         // The prices (edges) are directional, so we need to get the edges for VEUR.
@@ -398,7 +450,7 @@ mod tests {
     fn test_exchange() {
         let line = "P 2022-03-03 13:00:00 EUR 1.12 USD";
         let mut journal = Journal::new();
-        parse_text(line, &mut journal);
+        parse_text(line, &mut journal).unwrap();
 
         // act
         // exchange_commodities()
@@ -411,7 +463,7 @@ mod tests {
     // #[test]
     fn test_exchange_implicit() {
         let mut journal = Journal::new();
-        parse_file("tests/trade.ledger", &mut journal);
+        parse_file("tests/trade.ledger", &mut journal).unwrap();
 
         todo!()
     }
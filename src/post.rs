@@ -2,88 +2,169 @@
  * Posting
  */
 
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
 use crate::{
-    account::Account,
+    account::AccountId,
     amount::Amount,
-    xact::Xact,
+    scanner,
+    xact::XactId,
 };
 
-#[derive(Debug, PartialEq)]
+/// Identifies a Post by its transaction and its position in the transaction.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+pub struct PostId {
+    pub xact: XactId,
+    pub index: usize,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
 pub struct Post {
-    /// Pointer to the Account.
-    pub account: *const Account,
-    // pub account_index: AccountIndex,
-    /// Pointer to the Xact.
-    pub xact_ptr: *const Xact,
-    // pub xact_index: XactIndex,
+    /// The Account.
+    pub account: AccountId,
+    /// The Xact this post belongs to.
+    pub xact: XactId,
 
     pub amount: Option<Amount>,
     pub cost: Option<Amount>,
+    /// The cost comes from a fixed rate (`apply fixed`) and does not set the market price.
+    pub cost_fixated: bool,
     // given_cost
     // assigned_amount
     // checkin
     // checkout
     pub note: Option<String>,
+    /// Tags and metadata. (tag, value)
+    pub metadata: BTreeMap<String, Option<String>>,
+    /// The balance of the account after the post, from `= AMOUNT`, checked when parsed.
+    pub assertion: Option<Assertion>,
+}
+
+/// A balance assertion, `= AMOUNT`, with hledger's kinds: `==` also checks that the account
+/// has no other commodities, and `=*` includes the sub-accounts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Assertion {
+    pub amount: Amount,
+    /// `=*`, the balance includes the sub-accounts.
+    pub inclusive: bool,
+    /// `==`, the asserted amount is the only commodity in the balance.
+    pub total: bool,
+}
+
+impl Assertion {
+    /// The operator as written in the journal: `=`, `==`, `=*` or `==*`.
+    pub fn operator(&self) -> &'static str {
+        match (self.total, self.inclusive) {
+            (false, false) => "=",
+            (true, false) => "==",
+            (false, true) => "=*",
+            (true, true) => "==*",
+        }
+    }
 }
 
 impl Post {
     /// Creates a Post from post tokens.
     pub fn new(
-        account: *const Account,
-        xact_ptr: *const Xact,
+        account: AccountId,
+        xact: XactId,
         amount: Option<Amount>,
         cost: Option<Amount>,
         note: Option<&str>,
     ) -> Self {
-        Self {
+        let mut post = Self {
             account,
-            xact_ptr,
+            xact,
             amount,
             cost,
-            note: match note {
-                Some(content) => Some(content.to_owned()),
-                None => None,
-            },
+            cost_fixated: false,
+            note: None,
+            metadata: BTreeMap::new(),
+            assertion: None,
+        };
+        if let Some(content) = note {
+            post.add_note(content);
         }
+        post
     }
 
+    /// Sets the note and reads any tags from it.
     pub fn add_note(&mut self, note: &str) {
+        self.parse_tags(note);
         self.note = Some(note.into());
     }
+
+    /// Reads the tags and metadata from the note text.
+    pub fn parse_tags(&mut self, note: &str) {
+        for (tag, value) in scanner::scan_tags(note) {
+            self.set_tag(tag, value);
+        }
+    }
+
+    pub fn set_tag(&mut self, tag: &str, value: Option<&str>) {
+        self.metadata
+            .insert(tag.to_owned(), value.map(|v| v.to_owned()));
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.metadata.contains_key(tag)
+    }
+
+    pub fn get_tag(&self, tag: &str) -> Option<&str> {
+        self.metadata.get(tag).and_then(|value| value.as_deref())
+    }
 }
 
 impl Default for Post {
     fn default() -> Self {
         Self {
-            account: std::ptr::null(),
-            xact_ptr: std::ptr::null(),
+            account: Default::default(),
+            xact: Default::default(),
             amount: Default::default(),
             cost: Default::default(),
+            cost_fixated: false,
             note: Default::default(),
+            metadata: Default::default(),
+            assertion: None,
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::account::Account;
+    use crate::{account::AccountId, journal::Journal};
 
     use super::Post;
 
     #[test]
-    fn test_pointers() {
+    fn test_account_id() {
         const ACCT_NAME: &str = "Some Account";
+        let mut journal = Journal::new();
         let mut post = Post::default();
 
-        assert_eq!(std::ptr::null(), post.account);
+        assert_eq!(AccountId::MASTER, post.account);
 
         // Assign account.
-        let acct = Account::new(ACCT_NAME);
-        post.account = &acct as *const Account;
+        post.account = journal.register_account(ACCT_NAME).unwrap();
 
-        unsafe {
-            // println!("account is {:?}", *post.account);
-            assert_eq!(ACCT_NAME, (*post.account).name);
-        }
+        assert_eq!(ACCT_NAME, journal.get_account(post.account).name);
+    }
+
+    #[test]
+    fn test_tags_from_note() {
+        let post = Post::new(
+            Default::default(),
+            Default::default(),
+            None,
+            None,
+            Some(":food:shared: Receipt: 1234"),
+        );
+
+        assert!(post.has_tag("food"));
+        assert!(post.has_tag("shared"));
+        assert_eq!(None, post.get_tag("food"));
+        assert_eq!(Some("1234"), post.get_tag("Receipt"));
     }
 }
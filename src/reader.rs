@@ -1,27 +1,80 @@
 //! The Journal Reader.
 //! Reads directives from the given source and returns them as an iterator.
-//! 
-//! An attempt to replace the Parser::parse() method.
+//!
+//! The reader is a pull-parser. It does not build a Journal and keeps only the current
+//! directive in memory, so it can process large files. The accounts and commodities are
+//! returned by name, as written.
+//!
+//! The directives other than the transactions are interpreted here also for the
+//! [`crate::parser`], which reads them through [`LineSource`] and applies them to the
+//! Journal.
+//!
+//! ```
+//! use ledger_rs_lib::{directives::DirectiveType, reader::create_str_reader};
+//!
+//! let content = "2023-05-01 Shop\n    Expenses:Food  20 EUR\n    Assets:Cash\n";
+//! for directive in create_str_reader(content) {
+//!     let directive = directive.unwrap();
+//!     if let DirectiveType::Xact(xact) = directive.kind {
+//!         assert_eq!(2, xact.posts.len());
+//!         assert_eq!(1, directive.span.line);
+//!     }
+//! }
+//! ```
 
 use std::io::{BufRead, BufReader, Cursor, Read};
 
-use crate::directives::DirectiveType;
+use anyhow::{anyhow, Error};
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+
+use crate::{
+    directives::{
+        AccountDirective, CommodityDirective, Directive, DirectiveType, PayeeDirective,
+        PostDirective, PriceDirective, RawAmount, Span, XactDirective,
+    },
+    parser::{parse_date_in, ISO_TIME_FORMAT},
+    scanner,
+};
 
 pub fn create_reader<T: Read>(source: T) -> DirectiveIter<T> {
-    let iter = DirectiveIter::new(source);
-    iter
+    DirectiveIter::new(source)
+}
+
+pub fn create_str_reader(source: &str) -> DirectiveIter<Cursor<&str>> {
+    DirectiveIter::new(Cursor::new(source))
 }
 
-pub fn create_str_reader<T: Read>(source: &str) -> DirectiveIter<Cursor<&str>> {
-    let cursor = Cursor::new(source);
-    let iter: DirectiveIter<Cursor<&str>> = DirectiveIter::new(cursor);
-    iter
+/// The lines of a source, read one at a time. The directives that span several lines
+/// read their indented lines through it.
+pub(crate) trait LineSource {
+    /// Reads the next line into the buffer, replacing the previous one, and counts it.
+    /// Returns the number of bytes read, 0 at the end.
+    fn read_line(&mut self) -> std::io::Result<usize>;
+
+    /// The line read last.
+    fn buffer(&self) -> &str;
+
+    /// Checks whether the next line is indented, without consuming it.
+    fn peek_whitespace_line(&mut self) -> bool;
 }
 
+/// Iterates over the directives in the source.
+///
+/// An invalid directive is returned as an error, with the line number, and the reading
+/// continues with the next directive.
 pub struct DirectiveIter<T: Read> {
-    // source: T,
     reader: BufReader<T>,
     buffer: String,
+    /// The byte offset of the next line.
+    offset: usize,
+    /// The number of lines read.
+    linenum: usize,
+    /// The end of the last non-blank line, (offset, line).
+    content_end: (usize, usize),
+    /// The year for the dates without one, from the `Y` directive.
+    year: Option<i32>,
+    /// The date format of the source, i.e. from `--input-date-format`.
+    input_date_format: Option<String>,
 }
 
 impl<T: Read> DirectiveIter<T> {
@@ -29,56 +82,469 @@ impl<T: Read> DirectiveIter<T> {
         let reader = BufReader::new(source);
 
         Self {
-            // source,
             reader,
             buffer: String::new(),
+            offset: 0,
+            linenum: 0,
+            content_end: (0, 0),
+            year: None,
+            input_date_format: None,
+        }
+    }
+
+    /// Reads the dates in the given format, i.e. `%d.%m.%Y`.
+    pub fn with_input_date_format(mut self, format: &str) -> Self {
+        self.input_date_format = Some(format.to_owned());
+        self
+    }
+
+    /// The number of the last line read. After an error, the line where it happened.
+    pub fn line(&self) -> usize {
+        self.linenum
+    }
+
+    /// Parses the directive starting in the buffer. Reads its additional lines, if any.
+    fn read_directive(&mut self) -> Result<DirectiveType, Error> {
+        let first = self.buffer.chars().next().expect("a non-blank line");
+        match first {
+            c if is_comment_line(c) => {
+                Ok(DirectiveType::Comment(self.buffer[1..].trim().to_owned()))
+            }
+            '0'..='9' => self.xact_directive(),
+            ' ' | '\t' => Err(anyhow!("Unexpected indented line")),
+            _ => {
+                let input_format = self.input_date_format.clone();
+                let directive = general_directive(self, self.year, input_format.as_deref())?;
+                if let DirectiveType::Year(year) = directive {
+                    self.year = Some(year);
+                }
+                Ok(directive)
+            }
+        }
+    }
+
+    /// Parses the dates with the current year.
+    fn parse_date(&self, date_str: &str) -> Result<Option<NaiveDate>, Error> {
+        if date_str.is_empty() {
+            return Ok(None);
+        }
+        let input_format = self.input_date_format.as_deref();
+        Ok(Some(parse_date_in(date_str, self.year, input_format)?))
+    }
+
+    fn xact_directive(&mut self) -> Result<DirectiveType, Error> {
+        let [date, aux_date, payee, note] = scanner::tokenize_xact_header(self.buffer.trim_end());
+        let mut xact = XactDirective {
+            date: self.parse_date(date)?,
+            aux_date: self.parse_date(aux_date)?,
+            payee: payee.to_owned(),
+            ..Default::default()
+        };
+        if !note.is_empty() {
+            xact.add_note(note);
+        }
+
+        // The posts and the notes are indented. The transaction ends at a blank line or
+        // at the next directive.
+        while self.peek_whitespace_line() {
+            let start = self.offset;
+            if self.read_line()? == 0 {
+                break;
+            }
+            let input = self.buffer.trim();
+            if input.is_empty() {
+                break;
+            }
+
+            if let Some(note) = input.strip_prefix(';') {
+                // The note belongs to the previous element (xact/post).
+                let note = note.trim();
+                match xact.posts.last_mut() {
+                    Some(post) => {
+                        post.span.end = self.offset;
+                        post.span.end_line = self.linenum;
+                        if !note.is_empty() {
+                            post.add_note(note);
+                        }
+                    }
+                    None if !note.is_empty() => xact.add_note(note),
+                    None => (),
+                }
+                continue;
+            }
+
+            let mut post = self.parse_post(input)?;
+            post.span = Span {
+                start,
+                end: self.offset,
+                line: self.linenum,
+                end_line: self.linenum,
+            };
+            xact.posts.push(post);
+        }
+
+        Ok(DirectiveType::Xact(xact))
+    }
+
+    fn parse_post(&self, input: &str) -> Result<PostDirective, Error> {
+        let tokens = scanner::scan_post(input);
+        let mut post = PostDirective {
+            account: tokens.account.to_owned(),
+            amount: parse_raw_amount(tokens.quantity, tokens.symbol)?,
+            lot_price: parse_raw_amount(tokens.price_quantity, tokens.price_commodity)?,
+            lot_date: self.parse_date(tokens.price_date)?,
+            cost: parse_raw_amount(tokens.cost_quantity, tokens.cost_symbol)?,
+            is_per_unit: tokens.is_per_unit,
+            ..Default::default()
+        };
+        if !tokens.note.is_empty() {
+            post.add_note(tokens.note);
+        }
+        Ok(post)
+    }
+}
+
+impl<T: Read> LineSource for DirectiveIter<T> {
+    fn read_line(&mut self) -> std::io::Result<usize> {
+        self.buffer.clear();
+        let n = self.reader.read_line(&mut self.buffer)?;
+        if n > 0 {
+            self.offset += n;
+            self.linenum += 1;
+            if !self.buffer.trim().is_empty() {
+                self.content_end = (self.offset, self.linenum);
+            }
+        }
+        Ok(n)
+    }
+
+    fn buffer(&self) -> &str {
+        &self.buffer
+    }
+
+    fn peek_whitespace_line(&mut self) -> bool {
+        match self.reader.fill_buf() {
+            Ok(buf) => matches!(buf.first(), Some(b' ') | Some(b'\t')),
+            Err(_) => false,
         }
     }
 }
 
 impl<T: Read> Iterator for DirectiveIter<T> {
-    type Item = DirectiveType;
-
-    fn next(self: &mut DirectiveIter<T>) -> Option<Self::Item> {
-        // Read lines and recognise the directive.
-        match self.reader.read_line(&mut self.buffer) {
-            Err(error) => panic!("Error: {:?}", error),
-            Ok(0) => {
-                // end of file
-                return None;
+    type Item = Result<Directive, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Skip the blank lines.
+        loop {
+            match self.read_line() {
+                Err(error) => return Some(Err(error.into())),
+                Ok(0) => return None,
+                Ok(_) if self.buffer.trim().is_empty() => continue,
+                Ok(_) => break,
             }
-            Ok(result) => {
-                // TODO: Recognise directive, if any.
+        }
+
+        let start = self.offset - self.buffer.len();
+        let line = self.linenum;
+
+        let result = self
+            .read_directive()
+            .map_err(|e| anyhow!("line {}: {}", self.linenum, e));
+
+        Some(result.map(|kind| Directive {
+            kind,
+            span: Span {
+                start,
+                end: self.content_end.0,
+                line,
+                end_line: self.content_end.1,
+            },
+        }))
+    }
+}
+
+/// Whether the line that starts with the character is a comment.
+pub(crate) fn is_comment_line(first: char) -> bool {
+    matches!(first, ';' | '#' | '*' | '|' | '%')
+}
+
+/// textual.cc
+/// bool instance_t::general_directive(char *line)
+///
+/// Parses the directive in the buffer, other than a transaction or a comment line.
+/// Reads its indented lines, if any. The dates without a year get the given year.
+pub(crate) fn general_directive(
+    source: &mut impl LineSource,
+    year: Option<i32>,
+    input_format: Option<&str>,
+) -> Result<DirectiveType, Error> {
+    // The legacy directives are prefixed with `@` or `!`.
+    let line = source.buffer().trim_end().trim_start_matches(['@', '!']);
+    let (directive, argument) = match line.find(char::is_whitespace) {
+        Some(index) => (line[..index].to_owned(), line[index..].trim().to_owned()),
+        None => (line.to_owned(), String::new()),
+    };
+    // The single-letter directives. The argument follows the letter.
+    let c = line.chars().next().expect("a directive");
+    let letter_argument = line[c.len_utf8()..].trim().to_owned();
+
+    match directive.as_str() {
+        "account" => return account_directive(source, require_argument(&directive, argument)?),
+        "commodity" => {
+            return commodity_directive(source, require_argument(&directive, argument)?)
+        }
+        "comment" | "test" => return comment_block(source, &directive),
+        "include" => return Ok(DirectiveType::Include(require_argument(&directive, argument)?)),
+        "payee" => return payee_directive(source, require_argument(&directive, argument)?),
+        "tag" => return tag_directive(source, require_argument(&directive, argument)?),
+        "year" => return year_directive(&argument),
+        _ => (),
+    }
+
+    match c {
+        'P' => Ok(DirectiveType::Price(price_directive(
+            source.buffer().trim_end(),
+            year,
+            input_format,
+        )?)),
+        'Y' => year_directive(&letter_argument),
+        'A' | 'C' | 'D' | 'N' => Ok(DirectiveType::Other {
+            name: c.to_string(),
+            argument: letter_argument,
+        }),
+        _ => Ok(DirectiveType::Other {
+            name: directive,
+            argument,
+        }),
+    }
+}
+
+/// `P 2022-03-03 13:00:00 EUR 1.12 USD`
+pub(crate) fn price_directive(
+    line: &str,
+    year: Option<i32>,
+    input_format: Option<&str>,
+) -> Result<PriceDirective, Error> {
+    let [date, time, symbol, quantity, price_symbol] = scanner::scan_price_directive(line);
+
+    let date = parse_date_in(date, year, input_format)?;
+    let time = if time.is_empty() {
+        NaiveTime::MIN
+    } else {
+        NaiveTime::parse_from_str(time, ISO_TIME_FORMAT)
+            .map_err(|_| anyhow!("Invalid time: {}", time))?
+    };
+    if symbol.is_empty() || price_symbol.is_empty() {
+        return Err(anyhow!("Missing commodity in the price: {}", line));
+    }
+    let Some(price) = RawAmount::parse(quantity, price_symbol) else {
+        return Err(anyhow!("Invalid price: {}", quantity));
+    };
+
+    Ok(PriceDirective {
+        datetime: NaiveDateTime::new(date, time),
+        symbol: symbol.to_owned(),
+        price,
+    })
+}
+
+/// Reads the next indented line of a directive.
+/// Returns the trimmed content split into (keyword, argument), or None at the end of
+/// the directive.
+fn read_sub_directive(source: &mut impl LineSource) -> Result<Option<(String, String)>, Error> {
+    if !source.peek_whitespace_line() || source.read_line()? == 0 {
+        return Ok(None);
+    }
+
+    let line = source.buffer().trim();
+    if line.is_empty() {
+        return Ok(None);
+    }
+    let (keyword, argument) = match line.find(char::is_whitespace) {
+        Some(index) => (&line[..index], line[index..].trim()),
+        None => (line, ""),
+    };
+    Ok(Some((keyword.to_owned(), argument.to_owned())))
+}
+
+/// textual.cc
+/// void instance_t::account_directive(char * line)
+fn account_directive(
+    source: &mut impl LineSource,
+    argument: String,
+) -> Result<DirectiveType, Error> {
+    // hledger's comment, with the type, i.e. `account Assets:Cash  ; type: C`.
+    let mut account = match argument.split_once(';') {
+        Some((name, comment)) => AccountDirective {
+            name: name.trim().to_owned(),
+            comments: vec![comment.trim().to_owned()],
+            ..Default::default()
+        },
+        None => AccountDirective {
+            name: argument,
+            ..Default::default()
+        },
+    };
+
+    while let Some((keyword, argument)) = read_sub_directive(source)? {
+        if let Some(comment) = keyword.strip_prefix(';') {
+            account.comments.push(format!("{} {}", comment, argument).trim().to_owned());
+            continue;
+        }
+        // Ensure there's an argument for the directives that need one.
+        if argument.is_empty() && keyword != "default" && !is_comment(&keyword) {
+            return Err(anyhow!("Account directive '{}' requires an argument", keyword));
+        }
+
+        match keyword.as_str() {
+            k if is_comment(k) => (),
+            "alias" => account.aliases.push(argument),
+            "payee" => account.payees.push(argument),
+            "check" => account.checks.push(argument),
+            "assert" => account.assertions.push(argument),
+            "default" => account.default = true,
+            "note" => account.note = Some(argument),
+            _ => return Err(anyhow!("Unknown account directive '{}'", keyword)),
+        }
+    }
+
+    Ok(DirectiveType::Account(account))
+}
+
+/// void instance_t::commodity_directive(char * line)
+fn commodity_directive(
+    source: &mut impl LineSource,
+    symbol: String,
+) -> Result<DirectiveType, Error> {
+    let mut commodity = CommodityDirective {
+        symbol: symbol.trim_matches('"').to_owned(),
+        ..Default::default()
+    };
+
+    while let Some((keyword, argument)) = read_sub_directive(source)? {
+        // Ensure there's an argument for the directives that need one.
+        if argument.is_empty()
+            && keyword != "nomarket"
+            && keyword != "default"
+            && !is_comment(&keyword)
+        {
+            return Err(anyhow!("Commodity directive '{}' requires an argument", keyword));
+        }
+
+        match keyword.as_str() {
+            k if is_comment(k) => (),
+            "alias" => commodity.aliases.push(argument.trim_matches('"').to_owned()),
+            "format" => commodity.format = Some(argument),
+            "nomarket" => commodity.nomarket = true,
+            "default" => commodity.default = true,
+            "note" => commodity.note = Some(argument),
+            _ => return Err(anyhow!("Unknown commodity directive '{}'", keyword)),
+        }
+    }
 
-                // TODO: Read additional lines, if needed (like for Xact).
-                // TODO: Parse and return the directive.
+    Ok(DirectiveType::Commodity(commodity))
+}
 
-                // return Some(DirectiveType::Xact(Xact::default()))
-                println!("Result: {:?}", result);
-                return Some(DirectiveType::Comment);
+/// void instance_t::payee_directive(char * line)
+fn payee_directive(source: &mut impl LineSource, name: String) -> Result<DirectiveType, Error> {
+    let mut payee = PayeeDirective {
+        name,
+        ..Default::default()
+    };
+
+    while let Some((keyword, argument)) = read_sub_directive(source)? {
+        match keyword.as_str() {
+            k if is_comment(k) => (),
+            "alias" if argument.is_empty() => {
+                return Err(anyhow!("Payee directive 'alias' requires an argument"))
             }
-        };
+            "alias" => payee.aliases.push(argument),
+            _ => return Err(anyhow!("Unknown payee directive '{}'", keyword)),
+        }
+    }
+
+    Ok(DirectiveType::Payee(payee))
+}
+
+/// void instance_t::tag_directive(char * line)
+fn tag_directive(source: &mut impl LineSource, name: String) -> Result<DirectiveType, Error> {
+    while let Some((keyword, _)) = read_sub_directive(source)? {
+        if !is_comment(&keyword) {
+            return Err(anyhow!("Unknown tag directive '{}'", keyword));
+        }
+    }
+
+    Ok(DirectiveType::Tag(name))
+}
+
+/// `comment` or `test`, until `end comment` or `end test`.
+fn comment_block(source: &mut impl LineSource, directive: &str) -> Result<DirectiveType, Error> {
+    let end = format!("end {}", directive);
+    let mut lines: Vec<String> = vec![];
+    loop {
+        if source.read_line()? == 0 {
+            return Err(anyhow!("Missing '{}'", end));
+        }
+        let line = source.buffer().trim_end();
+        if line == end {
+            break;
+        }
+        lines.push(line.to_owned());
+    }
+
+    Ok(DirectiveType::Comment(lines.join("\n")))
+}
+
+/// `Y 2023` or `year 2023`, the year for the dates written without one.
+fn year_directive(argument: &str) -> Result<DirectiveType, Error> {
+    let Ok(year) = argument.trim().parse::<i32>() else {
+        return Err(anyhow!("Invalid year: {:?}", argument));
+    };
+    Ok(DirectiveType::Year(year))
+}
+
+/// The comment lines within a directive.
+fn is_comment(keyword: &str) -> bool {
+    keyword.starts_with(';') || keyword.starts_with('#')
+}
+
+/// Returns the argument of a directive, or an error if there is none.
+fn require_argument(directive: &str, argument: String) -> Result<String, Error> {
+    if argument.is_empty() {
+        return Err(anyhow!("Directive '{}' requires an argument", directive));
+    }
+    Ok(argument)
+}
+
+/// Parses the amount, if there is one.
+fn parse_raw_amount(quantity: &str, symbol: &str) -> Result<Option<RawAmount>, Error> {
+    if quantity.is_empty() {
+        return Ok(None);
+    }
+    match RawAmount::parse(quantity, symbol) {
+        Some(amount) => Ok(Some(amount)),
+        None => Err(anyhow!("Invalid amount: {} {}", quantity, symbol)),
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::io::Cursor;
+    use chrono::NaiveDate;
 
-    use crate::reader::create_str_reader;
+    use crate::{
+        amount::Quantity,
+        directives::{DirectiveType, RawAmount, Span},
+        reader::create_str_reader,
+    };
 
     #[test]
     fn basic_test() {
         let content = "; blah blah";
 
-        let output = create_str_reader::<Cursor<&str>>(content);
+        let output: Vec<_> = create_str_reader(content).map(|d| d.unwrap()).collect();
 
-        let mut counter = 0;
-        for item in output {
-            println!("item: {:?}", item);
-            counter += 1;
-        }
-        assert_eq!(1, counter);
+        assert_eq!(1, output.len());
+        assert_eq!(DirectiveType::Comment("blah blah".into()), output[0].kind);
     }
 
     #[test]
@@ -87,13 +553,183 @@ mod tests {
     Expenses:Clothing  20 EUR
     Assets:Credit Card
 "#;
-        //let iter = DirectiveIter::new();
-        let iter = create_str_reader::<Cursor<&str>>(content);
+        let mut iter = create_str_reader(content);
 
-        // iter.count()
-        for x in iter {
-            println!("Directive: {:?}", x);
-            // assert_eq!(DirectiveType::Xact())
-        }
+        let directive = iter.next().unwrap().unwrap();
+        assert!(iter.next().is_none());
+
+        let DirectiveType::Xact(xact) = directive.kind else {
+            panic!("expected a transaction");
+        };
+        assert_eq!(NaiveDate::from_ymd_opt(2023, 3, 4), xact.date);
+        assert_eq!("Shop", xact.payee);
+        assert_eq!(2, xact.posts.len());
+        assert_eq!("Expenses:Clothing", xact.posts[0].account);
+        assert_eq!(
+            Some(RawAmount {
+                quantity: Quantity::from(20),
+                symbol: "EUR".into()
+            }),
+            xact.posts[0].amount
+        );
+        assert_eq!("Assets:Credit Card", xact.posts[1].account);
+        assert_eq!(None, xact.posts[1].amount);
+        assert_eq!(
+            Span {
+                start: 0,
+                end: content.len(),
+                line: 1,
+                end_line: 3
+            },
+            directive.span
+        );
+        assert_eq!(3, xact.posts[1].span.line);
+    }
+
+    #[test]
+    fn test_spans() {
+        let content = "; first\n\nP 2023-05-01 EUR 1.10 USD\n\n2023-05-01 Shop\n    Expenses  1 EUR\n    Assets\n\ninclude other.ledger\n";
+
+        let spans: Vec<Span> = create_str_reader(content)
+            .map(|d| d.unwrap().span)
+            .collect();
+
+        assert_eq!(4, spans.len());
+        let texts: Vec<&str> = spans.iter().map(|s| &content[s.start..s.end]).collect();
+        assert_eq!("; first\n", texts[0]);
+        assert_eq!("P 2023-05-01 EUR 1.10 USD\n", texts[1]);
+        assert_eq!(
+            "2023-05-01 Shop\n    Expenses  1 EUR\n    Assets\n",
+            texts[2]
+        );
+        assert_eq!((5, 7), (spans[2].line, spans[2].end_line));
+        assert_eq!("include other.ledger\n", texts[3]);
+        assert_eq!(9, spans[3].line);
+    }
+
+    #[test]
+    fn test_directive_types() {
+        let content = r#"account Expenses:Food
+    note Food
+    alias food
+    check commodity == "EUR"
+    default
+
+commodity EUR
+    format 1.000,00 EUR
+    nomarket
+
+payee Shop
+    alias ^Shop.*
+
+tag Receipt
+Y 2022
+alias groceries=Expenses:Food
+comment
+Some text
+end comment
+P 05-01 13:00:00 EUR 1.10 USD
+"#;
+        let directives: Vec<DirectiveType> =
+            create_str_reader(content).map(|d| d.unwrap().kind).collect();
+
+        assert_eq!(8, directives.len());
+        let DirectiveType::Account(account) = &directives[0] else {
+            panic!("expected an account");
+        };
+        assert_eq!("Expenses:Food", account.name);
+        assert_eq!(Some("Food".into()), account.note);
+        assert_eq!(vec!["food".to_string()], account.aliases);
+        assert_eq!(vec!["commodity == \"EUR\"".to_string()], account.checks);
+        assert!(account.default);
+
+        let DirectiveType::Commodity(commodity) = &directives[1] else {
+            panic!("expected a commodity");
+        };
+        assert_eq!("EUR", commodity.symbol);
+        assert_eq!(Some("1.000,00 EUR".into()), commodity.format);
+        assert!(commodity.nomarket);
+
+        let DirectiveType::Payee(payee) = &directives[2] else {
+            panic!("expected a payee");
+        };
+        assert_eq!(vec!["^Shop.*".to_string()], payee.aliases);
+
+        assert_eq!(DirectiveType::Tag("Receipt".into()), directives[3]);
+        assert_eq!(DirectiveType::Year(2022), directives[4]);
+        assert_eq!(
+            DirectiveType::Other {
+                name: "alias".into(),
+                argument: "groceries=Expenses:Food".into()
+            },
+            directives[5]
+        );
+        assert_eq!(DirectiveType::Comment("Some text".into()), directives[6]);
+
+        // The date without the year uses the year from the `Y` directive.
+        let DirectiveType::Price(price) = &directives[7] else {
+            panic!("expected a price");
+        };
+        assert_eq!("2022-05-01 13:00:00", price.datetime.to_string());
+        assert_eq!("EUR", price.symbol);
+        assert_eq!("USD", price.price.symbol);
+    }
+
+    #[test]
+    fn test_account_comments() {
+        let content = "account Assets:Cash  ; type: C\n    ;\n    ; note\n    # other\n";
+
+        let directives: Vec<DirectiveType> =
+            create_str_reader(content).map(|d| d.unwrap().kind).collect();
+
+        let DirectiveType::Account(account) = &directives[0] else {
+            panic!("expected an account");
+        };
+        assert_eq!("Assets:Cash", account.name);
+        assert_eq!(vec!["type: C", "", "note"], account.comments);
+    }
+
+    #[test]
+    fn test_xact_notes_and_cost() {
+        let content = r#"2023-05-01 Broker  ; :trade:
+    Assets:Stocks  10 VEUR {20 EUR} [2023-04-01] @@ 250 EUR
+    ; Lot: first
+    Assets:Cash
+2023-05-02 Next
+    Expenses  1 EUR
+    Assets
+"#;
+        let directives: Vec<DirectiveType> =
+            create_str_reader(content).map(|d| d.unwrap().kind).collect();
+
+        // The transaction ends at the next directive, without a blank line.
+        assert_eq!(2, directives.len());
+        let DirectiveType::Xact(xact) = &directives[0] else {
+            panic!("expected a transaction");
+        };
+        assert!(xact.metadata.contains_key("trade"));
+
+        let post = &xact.posts[0];
+        assert_eq!("20", post.lot_price.as_ref().unwrap().quantity.to_string());
+        assert_eq!(NaiveDate::from_ymd_opt(2023, 4, 1), post.lot_date);
+        assert_eq!("EUR", post.cost.as_ref().unwrap().symbol);
+        assert!(!post.is_per_unit);
+        assert_eq!(Some(&Some("first".to_string())), post.metadata.get("Lot"));
+        assert_eq!((2, 3), (post.span.line, post.span.end_line));
+    }
+
+    #[test]
+    fn test_error_continues() {
+        let content = "account\n2023-05-01 Shop\n    Expenses  1 EUR\n    Assets\n";
+
+        let results: Vec<_> = create_str_reader(content).collect();
+
+        assert_eq!(2, results.len());
+        let error = results[0].as_ref().unwrap_err().to_string();
+        assert_eq!("line 1: Directive 'account' requires an argument", error);
+        assert!(matches!(
+            results[1].as_ref().unwrap().kind,
+            DirectiveType::Xact(_)
+        ));
     }
 }
@@ -0,0 +1,202 @@
+/*!
+ * Interactive mode
+ *
+ * The journal is read once and kept in memory. The commands, i.e. `bal Expenses` or
+ * `reg -M`, run against it. `reload` reads the journal files again.
+ * The line editing, with the history, is up to the client, i.e. the CLI.
+ *
+ * global.cc: global_scope_t::execute_command, with at_repl
+ */
+
+use crate::{
+    execute_command,
+    journal::Journal,
+    option::{self, InputOptions, OPTIONS},
+    session_read_journal_files,
+};
+
+/// The commands, for the completion.
+pub const COMMANDS: &[&str] = &[
+    "accounts",
+    "bal",
+    "balance",
+    "convert",
+    "duplicates",
+    "exit",
+    "fmt",
+    "print",
+    "quit",
+    "reg",
+    "register",
+    "reload",
+];
+
+/// The interactive session.
+pub struct Repl {
+    /// The options from the command line, `~/.ledgerrc` and the environment.
+    options: InputOptions,
+    journal: Journal,
+}
+
+impl Repl {
+    /// Reads the journal, with the options from the arguments, `~/.ledgerrc` and the
+    /// `LEDGER_*` environment variables.
+    pub fn new(args: Vec<String>) -> Result<Self, String> {
+        let (commands, options) = option::process_arguments_with_env(args, &crate::environment())?;
+        if let Some(command) = commands.first() {
+            return Err(format!(
+                "Unexpected command '{}' in the interactive mode",
+                command
+            ));
+        }
+        let journal = session_read_journal_files(&options)?;
+
+        Ok(Self { options, journal })
+    }
+
+    /// The interactive mode is for the arguments without a command, and without `--help`
+    /// or `--version`. The invalid arguments are left to [`crate::try_run`] to report.
+    pub fn is_interactive(args: &[String]) -> bool {
+        match option::process_arguments(args.to_vec()) {
+            Ok((commands, options)) => commands.is_empty() && !options.help && !options.version,
+            Err(_) => false,
+        }
+    }
+
+    pub fn journal(&self) -> &Journal {
+        &self.journal
+    }
+
+    /// Runs the command line, i.e. `bal Expenses`, against the journal in memory.
+    /// The options on the line apply to this command only.
+    pub fn execute(&mut self, line: &str) -> Result<Vec<String>, String> {
+        let args = shell_words::split(line).map_err(|e| e.to_string())?;
+        let (commands, line_options) = option::process_arguments(args)?;
+
+        match commands.first().map(String::as_str) {
+            Some("reload") => {
+                self.journal = session_read_journal_files(&self.options)?;
+                Ok(vec![])
+            }
+            None if !line_options.help && !line_options.version => Ok(vec![]),
+            _ => {
+                let options = self.options.clone().override_with(line_options);
+                execute_command(commands, options, Some(&self.journal))
+            }
+        }
+    }
+
+    /// The completions for the word before `pos`: the commands for the first word, the
+    /// options for the words starting with `--`, and the account names otherwise.
+    /// Returns the start of the word and the candidates.
+    pub fn complete(&self, line: &str, pos: usize) -> (usize, Vec<String>) {
+        let line = &line[..pos];
+        let start = line
+            .char_indices()
+            .rev()
+            .find(|(_, c)| c.is_whitespace())
+            .map_or(0, |(i, c)| i + c.len_utf8());
+        let word = &line[start..];
+
+        let mut candidates: Vec<String> = if line[..start].trim().is_empty() {
+            COMMANDS
+                .iter()
+                .filter(|command| command.starts_with(word))
+                .map(|command| command.to_string())
+                .collect()
+        } else if word.starts_with('-') {
+            OPTIONS
+                .iter()
+                .map(|option| format!("--{}", option.name))
+                .filter(|name| name.starts_with(word))
+                .collect()
+        } else {
+            self.journal
+                .master()
+                .flatten_account_tree(&self.journal)
+                .iter()
+                .map(|account| account.fullname())
+                .filter(|name| !name.is_empty() && name.starts_with(word))
+                .map(|name| name.to_owned())
+                .collect()
+        };
+        candidates.sort();
+        candidates.dedup();
+
+        (start, candidates)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Repl;
+
+    fn repl() -> Repl {
+        let args = shell_words::split("-f tests/basic.ledger --init-file /dev/null").unwrap();
+        Repl::new(args).unwrap()
+    }
+
+    #[test]
+    fn test_is_interactive() {
+        assert!(Repl::is_interactive(&[]));
+        assert!(Repl::is_interactive(&["-f".into(), "basic.ledger".into()]));
+        assert!(!Repl::is_interactive(&["bal".into()]));
+        assert!(!Repl::is_interactive(&["--version".into()]));
+        assert!(!Repl::is_interactive(&["--frobnicate".into()]));
+    }
+
+    #[test]
+    fn test_execute() {
+        let mut repl = repl();
+
+        let actual = repl.execute("bal Expenses").unwrap();
+
+        assert_eq!(
+            vec![
+                "Account Expenses has balance 20 EUR",
+                "Account Expenses:Food has balance 20 EUR"
+            ],
+            actual
+        );
+        assert!(repl.execute("").unwrap().is_empty());
+        assert!(repl.execute("reload").unwrap().is_empty());
+        assert_eq!(1, repl.journal().xacts.len());
+        assert_eq!(
+            Err("Unrecognized command 'frobnicate'".to_owned()),
+            repl.execute("frobnicate")
+        );
+    }
+
+    #[test]
+    fn test_options_apply_to_one_command() {
+        let mut repl = repl();
+
+        let with_format = repl.execute("print -y %Y/%m/%d").unwrap();
+        let without = repl.execute("print").unwrap();
+
+        assert_eq!("2023/04/21 Supermarket", with_format[0]);
+        assert_eq!("2023-04-21 Supermarket", without[0]);
+    }
+
+    #[test]
+    fn test_complete() {
+        let repl = repl();
+
+        assert_eq!(
+            (
+                0,
+                vec!["reg".to_owned(), "register".into(), "reload".into()]
+            ),
+            repl.complete("re", 2)
+        );
+        assert_eq!(
+            (4, vec!["Assets".to_owned(), "Assets:Cash".into()]),
+            repl.complete("bal As", 6)
+        );
+        assert_eq!(
+            (4, vec!["--monthly".to_owned()]),
+            repl.complete("reg --mo", 8)
+        );
+        assert_eq!((4, vec![]), repl.complete("bal Income", 10));
+    }
+}
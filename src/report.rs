@@ -2,7 +2,44 @@
  * Reports module containing the report definitions
  */
 
-use crate::{balance::Balance, journal::Journal, account::Account};
+use std::collections::BTreeMap;
+
+use chrono::{Datelike, NaiveDate};
+use regex::{Regex, RegexBuilder};
+
+use crate::{
+    account::Account, balance::Balance, compare::SortOrder, journal::Journal,
+    parser::ISO_DATE_FORMAT,
+};
+
+/// The account patterns from the command arguments, i.e. `Expenses` in `bal Expenses`.
+/// The patterns are case-insensitive regular expressions. An account matches if any of the
+/// patterns matches its full name. Without the patterns, all accounts match.
+///
+/// The account part of query_t in query.cc
+#[derive(Debug, Default)]
+pub struct AccountFilter {
+    patterns: Vec<Regex>,
+}
+
+impl AccountFilter {
+    pub fn new(args: &[String]) -> Result<Self, String> {
+        let patterns = args
+            .iter()
+            .map(|arg| {
+                RegexBuilder::new(arg)
+                    .case_insensitive(true)
+                    .build()
+                    .map_err(|e| format!("Invalid account pattern {}: {}", arg, e))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { patterns })
+    }
+
+    pub fn matches(&self, fullname: &str) -> bool {
+        self.patterns.is_empty() || self.patterns.iter().any(|p| p.is_match(fullname))
+    }
+}
 
 /// Accounts report. Command: `accounts`.
 ///
@@ -10,9 +47,15 @@ use crate::{balance::Balance, journal::Journal, account::Account};
 /// in output.cc
 /// report_accounts
 pub fn report_accounts(journal: &Journal) -> Vec<String> {
-    let accts = journal.master.flatten_account_tree();
+    filtered_accounts_report(journal, &AccountFilter::default())
+}
+
+/// Accounts report for the accounts that match the filter, i.e. `accounts Asset`.
+pub fn filtered_accounts_report(journal: &Journal, filter: &AccountFilter) -> Vec<String> {
+    let accts = journal.master().flatten_account_tree(journal);
     accts
         .iter()
+        .filter(|account| filter.matches(account.fullname()))
         .map(|account| account.name.to_string())
         .collect()
 }
@@ -36,39 +79,302 @@ pub fn balance_report(journal: &Journal) -> Vec<String> {
     // Format output
     // format_balance_report(balances, &journal)
 
-    get_children_lines(&journal.master, journal)
+    get_children_lines(journal.master(), journal, None, &AccountFilter::default())
+}
+
+/// Balance report for the accounts that match the filter, i.e. `bal Expenses`, with the
+/// sub-accounts in the given order, from `-S` or `--sort`.
+pub fn filtered_balance_report(
+    journal: &Journal,
+    filter: &AccountFilter,
+    sort: Option<&SortOrder>,
+) -> Vec<String> {
+    get_children_lines(journal.master(), journal, sort, filter)
 }
 
 /// Quick test of the account traversal for assembling the totals.
-fn get_children_lines<'a>(account: &'a Account, journal: &'a Journal) -> Vec<String> {
+fn get_children_lines<'a>(
+    account: &'a Account,
+    journal: &'a Journal,
+    sort: Option<&SortOrder>,
+    filter: &AccountFilter,
+) -> Vec<String> {
     let mut result = vec![];
 
-    let mut balance_line = String::new();
-    let total = account.total();
-    for amount in total.amounts {
-        balance_line += amount.quantity.to_string().as_str();
-        if amount.get_commodity().is_some() {
-            if let Some(c) = amount.get_commodity() {
-                balance_line += " ";
-                balance_line += c.symbol.as_str();
-            }
+    let fullname = account.fullname();
+    if filter.matches(fullname) {
+        let mut balance_line = String::new();
+        let total = account.total(journal);
+        for amount in total.amounts {
+            balance_line += amount.display(&journal.commodity_pool).to_string().as_str();
         }
+        result.push(format!("Account {} has balance {}", fullname, balance_line));
     }
-    result.push(format!("Account {} has balance {}", account.fullname(), balance_line));
 
     // Sort child account names alphabetically. Mainly for consistent output.
     let mut acct_names: Vec<_> = account.accounts.keys().collect();
     acct_names.sort();
+    let mut children: Vec<&Account> = acct_names
+        .iter()
+        .map(|name| journal.get_account(account.accounts[*name]))
+        .collect();
+    if let Some(sort) = sort {
+        children.sort_by(|a, b| sort.compare_accounts(a, b, journal));
+    }
 
     // children amounts
-    for acct_name in acct_names {
-        let acct = account.accounts.get(acct_name).unwrap();
-        result.extend(get_children_lines(acct, journal));
+    for acct in children {
+        result.extend(get_children_lines(acct, journal, sort, filter));
     }
 
     result
 }
 
+/// Print report. Command: `print`.
+/// Outputs the transactions in the journal format, with the dates in the given format,
+/// i.e. from `--date-format`, and in the given order, from `--sort`.
+///
+/// void report_t::print_xacts
+pub fn print_report(
+    journal: &Journal,
+    date_format: Option<&str>,
+    sort: Option<&SortOrder>,
+) -> Vec<String> {
+    let date_format = date_format.unwrap_or(ISO_DATE_FORMAT);
+    let mut output = vec![];
+
+    let mut xacts: Vec<_> = journal.xacts.iter().collect();
+    if let Some(sort) = sort {
+        xacts.sort_by(|a, b| sort.compare_xacts(a, b, journal));
+    }
+
+    for xact in xacts {
+        if !output.is_empty() {
+            output.push(String::new());
+        }
+
+        let mut header = match xact.date {
+            Some(date) => date.format(date_format).to_string(),
+            None => String::new(),
+        };
+        if let Some(aux_date) = xact.aux_date {
+            header += "=";
+            header += aux_date.format(date_format).to_string().as_str();
+        }
+        header += " ";
+        match (&xact.code, xact.payee.split_once(' ')) {
+            // The code goes after the state mark.
+            (Some(code), Some((state @ ("*" | "!"), payee))) => {
+                header += &format!("{} ({}) {}", state, code, payee)
+            }
+            (Some(code), _) => header += &format!("({}) {}", code, xact.payee),
+            (None, _) => header += &xact.payee,
+        }
+        output.push(header);
+
+        if let Some(note) = &xact.note {
+            output.push(format!("    ; {}", note));
+        }
+
+        for post in &xact.posts {
+            let mut line = format!("    {}", journal.get_account(post.account).fullname());
+            if let Some(amount) = post.amount {
+                line += "  ";
+                line += amount.display(&journal.commodity_pool).to_string().as_str();
+
+                if let Some(cost) = post.cost {
+                    if cost.get_commodity() != amount.get_commodity() {
+                        line += " @@ ";
+                        line += cost.abs().display(&journal.commodity_pool).to_string().as_str();
+                    }
+                }
+            }
+            if let Some(assertion) = post.assertion {
+                line += " ";
+                line += assertion.operator();
+                line += " ";
+                line += assertion.amount.display(&journal.commodity_pool).to_string().as_str();
+            }
+            if let Some(note) = &post.note {
+                line += "  ; ";
+                line += note;
+            }
+            output.push(line);
+        }
+    }
+
+    output
+}
+
+/// Prices report. Command: `prices`.
+/// Lists the price points, by date, i.e. `2023-05-01 VEUR 20 EUR`.
+///
+/// void report_t::commodities_report (pricedb)
+pub fn prices_report(journal: &Journal, date_format: Option<&str>) -> Vec<String> {
+    let date_format = date_format.unwrap_or(ISO_DATE_FORMAT);
+    let pool = &journal.commodity_pool;
+
+    pool.prices()
+        .iter()
+        .map(|price| {
+            format!(
+                "{} {} {}",
+                price.datetime.date().format(date_format),
+                pool.get(price.commodity).symbol,
+                price.price.display(pool)
+            )
+        })
+        .collect()
+}
+
+/// Register report. Command: `register`.
+/// Lists the postings to the accounts that match the filter, with the running total, in the
+/// given order, from `--sort`.
+///
+/// void report_t::posts_report
+pub fn register_report(
+    journal: &Journal,
+    filter: &AccountFilter,
+    date_format: Option<&str>,
+    sort: Option<&SortOrder>,
+) -> Vec<String> {
+    let date_format = date_format.unwrap_or(ISO_DATE_FORMAT);
+
+    let mut posts = journal.all_posts();
+    posts.retain(|post| filter.matches(journal.get_account(post.account).fullname()));
+    if let Some(sort) = sort {
+        posts.sort_by(|a, b| sort.compare_posts(a, b, journal));
+    }
+
+    let mut total = Balance::new();
+    let mut output = vec![];
+    for post in posts {
+        let xact = journal.get_xact(post.xact);
+        let date = match xact.date {
+            Some(date) => date.format(date_format).to_string(),
+            None => String::new(),
+        };
+        let amount = match post.amount {
+            Some(amount) => {
+                total.add(&amount);
+                amount.display(&journal.commodity_pool).to_string()
+            }
+            None => String::new(),
+        };
+        let total_text: Vec<String> = total
+            .amounts
+            .iter()
+            .map(|amount| amount.display(&journal.commodity_pool).to_string())
+            .collect();
+
+        output.push(format!(
+            "{} {}  {}  {}  {}",
+            date,
+            xact.payee,
+            journal.get_account(post.account).fullname(),
+            amount,
+            total_text.join(", ")
+        ));
+    }
+    output
+}
+
+/// Monthly register report. Command: `register -M`.
+/// Lists the total of the postings to each account in each month, with the running total.
+/// The postings without a date are skipped.
+///
+/// interval_posts in filters.cc
+pub fn monthly_register_report(
+    journal: &Journal,
+    filter: &AccountFilter,
+    date_format: Option<&str>,
+) -> Vec<String> {
+    let date_format = date_format.unwrap_or(ISO_DATE_FORMAT);
+
+    let display = |balance: &Balance| -> String {
+        let amounts: Vec<String> = balance
+            .amounts
+            .iter()
+            .map(|amount| amount.display(&journal.commodity_pool).to_string())
+            .collect();
+        amounts.join(", ")
+    };
+
+    monthly_totals(journal, filter)
+        .iter()
+        .map(|row| {
+            format!(
+                "{} - {}  {}  {}  {}",
+                row.first.format(date_format),
+                row.last.format(date_format),
+                row.account,
+                display(&row.amount),
+                display(&row.total)
+            )
+        })
+        .collect()
+}
+
+/// A row of the monthly register: the total of the postings to an account in a month,
+/// and the running total.
+pub(crate) struct MonthlyTotal<'a> {
+    pub first: NaiveDate,
+    pub last: NaiveDate,
+    pub account: &'a str,
+    pub amount: Balance,
+    pub total: Balance,
+}
+
+/// The monthly totals of the accounts that match the filter, by month and account.
+pub(crate) fn monthly_totals<'a>(
+    journal: &'a Journal,
+    filter: &AccountFilter,
+) -> Vec<MonthlyTotal<'a>> {
+    // (year, month) -> account -> the month's total
+    let mut months: BTreeMap<(i32, u32), BTreeMap<&str, Balance>> = BTreeMap::new();
+    for post in journal.all_posts() {
+        let account = journal.get_account(post.account).fullname();
+        let (Some(date), Some(amount)) = (journal.get_xact(post.xact).date, post.amount) else {
+            continue;
+        };
+        if !filter.matches(account) {
+            continue;
+        }
+        months
+            .entry((date.year(), date.month()))
+            .or_default()
+            .entry(account)
+            .or_insert_with(Balance::new)
+            .add(&amount);
+    }
+
+    let mut total = Balance::new();
+    let mut rows = vec![];
+    for ((year, month), accounts) in months {
+        let first = NaiveDate::from_ymd_opt(year, month, 1).unwrap();
+        let last = match month {
+            12 => NaiveDate::from_ymd_opt(year + 1, 1, 1),
+            _ => NaiveDate::from_ymd_opt(year, month + 1, 1),
+        }
+        .and_then(|next| next.pred_opt())
+        .unwrap();
+
+        for (account, amount) in accounts {
+            for item in &amount.amounts {
+                total.add(item);
+            }
+            rows.push(MonthlyTotal {
+                first,
+                last,
+                account,
+                amount,
+                total: total.clone(),
+            });
+        }
+    }
+    rows
+}
+
 /// To be deprecated, unless significantly faster than the account traversing.
 /// Calculates account balances.
 /// returns (account_name, balance)
@@ -77,11 +383,11 @@ fn get_account_balances(journal: &Journal) -> Vec<(&str, Balance)> {
     let mut balances = vec![];
 
     // calculate balances
-    for acc in journal.master.flatten_account_tree() {
+    for acc in journal.master().flatten_account_tree(journal) {
         // get posts for this account.
         let filtered_posts = journal
             .xacts.iter().flat_map(|x| x.posts.iter())
-            .filter(|post| post.account == acc);
+            .filter(|post| std::ptr::eq(journal.get_account(post.account), acc));
 
         // TODO: separate balance per currency
 
@@ -104,22 +410,11 @@ fn format_balance_report(mut balances: Vec<(String, Balance)>, journal: &Journal
     for (account, balance) in balances {
         let mut bal_text: String = String::new();
         for amount in &balance.amounts {
-            //
-            let symbol = match amount.get_commodity() {
-                Some(c) => c.symbol.as_str(),
-                None => "",
-            };
-
             if !bal_text.is_empty() {
                 bal_text += ", ";
             }
-            
-            bal_text += amount.quantity.to_string().as_str();
 
-            if !symbol.is_empty() {
-                bal_text += " ";
-                bal_text += symbol;
-            }
+            bal_text += amount.display(&journal.commodity_pool).to_string().as_str();
         }
         let line = format!("Account {} has balance {}", account, bal_text);
         output.push(line);
@@ -152,7 +447,7 @@ mod tests {
 
 "#;
         let mut journal = Journal::new();
-        parser::read_into_journal(Cursor::new(src), &mut journal);
+        parser::read_into_journal(Cursor::new(src), &mut journal).unwrap();
 
         let actual: Vec<String> = balance_report(&journal);
 
@@ -179,7 +474,7 @@ mod tests {
 "#;
         let source = Cursor::new(src);
         let mut journal = Journal::new();
-        parser::read_into_journal(source, &mut journal);
+        parser::read_into_journal(source, &mut journal).unwrap();
 
         // Act
         let actual: Vec<String> = balance_report(&journal);
@@ -201,7 +496,7 @@ mod tests {
 "#;
         let source = Cursor::new(src);
         let mut journal = Journal::new();
-        parser::read_into_journal(source, &mut journal);
+        parser::read_into_journal(source, &mut journal).unwrap();
 
         // Act
         let actual: Vec<String> = balance_report(&journal);
@@ -18,6 +18,14 @@ pub(crate) struct PostTokens<'a> {
     pub cost_quantity: &'a str,
     pub cost_symbol: &'a str,
     pub is_per_unit: bool,
+    /// The balance assertion, `= 100 EUR`.
+    pub assertion_quantity: &'a str,
+    pub assertion_symbol: &'a str,
+    /// hledger's `==`, the account has no other commodities.
+    pub assertion_total: bool,
+    /// hledger's `=*`, the balance includes the sub-accounts.
+    pub assertion_inclusive: bool,
+    pub note: &'a str,
 }
 
 impl PostTokens<'_> {
@@ -32,6 +40,11 @@ impl PostTokens<'_> {
             cost_quantity: "",
             cost_symbol: "",
             is_per_unit: false,
+            assertion_quantity: "",
+            assertion_symbol: "",
+            assertion_total: false,
+            assertion_inclusive: false,
+            note: "",
         }
     }
 }
@@ -177,6 +190,24 @@ fn tokenize_payee(input: &str) -> (&str, &str) {
     }
 }
 
+/// Splits the code, in parentheses, from the payee, i.e. `(1234) Shop`.
+/// The state mark, `*` or `!`, stays with the payee.
+/// Returns (code, payee).
+pub(crate) fn tokenize_code(payee: &str) -> (Option<&str>, String) {
+    let (state, rest) = match payee.split_once(' ') {
+        Some((state @ ("*" | "!"), rest)) => (Some(state), rest.trim_start()),
+        _ => (None, payee),
+    };
+    let Some((code, rest)) = rest.strip_prefix('(').and_then(|rest| rest.split_once(')')) else {
+        return (None, payee.to_owned());
+    };
+    let payee = match state {
+        Some(state) => format!("{} {}", state, rest.trim_start()),
+        None => rest.trim_start().to_owned(),
+    };
+    (Some(code.trim()), payee)
+}
+
 /// Parse tokens from a Post line.
 ///   ACCOUNT  AMOUNT  [; NOTE]
 ///
@@ -202,6 +233,12 @@ pub(crate) fn scan_post(input: &str) -> PostTokens {
 
     // todo: virtual, deferred account [] () <>
 
+    // The note runs from `;` to the end of the line.
+    let (input, note) = match input.find(';') {
+        Some(index) => (input[..index].trim_end(), input[index + 1..].trim()),
+        None => (input.trim_end(), ""),
+    };
+
     // two spaces is a separator betweer the account and amount.
     // Eventually, also support the tab as a separator:
     // something like |p| p == "  " || p  == '\t'
@@ -209,22 +246,42 @@ pub(crate) fn scan_post(input: &str) -> PostTokens {
     let Some(sep_index) = input.find("  ") else {
         let mut post_tokens = PostTokens::create_empty();
         post_tokens.account = input.trim_end();
+        post_tokens.note = note;
         return post_tokens;
     };
 
     // there's more content
 
     let account = &input[..sep_index];
-    let (amount_tokens, input) = scan_amount(&input[sep_index + 2..]);
+    let (input, assertion) = split_assertion(input[sep_index + 2..].trim());
+    let assertion_total = assertion.starts_with('=');
+    let assertion = assertion.trim_start_matches('=');
+    let assertion_inclusive = assertion.starts_with('*');
+    let assertion = assertion.trim_start_matches('*').trim_start();
+    let assertion_tokens = match assertion.is_empty() {
+        true => AmountTokens { quantity: "", symbol: "" },
+        false => scan_amount(assertion).0,
+    };
+
+    // `Assets  = 100 EUR` assigns the balance, without the amount.
+    if input.is_empty() {
+        let mut post_tokens = PostTokens::create_empty();
+        post_tokens.account = account;
+        post_tokens.assertion_quantity = assertion_tokens.quantity;
+        post_tokens.assertion_symbol = assertion_tokens.symbol;
+        post_tokens.assertion_total = assertion_total;
+        post_tokens.assertion_inclusive = assertion_inclusive;
+        post_tokens.note = note;
+        return post_tokens;
+    }
+
+    let (amount_tokens, input) = scan_amount(input);
     let (annotation_tokens, input) = scan_annotations(input);
     let cost_tokens = match input.is_empty() {
         true => CostTokens::new(),
         false => scan_cost(input),
     };
 
-    // TODO: handle post comment
-    // scan_xyz(input)
-
     return PostTokens {
         account,
         quantity: amount_tokens.quantity,
@@ -235,9 +292,27 @@ pub(crate) fn scan_post(input: &str) -> PostTokens {
         cost_quantity: cost_tokens.quantity,
         cost_symbol: cost_tokens.symbol,
         is_per_unit: cost_tokens.is_per_unit,
+        assertion_quantity: assertion_tokens.quantity,
+        assertion_symbol: assertion_tokens.symbol,
+        assertion_total,
+        assertion_inclusive,
+        note,
     };
 }
 
+/// Splits the balance assertion, `= 100 EUR`, from the amount part of the posting.
+/// The `=` must start the part or follow a space, so that `{=20 EUR}` is the lot price.
+/// The assertion keeps hledger's `=` and `*` that follow, as in `==* 100 EUR`.
+fn split_assertion(input: &str) -> (&str, &str) {
+    let index = input.char_indices().find(|(i, c)| {
+        *c == '=' && (*i == 0 || input[..*i].ends_with(char::is_whitespace))
+    });
+    match index {
+        Some((i, _)) => (input[..i].trim_end(), input[i + 1..].trim()),
+        None => (input, ""),
+    }
+}
+
 /// Scans the first Amount from the input
 ///
 /// returns: AmountTokens
@@ -397,6 +472,87 @@ fn scan_cost(input: &str) -> CostTokens {
     }
 }
 
+/// Scans the tags and the metadata from a note.
+///
+/// `:tag1:tag2:` are tags without a value, `Key: Value` is a metadata value,
+/// which extends to the end of the note.
+///
+/// returns [(tag, value)]
+///
+/// void item_t::parse_tags(const char * p, scope_t& scope, bool overwrite_existing)
+pub(crate) fn scan_tags(note: &str) -> Vec<(&str, Option<&str>)> {
+    let mut result = vec![];
+    if !note.contains(':') {
+        return result;
+    }
+
+    let mut rest = note;
+    while let Some(start) = rest.find(|c: char| !c.is_whitespace()) {
+        rest = &rest[start..];
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let token = &rest[..end];
+        rest = &rest[end..];
+
+        if token.len() < 2 {
+            continue;
+        }
+
+        if token.starts_with(':') && token.ends_with(':') {
+            // a series of tags
+            for tag in token.split(':').filter(|t| !t.is_empty()) {
+                result.push((tag, None));
+            }
+        } else if let Some(key) = token.strip_suffix(':') {
+            // a metadata setting, with the rest of the note as the value.
+            let value = rest.trim();
+            result.push((key, if value.is_empty() { None } else { Some(value) }));
+            break;
+        }
+    }
+
+    result
+}
+
+/// The text of the note, without the tags and the metadata, i.e. `Lunch` of
+/// `Lunch :food: project: home`. The hledger tags, `name:value`, are also left out.
+pub(crate) fn note_text(note: &str) -> String {
+    let mut words = vec![];
+    for token in note.split_whitespace() {
+        if token.len() > 1 && token.ends_with(':') && !token.starts_with(':') {
+            // metadata, with the rest of the note as the value.
+            break;
+        }
+        if !token.contains(':') {
+            words.push(token);
+        }
+    }
+    words.join(" ").trim_end_matches(',').to_owned()
+}
+
+/// Scans the tags of a comment in hledger's syntax, `name:value`, separated by commas.
+/// The name is the word before the colon, and the value runs to the comma or the end,
+/// i.e. `; a comment, project:home, client: Acme Inc`.
+pub(crate) fn scan_hledger_tags(note: &str) -> Vec<(&str, Option<&str>)> {
+    let mut result = vec![];
+
+    for part in note.split(',') {
+        let Some((before, value)) = part.split_once(':') else {
+            continue;
+        };
+        if before.ends_with(char::is_whitespace) {
+            continue;
+        }
+        let name = before.rsplit(char::is_whitespace).next().unwrap_or_default();
+        if name.is_empty() {
+            continue;
+        }
+        let value = value.trim();
+        result.push((name, if value.is_empty() { None } else { Some(value) }));
+    }
+
+    result
+}
+
 /// Scans the Price directive
 ///
 /// i.e.
@@ -460,7 +616,14 @@ fn next_element(input: &str) -> Option<&str> {
 
 #[cfg(test)]
 mod scanner_tests_xact {
-    use super::{scan_date, tokenize_xact_header};
+    use super::{scan_date, tokenize_code, tokenize_xact_header};
+
+    #[test]
+    fn test_tokenize_code() {
+        assert_eq!((Some("1234"), "Shop".to_owned()), tokenize_code("(1234) Shop"));
+        assert_eq!((Some("#5"), "* Shop".to_owned()), tokenize_code("* (#5) Shop"));
+        assert_eq!((None, "Shop (Vienna)".to_owned()), tokenize_code("Shop (Vienna)"));
+    }
 
     #[test]
     fn test_parsing_xact_header() {
@@ -596,6 +759,28 @@ mod scanner_tests_post {
         assert_eq!("20", tokens.quantity);
     }
 
+    #[test]
+    fn test_tokenize_post_assertion() {
+        let tokens = scan_post("Assets  -20 EUR @ 1.1 USD = 100 EUR ; note");
+        let assignment = scan_post("Assets  = 100 EUR");
+
+        assert_eq!("-20", tokens.quantity);
+        assert_eq!("1.1", tokens.cost_quantity);
+        assert_eq!("100", tokens.assertion_quantity);
+        assert_eq!("EUR", tokens.assertion_symbol);
+        assert_eq!("note", tokens.note);
+        assert_eq!("Assets", assignment.account);
+        assert_eq!("", assignment.quantity);
+        assert_eq!("100", assignment.assertion_quantity);
+        assert!(!assignment.assertion_total);
+
+        let hledger = scan_post("Assets  10 EUR ==* 100 EUR");
+        assert_eq!("10", hledger.quantity);
+        assert_eq!("100", hledger.assertion_quantity);
+        assert!(hledger.assertion_total);
+        assert!(hledger.assertion_inclusive);
+    }
+
     #[test]
     fn test_tokenize_post_account() {
         let input = "  Assets";
@@ -784,6 +969,29 @@ mod scanner_tests_post {
         assert_eq!(true, tokens.is_per_unit);
     }
 
+    #[test]
+    fn test_scanning_post_note() {
+        let input = "  Expenses:Food  20 EUR  ; :groceries:\n";
+
+        let tokens = scan_post(input);
+
+        assert_eq!("Expenses:Food", tokens.account);
+        assert_eq!("20", tokens.quantity);
+        assert_eq!("EUR", tokens.symbol);
+        assert_eq!(":groceries:", tokens.note);
+    }
+
+    #[test]
+    fn test_scanning_account_note() {
+        let input = "  Assets:Cash  ; paid in cash";
+
+        let tokens = scan_post(input);
+
+        assert_eq!("Assets:Cash", tokens.account);
+        assert_eq!("", tokens.quantity);
+        assert_eq!("paid in cash", tokens.note);
+    }
+
     #[test]
     fn test_scanning_total_cost() {
         let input = "  Account  5 VAS @@ 10 AUD";
@@ -886,6 +1094,42 @@ mod scanner_tests_amount {
     }
 }
 
+#[cfg(test)]
+mod scanner_tests_tags {
+    use super::{scan_hledger_tags, scan_tags};
+
+    #[test]
+    fn test_scan_tags() {
+        let actual = scan_tags(":food:shared:");
+
+        assert_eq!(vec![("food", None), ("shared", None)], actual);
+    }
+
+    #[test]
+    fn test_scan_metadata() {
+        let actual = scan_tags("Receipt: scanned copy  ");
+
+        assert_eq!(vec![("Receipt", Some("scanned copy"))], actual);
+    }
+
+    #[test]
+    fn test_scan_hledger_tags() {
+        let actual = scan_hledger_tags("a comment, project:home, client: Acme Inc, done:");
+
+        assert_eq!(
+            vec![("project", Some("home")), ("client", Some("Acme Inc")), ("done", None)],
+            actual
+        );
+        assert!(scan_hledger_tags("time 12 : 30").is_empty());
+    }
+
+    #[test]
+    fn test_scan_note_without_tags() {
+        assert!(scan_tags("just a comment").is_empty());
+        assert!(scan_tags("time 12:30").is_empty());
+    }
+}
+
 #[cfg(test)]
 mod scanner_tests_price_directive {
     use super::scan_price_directive;
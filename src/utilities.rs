@@ -2,3 +2,37 @@
  * Handy utility functions
  */
 
+use std::path::PathBuf;
+
+/// Replaces the leading `~` in the path with the home directory.
+///
+/// utils.cc: path expand_path(const path& pathname)
+pub fn expand_path(path: &str, home: Option<&str>) -> PathBuf {
+    match (path.strip_prefix('~'), home) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with(['/', '\\']) => {
+            let mut expanded = PathBuf::from(home);
+            expanded.push(rest.trim_start_matches(['/', '\\']));
+            expanded
+        }
+        _ => PathBuf::from(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::expand_path;
+
+    #[test]
+    fn test_expand_path() {
+        assert_eq!(
+            PathBuf::from("/home/user/journal.ledger"),
+            expand_path("~/journal.ledger", Some("/home/user"))
+        );
+        assert_eq!(PathBuf::from("/home/user"), expand_path("~", Some("/home/user")));
+        assert_eq!(PathBuf::from("~other/x"), expand_path("~other/x", Some("/home/user")));
+        assert_eq!(PathBuf::from("~/x"), expand_path("~/x", None));
+        assert_eq!(PathBuf::from("a/b"), expand_path("a/b", Some("/home/user")));
+    }
+}
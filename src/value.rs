@@ -59,7 +59,7 @@ mod tests {
     //#[test]
     fn test_exchange() {
         let mut journal = Journal::new();
-        parse_file("tests/commodity_exchange.ledger", &mut journal);
+        parse_file("tests/commodity_exchange.ledger", &mut journal).unwrap();
 
         // act
         todo!("run bal -X USD")
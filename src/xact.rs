@@ -5,119 +5,155 @@
  * It contains contains Postings.
  */
 
+use std::collections::BTreeMap;
+
 use chrono::NaiveDate;
+use serde::Serialize;
+
+use crate::{amount::Amount, balance::Balance, journal::Journal, post::Post, scanner};
+
+/// Index of the transaction in the journal.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+pub struct XactId(pub(crate) usize);
+
+impl XactId {
+    pub fn index(&self) -> usize {
+        self.0
+    }
+}
 
-use crate::{balance::Balance, journal::Journal, parser, post::Post};
+/// The location of an item in the source.
+///
+/// item.h, position_t
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct Position {
+    /// The name of the source file, empty for the text sources.
+    pub pathname: String,
+    /// The first line, 1-based.
+    pub beg_line: usize,
+    /// The last line, 1-based.
+    pub end_line: usize,
+}
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct Xact {
-    pub journal: *const Journal,
     pub date: Option<NaiveDate>,
     pub aux_date: Option<NaiveDate>,
+    /// The code, i.e. a check number, from `(1234)` before the payee.
+    pub code: Option<String>,
     pub payee: String,
     pub posts: Vec<Post>,
     pub note: Option<String>,
+    /// Tags and metadata. (tag, value)
+    pub metadata: BTreeMap<String, Option<String>>,
+    /// Where the transaction was read from.
+    pub pos: Option<Position>,
     // pub balance: Amount,
 }
 
 impl Xact {
     pub fn new(date: Option<NaiveDate>, payee: &str, note: Option<String>) -> Self {
-        // code: Option<String>
-
         Self {
             payee: payee.to_owned(),
             note,
             date,
             aux_date: None,
-            journal: std::ptr::null(),
+            code: None,
             posts: vec![],
+            metadata: BTreeMap::new(),
+            pos: None,
             // balance: Amount::null(),
         }
     }
 
-    /// Creates a new Transaction from the scanned tokens.
-    pub fn create(date: &str, aux_date: &str, payee: &str, note: &str) -> Self {
-        let _date = if date.is_empty() {
-            None
-        } else {
-            Some(parser::parse_date(date))
-        };
-
-        let _aux_date = if aux_date.is_empty() {
-            None
-        } else {
-            Some(parser::parse_date(aux_date))
-        };
-
+    /// Creates a new Transaction from the parsed dates and the scanned tokens.
+    pub fn create(
+        date: Option<NaiveDate>,
+        aux_date: Option<NaiveDate>,
+        payee: &str,
+        note: &str,
+    ) -> Self {
         let _payee = if payee.is_empty() {
             "Unknown Payee".to_string()
         } else {
             payee.to_string()
         };
 
-        let _note = if note.is_empty() {
-            None
-        } else {
-            Some(note.to_string())
-        };
-
-        Self {
-            date: _date,
+        let mut xact = Self {
+            date,
             payee: _payee,
-            note: _note,
-            aux_date: _aux_date,
-            journal: std::ptr::null(),
+            note: None,
+            aux_date,
+            code: None,
             posts: vec![],
+            metadata: BTreeMap::new(),
+            pos: None,
+        };
+        if !note.is_empty() {
+            xact.add_note(note);
         }
+        xact
     }
 
+    /// Sets the note and reads any tags from it.
     pub fn add_note(&mut self, note: &str) {
+        self.parse_tags(note);
         self.note = Some(note.into());
     }
 
-    /// Adds the post to the collection and returns the reference to it.
-    pub fn add_post(&mut self, mut post: Post) -> &Post {
-        post.xact_ptr = self as *const Xact;
-        self.posts.push(post);
+    /// Reads the tags and metadata from the note text.
+    pub fn parse_tags(&mut self, note: &str) {
+        for (tag, value) in scanner::scan_tags(note) {
+            self.set_tag(tag, value);
+        }
+    }
+
+    pub fn set_tag(&mut self, tag: &str, value: Option<&str>) {
+        self.metadata
+            .insert(tag.to_owned(), value.map(|v| v.to_owned()));
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.metadata.contains_key(tag)
+    }
 
-        self.posts.last().unwrap()
+    pub fn get_tag(&self, tag: &str) -> Option<&str> {
+        self.metadata.get(tag).and_then(|value| value.as_deref())
     }
 }
 
 impl Default for Xact {
     fn default() -> Self {
         Self {
-            journal: std::ptr::null(),
             date: Default::default(),
             aux_date: Default::default(),
+            code: Default::default(),
             payee: Default::default(),
             posts: Default::default(),
             note: Default::default(),
+            metadata: Default::default(),
+            pos: Default::default(),
         }
     }
 }
 
 /// Finalize transaction.
 /// Adds the Xact and the Posts to the Journal.
+/// Fails if the transaction does not balance.
 ///
 /// `bool xact_base_t::finalize()`
 ///
-pub fn finalize(xact_ptr: *const Xact, journal: &mut Journal) {
+pub fn finalize(xact_id: XactId, journal: &mut Journal) -> Result<(), String> {
     // Scan through and compute the total balance for the xact.  This is used
     // for auto-calculating the value of xacts with no cost, and the per-unit
     // price of unpriced commodities.
 
     let mut balance = Balance::new();
-    // The pointer to the post that has no amount.
-    let mut null_post: *mut Post = std::ptr::null_mut();
-    // let xact = journal.xacts.get_mut(xact_index).expect("xact");
-    let xact: &mut Xact;
-    unsafe {
-        xact = &mut *(xact_ptr.cast_mut());
-    }
+    // The index of the post that has no amount.
+    let mut null_post: Option<usize> = None;
 
     // Balance
-    for post in &mut xact.posts {
+    for (i, post) in journal.get_xact(xact_id).posts.iter().enumerate() {
         // must balance?
         // if (! post->must_balance())
 
@@ -129,60 +165,53 @@ pub fn finalize(xact_ptr: *const Xact, journal: &mut Journal) {
             post.amount
         };
 
-        if amount.is_some() {
+        if let Some(amt) = &amount {
             // Add to balance.
-            let Some(amt) = &amount
-                else {panic!("should not happen")};
-
             balance.add(amt);
-        } else if !null_post.is_null() {
-            todo!()
+        } else if null_post.is_some() {
+            return Err("Only one posting with null amount allowed per transaction".to_owned());
         } else {
-            null_post = post as *mut Post;
+            null_post = Some(i);
         }
     }
 
     // If there is only one post, balance against the default account if one has
-    // been set.
-    if xact.posts.len() == 1 {
-        todo!("handle")
+    // been set. A zero post, i.e. a balance assertion, balances by itself.
+    let is_zero = balance.amounts.iter().all(|amount| amount.is_zero());
+    if journal.get_xact(xact_id).posts.len() == 1 && !(is_zero && null_post.is_none()) {
+        // Without the default account, the balance check below reports it.
+        if let (Some(bucket), false) = (journal.bucket, balance.amounts.is_empty()) {
+            let post = Post::new(bucket, xact_id, None, None, None);
+            let post_id = journal.add_post(xact_id, post);
+            null_post = Some(post_id.index);
+        }
     }
 
-    if null_post.is_null() && balance.amounts.len() == 2 {
+    let xact = &mut journal.xacts[xact_id.0];
+
+    if null_post.is_none() && balance.amounts.len() == 2 {
         // When an xact involves two different commodities (regardless of how
         // many posts there are) determine the conversion ratio by dividing the
         // total value of one commodity by the total value of the other.  This
         // establishes the per-unit cost for this post for both commodities.
 
-        let mut top_post: Option<&Post> = None;
-        for post in &xact.posts {
-            if post.amount.is_some() && top_post.is_none() {
-                top_post = Some(post);
-            }
-        }
+        let top_post = xact.posts.iter().find(|post| post.amount.is_some());
 
         // if !saw_cost && top_post
-        if top_post.is_some() {
+        if let Some(top_post) = top_post {
             // log::debug("there were no costs, and a valid top_post")
 
-            // We need a separate readonly reference for reading the amounts.
-            let const_bal: &Balance;
-            unsafe {
-                let const_ptr = &balance as *const Balance;
-                const_bal = &*const_ptr;
-            }
-
-            let mut x = const_bal.amounts.iter().nth(0).unwrap();
-            let mut y = const_bal.amounts.iter().nth(1).unwrap();
+            let mut x = balance.amounts[0];
+            let mut y = balance.amounts[1];
 
             // if x && y
             if !x.is_zero() && !y.is_zero() {
-                if x.get_commodity() != top_post.unwrap().amount.unwrap().get_commodity() {
+                if x.get_commodity() != top_post.amount.unwrap().get_commodity() {
                     (x, y) = (y, x);
                 }
 
                 let comm = x.get_commodity();
-                let per_unit_cost = (*y / *x).abs();
+                let per_unit_cost = (y / x).abs();
 
                 for post in &mut xact.posts {
                     let amt = post.amount.unwrap();
@@ -201,10 +230,13 @@ pub fn finalize(xact_ptr: *const Xact, journal: &mut Journal) {
     // if (has_date())
     {
         for p in &mut xact.posts {
-            // let p = journal.posts.get_mut(*post_index).unwrap();
             if p.cost.is_none() {
                 continue;
             }
+            // A fixed rate does not establish a market value for the commodity.
+            if p.cost_fixated {
+                continue;
+            }
 
             let Some(amt) = &p.amount else {panic!("No amount found on the posting")};
             let Some(cost) = &p.cost else {panic!("No cost found on the posting")};
@@ -238,61 +270,72 @@ pub fn finalize(xact_ptr: *const Xact, journal: &mut Journal) {
     }
 
     // Handle null-amount post.
-    if !null_post.is_null() {
+    if let Some(null_post_index) = null_post {
         // If one post has no value at all, its value will become the inverse of
         // the rest.  If multiple commodities are involved, multiple posts are
         // generated to balance them all.
 
         log::debug!("There was a null posting");
 
-        // let Some(null_post_index) = null_post
-        //     else {panic!("should not happen")};
-        // let Some(post) = journal.posts.get_mut(null_post_index)
-        //     else {panic!("should not happen")};
-        let post: &mut Post;
-        unsafe {
-            post = &mut *null_post;
-        }
-
-        // use inverse amount
-        let amt = if balance.amounts.len() == 1 {
-            // only one commodity
-            let amt_bal = balance.amounts.iter().nth(0).unwrap();
-
-            log::debug!("null-post amount reversing {:?}", amt_bal);
+        let mut remainder = balance.amounts.iter().map(Amount::inverse);
+        let post = &mut xact.posts[null_post_index];
+        post.amount = Some(remainder.next().unwrap_or_else(Amount::null));
 
-            amt_bal.inverse()
-        } else {
-            // TODO: handle option when there are multiple currencies and only one blank posting.
-
-            todo!("check this option")
-        };
-
-        post.amount = Some(amt);
+        let account = post.account;
+        let note = post.note.clone();
+        for amount in remainder.collect::<Vec<_>>() {
+            let post = Post::new(account, xact_id, Some(amount), None, note.as_deref());
+            journal.add_post(xact_id, post);
+        }
+        return Ok(());
+    }
 
-        null_post = std::ptr::null_mut();
+    // The remainder is checked in the display precision of the commodity.
+    let pool = &journal.commodity_pool;
+    let remainder: Vec<String> = balance
+        .amounts
+        .iter()
+        .filter(|amount| {
+            let quantity = match amount.get_commodity() {
+                Some(commodity) => amount.quantity.round_dp(pool.get(commodity).precision.into()),
+                None => amount.quantity,
+            };
+            !quantity.is_zero()
+        })
+        .map(|amount| amount.display(pool).to_string())
+        .collect();
+    if !remainder.is_empty() {
+        return Err(format!(
+            "Transaction does not balance, the remainder is {}",
+            remainder.join(", ")
+        ));
     }
 
     // TODO: Process Commodities?
     // TODO: Process Account records from Posts.
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::post::Post;
+    use crate::{journal::Journal, post::Post};
 
     use super::Xact;
 
     #[test]
     fn test_add_post() {
-        let post = Post::new(std::ptr::null(), std::ptr::null(), None, None, None);
-        let mut xact = Xact::default();
+        let mut journal = Journal::new();
+        let xact_id = journal.add_xact(Xact::default());
+        let account = journal.register_account("Assets").unwrap();
+        let post = Post::new(account, Default::default(), None, None, None);
 
         // act
-        xact.add_post(post);
+        let post_id = journal.add_post(xact_id, post);
 
         // assert
+        let xact = journal.get_xact(xact_id);
         assert_eq!(1, xact.posts.len());
-        assert!(!xact.posts[0].xact_ptr.is_null());
+        assert_eq!(xact_id, xact.posts[0].xact);
+        assert_eq!(post_id, journal.get_account(account).posts[0]);
     }
 }
@@ -12,10 +12,10 @@ fn test_creating_account_tree() {
     let mut journal = Journal::new();
 
     // Act
-    ledger_rs_lib::parse_file(file_path, &mut journal);
+    ledger_rs_lib::parse_file(file_path, &mut journal).unwrap();
 
     // Assert
-    let accounts = journal.master.flatten_account_tree();
+    let accounts = journal.master().flatten_account_tree(&journal);
     assert_eq!(5, accounts.len());
     let mut iterator = accounts.iter();
     assert_eq!("", iterator.next().unwrap().name);
@@ -0,0 +1,213 @@
+/*!
+ * Runs the command-line binary.
+ */
+
+use std::{
+    io::Write,
+    path::Path,
+    process::{Command, Output, Stdio},
+};
+
+fn ledger_rs(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_ledger-rs"))
+        .args(args)
+        .output()
+        .expect("ledger-rs started")
+}
+
+#[test]
+fn test_balance() {
+    let output = ledger_rs(&["b", "-f", "tests/basic.ledger"]);
+
+    assert!(output.status.success());
+    let expected = "Account  has balance 0 EUR
+Account Assets has balance -20 EUR
+Account Assets:Cash has balance -20 EUR
+Account Expenses has balance 20 EUR
+Account Expenses:Food has balance 20 EUR
+";
+    assert_eq!(expected, String::from_utf8(output.stdout).unwrap());
+    assert!(output.stderr.is_empty());
+}
+
+/// The `--strict` warnings go to stderr, and the report is printed.
+#[test]
+fn test_strict_warnings() {
+    let output = Command::new(env!("CARGO_BIN_EXE_ledger-rs"))
+        .args(["--strict", "b", "-f", "tests/basic.ledger"])
+        .env_remove("RUST_LOG")
+        .output()
+        .expect("ledger-rs started");
+
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let warning = "Warning: \"tests/basic.ledger\", line 4: Unknown account 'Expenses:Food'\n";
+    assert!(stderr.contains(warning));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Account Expenses:Food has balance 20 EUR"));
+}
+
+#[test]
+fn test_version() {
+    let output = ledger_rs(&["--version"]);
+
+    assert!(output.status.success());
+    assert_eq!(
+        format!("ledger-rs {}\n", env!("CARGO_PKG_VERSION")),
+        String::from_utf8(output.stdout).unwrap()
+    );
+}
+
+#[test]
+fn test_help() {
+    let output = ledger_rs(&["--help"]);
+
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .starts_with("Usage: ledger-rs"));
+}
+
+#[test]
+fn test_missing_file() {
+    let output = ledger_rs(&["b", "-f", "tests/missing.ledger"]);
+
+    assert_eq!(Some(1), output.status.code());
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .starts_with("Error: tests/missing.ledger:"));
+}
+
+#[test]
+fn test_unknown_command() {
+    let output = ledger_rs(&["frobnicate", "-f", "tests/basic.ledger"]);
+
+    assert_eq!(Some(1), output.status.code());
+    assert_eq!(
+        "Error: Unrecognized command 'frobnicate'\n",
+        String::from_utf8(output.stderr).unwrap()
+    );
+}
+
+/// Runs with the given input, in an empty home directory.
+fn ledger_rs_with_input(args: &[&str], input: &str, home: &Path) -> Output {
+    std::fs::create_dir_all(home).unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ledger-rs"))
+        .args(args)
+        .env("HOME", home)
+        .env_remove("LEDGER_FILE")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("ledger-rs started");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn test_no_arguments() {
+    let home = std::env::temp_dir().join("ledger-rs-cli-no-arguments");
+    let output = ledger_rs_with_input(&[], "", &home);
+
+    assert_eq!(Some(1), output.status.code());
+    assert_eq!(
+        "Error: No journal file was specified (please use -f or LEDGER_FILE)\n",
+        String::from_utf8(output.stderr).unwrap()
+    );
+    std::fs::remove_dir_all(&home).unwrap();
+}
+
+#[test]
+fn test_repl() {
+    let home = std::env::temp_dir().join("ledger-rs-cli-repl");
+    let input = "bal Expenses\nfrobnicate\nreload\nreg -M Food\nquit\nbal\n";
+
+    let output = ledger_rs_with_input(&["-f", "tests/basic.ledger"], input, &home);
+
+    assert!(output.status.success());
+    assert_eq!(
+        "Account Expenses has balance 20 EUR
+Account Expenses:Food has balance 20 EUR
+2023-04-01 - 2023-04-30  Expenses:Food  20 EUR  20 EUR
+",
+        String::from_utf8(output.stdout).unwrap()
+    );
+    assert_eq!(
+        "Error: Unrecognized command 'frobnicate'\n",
+        String::from_utf8(output.stderr).unwrap()
+    );
+    // The commands are saved in the history.
+    let history = std::fs::read_to_string(home.join(".ledger-rs_history")).unwrap();
+    assert!(history.contains("reg -M Food"));
+    std::fs::remove_dir_all(&home).unwrap();
+}
+
+#[test]
+fn test_ledgerrc_and_ledger_file() {
+    let home = std::env::temp_dir().join("ledger-rs-cli-home");
+    std::fs::create_dir_all(&home).unwrap();
+    let journal = std::fs::canonicalize("tests/basic.ledger").unwrap();
+    std::fs::write(home.join(".ledgerrc"), "--file does-not-exist.ledger\n").unwrap();
+
+    let run = |ledger_file: Option<&str>| {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_ledger-rs"));
+        command
+            .args(["accounts"])
+            .env("HOME", &home)
+            .env_remove("LEDGER_FILE");
+        if let Some(file) = ledger_file {
+            command.env("LEDGER_FILE", file);
+        }
+        command.output().expect("ledger-rs started")
+    };
+
+    // The init file is read.
+    let from_init = run(None);
+    assert_eq!(Some(1), from_init.status.code());
+    assert!(String::from_utf8(from_init.stderr)
+        .unwrap()
+        .contains("does-not-exist.ledger"));
+
+    // LEDGER_FILE overrides it.
+    let from_env = run(Some(journal.to_str().unwrap()));
+    assert!(from_env.status.success());
+    assert_eq!(
+        "\nAssets\nCash\nExpenses\nFood\n",
+        String::from_utf8(from_env.stdout).unwrap()
+    );
+
+    std::fs::remove_dir_all(&home).unwrap();
+}
+
+#[test]
+fn test_journal_from_stdin() {
+    let home = std::env::temp_dir().join("ledger-rs-cli-stdin");
+    let input = "2023-04-22 Bakery\n    Expenses:Bread  5 EUR\n    Assets:Cash\n";
+
+    let output = ledger_rs_with_input(
+        &["accounts", "-f", "tests/basic.ledger", "-f", "-"],
+        input,
+        &home,
+    );
+
+    assert!(output.status.success());
+    assert_eq!(
+        "\nAssets\nBread\nCash\nExpenses\nFood\n",
+        String::from_utf8(output.stdout).unwrap()
+    );
+
+    // The errors name the stdin.
+    let output = ledger_rs_with_input(&["bal", "-f", "-"], "Y abc\n", &home);
+    assert_eq!(Some(1), output.status.code());
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .starts_with("Error: \"/dev/stdin\", line 1: "));
+    std::fs::remove_dir_all(&home).unwrap();
+}
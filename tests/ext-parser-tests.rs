@@ -4,10 +4,13 @@
 
 use chrono::NaiveDate;
 use ledger_rs_lib::{
+    account::AccountType,
     amount::{Amount, Quantity},
+    hledger,
     journal::Journal,
-    parse_file,
-    pool::CommodityIndex, parse_text, commodity::Commodity,
+    parse_file, parse_sources,
+    pool::CommodityIndex, parse_text,
+    report,
 };
 
 #[test]
@@ -15,7 +18,7 @@ fn smoke_test_parsing() {
     let file_path = "tests/minimal.ledger";
     let mut journal = Journal::new();
 
-    ledger_rs_lib::parse_file(file_path, &mut journal);
+    ledger_rs_lib::parse_file(file_path, &mut journal).unwrap();
 
     assert_eq!(1, journal.xacts.len());
     let xact = &journal.xacts[0];
@@ -28,7 +31,7 @@ fn test_parsing_two_xact() {
     let file_path = "tests/two_xact.ledger";
     let mut journal = Journal::new();
 
-    ledger_rs_lib::parse_file(file_path, &mut journal);
+    ledger_rs_lib::parse_file(file_path, &mut journal).unwrap();
 
     assert_eq!(2, journal.xacts.len());
     let xact0 = &journal.xacts[0];
@@ -43,7 +46,7 @@ fn detailed_basic_test() {
     let mut journal = Journal::new();
 
     // Act
-    ledger_rs_lib::parse_file(file_path, &mut journal);
+    ledger_rs_lib::parse_file(file_path, &mut journal).unwrap();
 
     // Assert
     assert_eq!(1, journal.xacts.len());
@@ -59,28 +62,26 @@ fn detailed_basic_test() {
     assert_eq!("Food", journal.get_account(post1.account).name);
     let amount1 = &post1.amount.as_ref().unwrap();
     assert_eq!(Quantity::from(20), amount1.quantity);
-    let symbol = &amount1.get_commodity().unwrap().symbol;
+    let symbol = &journal.commodity_pool.get(amount1.get_commodity().unwrap()).symbol;
     assert_eq!("EUR", symbol);
 
     let post2 = &xact.posts[1];
     assert_eq!("Cash", journal.get_account(post2.account).name);
     let amount2 = &post2.amount.as_ref().unwrap();
     assert_eq!(Quantity::from(-20), amount2.quantity);
-    let symbol = &amount2.get_commodity().unwrap().symbol;
+    let symbol = &journal.commodity_pool.get(amount2.get_commodity().unwrap()).symbol;
     assert_eq!("EUR", symbol);
 }
 
-/// TODO: include when the feature is implemented
-//#[test]
+#[test]
 fn test_include() {
     // let args = split("accounts -f tests/include.ledger").unwrap();
     let input = "include tests/minimal.ledger";
     let mut journal = Journal::new();
 
-    ledger_rs_lib::parse_text(input, &mut journal);
+    ledger_rs_lib::parse_text(input, &mut journal).unwrap();
 
     assert_eq!(1, journal.xacts.len());
-    todo!("complete the feature")
 }
 
 #[test]
@@ -90,7 +91,7 @@ fn test_parsing_multiple_currencies() {
     let mut journal = Journal::new();
 
     // Act
-    ledger_rs_lib::parse_file(file_path, &mut journal);
+    ledger_rs_lib::parse_file(file_path, &mut journal).unwrap();
 
     // Assert
     assert!(!journal.xacts.is_empty());
@@ -104,18 +105,18 @@ fn test_parsing_account_tree() {
     let mut journal = Journal::new();
 
     // Act
-    ledger_rs_lib::parse_file(file_path, &mut journal);
+    ledger_rs_lib::parse_file(file_path, &mut journal).unwrap();
 
     // Assert
     assert!(!journal.xacts.is_empty());
-    assert_eq!(5, journal.master.flatten_account_tree().len());
+    assert_eq!(5, journal.master().flatten_account_tree(&journal).len());
 }
 
 #[test]
 fn test_parsing_lots_per_unit() {
     let mut journal = Journal::new();
 
-    parse_file("tests/trade-buy-sell.ledger", &mut journal);
+    parse_file("tests/trade-buy-sell.ledger", &mut journal).unwrap();
 
     // Assert
 
@@ -132,13 +133,13 @@ fn test_parsing_lots_per_unit() {
     assert_eq!(cost.quantity, 200.into());
     // sell
     // let cur_index: CommodityIndex = 1.into();
-    let cdty = journal.commodity_pool.find("EUR").unwrap();
+    let cdty = journal.commodity_pool.find_id("EUR").unwrap();
     let expected_cost = Amount::new((-250).into(), Some(cdty));
     let xact1 = &journal.xacts[1];
     assert_eq!(expected_cost, xact1.posts[0].cost.unwrap());
 
     // let cur = journal.get_commodity(cur_index);
-    assert_eq!("EUR", cdty.symbol);
+    assert_eq!("EUR", journal.commodity_pool.get(cdty).symbol);
 }
 
 #[test]
@@ -147,7 +148,7 @@ fn test_parsing_lots_full_price() {
     let mut journal = Journal::new();
 
     // act
-    parse_file("tests/trade-buy-sell-full-price.ledger", &mut journal);
+    parse_file("tests/trade-buy-sell-full-price.ledger", &mut journal).unwrap();
 
     // Assert
 
@@ -158,7 +159,7 @@ fn test_parsing_lots_full_price() {
     // posts
     assert_eq!(4, journal.all_posts().len());
     let xact = &journal.xacts[1];
-    let eur = journal.commodity_pool.find("EUR").unwrap() as *const Commodity;
+    let eur = journal.commodity_pool.find_id("EUR").unwrap();
     let expected_cost = Amount::new(25.into(), Some(eur));
     assert_eq!(expected_cost, xact.posts[0].cost.unwrap());
 }
@@ -173,7 +174,7 @@ fn test_lot_sale() {
     let mut journal = Journal::new();
 
     // act
-    parse_text(input, &mut journal);
+    parse_text(input, &mut journal).unwrap();
 
     // assert
     assert_eq!(1, journal.xacts.len());
@@ -186,21 +187,23 @@ fn test_lot_sale() {
     let xact = &journal.xacts[0];
     let sale_post = &xact.posts[1];
     assert_eq!(sale_post.amount.unwrap().quantity, (-10).into());
-    assert_eq!(sale_post.amount.unwrap().get_commodity().unwrap().graph_index, veur);
+    let commodity = sale_post.amount.unwrap().get_commodity().unwrap();
+    assert_eq!(journal.commodity_pool.get(commodity).graph_index, veur);
     
     // annotations
     // todo!("annotations")
 
     // cost
     assert_eq!(sale_post.cost.unwrap().quantity, (250).into());
-    assert_eq!(sale_post.cost.unwrap().get_commodity().unwrap().graph_index, eur);
+    let commodity = sale_post.cost.unwrap().get_commodity().unwrap();
+    assert_eq!(journal.commodity_pool.get(commodity).graph_index, eur);
 }
 
 // #[test]
 fn test_parsing_trade_lot() {
     let mut journal = Journal::new();
 
-    parse_file("tests/trade-buy-sell-lot.ledger", &mut journal);
+    parse_file("tests/trade-buy-sell-lot.ledger", &mut journal).unwrap();
 
     // Assert
     assert_eq!(2, journal.xacts.len());
@@ -210,3 +213,153 @@ fn test_parsing_trade_lot() {
     assert_eq!(sale_post.amount.unwrap().quantity, (-10).into());
     assert_eq!(Quantity::from(-250), sale_post.cost.unwrap().quantity);
 }
+
+/// The directive reader returns the same transactions as the parser, without a Journal.
+#[test]
+fn test_reading_directives_from_file() {
+    let file_path = "tests/trade-buy-sell-lot.ledger";
+    let file = std::fs::File::open(file_path).unwrap();
+    let mut journal = Journal::new();
+    ledger_rs_lib::parse_file(file_path, &mut journal).unwrap();
+
+    let xacts: Vec<_> = ledger_rs_lib::reader::create_reader(file)
+        .map(|directive| directive.unwrap().kind)
+        .filter_map(|kind| match kind {
+            ledger_rs_lib::directives::DirectiveType::Xact(xact) => Some(xact),
+            _ => None,
+        })
+        .collect();
+
+    assert_eq!(journal.xacts.len(), xacts.len());
+    assert_eq!(journal.xacts[0].payee, xacts[0].payee);
+    assert_eq!(journal.xacts[0].posts.len(), xacts[0].posts.len());
+}
+
+/// The syntax tree reproduces every test journal byte for byte.
+#[test]
+fn test_syntax_tree_round_trip() {
+    for entry in std::fs::read_dir("tests").unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().and_then(|e| e.to_str()) != Some("ledger") {
+            continue;
+        }
+        let source = std::fs::read_to_string(&path).unwrap();
+
+        let tree = ledger_rs_lib::cst::SyntaxTree::parse(&source);
+
+        assert_eq!(source, tree.to_string(), "{:?}", path);
+    }
+}
+
+/// The `.hledger` files are read with hledger's tags, `name:value`.
+#[test]
+fn test_parsing_hledger_syntax() {
+    let mut journal = Journal::new();
+
+    parse_file("tests/sample.hledger", &mut journal).unwrap();
+
+    assert_eq!(4, journal.xacts.len());
+    let groceries = &journal.xacts[0];
+    assert_eq!(Some("london"), groceries.get_tag("trip"));
+    assert_eq!(Some("R-1001"), groceries.get_tag("receipt"));
+    assert_eq!(Some("groceries"), groceries.posts[0].get_tag("category"));
+    let salary = journal.find_account("Revenue:Salary").unwrap();
+    assert_eq!(Some(AccountType::Revenue), salary.kind);
+    let bank = &journal.xacts[2].posts[1];
+    assert_eq!(
+        "2254.70 EUR",
+        bank.assertion
+            .unwrap()
+            .amount
+            .display(&journal.commodity_pool)
+            .to_string()
+    );
+    // The cost of the VEUR on the same day is the same price point.
+    let prices = journal.commodity_pool.prices();
+    assert_eq!(1, prices.len());
+    assert_eq!(
+        NaiveDate::from_ymd_opt(2023, 1, 15).unwrap(),
+        prices[0].datetime.date()
+    );
+}
+
+#[test]
+fn test_parsing_hledger_total_assertion_fails() {
+    let mut journal = Journal::new();
+    let text = "2023-01-01 Broker\n    Assets  10 VEUR\n    Assets  20 EUR\n    Equity  -10 VEUR\n    Equity  -20 EUR\n\n2023-01-02 Check\n    Assets  0 EUR == 20 EUR\n";
+
+    let actual = parse_sources([("test.hledger", text.as_bytes())], &mut journal);
+
+    assert_eq!(
+        "\"test.hledger\", line 8: Balance assertion failed, the account also has 10 VEUR",
+        actual.unwrap_err()
+    );
+}
+
+/// The printed journal, with the `==` and `=*` assertions, reads back the same.
+#[test]
+fn test_printing_hledger_assertions_round_trip() {
+    let mut journal = Journal::new();
+    parse_file("tests/sample.hledger", &mut journal).unwrap();
+    let printed = report::print_report(&journal, None, None).join("\n");
+    let written = hledger::write_journal(&journal).join("\n");
+
+    assert!(printed.contains("Assets:Bank  -200.00 EUR == 2254.70 EUR"));
+    assert!(printed.contains("Assets  0 EUR =* 2254.70 EUR"));
+    let mut reparsed = Journal::new();
+    parse_sources([("print.ledger", printed.as_bytes())], &mut reparsed).unwrap();
+    assert_eq!(printed, report::print_report(&reparsed, None, None).join("\n"));
+    let mut reparsed = Journal::new();
+    parse_sources([("write.hledger", written.as_bytes())], &mut reparsed).unwrap();
+    assert_eq!(written, hledger::write_journal(&reparsed).join("\n"));
+}
+
+/// The Beancount directives are sorted by date and converted into the journal.
+#[test]
+fn test_parsing_beancount() {
+    let mut journal = Journal::new();
+
+    parse_file("tests/sample.beancount", &mut journal).unwrap();
+
+    let payees: Vec<&str> = journal.xacts.iter().map(|x| x.payee.as_str()).collect();
+    assert_eq!(
+        vec![
+            "Padding",
+            "Balance",
+            "* Grocery Store",
+            "! Employer",
+            "* Broker",
+            "Balance"
+        ],
+        payees
+    );
+    let groceries = &journal.xacts[2];
+    assert_eq!(Some("Weekly shopping :2023:food:"), groceries.note.as_deref());
+    assert!(groceries.metadata.contains_key("food"));
+    assert_eq!(Some("R-1001"), groceries.get_tag("receipt"));
+    // The padding brings the account to the balance.
+    let padding = &journal.xacts[0];
+    assert_eq!(
+        "-1000.00 EUR",
+        padding.posts[1]
+            .amount
+            .unwrap()
+            .display(&journal.commodity_pool)
+            .to_string()
+    );
+    let bank = journal.find_account("Assets:Bank:Checking").unwrap();
+    assert_eq!(Some(AccountType::Asset), bank.kind);
+    assert!(!journal.commodity_pool.prices().is_empty());
+    // The position of the transaction is in the Beancount file.
+    assert_eq!(21, groceries.pos.as_ref().unwrap().beg_line);
+}
+
+#[test]
+fn test_parsing_beancount_errors() {
+    let mut journal = Journal::new();
+    let text = "2023-01-01 open Assets:Cash\n\n2023-01-05 * \"Shop\"\n  Expenses:Food  10 EUR\n  Assets:Cash\n\n2023-01-06 balance Assets:Cash  10 EUR\n";
+
+    let actual = parse_sources([("test.beancount", text.as_bytes())], &mut journal);
+
+    assert!(actual.unwrap_err().starts_with("\"test.beancount\", line 7: Balance assertion off by"));
+}
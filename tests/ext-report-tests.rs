@@ -2,6 +2,10 @@
  * External reports tests
  */
 
+use std::{sync::Arc, thread};
+
+use ledger_rs_lib::{journal::Journal, report};
+
 #[test]
 fn test_balance_minimal() {
     // Act
@@ -38,8 +42,7 @@ fn test_accounts() {
     assert_eq!(expected, actual);
 }
 
-/// TODO: enable test when the functionality is implemented
-//#[test]
+#[test]
 fn test_account_filter() {
     // Act
     let actual = ledger_rs_lib::run_command("accounts Asset -f tests/minimal.ledger");
@@ -79,4 +82,248 @@ fn test_balance_multiple_currencies() {
 
     assert!(false);
     // assert_eq!("Account Assets:Cash has balance -20 ");
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_accounts_with_alias_option() {
+    let actual =
+        ledger_rs_lib::run_command("accounts -f tests/basic.ledger --alias Expenses=Costs");
+
+    let expected = vec!["", "Assets", "Cash", "Costs", "Food"];
+    assert_eq!(expected, actual);
+}
+
+#[test]
+fn test_print_with_date_format() {
+    let actual = ledger_rs_lib::run_command("print -f tests/basic.ledger --date-format %Y/%m/%d");
+
+    let expected = vec![
+        "2023/04/21 Supermarket",
+        "    Expenses:Food  20 EUR",
+        "    Assets:Cash  -20 EUR",
+    ];
+    assert_eq!(expected, actual);
+}
+
+/// The parsed journal is shared between the threads, which run the reports concurrently.
+#[test]
+fn test_balance_report_from_threads() {
+    let mut journal = Journal::new();
+    ledger_rs_lib::parse_file("tests/two-xact-sub-acct.ledger", &mut journal).unwrap();
+    let journal = Arc::new(journal);
+    let expected = report::balance_report(&journal);
+
+    let handles: Vec<_> = (0..8)
+        .map(|_| {
+            let journal = Arc::clone(&journal);
+            thread::spawn(move || report::balance_report(&journal))
+        })
+        .collect();
+
+    for handle in handles {
+        assert_eq!(expected, handle.join().unwrap());
+    }
+}
+
+#[test]
+fn test_fmt_check() {
+    let path = std::env::temp_dir()
+        .join(format!("ledger-rs-fmt-check-{}.ledger", std::process::id()));
+    std::fs::write(&path, "2023-04-21 Supermarket\n    Expenses:Food  20 EUR\n    Assets:Cash\n")
+        .unwrap();
+
+    let actual = ledger_rs_lib::run_command(&format!("fmt --check {}", path.display()));
+
+    assert!(actual.is_empty());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_print_sorted_by_date() {
+    let actual = ledger_rs_lib::run_command("print -f tests/trade-buy-sell.ledger --sort -date");
+    let headers: Vec<&String> = actual.iter().filter(|line| line.starts_with("20")).collect();
+
+    let mut expected = headers.clone();
+    expected.sort_by(|a, b| b.cmp(a));
+    assert_eq!(expected, headers);
+}
+
+#[test]
+fn test_register_sorted_by_amount() {
+    let actual = ledger_rs_lib::run_command("reg -f tests/basic.ledger -S amount");
+
+    let expected = vec![
+        "2023-04-21 Supermarket  Assets:Cash  -20 EUR  -20 EUR",
+        "2023-04-21 Supermarket  Expenses:Food  20 EUR  0 EUR",
+    ];
+    assert_eq!(expected, actual);
+}
+
+#[test]
+fn test_register_monthly() {
+    let actual = ledger_rs_lib::run_command("reg -M -f tests/basic.ledger");
+
+    let expected = vec![
+        "2023-04-01 - 2023-04-30  Assets:Cash  -20 EUR  -20 EUR",
+        "2023-04-01 - 2023-04-30  Expenses:Food  20 EUR  0 EUR",
+    ];
+    assert_eq!(expected, actual);
+}
+
+#[test]
+fn test_duplicates() {
+    let path = std::env::temp_dir()
+        .join(format!("ledger-rs-duplicates-command-{}.ledger", std::process::id()));
+    std::fs::write(
+        &path,
+        "2023-04-21 Supermarket\n    Expenses:Food  20 EUR\n    Assets:Cash\n\n2023-04-22 SUPERMARKET\n    Expenses:Food  20 EUR\n    Assets:Cash\n",
+    )
+    .unwrap();
+    let file = path.display();
+
+    let actual = ledger_rs_lib::run_command(&format!("duplicates -f {}", file));
+
+    assert_eq!(
+        vec![format!(
+            "\"{file}\", line 5: 2023-04-22 SUPERMARKET 20 EUR duplicates \"{file}\", line 1: 2023-04-21 Supermarket 20 EUR"
+        )],
+        actual
+    );
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_convert_csv() {
+    let dir = std::env::temp_dir()
+        .join(format!("ledger-rs-convert-command-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let statement = dir.join("bank.csv");
+    let rules = dir.join("bank.rules");
+    let journal = dir.join("journal.ledger");
+    std::fs::write(
+        &statement,
+        "2023-04-21,Supermarket,-20\n2023-04-22,Bakery,-5\n",
+    )
+    .unwrap();
+    std::fs::write(
+        &rules,
+        "fields date, payee, amount\ncurrency EUR\naccount1 Assets:Cash\nif bakery\n    account2 Expenses:Food\n",
+    )
+    .unwrap();
+    std::fs::write(
+        &journal,
+        "2023-04-21 Supermarket\n    Expenses:Food  20 EUR\n    Assets:Cash\n",
+    )
+    .unwrap();
+
+    let actual = ledger_rs_lib::run_command(&format!(
+        "convert {} --rules {} -f {}",
+        statement.display(),
+        rules.display(),
+        journal.display()
+    ));
+
+    // The supermarket is already in the journal.
+    let expected = vec![
+        "2023-04-22 Bakery",
+        "    Assets:Cash  -5 EUR",
+        "    Expenses:Food",
+    ];
+    assert_eq!(expected, actual);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_convert_ofx() {
+    let actual = ledger_rs_lib::run_command("convert tests/statement.ofx tests/statement.qfx");
+
+    assert_eq!(30, actual.len());
+    assert_eq!(
+        vec![
+            "2023-05-05 (1001) Landlord",
+            "    ; FITID: 202305052",
+            "    Assets:Bank:Checking  -1200.00 USD",
+            "    Expenses:Rent",
+        ],
+        actual[5..9]
+    );
+    assert_eq!("    Assets:Bank:Checking  0 USD = 1254.70 USD", actual[16]);
+    assert_eq!("2023-06-15 Refund <online>", actual[23]);
+}
+
+#[test]
+fn test_print_as_beancount() {
+    let actual = ledger_rs_lib::run_command("print -f tests/basic.ledger --output-format beancount");
+
+    let expected = vec![
+        "2023-04-21 open Assets:Cash",
+        "2023-04-21 open Expenses:Food",
+        "",
+        r#"2023-04-21 * "Supermarket" """#,
+        "  Expenses:Food  20 EUR",
+        "  Assets:Cash  -20 EUR",
+    ];
+    assert_eq!(expected, actual);
+}
+
+/// The Beancount journal is converted into hledger's.
+#[test]
+fn test_print_beancount_as_hledger() {
+    let actual = ledger_rs_lib::run_command(
+        "print -f tests/sample.beancount --input-format beancount --output-format hledger",
+    );
+
+    assert_eq!("account Assets:Bank:Checking  ; type: Asset", actual[0]);
+    assert!(actual.contains(&"2023-01-05 * Grocery Store  ; Weekly shopping, 2023:, food:, receipt:R-1001".to_owned()));
+    assert!(actual.contains(&"    Assets:Bank:Checking  0 EUR =* 3254.70 EUR".to_owned()));
+}
+
+#[test]
+fn test_register_as_json() {
+    let actual = ledger_rs_lib::run_command("reg Food -f tests/basic.ledger --output-format json");
+
+    let rows: serde_json::Value = serde_json::from_str(&actual.join("\n")).unwrap();
+    assert_eq!(
+        serde_json::json!([{
+            "date": "2023-04-21",
+            "payee": "Supermarket",
+            "account": "Expenses:Food",
+            "amount": { "quantity": "20", "commodity": "EUR" },
+            "total": [{ "quantity": "20", "commodity": "EUR" }],
+        }]),
+        rows
+    );
+}
+
+#[test]
+fn test_register_monthly_as_json() {
+    let actual =
+        ledger_rs_lib::run_command("reg -M Food -f tests/basic.ledger --output-format json");
+
+    let rows: serde_json::Value = serde_json::from_str(&actual.join("\n")).unwrap();
+    assert_eq!(
+        serde_json::json!([{
+            "begin": "2023-04-01",
+            "end": "2023-04-30",
+            "account": "Expenses:Food",
+            "amount": [{ "quantity": "20", "commodity": "EUR" }],
+            "total": [{ "quantity": "20", "commodity": "EUR" }],
+        }]),
+        rows
+    );
+}
+
+#[test]
+fn test_accounts_as_json() {
+    let actual = ledger_rs_lib::run_command("accounts -f tests/basic.ledger --output-format json");
+
+    let accounts: Vec<String> = serde_json::from_str(&actual.join("\n")).unwrap();
+    assert_eq!(vec!["Assets", "Assets:Cash", "Expenses", "Expenses:Food"], accounts);
+}
+
+#[test]
+fn test_prices() {
+    let actual = ledger_rs_lib::run_command("prices -f tests/sample.hledger");
+
+    assert_eq!(vec!["2023-01-15 VEUR 20.00 EUR"], actual);
+}
@@ -17,11 +17,11 @@ fn test_price_reading() {
 P 2022-03-03 13:00:00 EUR 1.12 USD
 "#;
     // Act
-    ledger_rs_lib::parse_text(text, &mut j);
+    ledger_rs_lib::parse_text(text, &mut j).unwrap();
 
     // Assert
-    let eur = j.commodity_pool.find("EUR").unwrap();
-    let usd = j.commodity_pool.find("USD").unwrap();
+    let eur = j.commodity_pool.find_id("EUR").unwrap();
+    let usd = j.commodity_pool.find_id("USD").unwrap();
     let three_eur = Amount::new(Quantity::from(3), Some(eur));
     let exch_rate = Amount::new(Quantity::from(1.5), Some(usd));
 
@@ -39,7 +39,7 @@ P 2022-03-03 13:00:00 EUR 1.12 USD
     Assets:Cash
 "#;
     let mut journal = Journal::new();
-    journal.read(Cursor::new(input));
+    journal.read(Cursor::new(input)).unwrap();
 
     todo!("run a report with -X USD")
 
@@ -0,0 +1,59 @@
+/*!
+ * Drives the language server binary with JSON-RPC over stdin.
+ */
+
+use std::{
+    io::{BufReader, Write},
+    process::{Command, Stdio},
+};
+
+use ledger_rs_lib::lsp::{read_message, write_message};
+use serde_json::{json, Value};
+
+#[test]
+fn test_lsp_session() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ledger-lsp"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("server started");
+
+    let messages = [
+        json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {"capabilities": {}}}),
+        json!({"jsonrpc": "2.0", "method": "initialized", "params": {}}),
+        json!({"jsonrpc": "2.0", "method": "textDocument/didOpen", "params": {"textDocument": {
+            "uri": "file:///tmp/test.ledger", "languageId": "ledger", "version": 1,
+            "text": "2023-05-01 Shop\n    Expenses:Food  20 EUR\n    Assets:Cash  -10 EUR\n"}}}),
+        json!({"jsonrpc": "2.0", "id": 2, "method": "textDocument/hover", "params": {
+            "textDocument": {"uri": "file:///tmp/test.ledger"},
+            "position": {"line": 1, "character": 6}}}),
+        json!({"jsonrpc": "2.0", "id": 3, "method": "shutdown"}),
+        json!({"jsonrpc": "2.0", "method": "exit"}),
+    ];
+    let mut stdin = child.stdin.take().unwrap();
+    for message in &messages {
+        write_message(&mut stdin, message).unwrap();
+    }
+    stdin.flush().unwrap();
+
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut replies: Vec<Value> = vec![];
+    while let Some(content) = read_message(&mut stdout).unwrap() {
+        replies.push(serde_json::from_str(&content).unwrap());
+    }
+    let status = child.wait().unwrap();
+
+    assert!(status.success());
+    assert_eq!(4, replies.len());
+    assert_eq!(true, replies[0]["result"]["capabilities"]["hoverProvider"]);
+    assert_eq!("textDocument/publishDiagnostics", replies[1]["method"]);
+    assert_eq!(
+        "Transaction does not balance, the remainder is 10 EUR",
+        replies[1]["params"]["diagnostics"][0]["message"]
+    );
+    assert_eq!(
+        "**Expenses:Food**\n\nBalance: 20 EUR",
+        replies[2]["result"]["contents"]["value"]
+    );
+    assert_eq!(Value::Null, replies[3]["result"]);
+}
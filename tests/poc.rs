@@ -9,7 +9,7 @@
 use core::panic;
 use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
-use ledger_rs_lib::commodity::Commodity;
+use ledger_rs_lib::{commodity::Commodity, pool::CommodityPool};
 
 #[derive(Debug, PartialEq)]
 struct Account {
@@ -102,30 +102,27 @@ fn test_ref_w_rc() {
     assert_eq!("bank", name);
 }
 
-/// Pointer gymnastics. Pass pointers around and convert to references
-/// when needed.
-/// In a structure where the data is only populated and never deleted,
-/// this should be safe.
+/// Index passing. The pool owns the commodities and hands out ids, which stay
+/// valid when the storage grows and moves its contents.
+/// This replaced the pointer gymnastics, where a pointer taken before moving the
+/// value into a container was left dangling.
 #[test]
-fn test_pointer_passing() {
-    // alchemy?
+fn test_index_passing() {
     // arrange
     const CURRENCY: &str = "EUR";
-    let mut container = HashMap::new();
-    let eur = Commodity::new(CURRENCY);
-    let eur_ptr = &eur as *const Commodity;
-    let eur_mut_ptr = eur_ptr as *mut Commodity;
-    
+    let mut pool = CommodityPool::new();
+    let eur = pool.create(CURRENCY, None);
+
     // act
-    container.insert(eur.symbol.to_owned(), eur);
+    // Force the reallocation of the storage.
+    for i in 0..100 {
+        pool.create(&format!("C{}", i), None);
+    }
+    pool.get_mut(eur).note = Some("Euro".to_owned());
 
     // assert
-    let expected_ref: &Commodity;
-    let expected_mut_ref: &mut Commodity;
-    unsafe {
-        expected_ref = &*eur_ptr;
-        expected_mut_ref = &mut*eur_mut_ptr;
-    }
+    let expected_ref: &Commodity = pool.get(eur);
     assert_eq!(CURRENCY, expected_ref.symbol);
-    assert_eq!(CURRENCY, expected_mut_ref.symbol);
-}
\ No newline at end of file
+    assert_eq!(Some("Euro"), expected_ref.note.as_deref());
+    assert_eq!(Some(eur), pool.find_id(CURRENCY));
+}
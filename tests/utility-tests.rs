@@ -14,7 +14,7 @@ fn test_xact_verification() {
     let mut journal = Journal::new();
 
     // Act
-    ledger_rs_lib::parse_text(src, &mut journal);
+    ledger_rs_lib::parse_text(src, &mut journal).unwrap();
 
     // Assert
     assert_eq!(1, journal.xacts.len());